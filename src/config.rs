@@ -1,12 +1,54 @@
-use std::{env, error::Error};
+use std::{env, error::Error, fmt::Display, str::FromStr};
+
+use crate::infrastructure::client::provider_functions::ProviderFunctions;
+use crate::infrastructure::event_producer::apns::ApnsSettings;
+use crate::models::cors::CorsSettings;
+use crate::models::feature_flags::FeatureFlags;
+use crate::models::rate_limit::{parse_route_limits, RateLimitSettings, DEFAULT_ROUTE_LIMITS};
+use crate::models::token::DeviceTokenPolicy;
 
 pub struct Config {
     pub port: String,
     pub mongo_uri: String,
     pub base_url: String,
     pub format_url: String,
+    pub provider_functions: ProviderFunctions,
+    pub provider_gzip: bool,
+    pub provider_max_retries: u32,
+    pub provider_retry_backoff_ms: u64,
+    pub provider_retry_budget: u32,
+    pub interactive_provider_concurrency: usize,
+    pub background_provider_concurrency: usize,
     pub kafka_url: String,
     pub batch_size: i64,
+    pub registration_concurrency: usize,
+    pub admin_api_key: Option<String>,
+    pub api_keys: Vec<String>,
+    pub provider_webhook_secret: Option<String>,
+    /// Derives the secrets user webhook events are signed with; user webhooks are off when unset.
+    pub user_webhook_signing_key: Option<String>,
+    pub cycle_report_retention_days: u64,
+    pub feature_flags: FeatureFlags,
+    pub notification_dedup_window_hours: i64,
+    pub producer_max_retries: u32,
+    /// First retry delay of a failed push; doubled per attempt, with jitter.
+    pub producer_retry_backoff_ms: u64,
+    /// Older history is moved to the archive.
+    pub notification_history_retention_days: u64,
+    pub notification_archive_retention_days: u64,
+    pub device_token_policy: DeviceTokenPolicy,
+    pub ios_notification_topic: Option<String>,
+    pub android_notification_topic: Option<String>,
+    /// Service-account key file; when set, Android pushes go to FCM directly.
+    pub fcm_service_account_file: Option<String>,
+    /// Bot token; when set, Telegram-linked chats get messages from the bot.
+    pub telegram_bot_token: Option<String>,
+    /// When set, iOS pushes go to APNs directly.
+    pub apns: Option<ApnsSettings>,
+    /// JSON file of handlebars templates overriding notification wording.
+    pub notification_templates_file: Option<String>,
+    pub rate_limit: RateLimitSettings,
+    pub cors: CorsSettings,
 }
 
 impl Config {
@@ -16,10 +58,160 @@ impl Config {
             mongo_uri: env::var("MONGODB_URI")?,
             base_url: env::var("BASE_URL")?,
             format_url: env::var("FORMAT_URL")?,
+            provider_functions: ProviderFunctions::from_mapping(
+                &env::var("PROVIDER_FUNCTIONS").unwrap_or_default(),
+            )?,
+            provider_gzip: optional_var("PROVIDER_GZIP", true)?,
+            provider_max_retries: optional_var("PROVIDER_MAX_RETRIES", 2)?,
+            provider_retry_backoff_ms: optional_var("PROVIDER_RETRY_BACKOFF_MS", 200)?,
+            provider_retry_budget: optional_var("PROVIDER_RETRY_BUDGET", 5)?,
+            interactive_provider_concurrency: optional_var("INTERACTIVE_PROVIDER_CONCURRENCY", 8)?,
+            background_provider_concurrency: optional_var("BACKGROUND_PROVIDER_CONCURRENCY", 4)?,
             kafka_url: env::var("KAFKA_URL")?,
             batch_size: env::var("BATCH_SIZE")?
                 .parse::<i64>()
                 .map_err(|e| format!("Invalid BATCH_SIZE: {}", e))?,
+            registration_concurrency: optional_var("REGISTRATION_CONCURRENCY", 4)?,
+            admin_api_key: env::var("ADMIN_API_KEY").ok(),
+            api_keys: list_var("API_KEYS").unwrap_or_default(),
+            provider_webhook_secret: env::var("PROVIDER_WEBHOOK_SECRET").ok(),
+            user_webhook_signing_key: env::var("USER_WEBHOOK_SIGNING_KEY").ok(),
+            cycle_report_retention_days: optional_var("CYCLE_REPORT_RETENTION_DAYS", 30)?,
+            feature_flags: feature_flags_from_env()?,
+            notification_dedup_window_hours: optional_var("NOTIFICATION_DEDUP_WINDOW_HOURS", 24)?,
+            producer_max_retries: optional_var("PRODUCER_MAX_RETRIES", 3)?,
+            producer_retry_backoff_ms: optional_var("PRODUCER_RETRY_BACKOFF_MS", 500)?,
+            notification_history_retention_days: optional_var(
+                "NOTIFICATION_HISTORY_RETENTION_DAYS",
+                30,
+            )?,
+            notification_archive_retention_days: optional_var(
+                "NOTIFICATION_ARCHIVE_RETENTION_DAYS",
+                365,
+            )?,
+            device_token_policy: optional_var(
+                "DUPLICATE_DEVICE_TOKEN_POLICY",
+                DeviceTokenPolicy::default(),
+            )?,
+            ios_notification_topic: env::var("IOS_NOTIFICATION_TOPIC").ok(),
+            android_notification_topic: env::var("ANDROID_NOTIFICATION_TOPIC").ok(),
+            fcm_service_account_file: env::var("FCM_SERVICE_ACCOUNT_FILE").ok(),
+            telegram_bot_token: env::var("TELEGRAM_BOT_TOKEN").ok(),
+            notification_templates_file: env::var("NOTIFICATION_TEMPLATES_FILE").ok(),
+            apns: apns_from_env()?,
+            rate_limit: rate_limit_from_env()?,
+            cors: cors_from_env()?,
         })
     }
 }
+
+fn feature_flags_from_env() -> Result<FeatureFlags, Box<dyn Error>> {
+    let defaults = FeatureFlags::default();
+    Ok(FeatureFlags {
+        grade_overview: optional_var("GRADE_OVERVIEW", defaults.grade_overview)?,
+        suppress_overview_after_grade: optional_var(
+            "SUPPRESS_OVERVIEW_AFTER_GRADE",
+            defaults.suppress_overview_after_grade,
+        )?,
+        grade_notify_threshold: optional_value("GRADE_NOTIFY_THRESHOLD")?,
+        validate_device_tokens: optional_var(
+            "VALIDATE_DEVICE_TOKENS",
+            defaults.validate_device_tokens,
+        )?,
+        verify_registration: optional_var("VERIFY_REGISTRATION", defaults.verify_registration)?,
+        best_effort_registration: optional_var(
+            "BEST_EFFORT_REGISTRATION",
+            defaults.best_effort_registration,
+        )?,
+        allow_clearing_grades: optional_var(
+            "ALLOW_CLEARING_GRADES",
+            defaults.allow_clearing_grades,
+        )?,
+        grade_notifications_per_course: optional_value("GRADE_NOTIFICATIONS_PER_COURSE")?,
+        canary_percent: optional_var("CANARY_PERCENT", defaults.canary_percent)?,
+        canary_comparison: optional_var("CANARY_COMPARISON", defaults.canary_comparison)?,
+        watch_device_tokens: optional_var("WATCH_DEVICE_TOKENS", defaults.watch_device_tokens)?,
+        deadline_reminder_hours: optional_value("DEADLINE_REMINDER_HOURS")?,
+        scheduled_reminders: optional_var("SCHEDULED_REMINDERS", defaults.scheduled_reminders)?,
+        notifications_per_hour: optional_value("NOTIFICATIONS_PER_HOUR")?,
+        background_sync_pushes: optional_var(
+            "BACKGROUND_SYNC_PUSHES",
+            defaults.background_sync_pushes,
+        )?,
+    })
+}
+
+/// APNs is off unless `APNS_KEY_FILE` is set; the key id, team id and topic are then required.
+fn apns_from_env() -> Result<Option<ApnsSettings>, Box<dyn Error>> {
+    let Ok(key_file) = env::var("APNS_KEY_FILE") else {
+        return Ok(None);
+    };
+    Ok(Some(ApnsSettings {
+        key_file,
+        key_id: env::var("APNS_KEY_ID")?,
+        team_id: env::var("APNS_TEAM_ID")?,
+        topic: env::var("APNS_TOPIC")?,
+        sandbox: optional_var("APNS_SANDBOX", false)?,
+    }))
+}
+
+fn rate_limit_from_env() -> Result<RateLimitSettings, Box<dyn Error>> {
+    let defaults = RateLimitSettings::default();
+    Ok(RateLimitSettings {
+        per_minute: optional_var("RATE_LIMIT_PER_MINUTE", defaults.per_minute)?,
+        routes: parse_route_limits(
+            &env::var("RATE_LIMIT_ROUTES").unwrap_or_else(|_| DEFAULT_ROUTE_LIMITS.to_string()),
+        )?,
+        // The older boolean switch stands for a single proxy
+        trusted_proxies: match optional_value("RATE_LIMIT_TRUSTED_PROXIES")? {
+            Some(hops) => hops,
+            None if optional_var("RATE_LIMIT_TRUST_PROXY", false)? => 1,
+            None => defaults.trusted_proxies,
+        },
+    })
+}
+
+fn cors_from_env() -> Result<CorsSettings, Box<dyn Error>> {
+    let defaults = CorsSettings::default();
+    Ok(CorsSettings {
+        allowed_origins: list_var("CORS_ALLOWED_ORIGINS").unwrap_or(defaults.allowed_origins),
+        allowed_methods: list_var("CORS_ALLOWED_METHODS").unwrap_or(defaults.allowed_methods),
+        allowed_headers: list_var("CORS_ALLOWED_HEADERS").unwrap_or(defaults.allowed_headers),
+        max_age_secs: optional_var("CORS_MAX_AGE_SECS", defaults.max_age_secs)?,
+    })
+}
+
+/// Comma-separated values, blanks dropped; `None` when the variable is unset.
+fn list_var(key: &str) -> Option<Vec<String>> {
+    env::var(key).ok().map(|value| {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+fn optional_var<T>(key: &str, default: T) -> Result<T, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    Ok(optional_value(key)?.unwrap_or(default))
+}
+
+fn optional_value<T>(key: &str) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(key) {
+        Ok(value) => Ok(Some(
+            value
+                .parse::<T>()
+                .map_err(|e| format!("Invalid {}: {}", key, e))?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
@@ -0,0 +1,254 @@
+use crate::controllers::shared::{admin_auth::require_admin_key, app_state::AppState};
+use crate::controllers::user_controller::MAX_BATCH_SIZE;
+use crate::models::broadcast::{Broadcast, BroadcastReport};
+use crate::models::dead_letter::RedriveReport;
+use crate::models::errors::ApiError;
+use crate::models::history::{HistoryDiffQuery, NotificationDelivery};
+use crate::models::preferences::ExamPeriod;
+use crate::models::rate_limit::REGISTRATION_ROUTE;
+use crate::models::token::Token;
+use crate::models::user_list::UserListQuery;
+use actix_web::{delete, get, middleware::from_fn, post, put, web, HttpResponse};
+use std::slice;
+
+const STATS_PERIOD_DAYS: i64 = 7;
+/// Bulk imports draw registrations from their own bucket, not a client's.
+const BULK_CLIENT: &str = "admin-bulk";
+
+pub fn admin_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/admin")
+            .wrap(from_fn(require_admin_key))
+            .service(create_users_bulk)
+            .service(list_users)
+            .service(get_stats)
+            .service(get_provider_calls)
+            .service(get_flags)
+            .service(get_history_diff)
+            .service(get_notification)
+            .service(broadcast)
+            .service(redrive_dead_letters)
+            .service(get_exam_period)
+            .service(set_exam_period)
+            .service(end_exam_period),
+    );
+}
+
+#[utoipa::path(
+    post, path = "/admin/users/bulk", tag = "admin",
+    request_body = Vec<Token>,
+    responses((status = 200, description = "Registration report per token"), (status = 400, description = "Empty or oversized batch"), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[post("/users/bulk")]
+async fn create_users_bulk(
+    tokens: web::Json<Vec<Token>>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    if tokens.is_empty() || tokens.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::InvalidInput {
+            field: "tokens".to_string(),
+        });
+    }
+    let mut reports = Vec::with_capacity(tokens.len());
+    for token in tokens.iter() {
+        // Paced at the create_user limit so the import doesn't flood Moodle
+        app_state
+            .rate_limiter
+            .acquire(BULK_CLIENT, REGISTRATION_ROUTE)
+            .await;
+        reports.extend(
+            app_state
+                .data_service
+                .register_users(slice::from_ref(token))
+                .await,
+        );
+    }
+    Ok(HttpResponse::Ok().json(reports))
+}
+
+#[utoipa::path(
+    get, path = "/admin/users", tag = "admin",
+    params(UserListQuery),
+    responses((status = 200, description = "One page of registered users"), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[get("/users")]
+async fn list_users(
+    query: web::Query<UserListQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let users = app_state.data_service.list_users(&query).await?;
+    Ok(HttpResponse::Ok().json(users))
+}
+
+#[utoipa::path(
+    get, path = "/admin/stats", tag = "admin",
+    responses((status = 200, description = "Notification and sync stats"), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[get("/stats")]
+async fn get_stats(app_state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let stats = app_state.stats_service.get_stats(STATS_PERIOD_DAYS).await?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+#[utoipa::path(
+    get, path = "/admin/provider_calls", tag = "admin",
+    responses((status = 200, description = "Provider call latency histograms"), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[get("/provider_calls")]
+async fn get_provider_calls(app_state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(app_state.provider_tracer.histograms()))
+}
+
+#[utoipa::path(
+    get, path = "/admin/flags", tag = "admin",
+    responses((status = 200, description = "Active feature flags"), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[get("/flags")]
+async fn get_flags(app_state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(&app_state.feature_flags))
+}
+
+#[utoipa::path(
+    get, path = "/admin/users/{token}/history/diff", tag = "admin",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, description = "Grade changes between two points in time"), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[get("/users/{token}/history/diff")]
+async fn get_history_diff(
+    token: web::Path<String>,
+    query: web::Query<HistoryDiffQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let history = app_state
+        .history_service
+        .get_history_diff(&token.into_inner(), &query)
+        .await?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+/// Shows where a notification got to, for "I never got the push" reports.
+#[utoipa::path(
+    get, path = "/admin/notifications/{id}", tag = "admin",
+    params(("id" = String, Path, description = "Notification id, as listed in the user's inbox")),
+    responses((status = 200, body = NotificationDelivery), (status = 401, description = "Missing or wrong admin key"), (status = 404, description = "Unknown notification"))
+)]
+#[get("/notifications/{id}")]
+async fn get_notification(
+    id: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let notification = app_state
+        .history_service
+        .get_notification(&id.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(notification))
+}
+
+/// Pushes an announcement, such as a schedule change, to every registered
+/// device or to the segment given. Sending the same announcement again skips
+/// the users it already reached within the dedup window.
+#[utoipa::path(
+    post, path = "/admin/broadcast", tag = "admin",
+    request_body = Broadcast,
+    responses((status = 200, body = BroadcastReport), (status = 400, description = "Empty title or body"), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[post("/broadcast")]
+async fn broadcast(
+    broadcast: web::Json<Broadcast>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    broadcast
+        .validate()
+        .map_err(|field| ApiError::InvalidInput { field })?;
+    let report = app_state
+        .producer_service
+        .broadcast(&broadcast)
+        .await
+        .map_err(|e| {
+            eprintln!("Error sending broadcast: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Re-sends the oldest pushes that failed after every retry, once the outage
+/// behind them is over. Call again while the report shows deliveries.
+#[utoipa::path(
+    post, path = "/admin/dead-letters/redrive", tag = "admin",
+    responses((status = 200, body = RedriveReport), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[post("/dead-letters/redrive")]
+async fn redrive_dead_letters(app_state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let report = app_state
+        .producer_service
+        .redrive_dead_letters()
+        .await
+        .map_err(|e| {
+            eprintln!("Error re-driving dead letters: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[utoipa::path(
+    get, path = "/admin/exam-period", tag = "admin",
+    responses((status = 200, body = Option<ExamPeriod>), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[get("/exam-period")]
+async fn get_exam_period(app_state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let period = app_state
+        .producer_service
+        .get_exam_period()
+        .await
+        .map_err(|e| {
+            eprintln!("Error loading exam period: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    Ok(HttpResponse::Ok().json(period))
+}
+
+/// Holds everything but urgent deadlines for the morning digest, for every
+/// user, between `start` and `end`. Users can also set their own period in
+/// their preferences.
+#[utoipa::path(
+    put, path = "/admin/exam-period", tag = "admin",
+    request_body = ExamPeriod,
+    responses((status = 200, body = ExamPeriod), (status = 400, description = "Period ends before it starts"), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[put("/exam-period")]
+async fn set_exam_period(
+    period: web::Json<ExamPeriod>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    if !period.is_valid() {
+        return Err(ApiError::InvalidInput {
+            field: "exam_period".to_string(),
+        });
+    }
+    app_state
+        .producer_service
+        .set_exam_period(Some(&period))
+        .await
+        .map_err(|e| {
+            eprintln!("Error saving exam period: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    Ok(HttpResponse::Ok().json(period.into_inner()))
+}
+
+#[utoipa::path(
+    delete, path = "/admin/exam-period", tag = "admin",
+    responses((status = 200, description = "Exam period was ended"), (status = 401, description = "Missing or wrong admin key"))
+)]
+#[delete("/exam-period")]
+async fn end_exam_period(app_state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    app_state
+        .producer_service
+        .set_exam_period(None)
+        .await
+        .map_err(|e| {
+            eprintln!("Error ending exam period: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    Ok(HttpResponse::Ok().json("Exam period was ended"))
+}
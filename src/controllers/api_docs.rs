@@ -0,0 +1,165 @@
+use crate::controllers::{
+    admin_controller, calendar_controller, course_controller, dashboard_controller,
+    deadline_controller, grade_controller, health_controller, live_controller, metrics_controller,
+    notification_controller, provider_controller, reminder_controller, user_controller,
+};
+use crate::models::broadcast::{Broadcast, BroadcastReport, BroadcastSegment};
+use crate::models::calendar::CalendarLink;
+use crate::models::course::Course;
+use crate::models::dashboard::{Dashboard, RecentGrade};
+use crate::models::dead_letter::RedriveReport;
+use crate::models::deadline::Deadline;
+use crate::models::errors::ErrorBody;
+use crate::models::grade::{CourseGradeItem, Grade, GradeItems, GradeOverview};
+use crate::models::history::{
+    DeliveryStats, DeliveryStatus, InboxNotification, NotificationDelivery, UnreadNotifications,
+};
+use crate::models::notification::{NotificationKind, Priority};
+use crate::models::preferences::{
+    ChannelPreferences, ExamPeriod, NotificationCategories, NotificationPause, Preferences,
+    QuietHours, WeeklyReport,
+};
+use crate::models::reminder::{DeadlineSnooze, Reminder, ReminderRequest, SnoozeRequest};
+use crate::models::settings::UserSettings;
+use crate::models::sync::SyncedData;
+use crate::models::token::{DeviceTokenUpdate, Platform, Token};
+use crate::models::unread::{UnreadAck, UnreadCourse};
+use crate::models::user::User;
+use crate::models::webhook::{UserWebhook, UserWebhookRequest};
+use utoipa::OpenApi;
+
+pub const OPENAPI_URL: &str = "/api-docs/openapi.json";
+
+/// Paths relative to the version prefix, nested under it by [`ApiDoc`].
+#[derive(OpenApi)]
+#[openapi(paths(
+    user_controller::create_user,
+    user_controller::create_users_batch,
+    user_controller::get_user,
+    user_controller::delete_user,
+    user_controller::unregister_device,
+    user_controller::update_device_token,
+    user_controller::refresh_data,
+    user_controller::get_preferences,
+    user_controller::update_preferences,
+    user_controller::mute_course,
+    user_controller::unmute_course,
+    user_controller::get_settings,
+    user_controller::update_settings,
+    user_controller::pause_notifications,
+    user_controller::resume_notifications,
+    user_controller::get_notification_stats,
+    user_controller::get_calendar_link,
+    user_controller::rotate_calendar_secret,
+    user_controller::set_webhook,
+    user_controller::remove_webhook,
+    user_controller::get_unread_courses,
+    user_controller::ack_unread_courses,
+    course_controller::get_courses,
+    dashboard_controller::get_dashboard,
+    course_controller::search_courses,
+    course_controller::get_course_deadlines,
+    course_controller::get_course_grades,
+    grade_controller::get_grades,
+    grade_controller::export_grades,
+    grade_controller::get_grades_overview,
+    deadline_controller::get_deadlines,
+    deadline_controller::get_deadlines_calendar,
+    deadline_controller::snooze_deadline,
+    reminder_controller::create_reminder,
+    notification_controller::get_inbox,
+    notification_controller::get_unread_count,
+    notification_controller::mark_read,
+    live_controller::live_updates,
+    live_controller::live_events,
+    admin_controller::create_users_bulk,
+    admin_controller::list_users,
+    admin_controller::get_stats,
+    admin_controller::get_provider_calls,
+    admin_controller::get_flags,
+    admin_controller::get_history_diff,
+    admin_controller::get_notification,
+    admin_controller::broadcast,
+    admin_controller::redrive_dead_letters,
+    admin_controller::get_exam_period,
+    admin_controller::set_exam_period,
+    admin_controller::end_exam_period,
+    calendar_controller::get_calendar_feed,
+    provider_controller::receive_webhook,
+))]
+struct V1Doc;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "AITU Web App API"),
+    nest((path = "/api/v1", api = V1Doc)),
+    paths(health_controller::live, health_controller::ready, metrics_controller::metrics),
+    components(schemas(
+        ErrorBody,
+        CourseGradeItem,
+        Token,
+        Platform,
+        DeviceTokenUpdate,
+        User,
+        Course,
+        Grade,
+        GradeItems,
+        GradeOverview,
+        Deadline,
+        DeadlineSnooze,
+        SnoozeRequest,
+        Reminder,
+        ReminderRequest,
+        Broadcast,
+        BroadcastSegment,
+        BroadcastReport,
+        RedriveReport,
+        Preferences,
+        UserSettings,
+        NotificationPause,
+        QuietHours,
+        WeeklyReport,
+        ExamPeriod,
+        NotificationCategories,
+        ChannelPreferences,
+        Priority,
+        CalendarLink,
+        UserWebhookRequest,
+        UserWebhook,
+        DeliveryStats,
+        DeliveryStatus,
+        NotificationDelivery,
+        InboxNotification,
+        UnreadNotifications,
+        NotificationKind,
+        SyncedData,
+        Dashboard,
+        RecentGrade,
+        UnreadCourse,
+        UnreadAck,
+    ))
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_covers_controllers_and_models() {
+        let spec = ApiDoc::openapi();
+        for path in [
+            "/api/v1/users/create_user",
+            "/api/v1/courses/{token}",
+            "/api/v1/grades/{token}",
+            "/api/v1/deadlines/{token}",
+            "/health/ready",
+        ] {
+            assert!(spec.paths.paths.contains_key(path), "missing {}", path);
+        }
+        let schemas = spec.components.unwrap().schemas;
+        for schema in ["Token", "User", "Course", "Grade", "Deadline"] {
+            assert!(schemas.contains_key(schema), "missing {}", schema);
+        }
+    }
+}
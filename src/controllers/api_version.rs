@@ -0,0 +1,37 @@
+use crate::controllers::admin_controller::admin_routes;
+use crate::controllers::calendar_controller::calendar_routes;
+use crate::controllers::course_controller::course_routes;
+use crate::controllers::dashboard_controller::dashboard_routes;
+use crate::controllers::deadline_controller::deadline_routes;
+use crate::controllers::grade_controller::grade_routes;
+use crate::controllers::graphql_controller::graphql_routes;
+use crate::controllers::live_controller::live_routes;
+use crate::controllers::notification_controller::notification_routes;
+use crate::controllers::provider_controller::provider_routes;
+use crate::controllers::reminder_controller::reminder_routes;
+use crate::controllers::user_controller::user_routes;
+use actix_web::web;
+
+pub const V1: &str = "/api/v1";
+
+/// Mounts every API version under its prefix. A new version gets its own
+/// scope here; the unprefixed v1 routes stay for app installs that predate it.
+pub fn api_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope(V1).configure(v1_routes));
+    cfg.configure(v1_routes);
+}
+
+fn v1_routes(cfg: &mut web::ServiceConfig) {
+    cfg.configure(user_routes)
+        .configure(course_routes)
+        .configure(dashboard_routes)
+        .configure(grade_routes)
+        .configure(deadline_routes)
+        .configure(reminder_routes)
+        .configure(notification_routes)
+        .configure(live_routes)
+        .configure(graphql_routes)
+        .configure(admin_routes)
+        .configure(calendar_routes)
+        .configure(provider_routes);
+}
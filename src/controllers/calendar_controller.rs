@@ -0,0 +1,65 @@
+use crate::models::calendar::CalendarFeed;
+use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
+use actix_web::http::header::{
+    self, ContentType, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch, LastModified,
+};
+use actix_web::{get, web, HttpMessage, HttpRequest, HttpResponse};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub fn calendar_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/calendar").service(get_calendar_feed));
+}
+
+#[utoipa::path(
+    get, path = "/calendar/{feed_secret}.ics", tag = "calendar",
+    params(("feed_secret" = String, Path, description = "Secret from the user's calendar link")),
+    responses((status = 200, description = "iCalendar feed", content_type = "text/calendar"), (status = 304, description = "Feed not modified"), (status = 404, description = "Feed not found"))
+)]
+#[get("/{feed_secret}.ics")]
+async fn get_calendar_feed(
+    feed_secret: web::Path<String>,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let feed = app_state
+        .data_service
+        .get_calendar_feed(&feed_secret.into_inner())
+        .await?;
+    Ok(feed_response(feed, &req))
+}
+
+/// Serves a feed as `text/calendar`, answering conditional requests with 304.
+pub fn feed_response(feed: CalendarFeed, req: &HttpRequest) -> HttpResponse {
+    let etag = EntityTag::new_strong(feed.etag);
+    let last_modified = feed
+        .last_modified
+        .map(|time| UNIX_EPOCH + Duration::from_secs(time.timestamp().max(0) as u64));
+
+    let not_modified = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => match (req.get_header::<IfModifiedSince>(), last_modified) {
+            (Some(IfModifiedSince(since)), Some(last_modified)) => {
+                last_modified <= SystemTime::from(since)
+            }
+            _ => false,
+        },
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response.insert_header(header::ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(HttpDate::from(last_modified)));
+    }
+
+    if not_modified {
+        return response.finish();
+    }
+    response
+        .content_type(ContentType("text/calendar; charset=utf-8".parse().unwrap()))
+        .body(feed.body)
+}
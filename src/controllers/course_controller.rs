@@ -1,18 +1,96 @@
+use crate::models::course::{Course, CourseQuery, CourseSearchQuery};
+use crate::models::deadline::{Deadline, DeadlineQuery};
+use crate::models::grade::CourseGradeItem;
 use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
-use actix_web::{get, web, HttpResponse};
+use actix_web::{get, routes, web, HttpResponse};
 
 pub fn course_routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("/courses").service(get_courses));
+    cfg.service(
+        web::scope("/courses")
+            .service(search_courses)
+            .service(get_courses)
+            .service(get_course_deadlines)
+            .service(get_course_grades),
+    );
 }
 
+#[utoipa::path(
+    get, path = "/courses/{token}", tag = "courses",
+    params(("token" = String, Path, description = "Moodle web service token"), CourseQuery),
+    responses((status = 200, body = Vec<Course>), (status = 404, description = "Courses not found"))
+)]
+#[routes]
+#[get("/{token}")]
 #[get("/get_courses/{token}")]
 async fn get_courses(
     token: web::Path<String>,
+    query: web::Query<CourseQuery>,
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-    let courses = app_state
+    let mut courses = app_state
         .data_service
         .get_courses(&token.into_inner())
         .await?;
+    if query.active {
+        Course::delete_past_courses(&mut courses);
+    }
     Ok(HttpResponse::Ok().json(courses))
 }
+
+/// Matches stored courses, so a blank `q` is rejected rather than listing everything.
+#[utoipa::path(
+    get, path = "/courses/{token}/search", tag = "courses",
+    params(("token" = String, Path, description = "Moodle web service token"), CourseSearchQuery),
+    responses((status = 200, body = Vec<Course>), (status = 400, description = "Empty search text"))
+)]
+#[get("/{token}/search")]
+async fn search_courses(
+    token: web::Path<String>,
+    query: web::Query<CourseSearchQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let courses = app_state
+        .data_service
+        .search_courses(&token.into_inner(), &query)
+        .await?;
+    Ok(HttpResponse::Ok().json(courses))
+}
+
+/// Deadlines of a single course, soonest first.
+#[utoipa::path(
+    get, path = "/courses/{token}/{course_id}/deadlines", tag = "courses",
+    params(("token" = String, Path, description = "Moodle web service token"), ("course_id" = i64, Path, description = "Course id"), DeadlineQuery),
+    responses((status = 200, body = Vec<Deadline>), (status = 400, description = "Days out of range"), (status = 404, description = "Deadlines not found"))
+)]
+#[get("/{token}/{course_id}/deadlines")]
+async fn get_course_deadlines(
+    path: web::Path<(String, i64)>,
+    query: web::Query<DeadlineQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let (token, course_id) = path.into_inner();
+    let deadlines = app_state
+        .data_service
+        .get_course_deadlines(&token, course_id, &query)
+        .await?;
+    Ok(HttpResponse::Ok().json(deadlines))
+}
+
+/// Grade items of a single course.
+#[utoipa::path(
+    get, path = "/courses/{token}/{course_id}/grades", tag = "courses",
+    params(("token" = String, Path, description = "Moodle web service token"), ("course_id" = i64, Path, description = "Course id")),
+    responses((status = 200, body = Vec<CourseGradeItem>), (status = 404, description = "No grades for the course"))
+)]
+#[get("/{token}/{course_id}/grades")]
+async fn get_course_grades(
+    path: web::Path<(String, i64)>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let (token, course_id) = path.into_inner();
+    let items = app_state
+        .data_service
+        .get_course_grades(&token, course_id)
+        .await?;
+    Ok(HttpResponse::Ok().json(items))
+}
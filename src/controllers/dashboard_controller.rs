@@ -0,0 +1,25 @@
+use crate::models::dashboard::Dashboard;
+use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
+use actix_web::{get, web, HttpResponse};
+
+pub fn dashboard_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/dashboard").service(get_dashboard));
+}
+
+/// Stored user data the app needs on launch, without syncing with the provider.
+#[utoipa::path(
+    get, path = "/dashboard/{token}", tag = "dashboard",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = Dashboard), (status = 404, description = "User not found"))
+)]
+#[get("/{token}")]
+async fn get_dashboard(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let dashboard = app_state
+        .data_service
+        .get_dashboard(&token.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(dashboard))
+}
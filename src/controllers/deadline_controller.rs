@@ -1,18 +1,92 @@
+use crate::controllers::calendar_controller::feed_response;
+use crate::models::deadline::{Deadline, DeadlineQuery};
+use crate::models::pagination::{PageQuery, TOTAL_COUNT_HEADER};
+use crate::models::reminder::{DeadlineSnooze, SnoozeRequest};
 use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
-use actix_web::{get, web, HttpResponse};
+use actix_web::{get, post, routes, web, HttpRequest, HttpResponse};
 
 pub fn deadline_routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("/deadlines").service(get_deadlines));
+    cfg.service(
+        web::scope("/deadlines")
+            .service(get_deadlines)
+            .service(get_deadlines_calendar)
+            .service(snooze_deadline),
+    );
 }
 
+/// Deadlines as stored by the last sync, soonest first. With `page` or
+/// `per_page` the list is paged and `limit` is ignored.
+#[utoipa::path(
+    get, path = "/deadlines/{token}", tag = "deadlines",
+    params(("token" = String, Path, description = "Moodle web service token"), DeadlineQuery, PageQuery),
+    responses((status = 200, body = Vec<Deadline>, headers(("X-Total-Count" = u64, description = "Matching deadlines in total"))), (status = 400, description = "Days out of range"), (status = 404, description = "Deadlines not found"))
+)]
+#[routes]
+#[get("/{token}")]
 #[get("/get_deadlines/{token}")]
 async fn get_deadlines(
     token: web::Path<String>,
+    query: web::Query<DeadlineQuery>,
+    page: web::Query<PageQuery>,
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
+    let token = token.into_inner();
+    if page.is_requested() {
+        let page = app_state
+            .data_service
+            .get_deadlines_page(&token, &query, &page)
+            .await?;
+        return Ok(HttpResponse::Ok()
+            .insert_header((TOTAL_COUNT_HEADER, page.total))
+            .json(page.items));
+    }
     let deadlines = app_state
         .data_service
-        .get_deadlines(&token.into_inner())
+        .get_upcoming_deadlines(&token, &query)
         .await?;
-    Ok(HttpResponse::Ok().json(deadlines))
+    Ok(HttpResponse::Ok()
+        .insert_header((TOTAL_COUNT_HEADER, deadlines.len()))
+        .json(deadlines))
+}
+
+/// Stored deadlines as an iCalendar feed. Calendar apps, which can't send
+/// the API key, subscribe to `/calendar/{feed_secret}.ics` instead.
+#[utoipa::path(
+    get, path = "/deadlines/{token}/calendar.ics", tag = "deadlines",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, description = "iCalendar feed", content_type = "text/calendar"), (status = 304, description = "Feed not modified"), (status = 404, description = "User not found"))
+)]
+#[get("/{token}/calendar.ics")]
+async fn get_deadlines_calendar(
+    token: web::Path<String>,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let feed = app_state
+        .data_service
+        .get_deadlines_calendar(&token.into_inner())
+        .await?;
+    Ok(feed_response(feed, &req))
+}
+
+/// Holds back reminders for a stored deadline for `minutes`; a new snooze
+/// replaces the previous one.
+#[utoipa::path(
+    post, path = "/deadlines/{token}/{deadline_id}/snooze", tag = "deadlines",
+    params(("token" = String, Path, description = "Moodle web service token"), ("deadline_id" = i32, Path, description = "Deadline id")),
+    request_body = SnoozeRequest,
+    responses((status = 200, body = DeadlineSnooze), (status = 400, description = "Snooze outside 1 minute to 1 week"), (status = 404, description = "Deadline not found"))
+)]
+#[post("/{token}/{deadline_id}/snooze")]
+async fn snooze_deadline(
+    path: web::Path<(String, i32)>,
+    request: web::Json<SnoozeRequest>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let (token, deadline_id) = path.into_inner();
+    let snooze = app_state
+        .data_service
+        .snooze_deadline(&token, deadline_id, &request)
+        .await?;
+    Ok(HttpResponse::Ok().json(snooze))
 }
@@ -1,27 +1,93 @@
+use crate::models::grade::{grades_to_csv, Grade, GradeOverview};
+use crate::models::pagination::{PageQuery, TOTAL_COUNT_HEADER};
+use crate::services::errors::ServiceError;
 use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
-use actix_web::{get, web, HttpResponse};
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{get, routes, web, HttpResponse};
 
 pub fn grade_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/grades")
             .service(get_grades)
-            .service(get_grades_overview),
+            .service(export_grades)
+            .route(
+                "/get_grades_overview/{token}",
+                web::get().to(get_grades_overview),
+            ),
+    );
+    cfg.service(
+        web::scope("/grades_overview").route("/{token}", web::get().to(get_grades_overview)),
     );
 }
 
+/// A user whose courses have no grades yet gets 204 instead of 404. With
+/// `page` or `per_page` the courses' grades are paged.
+#[utoipa::path(
+    get, path = "/grades/{token}", tag = "grades",
+    params(("token" = String, Path, description = "Moodle web service token"), PageQuery),
+    responses((status = 200, body = Vec<Grade>, headers(("X-Total-Count" = u64, description = "Courses with grades in total"))), (status = 204, description = "No grades yet"), (status = 404, description = "Grades not found"))
+)]
+#[routes]
+#[get("/{token}")]
 #[get("/get_grades/{token}")]
 async fn get_grades(
     token: web::Path<String>,
+    page: web::Query<PageQuery>,
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-    let grades = app_state
-        .data_service
-        .get_grades(&token.into_inner())
-        .await?;
-    Ok(HttpResponse::Ok().json(grades))
+    let token = token.into_inner();
+    if page.is_requested() {
+        let page = app_state
+            .data_service
+            .get_grades_page(&token, &page)
+            .await?;
+        if page.total == 0 {
+            return Ok(HttpResponse::NoContent().finish());
+        }
+        return Ok(HttpResponse::Ok()
+            .insert_header((TOTAL_COUNT_HEADER, page.total))
+            .json(page.items));
+    }
+    match app_state.data_service.get_grades(&token).await {
+        Ok(grades) => Ok(HttpResponse::Ok()
+            .insert_header((TOTAL_COUNT_HEADER, grades.len()))
+            .json(grades)),
+        Err(ServiceError::DataIsEmpty(_)) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Stored grade items as a CSV attachment; no grades yet gives just the header.
+#[utoipa::path(
+    get, path = "/grades/{token}/export.csv", tag = "grades",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, description = "Grade items as CSV", content_type = "text/csv"), (status = 404, description = "Grades not found"))
+)]
+#[get("/{token}/export.csv")]
+async fn export_grades(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let grades = match app_state.data_service.get_grades(&token.into_inner()).await {
+        Ok(grades) => grades,
+        Err(ServiceError::DataIsEmpty(_)) => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType("text/csv; charset=utf-8".parse().unwrap()))
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("grades.csv".to_string())],
+        })
+        .body(grades_to_csv(&grades)))
 }
 
-#[get("/get_grades_overview/{token}")]
+/// Stored course totals, served without syncing with the provider.
+#[utoipa::path(
+    get, path = "/grades_overview/{token}", tag = "grades",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = Vec<GradeOverview>), (status = 404, description = "Grades not found"))
+)]
 async fn get_grades_overview(
     token: web::Path<String>,
     app_state: web::Data<AppState>,
@@ -0,0 +1,169 @@
+use crate::controllers::shared::app_state::AppState;
+use crate::models::course::Course;
+use crate::models::deadline::Deadline;
+use crate::models::grade::{Grade, GradeOverview};
+use crate::models::user::User;
+use crate::services::data_service_interfaces::DataServiceInterfaces;
+use crate::services::errors::ServiceError;
+use actix_web::{post, web};
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use std::sync::Arc;
+
+pub type StoredDataSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Deepest selection accepted; the schema itself nests four levels.
+const MAX_QUERY_DEPTH: usize = 6;
+/// Fields a query may select, aliases included, as each `user` reads the database.
+const MAX_QUERY_COMPLEXITY: usize = 100;
+
+pub fn graphql_routes(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::Data::new(schema())).service(graphql);
+}
+
+pub fn schema() -> StoredDataSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+}
+
+#[post("/graphql")]
+async fn graphql(
+    schema: web::Data<StoredDataSchema>,
+    request: GraphQLRequest,
+    app_state: web::Data<AppState>,
+) -> GraphQLResponse {
+    let request = request
+        .into_inner()
+        .data(Arc::clone(&app_state.data_service));
+    schema.execute(request).await.into()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Stored data of a registered user; fields are read only when selected.
+    async fn user(&self, ctx: &Context<'_>, token: String) -> async_graphql::Result<UserNode> {
+        let user = data_service(ctx).get_user(&token).await?;
+        Ok(UserNode { token, user })
+    }
+}
+
+pub struct UserNode {
+    token: String,
+    user: User,
+}
+
+#[Object]
+impl UserNode {
+    async fn profile(&self) -> &User {
+        &self.user
+    }
+
+    async fn courses(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Course>> {
+        or_empty(data_service(ctx).get_courses(&self.token).await)
+    }
+
+    async fn grades(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Grade>> {
+        or_empty(data_service(ctx).get_grades(&self.token).await)
+    }
+
+    async fn grades_overview(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<GradeOverview>> {
+        or_empty(data_service(ctx).get_grades_overview(&self.token).await)
+    }
+
+    async fn deadlines(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Deadline>> {
+        or_empty(data_service(ctx).get_deadlines(&self.token).await)
+    }
+}
+
+fn data_service<'a>(ctx: &Context<'a>) -> &'a Arc<dyn DataServiceInterfaces> {
+    ctx.data_unchecked::<Arc<dyn DataServiceInterfaces>>()
+}
+
+/// The user was already found, so missing data just hasn't been synced yet.
+fn or_empty<T>(result: Result<Vec<T>, ServiceError>) -> async_graphql::Result<Vec<T>> {
+    match result {
+        Ok(data) => Ok(data),
+        Err(ServiceError::DataIsEmpty(_) | ServiceError::DataNotFound(_)) => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::feature_flags::FeatureFlags;
+    use crate::models::registration::RegistrationSettings;
+    use crate::services::data_service::DataService;
+    use crate::services::mocks::{course, grade, user, MockProvider, MockRepository};
+
+    #[tokio::test]
+    async fn test_query_returns_only_selected_fields() {
+        let repository = MockRepository::with_tokens(&["token"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.user = Some(user(1));
+            stored.courses = Some(vec![course(1)]);
+            stored.grades = Some(vec![grade(1, &[(10, "60.00 %")])]);
+        }
+        let data_service: Arc<dyn DataServiceInterfaces> = Arc::new(DataService::new(
+            Arc::new(MockProvider::default()),
+            Box::new(repository),
+            RegistrationSettings::default(),
+            FeatureFlags::default(),
+        ));
+
+        let query = r#"{ user(token: "token") {
+            profile { userid }
+            courses { id fullname }
+            grades { courseid gradeitems { percentageformatted } }
+            deadlines { name }
+        } }"#;
+        let response = schema()
+            .execute(async_graphql::Request::new(query).data(data_service))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({"user": {
+                "profile": {"userid": 1},
+                "courses": [{"id": 1, "fullname": "Course 1"}],
+                "grades": [{"courseid": 1, "gradeitems": [{"percentageformatted": "60.00 %"}]}],
+                "deadlines": [],
+            }})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_over_complexity_limit_is_rejected() {
+        let data_service: Arc<dyn DataServiceInterfaces> = Arc::new(DataService::new(
+            Arc::new(MockProvider::default()),
+            Box::new(MockRepository::with_tokens(&["token"])),
+            RegistrationSettings::default(),
+            FeatureFlags::default(),
+        ));
+        let aliases: String = (0..MAX_QUERY_COMPLEXITY)
+            .map(|i| {
+                format!(
+                    r#"u{}: user(token: "token") {{ profile {{ userid }} }} "#,
+                    i
+                )
+            })
+            .collect();
+
+        let response = schema()
+            .execute(async_graphql::Request::new(format!("{{ {} }}", aliases)).data(data_service))
+            .await;
+
+        assert!(!response.errors.is_empty());
+        assert!(response.data.into_json().unwrap().is_null());
+    }
+}
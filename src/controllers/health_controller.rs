@@ -0,0 +1,34 @@
+use crate::controllers::shared::app_state::AppState;
+use crate::services::health::check_readiness;
+use actix_web::{get, web, HttpResponse};
+use serde_json::json;
+use std::time::Duration;
+
+const READINESS_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub fn health_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/health").service(live).service(ready));
+}
+
+#[utoipa::path(
+    get, path = "/health/live", tag = "health",
+    responses((status = 200, description = "Process is up"))
+)]
+#[get("/live")]
+async fn live() -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "status": "ok" }))
+}
+
+#[utoipa::path(
+    get, path = "/health/ready", tag = "health",
+    responses((status = 200, description = "All dependencies are reachable"), (status = 503, description = "A dependency is unreachable"))
+)]
+#[get("/ready")]
+async fn ready(app_state: web::Data<AppState>) -> HttpResponse {
+    let readiness = check_readiness(&app_state.health_checks, READINESS_TIMEOUT).await;
+    if readiness.ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
@@ -0,0 +1,136 @@
+use crate::models::notification::ChangeEvent;
+use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
+use actix_web::{get, rt, web, HttpRequest, HttpResponse};
+use actix_ws::Message;
+use futures_util::{stream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+
+pub fn live_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/ws").service(live_updates));
+    cfg.service(web::scope("/events").service(live_events));
+}
+
+/// Streams change events as JSON text frames while the client stays connected.
+#[utoipa::path(
+    get, path = "/ws/{token}", tag = "live",
+    params(
+        ("token" = String, Path, description = "Moodle web service token"),
+        ("api_key" = Option<String>, Query, description = "API key, as WebSocket clients can't send X-Api-Key")
+    ),
+    responses((status = 101, description = "Switched to WebSocket"), (status = 404, description = "User not found"))
+)]
+#[get("/{token}")]
+async fn live_updates(
+    req: HttpRequest,
+    body: web::Payload,
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let token = token.into_inner();
+    app_state.data_service.get_user(&token).await?;
+    let (response, mut session, mut messages) =
+        actix_ws::handle(&req, body).map_err(|_| ApiError::InvalidInput {
+            field: "WebSocket handshake".to_string(),
+        })?;
+    let mut events = app_state.live_updates.subscribe(&token);
+
+    rt::spawn(async move {
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        let Ok(text) = serde_json::to_string(&event) else {
+                            continue;
+                        };
+                        if session.text(text).await.is_err() {
+                            return;
+                        }
+                    }
+                    // A lagging client misses the oldest events but stays connected
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                message = messages.next() => match message {
+                    Some(Ok(Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
+
+/// Same events as the WebSocket, as a `text/event-stream` for the web client.
+#[utoipa::path(
+    get, path = "/events/{token}", tag = "live",
+    params(
+        ("token" = String, Path, description = "Moodle web service token"),
+        ("api_key" = Option<String>, Query, description = "API key, as EventSource can't send X-Api-Key")
+    ),
+    responses((status = 200, description = "Server-sent change events", content_type = "text/event-stream"), (status = 404, description = "User not found"))
+)]
+#[get("/{token}")]
+async fn live_events(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let token = token.into_inner();
+    app_state.data_service.get_user(&token).await?;
+    let events = app_state.live_updates.subscribe(&token);
+
+    let frames = stream::unfold(events, |mut events| async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let Some(frame) = sse_frame(&event) else {
+                        continue;
+                    };
+                    return Some((Ok::<_, actix_web::Error>(web::Bytes::from(frame)), events));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        // Compression would hold events back until the encoder's buffer fills
+        .insert_header(("Content-Encoding", "identity"))
+        .streaming(frames))
+}
+
+fn sse_frame(event: &ChangeEvent) -> Option<String> {
+    let data = serde_json::to_string(event).ok()?;
+    Some(format!(
+        "event: {}\ndata: {}\n\n",
+        event.category.as_str(),
+        data
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::NotificationKind;
+
+    #[test]
+    fn test_sse_frame_carries_category_and_json() {
+        let event = ChangeEvent {
+            category: NotificationKind::Deadline,
+            title: "Math".to_string(),
+            body: "Essay due".to_string(),
+        };
+        assert_eq!(
+            sse_frame(&event).unwrap(),
+            "event: deadline\ndata: {\"category\":\"deadline\",\"title\":\"Math\",\"body\":\"Essay due\"}\n\n"
+        );
+    }
+}
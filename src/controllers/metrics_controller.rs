@@ -0,0 +1,21 @@
+use crate::controllers::shared::app_state::AppState;
+use actix_web::{get, web, HttpResponse};
+
+pub fn metrics_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(metrics);
+}
+
+#[utoipa::path(
+    get, path = "/metrics", tag = "metrics",
+    responses((status = 200, description = "Prometheus text exposition", content_type = "text/plain"))
+)]
+#[get("/metrics")]
+async fn metrics(app_state: web::Data<AppState>) -> HttpResponse {
+    // Refreshes the registered_users gauge; a failed count keeps the last value
+    if let Err(e) = app_state.stats_service.get_user_stats().await {
+        eprintln!("Error counting users for metrics: {}", e);
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(app_state.metrics.encode())
+}
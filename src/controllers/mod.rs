@@ -1,5 +1,17 @@
+pub mod admin_controller;
+pub mod api_docs;
+pub mod api_version;
+pub mod calendar_controller;
 pub mod course_controller;
+pub mod dashboard_controller;
 pub mod deadline_controller;
 pub mod grade_controller;
+pub mod graphql_controller;
+pub mod health_controller;
+pub mod live_controller;
+pub mod metrics_controller;
+pub mod notification_controller;
+pub mod provider_controller;
+pub mod reminder_controller;
 pub mod shared;
 pub mod user_controller;
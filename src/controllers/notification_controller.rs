@@ -0,0 +1,68 @@
+use crate::models::history::{InboxNotification, UnreadNotifications};
+use crate::models::pagination::{PageQuery, TOTAL_COUNT_HEADER};
+use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
+use actix_web::{get, post, web, HttpResponse};
+
+pub fn notification_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/notifications")
+            .service(get_inbox)
+            .service(get_unread_count)
+            .service(mark_read),
+    );
+}
+
+/// Notifications delivered to the user, newest first, for the app's inbox.
+#[utoipa::path(
+    get, path = "/notifications/{token}", tag = "notifications",
+    params(("token" = String, Path, description = "Moodle web service token"), PageQuery),
+    responses((status = 200, body = Vec<InboxNotification>, headers(("X-Total-Count" = u64, description = "Delivered notifications in total"))), (status = 404, description = "User not found"))
+)]
+#[get("/{token}")]
+async fn get_inbox(
+    token: web::Path<String>,
+    query: web::Query<PageQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let token = token.into_inner();
+    app_state.data_service.get_user(&token).await?;
+    let page = app_state.history_service.get_inbox(&token, &query).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((TOTAL_COUNT_HEADER, page.total))
+        .json(page.items))
+}
+
+/// Read state is stored server-side, so the badge matches across devices.
+#[utoipa::path(
+    get, path = "/notifications/{token}/unread_count", tag = "notifications",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = UnreadNotifications), (status = 404, description = "User not found"))
+)]
+#[get("/{token}/unread_count")]
+async fn get_unread_count(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let token = token.into_inner();
+    app_state.data_service.get_user(&token).await?;
+    let unread = app_state.history_service.get_unread_count(&token).await?;
+    Ok(HttpResponse::Ok().json(unread))
+}
+
+#[utoipa::path(
+    post, path = "/notifications/{token}/{notification_id}/read", tag = "notifications",
+    params(("token" = String, Path, description = "Moodle web service token"), ("notification_id" = String, Path, description = "Id from the inbox")),
+    responses((status = 200, body = UnreadNotifications), (status = 404, description = "Notification not found"))
+)]
+#[post("/{token}/{notification_id}/read")]
+async fn mark_read(
+    path: web::Path<(String, String)>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let (token, notification_id) = path.into_inner();
+    let unread = app_state
+        .history_service
+        .mark_read(&token, &notification_id)
+        .await?;
+    Ok(HttpResponse::Ok().json(unread))
+}
@@ -0,0 +1,52 @@
+use crate::controllers::shared::app_state::AppState;
+use crate::models::errors::ApiError;
+use crate::models::webhook::{verify_signature, ProviderEvent};
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use serde_json::json;
+
+pub const SIGNATURE_HEADER: &str = "X-Provider-Signature";
+
+pub fn provider_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/provider").service(receive_webhook));
+}
+
+#[utoipa::path(
+    post, path = "/provider/webhook", tag = "provider",
+    request_body(content = String, description = "Signed provider event", content_type = "application/json"),
+    responses((status = 200, description = "Event was processed"), (status = 401, description = "Missing or invalid signature"))
+)]
+#[post("/webhook")]
+async fn receive_webhook(
+    req: HttpRequest,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let secret = app_state
+        .provider_webhook_secret
+        .as_deref()
+        .ok_or_else(|| ApiError::DataNotFound {
+            field: "Webhook".to_string(),
+        })?;
+    let signature = req
+        .headers()
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(ApiError::Unauthorized)?;
+    if !verify_signature(secret, &body, signature) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let event: ProviderEvent =
+        serde_json::from_slice(&body).map_err(|_| ApiError::InvalidInput {
+            field: "event".to_string(),
+        })?;
+    let processed = app_state
+        .producer_service
+        .process_event(&event)
+        .await
+        .map_err(|e| {
+            eprintln!("Error processing provider event: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    Ok(HttpResponse::Ok().json(json!({ "processed": processed })))
+}
@@ -0,0 +1,28 @@
+use crate::models::reminder::{Reminder, ReminderRequest};
+use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
+use actix_web::{post, web, HttpResponse};
+
+pub fn reminder_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/reminders").service(create_reminder));
+}
+
+/// Schedules a reminder before a stored deadline, e.g. 3 days and 2 hours
+/// before it. Several reminders may be set for the same deadline.
+#[utoipa::path(
+    post, path = "/reminders/{token}", tag = "reminders",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    request_body = ReminderRequest,
+    responses((status = 201, body = Reminder), (status = 400, description = "Offset out of range or already past"), (status = 404, description = "Deadline not found"))
+)]
+#[post("/{token}")]
+async fn create_reminder(
+    token: web::Path<String>,
+    request: web::Json<ReminderRequest>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let reminder = app_state
+        .reminder_service
+        .create_reminder(&token.into_inner(), &request)
+        .await?;
+    Ok(HttpResponse::Created().json(reminder))
+}
@@ -0,0 +1,30 @@
+use crate::controllers::shared::api_key_auth::key_matches;
+use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error,
+};
+
+pub const ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+pub async fn require_admin_key(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let expected_key = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|app_state| app_state.admin_api_key.clone());
+    let provided_key = req
+        .headers()
+        .get(ADMIN_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    match (expected_key, provided_key) {
+        (Some(expected), Some(provided)) if key_matches(&expected, provided) => {
+            next.call(req).await
+        }
+        _ => Err(ApiError::Unauthorized.into()),
+    }
+}
@@ -0,0 +1,117 @@
+use crate::controllers::api_version::V1;
+use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+/// Query parameter taking the key where browsers can't set headers.
+pub const API_KEY_PARAM: &str = "api_key";
+
+/// WebSocket and event-stream routes, opened by `WebSocket` and
+/// `EventSource`, which send no custom headers.
+const STREAM_PREFIXES: [&str; 2] = ["/ws/", "/events/"];
+
+/// Callers that can't send the header: probes, scrapers, calendar apps
+/// and the provider, whose webhook is signed instead. Calendar feeds are
+/// addressed by a secret.
+const PUBLIC_PREFIXES: [&str; 6] = [
+    "/health",
+    "/metrics",
+    "/calendar/",
+    "/provider/webhook",
+    "/swagger-ui",
+    "/api-docs",
+];
+
+fn is_public(path: &str) -> bool {
+    let path = path.strip_prefix(V1).unwrap_or(path);
+    PUBLIC_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+fn is_stream(path: &str) -> bool {
+    let path = path.strip_prefix(V1).unwrap_or(path);
+    STREAM_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+/// Compares SHA-256 digests in constant time, so response timing leaks
+/// neither the key's bytes nor its length.
+pub fn key_matches(expected: &str, provided: &str) -> bool {
+    Sha256::digest(expected)
+        .ct_eq(&Sha256::digest(provided))
+        .into()
+}
+
+/// Passes everything through while no keys are configured.
+pub async fn require_api_key(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let api_keys = req
+        .app_data::<web::Data<AppState>>()
+        .map(|app_state| app_state.api_keys.clone())
+        .unwrap_or_default();
+    if api_keys.is_empty() || is_public(req.path()) {
+        return next.call(req).await;
+    }
+    let provided_key = match req.headers().get(API_KEY_HEADER) {
+        Some(value) => value.to_str().ok().map(str::to_string),
+        None if is_stream(req.path()) => {
+            web::Query::<HashMap<String, String>>::from_query(req.query_string())
+                .ok()
+                .and_then(|query| query.get(API_KEY_PARAM).cloned())
+        }
+        None => None,
+    };
+
+    // Every key is compared, so timing doesn't reveal which one matched
+    match provided_key {
+        Some(provided)
+            if api_keys
+                .iter()
+                .fold(false, |found, key| found | key_matches(key, &provided)) =>
+        {
+            next.call(req).await
+        }
+        _ => Err(ApiError::Unauthorized.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_header_less_callers_are_public() {
+        assert!(is_public("/health/ready"));
+        assert!(is_public("/calendar/secret.ics"));
+        assert!(is_public("/api/v1/calendar/secret.ics"));
+        assert!(is_public("/api/v1/provider/webhook"));
+        assert!(!is_public("/api/v1/deadlines/token/calendar.ics"));
+        assert!(!is_public("/deadlines/token"));
+        assert!(!is_public("/users/create_user"));
+        assert!(!is_public("/api/v1/grades/token"));
+        assert!(!is_public("/admin/stats"));
+        assert!(is_stream("/api/v1/events/token"));
+        assert!(is_stream("/ws/token"));
+        assert!(!is_stream("/users/get_user/token"));
+    }
+
+    #[test]
+    fn test_key_matches_only_the_exact_key() {
+        assert!(key_matches("secret", "secret"));
+        assert!(!key_matches("secret", "secre"));
+        assert!(!key_matches("secret", "secret2"));
+        assert!(!key_matches("secret", ""));
+    }
+}
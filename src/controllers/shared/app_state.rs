@@ -1,13 +1,30 @@
+use crate::controllers::shared::rate_limit::RateLimiter;
+use crate::infrastructure::client::provider_tracing::ProviderTracer;
+use crate::models::feature_flags::FeatureFlags;
 use crate::services::data_service_interfaces::DataServiceInterfaces;
-use actix_web::web;
+use crate::services::health::HealthCheck;
+use crate::services::history_service_interfaces::HistoryServiceInterface;
+use crate::services::live_updates::LiveUpdates;
+use crate::services::metrics::Metrics;
+use crate::services::producer_service_interfaces::ProducerServiceInterface;
+use crate::services::reminder_service_interfaces::ReminderServiceInterface;
+use crate::services::stats_service_interfaces::StatsServiceInterface;
 use std::sync::Arc;
 
 pub struct AppState {
     pub data_service: Arc<dyn DataServiceInterfaces>,
-}
-
-impl AppState {
-    pub fn new(data_service: Arc<dyn DataServiceInterfaces>) -> web::Data<Self> {
-        web::Data::new(Self { data_service })
-    }
+    pub stats_service: Arc<dyn StatsServiceInterface>,
+    pub producer_service: Arc<dyn ProducerServiceInterface>,
+    pub history_service: Arc<dyn HistoryServiceInterface>,
+    pub reminder_service: Arc<dyn ReminderServiceInterface>,
+    pub provider_tracer: Arc<ProviderTracer>,
+    pub health_checks: Vec<Arc<dyn HealthCheck>>,
+    pub metrics: Arc<Metrics>,
+    pub live_updates: Arc<LiveUpdates>,
+    pub rate_limiter: RateLimiter,
+    pub feature_flags: FeatureFlags,
+    pub admin_api_key: Option<String>,
+    pub api_keys: Vec<String>,
+    pub provider_webhook_secret: Option<String>,
+    pub user_webhook_signing_key: Option<String>,
 }
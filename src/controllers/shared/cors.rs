@@ -0,0 +1,66 @@
+use crate::models::cors::CorsSettings;
+use crate::models::pagination::TOTAL_COUNT_HEADER;
+use actix_cors::Cors;
+
+pub fn cors(settings: &CorsSettings) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(settings.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(settings.allowed_headers.iter().map(String::as_str))
+        .expose_headers([TOTAL_COUNT_HEADER])
+        .max_age(settings.max_age_secs);
+    for origin in &settings.allowed_origins {
+        cors = if origin == "*" {
+            cors.allow_any_origin()
+        } else {
+            cors.allowed_origin(origin)
+        };
+    }
+    cors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_only_configured_origins_pass_preflight() {
+        let settings = CorsSettings {
+            allowed_origins: vec!["https://app.example.com".to_string()],
+            ..Default::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(cors(&settings))
+                .route("/grades/token", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let preflight = |origin: &str| {
+            test::TestRequest::default()
+                .method(actix_web::http::Method::OPTIONS)
+                .uri("/grades/token")
+                .insert_header((header::ORIGIN, origin))
+                .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+                .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, "x-api-key"))
+                .to_request()
+        };
+        let allowed = test::call_service(&app, preflight("https://app.example.com")).await;
+        assert_eq!(allowed.status(), StatusCode::OK);
+        assert_eq!(
+            allowed
+                .headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            "https://app.example.com"
+        );
+
+        let other = test::call_service(&app, preflight("https://evil.example.com")).await;
+        assert_eq!(other.status(), StatusCode::BAD_REQUEST);
+        assert!(other
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+}
@@ -1 +1,5 @@
+pub mod admin_auth;
+pub mod api_key_auth;
 pub mod app_state;
+pub mod cors;
+pub mod rate_limit;
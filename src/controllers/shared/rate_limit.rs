@@ -0,0 +1,219 @@
+use crate::controllers::api_version::V1;
+use crate::controllers::shared::app_state::AppState;
+use crate::models::errors::ApiError;
+use crate::models::rate_limit::{client_address, RateLimitSettings};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error, HttpRequest,
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Buckets kept before refilled ones are first dropped.
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    /// Burst size of the bucket's route, which refills it over a minute.
+    capacity: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn refilled(&self, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        (self.tokens + elapsed * self.capacity / 60.0).min(self.capacity)
+    }
+}
+
+struct Buckets {
+    by_client: HashMap<(String, String), Bucket>,
+    /// Size at which refilled buckets are next dropped; it doubles with the
+    /// buckets still in use, so sweeps stay rare under load.
+    sweep_at: usize,
+}
+
+impl Buckets {
+    fn sweep(&mut self, now: Instant) {
+        self.by_client
+            .retain(|_, bucket| bucket.refilled(now) < bucket.capacity);
+        self.sweep_at = (self.by_client.len() * 2).max(MAX_TRACKED_BUCKETS);
+    }
+}
+
+pub struct RateLimiter {
+    settings: RateLimitSettings,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(settings: RateLimitSettings) -> Self {
+        Self {
+            settings,
+            buckets: Mutex::new(Buckets {
+                by_client: HashMap::new(),
+                sweep_at: MAX_TRACKED_BUCKETS,
+            }),
+        }
+    }
+
+    /// Takes a token from the client's bucket for the path, if one is left.
+    pub fn allow(&self, client: &str, path: &str, now: Instant) -> bool {
+        self.take(client, path, 1, now)
+    }
+
+    /// Takes `count` tokens from the client's bucket for the path, or none
+    /// when fewer are left.
+    pub fn take(&self, client: &str, path: &str, count: u32, now: Instant) -> bool {
+        let path = path.strip_prefix(V1).unwrap_or(path);
+        let (route, per_minute) = self.settings.limit_for(path);
+        if per_minute == 0 || self.settings.per_minute == 0 {
+            return true;
+        }
+        let capacity = per_minute as f64;
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.by_client.len() >= buckets.sweep_at {
+            buckets.sweep(now);
+        }
+        let bucket = buckets
+            .by_client
+            .entry((client.to_string(), route.to_string()))
+            .or_insert(Bucket {
+                tokens: capacity,
+                capacity,
+                updated_at: now,
+            });
+        bucket.tokens = bucket.refilled(now);
+        bucket.updated_at = now;
+        if bucket.tokens < count as f64 {
+            return false;
+        }
+        bucket.tokens -= count as f64;
+        true
+    }
+
+    /// The address the request's buckets are kept under.
+    pub fn client(&self, req: &HttpRequest) -> String {
+        let peer = req.peer_addr().map(|addr| addr.ip().to_string());
+        let forwarded_for = req
+            .headers()
+            .get("X-Forwarded-For")
+            .and_then(|value| value.to_str().ok());
+        client_address(
+            peer.as_deref(),
+            forwarded_for,
+            self.settings.trusted_proxies,
+        )
+        .unwrap_or_default()
+    }
+
+    /// Waits until the client's bucket for the path has a token and takes it,
+    /// for jobs that should slow down instead of failing.
+    pub async fn acquire(&self, client: &str, path: &str) {
+        let path = path.strip_prefix(V1).unwrap_or(path);
+        let (_, per_minute) = self.settings.limit_for(path);
+        while !self.allow(client, path, Instant::now()) {
+            tokio::time::sleep(Duration::from_secs_f64(60.0 / per_minute as f64)).await;
+        }
+    }
+}
+
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(app_state) = req.app_data::<web::Data<AppState>>() {
+        let limiter = &app_state.rate_limiter;
+        let client = limiter.client(req.request());
+        if !limiter.allow(&client, req.path(), Instant::now()) {
+            return Err(ApiError::TooManyRequests.into());
+        }
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::rate_limit::parse_route_limits;
+
+    #[test]
+    fn test_bucket_per_client_and_route_refills_over_time() {
+        let limiter = RateLimiter::new(RateLimitSettings {
+            per_minute: 60,
+            routes: parse_route_limits("/users/create_user=2,/health=0").unwrap(),
+            trusted_proxies: 0,
+        });
+        let start = Instant::now();
+
+        assert!(limiter.allow("1.1.1.1", "/users/create_user", start));
+        assert!(limiter.allow("1.1.1.1", "/api/v1/users/create_user", start));
+        assert!(!limiter.allow("1.1.1.1", "/users/create_user", start));
+        // Other clients and routes have their own buckets
+        assert!(limiter.allow("2.2.2.2", "/users/create_user", start));
+        assert!(limiter.allow("1.1.1.1", "/grades/token", start));
+        assert!((0..100).all(|_| limiter.allow("1.1.1.1", "/health/live", start)));
+
+        // Two per minute refill one token every 30 seconds
+        let later = start + Duration::from_secs(30);
+        assert!(limiter.allow("1.1.1.1", "/users/create_user", later));
+        assert!(!limiter.allow("1.1.1.1", "/users/create_user", later));
+    }
+
+    #[test]
+    fn test_sweep_drops_buckets_refilled_to_their_own_capacity() {
+        let limiter = RateLimiter::new(RateLimitSettings {
+            per_minute: 60,
+            routes: parse_route_limits("/users/create_user=10").unwrap(),
+            trusted_proxies: 0,
+        });
+        let start = Instant::now();
+        assert!(limiter.take("1.1.1.1", "/grades/token", 30, start));
+        assert!(limiter.allow("1.1.1.1", "/users/create_user", start));
+
+        // Six seconds refill one create_user token, but only 6 of the 30 taken
+        let mut buckets = limiter.buckets.lock().unwrap();
+        buckets.sweep(start + Duration::from_secs(6));
+
+        let routes: Vec<_> = buckets.by_client.keys().map(|(_, route)| route).collect();
+        assert_eq!(routes, ["*"]);
+        assert_eq!(buckets.sweep_at, MAX_TRACKED_BUCKETS);
+    }
+
+    #[test]
+    fn test_take_charges_all_or_nothing() {
+        let limiter = RateLimiter::new(RateLimitSettings {
+            per_minute: 60,
+            routes: parse_route_limits("/users/create_user=10").unwrap(),
+            trusted_proxies: 0,
+        });
+        let start = Instant::now();
+
+        assert!(limiter.take("1.1.1.1", "/users/create_user", 8, start));
+        assert!(!limiter.take("1.1.1.1", "/users/create_user", 3, start));
+        assert!(limiter.allow("1.1.1.1", "/users/create_user", start));
+        assert!(limiter.allow("1.1.1.1", "/users/create_user", start));
+        assert!(!limiter.allow("1.1.1.1", "/users/create_user", start));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_a_refill() {
+        let limiter = RateLimiter::new(RateLimitSettings {
+            per_minute: 600,
+            routes: Vec::new(),
+            trusted_proxies: 0,
+        });
+        let start = Instant::now();
+        assert!((0..600).all(|_| limiter.allow("job", "/users/create_user", start)));
+
+        limiter.acquire("job", "/users/create_user").await;
+
+        // 600 per minute refill one token every 100ms
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(!limiter.allow("job", "/users/create_user", Instant::now()));
+    }
+}
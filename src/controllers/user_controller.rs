@@ -1,25 +1,114 @@
-use crate::models::token::Token;
+use crate::models::calendar::CalendarLink;
+use crate::models::history::{DeliveryStats, DeliveryStatsQuery};
+use crate::models::preferences::{NotificationPause, Preferences};
+use crate::models::rate_limit::REGISTRATION_ROUTE;
+use crate::models::settings::UserSettings;
+use crate::models::sync::SyncedData;
+use crate::models::token::{DeviceTokenUpdate, Token};
+use crate::models::unread::{UnreadAck, UnreadCourse};
+use crate::models::user::User;
+use crate::models::webhook::{UserWebhook, UserWebhookRequest};
 use crate::{controllers::shared::app_state::AppState, models::errors::ApiError};
-use actix_web::{delete, get, post, web, HttpResponse};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
+use serde_json::json;
+use std::time::Instant;
+
+/// Also caps admin bulk imports, which are paced instead of rejected.
+pub(crate) const MAX_BATCH_SIZE: usize = 500;
 
 pub fn user_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/users")
             .service(create_user)
+            .service(create_users_batch)
             .service(get_user)
-            .service(delete_user),
+            .service(delete_user)
+            .service(unregister_device)
+            .service(update_device_token)
+            .service(refresh_data)
+            .service(get_preferences)
+            .service(update_preferences)
+            .service(get_settings)
+            .service(mute_course)
+            .service(unmute_course)
+            .service(update_settings)
+            .service(pause_notifications)
+            .service(resume_notifications)
+            .service(get_notification_stats)
+            .service(get_calendar_link)
+            .service(rotate_calendar_secret)
+            .service(set_webhook)
+            .service(remove_webhook)
+            .service(get_unread_courses)
+            .service(ack_unread_courses),
     );
 }
 
+#[utoipa::path(
+    post, path = "/users/create_user", tag = "users",
+    request_body = Token,
+    responses((status = 200, description = "User was created"), (status = 201, description = "User was created with data pending backfill"), (status = 401, description = "Invalid token"))
+)]
 #[post("/create_user")]
 async fn create_user(
     token: web::Json<Token>,
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-    app_state.data_service.register_user(&token).await?;
-    Ok(HttpResponse::Ok().json("User was created"))
+    let outcome = app_state.data_service.register_user(&token).await?;
+    if !outcome.pending.is_empty() {
+        // Created without some data; the next cycle backfills it
+        return Ok(HttpResponse::Created().json(json!({
+            "message": "User was created",
+            "pending": outcome.pending,
+            "verification": outcome.verification,
+        })));
+    }
+    match outcome.verification {
+        Some(verification) => Ok(HttpResponse::Ok().json(json!({
+            "message": "User was created",
+            "verification": verification,
+        }))),
+        None => Ok(HttpResponse::Ok().json("User was created")),
+    }
+}
+
+/// Registers every pair concurrently; each item reports its own outcome.
+/// Each pair counts against the client's `/users/create_user` limit.
+#[utoipa::path(
+    post, path = "/users/batch", tag = "users",
+    request_body = Vec<Token>,
+    responses((status = 200, description = "Registration report per token"), (status = 400, description = "Empty or oversized batch"), (status = 429, description = "Batch exceeds the registrations left this minute"))
+)]
+#[post("/batch")]
+async fn create_users_batch(
+    tokens: web::Json<Vec<Token>>,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    if tokens.is_empty() || tokens.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::InvalidInput {
+            field: "tokens".to_string(),
+        });
+    }
+    let limiter = &app_state.rate_limiter;
+    let client = limiter.client(&req);
+    if !limiter.take(
+        &client,
+        REGISTRATION_ROUTE,
+        tokens.len() as u32,
+        Instant::now(),
+    ) {
+        return Err(ApiError::TooManyRequests);
+    }
+    let reports = app_state.data_service.register_users(&tokens).await;
+    Ok(HttpResponse::Ok().json(reports))
 }
 
+#[utoipa::path(
+    get, path = "/users/get_user/{token}", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = User), (status = 404, description = "User not found"))
+)]
 #[get("/get_user/{token}")]
 async fn get_user(
     token: web::Path<String>,
@@ -29,6 +118,11 @@ async fn get_user(
     Ok(HttpResponse::Ok().json(user))
 }
 
+#[utoipa::path(
+    delete, path = "/users/delete_user/{token}", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, description = "User was deleted"), (status = 404, description = "User not found"))
+)]
 #[delete("/delete_user/{token}")]
 async fn delete_user(
     token: web::Path<String>,
@@ -37,3 +131,336 @@ async fn delete_user(
     app_state.data_service.delete_one_user(&token).await?;
     Ok(HttpResponse::Ok().json("User was deleted"))
 }
+
+#[utoipa::path(
+    delete, path = "/users/{token}/devices/{device_token}", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token"), ("device_token" = String, Path, description = "Device push token")),
+    responses((status = 200, description = "Device was unregistered"), (status = 404, description = "Device not found"))
+)]
+#[delete("/{token}/devices/{device_token}")]
+async fn unregister_device(
+    path: web::Path<(String, String)>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let (token, device_token) = path.into_inner();
+    app_state
+        .data_service
+        .unregister_device(&token, &device_token)
+        .await?;
+    Ok(HttpResponse::Ok().json("Device was unregistered"))
+}
+
+#[utoipa::path(
+    patch, path = "/users/{token}/device_token", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    request_body = DeviceTokenUpdate,
+    responses((status = 200, description = "Device token was updated"), (status = 404, description = "User not found"))
+)]
+#[patch("/{token}/device_token")]
+async fn update_device_token(
+    token: web::Path<String>,
+    update: web::Json<DeviceTokenUpdate>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    app_state
+        .data_service
+        .update_device_token(&token.into_inner(), &update)
+        .await?;
+    Ok(HttpResponse::Ok().json("Device token was updated"))
+}
+
+#[utoipa::path(
+    post, path = "/users/{token}/refresh", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = SyncedData), (status = 401, description = "Token was rejected by the provider"), (status = 404, description = "User not found"))
+)]
+#[post("/{token}/refresh")]
+async fn refresh_data(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let data = app_state
+        .data_service
+        .refresh_data(&token.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(data))
+}
+
+#[utoipa::path(
+    get, path = "/users/{token}/preferences", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = Preferences), (status = 404, description = "User not found"))
+)]
+#[get("/{token}/preferences")]
+async fn get_preferences(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let preferences = app_state
+        .data_service
+        .get_preferences(&token.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[utoipa::path(
+    put, path = "/users/{token}/preferences", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    request_body = Preferences,
+    responses((status = 200, body = Preferences), (status = 404, description = "User not found"))
+)]
+#[put("/{token}/preferences")]
+async fn update_preferences(
+    token: web::Path<String>,
+    preferences: web::Json<Preferences>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let preferences = app_state
+        .data_service
+        .update_preferences(&token.into_inner(), &preferences)
+        .await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[utoipa::path(
+    put, path = "/users/{token}/courses/{course_id}/mute", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token"), ("course_id" = i64, Path, description = "Course id")),
+    responses((status = 200, body = Preferences), (status = 404, description = "User not found"))
+)]
+#[put("/{token}/courses/{course_id}/mute")]
+async fn mute_course(
+    path: web::Path<(String, i64)>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let (token, course_id) = path.into_inner();
+    let preferences = app_state
+        .data_service
+        .set_course_muted(&token, course_id, true)
+        .await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[utoipa::path(
+    delete, path = "/users/{token}/courses/{course_id}/mute", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token"), ("course_id" = i64, Path, description = "Course id")),
+    responses((status = 200, body = Preferences), (status = 404, description = "User not found"))
+)]
+#[delete("/{token}/courses/{course_id}/mute")]
+async fn unmute_course(
+    path: web::Path<(String, i64)>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let (token, course_id) = path.into_inner();
+    let preferences = app_state
+        .data_service
+        .set_course_muted(&token, course_id, false)
+        .await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[utoipa::path(
+    get, path = "/users/{token}/settings", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = UserSettings), (status = 404, description = "User not found"))
+)]
+#[get("/{token}/settings")]
+async fn get_settings(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let settings = app_state
+        .data_service
+        .get_settings(&token.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+#[utoipa::path(
+    put, path = "/users/{token}/settings", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    request_body = UserSettings,
+    responses((status = 200, body = UserSettings), (status = 400, description = "Unsupported language, unknown timezone or invalid sound name"), (status = 404, description = "User not found"))
+)]
+#[put("/{token}/settings")]
+async fn update_settings(
+    token: web::Path<String>,
+    settings: web::Json<UserSettings>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let settings = app_state
+        .data_service
+        .update_settings(&token.into_inner(), &settings)
+        .await?;
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+#[utoipa::path(
+    post, path = "/users/{token}/notifications/pause", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    request_body(content = Option<NotificationPause>),
+    responses((status = 200, body = Preferences), (status = 404, description = "User not found"))
+)]
+#[post("/{token}/notifications/pause")]
+async fn pause_notifications(
+    token: web::Path<String>,
+    pause: Option<web::Json<NotificationPause>>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let until = pause.and_then(|pause| pause.until);
+    let preferences = app_state
+        .data_service
+        .pause_notifications(&token.into_inner(), until)
+        .await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[utoipa::path(
+    post, path = "/users/{token}/notifications/resume", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = Preferences), (status = 404, description = "User not found"))
+)]
+#[post("/{token}/notifications/resume")]
+async fn resume_notifications(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let preferences = app_state
+        .data_service
+        .resume_notifications(&token.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[utoipa::path(
+    get, path = "/users/{token}/notifications/stats", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token"), DeliveryStatsQuery),
+    responses((status = 200, body = DeliveryStats), (status = 404, description = "User not found"))
+)]
+#[get("/{token}/notifications/stats")]
+async fn get_notification_stats(
+    token: web::Path<String>,
+    query: web::Query<DeliveryStatsQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let token = token.into_inner();
+    app_state.data_service.get_user(&token).await?;
+    let stats = app_state
+        .history_service
+        .get_delivery_stats(&token, &query)
+        .await?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+#[utoipa::path(
+    get, path = "/users/{token}/calendar", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = CalendarLink), (status = 404, description = "User not found"))
+)]
+#[get("/{token}/calendar")]
+async fn get_calendar_link(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let secret = app_state
+        .data_service
+        .get_calendar_secret(&token.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(CalendarLink::new(&secret)))
+}
+
+#[utoipa::path(
+    post, path = "/users/{token}/calendar/rotate", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = CalendarLink), (status = 404, description = "User not found"))
+)]
+#[post("/{token}/calendar/rotate")]
+async fn rotate_calendar_secret(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let secret = app_state
+        .data_service
+        .rotate_calendar_secret(&token.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(CalendarLink::new(&secret)))
+}
+
+/// Events are signed with the returned secret in the `X-Keeper-Signature` header.
+#[utoipa::path(
+    put, path = "/users/{token}/webhook", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    request_body = UserWebhookRequest,
+    responses((status = 200, body = UserWebhook), (status = 400, description = "Not a public https URL"), (status = 404, description = "User not found or webhooks are off"))
+)]
+#[put("/{token}/webhook")]
+async fn set_webhook(
+    token: web::Path<String>,
+    request: web::Json<UserWebhookRequest>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let signing_key = app_state
+        .user_webhook_signing_key
+        .as_deref()
+        .ok_or_else(|| ApiError::DataNotFound {
+            field: "Webhook".to_string(),
+        })?;
+    let url = app_state
+        .data_service
+        .set_webhook(&token.into_inner(), &request.url)
+        .await?;
+    Ok(HttpResponse::Ok().json(UserWebhook::new(signing_key, url)))
+}
+
+#[utoipa::path(
+    delete, path = "/users/{token}/webhook", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, description = "Webhook was removed"), (status = 404, description = "No webhook registered"))
+)]
+#[delete("/{token}/webhook")]
+async fn remove_webhook(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    app_state
+        .data_service
+        .remove_webhook(&token.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json("Webhook was removed"))
+}
+
+#[utoipa::path(
+    get, path = "/users/{token}/courses/unread", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    responses((status = 200, body = Vec<UnreadCourse>), (status = 404, description = "User not found"))
+)]
+#[get("/{token}/courses/unread")]
+async fn get_unread_courses(
+    token: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let unread = app_state
+        .data_service
+        .get_unread_courses(&token.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(unread))
+}
+
+#[utoipa::path(
+    post, path = "/users/{token}/courses/unread/ack", tag = "users",
+    params(("token" = String, Path, description = "Moodle web service token")),
+    request_body(content = Option<UnreadAck>),
+    responses((status = 200, body = Vec<UnreadCourse>), (status = 404, description = "User not found"))
+)]
+#[post("/{token}/courses/unread/ack")]
+async fn ack_unread_courses(
+    token: web::Path<String>,
+    ack: Option<web::Json<UnreadAck>>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let course_ids = ack.and_then(|ack| ack.into_inner().course_ids);
+    let unread = app_state
+        .data_service
+        .ack_unread(&token.into_inner(), course_ids.as_deref())
+        .await?;
+    Ok(HttpResponse::Ok().json(unread))
+}
@@ -1,70 +1,321 @@
 use crate::{
     config::Config,
-    controllers::shared::app_state::AppState,
-    repositories::data_repository::DataRepository,
+    controllers::shared::{app_state::AppState, rate_limit::RateLimiter},
+    models::{
+        registration::RegistrationSettings,
+        stats::{BatchReport, CycleReport},
+        templates::NotificationTemplates,
+        token::Platform,
+    },
+    repositories::{
+        data_repository::DataRepository, dead_letter_repository::DeadLetterRepository,
+        exam_period_repository::ExamPeriodRepository,
+        history_archive_repository::HistoryArchiveRepository,
+        history_repository::HistoryRepository, reminder_repository::ReminderRepository,
+        stats_repository::StatsRepository, token_change_stream::TokenChangeStream,
+    },
     services::{
-        data_service::DataService, data_service_interfaces::DataServiceInterfaces,
-        producer_service::ProducerService, producer_service_interfaces::ProducerServiceInterface,
+        change_listener::{listen_device_token_changes, DeviceTokenChangeSource},
+        data_service::DataService,
+        data_service_interfaces::DataServiceInterfaces,
+        health::HealthCheck,
+        history_service::HistoryService,
+        history_service_interfaces::HistoryServiceInterface,
+        live_updates::LiveUpdates,
+        metrics::Metrics,
+        producer_service::ProducerService,
+        producer_service_interfaces::ProducerServiceInterface,
         provider_interfaces::DataProviderInterface,
+        reminder_service::ReminderService,
+        reminder_service_interfaces::ReminderServiceInterface,
+        retention_service::{RetentionService, PRUNE_INTERVAL},
+        retry_budget::RetryBudget,
+        stats_service::StatsService,
+        stats_service_interfaces::StatsServiceInterface,
     },
 };
 use actix_web::web::Data;
 use anyhow::Result;
+use mongodb::bson::DateTime;
 use std::sync::Arc;
+use std::time::Duration;
 
 use super::{
-    client::moodle_client::MoodleClient, db::db_connection::connect,
-    event_producer::producer::EventProducer,
+    client::{
+        limited_provider::LimitedProvider, moodle_client::MoodleClient,
+        provider_tracing::ProviderTracer, retrying_provider::RetryingProvider,
+    },
+    db::db_connection::{connect, MongoHealthCheck},
+    event_producer::{
+        apns::ApnsProducer, fcm::FcmProducer, producer::EventProducer,
+        retrying_producer::RetryingProducer, telegram::TelegramProducer,
+        transport_router::TransportRouter, webhook::WebhookProducer,
+    },
 };
 
 pub struct AppDependencies {
     pub data_service: Arc<dyn DataServiceInterfaces>,
-    pub producer_service: Box<dyn ProducerServiceInterface>,
+    pub producer_service: Arc<dyn ProducerServiceInterface>,
+    pub stats_service: Arc<dyn StatsServiceInterface>,
+    pub history_service: Arc<dyn HistoryServiceInterface>,
+    pub reminder_service: Arc<dyn ReminderServiceInterface>,
+    pub retention_service: Arc<RetentionService>,
+    pub provider_tracer: Arc<ProviderTracer>,
+    pub device_token_changes: Option<Box<dyn DeviceTokenChangeSource>>,
+    pub health_checks: Vec<Arc<dyn HealthCheck>>,
+    pub metrics: Arc<Metrics>,
+    pub live_updates: Arc<LiveUpdates>,
 }
 
 pub async fn initialize_dependencies(config: &Config) -> Result<AppDependencies> {
     // Initialize Moodle client
-    let moodle_client: Arc<dyn DataProviderInterface> = Arc::new(MoodleClient::new(
+    let provider_tracer = Arc::new(ProviderTracer::default());
+    let metrics = Arc::new(Metrics::new());
+    let retry_budget = Arc::new(RetryBudget::new(config.provider_retry_budget));
+    let moodle = Arc::new(MoodleClient::new(
         config.base_url.clone(),
         config.format_url.clone(),
+        config.provider_functions.clone(),
+        config.provider_gzip,
+        Arc::clone(&provider_tracer),
+        Arc::clone(&metrics),
     ));
+    let moodle_client: Arc<dyn DataProviderInterface> = moodle.clone();
+    // Registration and the producer loop get separate lanes so neither starves the other
+    let provider_lane = |max_concurrent: usize| -> Arc<dyn DataProviderInterface> {
+        Arc::new(RetryingProvider::new(
+            Arc::new(LimitedProvider::new(
+                Arc::clone(&moodle_client),
+                max_concurrent,
+            )),
+            config.provider_max_retries,
+            Duration::from_millis(config.provider_retry_backoff_ms),
+            Arc::clone(&retry_budget),
+        ))
+    };
+    let interactive_provider = provider_lane(config.interactive_provider_concurrency);
+    let background_provider = provider_lane(config.background_provider_concurrency);
 
     // Initialize database
-    let db = connect(&config.mongo_uri).await?.collection("users");
-    let data_repository = Box::new(DataRepository::new(db));
+    let db = connect(&config.mongo_uri).await?;
+    let health_checks: Vec<Arc<dyn HealthCheck>> =
+        vec![Arc::new(MongoHealthCheck::new(db.clone())), moodle];
+    let stats_repository = StatsRepository::new(
+        db.collection("users"),
+        db.collection("notification_stats"),
+        db.collection("cycle_reports"),
+    );
+    stats_repository
+        .create_indexes(config.cycle_report_retention_days)
+        .await?;
+    let history_repository = HistoryRepository::new(db.collection("notification_history"));
+    history_repository.create_indexes().await?;
+    let history_archive_repository = HistoryArchiveRepository::new(
+        db.collection("notification_history"),
+        db.collection("notification_history_archive"),
+    );
+    history_archive_repository
+        .create_indexes(config.notification_archive_retention_days)
+        .await?;
+    let reminder_repository = ReminderRepository::new(db.collection("reminders"));
+    reminder_repository.create_indexes().await?;
+    let dead_letter_repository = DeadLetterRepository::new(db.collection("dead_letters"));
+    dead_letter_repository.create_indexes().await?;
+    let exam_period_repository = ExamPeriodRepository::new(db.collection("exam_periods"));
+    let data_repository = DataRepository::new(db.collection("users"));
+    data_repository.create_indexes().await?;
+    let migrated = data_repository.migrate_device_tokens().await?;
+    if migrated > 0 {
+        println!("Moved {} device tokens to device lists", migrated);
+    }
+
+    let device_token_changes: Option<Box<dyn DeviceTokenChangeSource>> =
+        if config.feature_flags.watch_device_tokens {
+            Some(Box::new(
+                TokenChangeStream::watch(&db.collection("users")).await?,
+            ))
+        } else {
+            None
+        };
 
     // Initialize services
+    let registration_settings = RegistrationSettings {
+        concurrency: config.registration_concurrency,
+        device_token_policy: config.device_token_policy,
+    };
     let data_service: Arc<dyn DataServiceInterfaces> = Arc::new(DataService::new(
-        Arc::clone(&moodle_client),
-        data_repository,
+        interactive_provider,
+        Box::new(data_repository),
+        registration_settings.clone(),
+        config.feature_flags.clone(),
+    ));
+    let background_data_service: Arc<dyn DataServiceInterfaces> = Arc::new(DataService::new(
+        Arc::clone(&background_provider),
+        Box::new(DataRepository::new(db.collection("users"))),
+        registration_settings,
+        config.feature_flags.clone(),
     ));
-    let producer = Box::new(EventProducer::new(&config.kafka_url));
-    let producer_service = Box::new(ProducerService::new(
-        producer,
-        Arc::clone(&moodle_client),
+    let stats_service: Arc<dyn StatsServiceInterface> = Arc::new(StatsService::new(
+        Box::new(stats_repository),
+        Arc::clone(&metrics),
+    ));
+    let history_service: Arc<dyn HistoryServiceInterface> = Arc::new(HistoryService::new(
+        Box::new(history_repository),
+        config.notification_dedup_window_hours,
+    ));
+    let retention_service = Arc::new(RetentionService::new(
+        Box::new(history_archive_repository),
+        config.notification_history_retention_days,
+    ));
+    let reminder_service: Arc<dyn ReminderServiceInterface> = Arc::new(ReminderService::new(
+        Box::new(reminder_repository),
         Arc::clone(&data_service),
     ));
+    let event_producer = EventProducer::new(&config.kafka_url);
+    let platform_transports: Vec<_> = [
+        (Platform::Ios, &config.ios_notification_topic),
+        (Platform::Android, &config.android_notification_topic),
+    ]
+    .into_iter()
+    .filter_map(|(platform, topic)| {
+        topic
+            .as_ref()
+            .map(|topic| (platform, event_producer.with_topic(topic)))
+    })
+    .collect();
+    let mut transport_router = TransportRouter::new(Box::new(event_producer));
+    for (platform, transport) in platform_transports {
+        transport_router = transport_router.route(platform, Box::new(transport));
+    }
+    if let Some(path) = &config.fcm_service_account_file {
+        // Replaces the Android topic route, bypassing the broker
+        transport_router =
+            transport_router.route(Platform::Android, Box::new(FcmProducer::from_file(path)?));
+    }
+    if let Some(apns) = &config.apns {
+        transport_router =
+            transport_router.route(Platform::Ios, Box::new(ApnsProducer::new(apns.clone())?));
+    }
+    if let Some(bot_token) = &config.telegram_bot_token {
+        transport_router = transport_router.route(
+            Platform::Telegram,
+            Box::new(TelegramProducer::new(bot_token.clone())?),
+        );
+    }
+    if let Some(signing_key) = &config.user_webhook_signing_key {
+        transport_router = transport_router.route(
+            Platform::Webhook,
+            Box::new(WebhookProducer::new(signing_key.clone())?),
+        );
+    }
+    let producer = Box::new(RetryingProducer::new(
+        Box::new(transport_router),
+        config.producer_max_retries,
+        Duration::from_millis(config.producer_retry_backoff_ms),
+    ));
+    let templates = match &config.notification_templates_file {
+        Some(path) => NotificationTemplates::from_file(path)?,
+        None => NotificationTemplates::default(),
+    };
+    let live_updates = Arc::new(LiveUpdates::default());
+    let producer_service: Arc<dyn ProducerServiceInterface> = Arc::new(
+        ProducerService::new(
+            producer,
+            background_provider,
+            background_data_service,
+            Arc::clone(&stats_service),
+            Arc::clone(&history_service),
+            config.feature_flags.clone(),
+            retry_budget,
+        )
+        .with_live_updates(Arc::clone(&live_updates))
+        .with_reminders(Arc::clone(&reminder_service))
+        .with_templates(Arc::new(templates))
+        .with_dead_letters(Arc::new(dead_letter_repository))
+        .with_exam_periods(Arc::new(exam_period_repository)),
+    );
 
     Ok(AppDependencies {
         data_service,
         producer_service,
+        stats_service,
+        history_service,
+        reminder_service,
+        retention_service,
+        provider_tracer,
+        device_token_changes,
+        health_checks,
+        metrics,
+        live_updates,
     })
 }
 
 pub async fn spawn_background_tasks(
-    producer_service: Box<dyn ProducerServiceInterface>,
+    producer_service: Arc<dyn ProducerServiceInterface>,
+    stats_service: Arc<dyn StatsServiceInterface>,
     batch_size: i64,
 ) {
     tokio::spawn(async move {
         let mut skip = 0;
+        let mut cycle_started_at = DateTime::now();
+        let mut cycle = BatchReport::default();
+        loop {
+            match producer_service.get_batches(batch_size, &mut skip).await {
+                Ok(report) => cycle += report,
+                Err(e) => eprintln!("Error in sending notifications: {}", e),
+            }
+
+            // Skip is reset once every token has been visited
+            if skip == 0 {
+                if cycle.tokens_processed + cycle.errors > 0 {
+                    let report = CycleReport::new(cycle_started_at, DateTime::now(), cycle);
+                    if let Err(e) = stats_service.record_cycle(&report).await {
+                        eprintln!("Error saving cycle report: {}", e);
+                    }
+                }
+                cycle_started_at = DateTime::now();
+                cycle = BatchReport::default();
+            }
+        }
+    });
+}
+
+pub fn spawn_retention_job(retention_service: Arc<RetentionService>) {
+    tokio::spawn(async move {
         loop {
-            if let Err(e) = producer_service.get_batches(batch_size, &mut skip).await {
-                eprintln!("Error in sending notifications: {}", e);
+            match retention_service.prune_history().await {
+                Ok(0) => {}
+                Ok(archived) => println!("Archived {} notification history entries", archived),
+                Err(e) => eprintln!("Error pruning notification history: {}", e),
             }
+            tokio::time::sleep(PRUNE_INTERVAL).await;
         }
     });
 }
 
-pub fn create_app_state(data_service: Arc<dyn DataServiceInterfaces>) -> Data<AppState> {
-    AppState::new(data_service)
+pub fn spawn_device_token_listener(
+    source: Box<dyn DeviceTokenChangeSource>,
+    producer_service: Arc<dyn ProducerServiceInterface>,
+) {
+    tokio::spawn(listen_device_token_changes(source, producer_service));
+}
+
+pub fn create_app_state(deps: AppDependencies, config: &Config) -> Data<AppState> {
+    Data::new(AppState {
+        data_service: deps.data_service,
+        stats_service: deps.stats_service,
+        producer_service: deps.producer_service,
+        history_service: deps.history_service,
+        reminder_service: deps.reminder_service,
+        provider_tracer: deps.provider_tracer,
+        health_checks: deps.health_checks,
+        metrics: deps.metrics,
+        live_updates: deps.live_updates,
+        rate_limiter: RateLimiter::new(config.rate_limit.clone()),
+        feature_flags: config.feature_flags.clone(),
+        admin_api_key: config.admin_api_key.clone(),
+        api_keys: config.api_keys.clone(),
+        provider_webhook_secret: config.provider_webhook_secret.clone(),
+        user_webhook_signing_key: config.user_webhook_signing_key.clone(),
+    })
 }
@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::Error;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::models::course::Course;
+use crate::models::course_content::ContentSection;
+use crate::models::deadline::Events;
+use crate::models::grade::{GradesOverview, UserGrades};
+use crate::models::user::User;
+use crate::services::provider_interfaces::DataProviderInterface;
+
+/// Caps in-flight provider calls of one caller group, so one group can't starve another.
+pub struct LimitedProvider {
+    inner: Arc<dyn DataProviderInterface>,
+    permits: Semaphore,
+}
+
+impl LimitedProvider {
+    pub fn new(inner: Arc<dyn DataProviderInterface>, max_concurrent: usize) -> Self {
+        Self {
+            inner,
+            permits: Semaphore::new(max_concurrent.max(1)),
+        }
+    }
+
+    async fn permit(&self) -> SemaphorePermit<'_> {
+        self.permits
+            .acquire()
+            .await
+            .expect("provider semaphore is never closed")
+    }
+}
+
+#[async_trait]
+impl DataProviderInterface for LimitedProvider {
+    async fn get_user(&self, token: &str) -> Result<User, Error> {
+        let _permit = self.permit().await;
+        self.inner.get_user(token).await
+    }
+
+    async fn valid_token(&self, token: &str) -> Result<(), Error> {
+        let _permit = self.permit().await;
+        self.inner.valid_token(token).await
+    }
+
+    async fn get_courses(&self, token: &str, user_id: i64) -> Result<Vec<Course>, Error> {
+        let _permit = self.permit().await;
+        self.inner.get_courses(token, user_id).await
+    }
+
+    async fn get_grades_by_course_id(
+        &self,
+        token: &str,
+        user_id: i64,
+        course_id: i64,
+    ) -> Result<UserGrades, Error> {
+        let _permit = self.permit().await;
+        self.inner
+            .get_grades_by_course_id(token, user_id, course_id)
+            .await
+    }
+
+    async fn get_deadline_by_course_id(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Events, Error> {
+        let _permit = self.permit().await;
+        self.inner.get_deadline_by_course_id(token, course_id).await
+    }
+
+    async fn get_grades_overview(&self, token: &str) -> Result<GradesOverview, Error> {
+        let _permit = self.permit().await;
+        self.inner.get_grades_overview(token).await
+    }
+
+    async fn get_course_contents(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Vec<ContentSection>, Error> {
+        let _permit = self.permit().await;
+        self.inner.get_course_contents(token, course_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mocks::{user, MockProvider};
+    use std::time::Duration;
+
+    /// Calls for the "slow" token never complete.
+    struct SlowProvider {
+        inner: MockProvider,
+    }
+
+    #[async_trait]
+    impl DataProviderInterface for SlowProvider {
+        async fn get_user(&self, token: &str) -> Result<User, Error> {
+            if token == "slow" {
+                std::future::pending::<()>().await;
+            }
+            self.inner.get_user(token).await
+        }
+
+        async fn valid_token(&self, token: &str) -> Result<(), Error> {
+            self.inner.valid_token(token).await
+        }
+
+        async fn get_courses(&self, token: &str, user_id: i64) -> Result<Vec<Course>, Error> {
+            self.inner.get_courses(token, user_id).await
+        }
+
+        async fn get_grades_by_course_id(
+            &self,
+            token: &str,
+            user_id: i64,
+            course_id: i64,
+        ) -> Result<UserGrades, Error> {
+            self.inner
+                .get_grades_by_course_id(token, user_id, course_id)
+                .await
+        }
+
+        async fn get_deadline_by_course_id(
+            &self,
+            token: &str,
+            course_id: i64,
+        ) -> Result<Events, Error> {
+            self.inner.get_deadline_by_course_id(token, course_id).await
+        }
+
+        async fn get_grades_overview(&self, token: &str) -> Result<GradesOverview, Error> {
+            self.inner.get_grades_overview(token).await
+        }
+
+        async fn get_course_contents(
+            &self,
+            token: &str,
+            course_id: i64,
+        ) -> Result<Vec<ContentSection>, Error> {
+            self.inner.get_course_contents(token, course_id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_saturated_background_lane_does_not_starve_interactive_lane() {
+        let shared = Arc::new(SlowProvider {
+            inner: MockProvider::default(),
+        });
+        let background = Arc::new(LimitedProvider::new(shared.clone(), 2));
+        let interactive = LimitedProvider::new(shared.clone(), 2);
+
+        let mut stuck = Vec::new();
+        for _ in 0..4 {
+            let background = Arc::clone(&background);
+            stuck.push(tokio::spawn(
+                async move { background.get_user("slow").await },
+            ));
+        }
+        tokio::task::yield_now().await;
+        assert_eq!(background.permits.available_permits(), 0);
+
+        let timeout = Duration::from_secs(1);
+        let registered = tokio::time::timeout(timeout, interactive.get_user("fast")).await;
+        assert_eq!(registered.unwrap().unwrap(), user(1));
+
+        // The same call queued behind the background load would not get through
+        let queued = tokio::time::timeout(Duration::from_millis(50), background.get_user("fast"));
+        assert!(queued.await.is_err());
+
+        for call in stuck {
+            call.abort();
+        }
+    }
+}
@@ -1 +1,5 @@
+pub mod limited_provider;
 pub mod moodle_client;
+pub mod provider_functions;
+pub mod provider_tracing;
+pub mod retrying_provider;
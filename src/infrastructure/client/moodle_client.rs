@@ -1,60 +1,147 @@
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::models::course::Course;
+use crate::models::course_content::ContentSection;
 use crate::models::deadline::Events;
 use crate::models::grade::{GradesOverview, UserGrades};
 use crate::models::user::User;
+use crate::services::health::HealthCheck;
+use crate::services::metrics::Metrics;
 use crate::services::provider_interfaces::DataProviderInterface;
 use async_trait::async_trait;
-use reqwest::{Client, Error};
+use reqwest::{Client, Error, Response};
+use serde::de::DeserializeOwned;
+
+use super::provider_functions::ProviderFunctions;
+use super::provider_tracing::{ProviderCall, ProviderTracer};
 
 pub struct MoodleClient {
     client: Client,
     base_url: String,
     format: String,
+    functions: ProviderFunctions,
+    tracer: Arc<ProviderTracer>,
+    metrics: Arc<Metrics>,
 }
 
 impl MoodleClient {
-    pub fn new(base_url: String, format: String) -> Self {
+    /// With `gzip` the client advertises `Accept-Encoding: gzip` and decodes
+    /// compressed responses transparently.
+    pub fn new(
+        base_url: String,
+        format: String,
+        functions: ProviderFunctions,
+        gzip: bool,
+        tracer: Arc<ProviderTracer>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
         Self {
             client: Client::builder()
                 .timeout(Duration::from_secs(15))
+                .gzip(gzip)
                 .build()
                 .unwrap(),
             base_url,
             format,
+            functions,
+            tracer,
+            metrics,
+        }
+    }
+
+    fn url(&self, token: &str, function: &str, params: &str) -> String {
+        format!(
+            "{}wstoken={}&wsfunction={}{}{}",
+            self.base_url, token, function, self.format, params
+        )
+    }
+
+    fn trace(&self, call: &ProviderCall) {
+        self.tracer.record(call);
+        self.metrics.provider_request(&call.endpoint, call.duration);
+    }
+
+    /// Sends the request and traces it; the body is buffered so its size can be recorded.
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        function: &str,
+        params: &str,
+    ) -> Result<T, Error> {
+        let url = self.url(token, function, params);
+        let started = Instant::now();
+        let response = match self.client.get(&url).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                let status = e.status().map(|status| status.as_u16());
+                let call = ProviderCall::new(function, &url, status, 0, started.elapsed());
+                self.trace(&call);
+                return Err(e.without_url());
+            }
+        };
+
+        let status = response.status().as_u16();
+        let body = response.bytes().await;
+        let response_bytes = body.as_ref().map_or(0, |body| body.len());
+        let call = ProviderCall::new(
+            function,
+            &url,
+            Some(status),
+            response_bytes,
+            started.elapsed(),
+        );
+        self.trace(&call);
+
+        // Errors carry the request URL, which contains the token
+        let body = body.map_err(Error::without_url)?;
+        Response::from(http::Response::new(body))
+            .json::<T>()
+            .await
+            .map_err(Error::without_url)
+    }
+}
+
+/// Any response short of a server error means Moodle is reachable; no token is sent.
+#[async_trait]
+impl HealthCheck for MoodleClient {
+    fn name(&self) -> &'static str {
+        "moodle"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        let response = self
+            .client
+            .get(&self.base_url)
+            .send()
+            .await
+            .map_err(|e| e.without_url().to_string())?;
+        if response.status().is_server_error() {
+            return Err(format!("status {}", response.status()));
         }
+        Ok(())
     }
 }
 
 #[async_trait]
 impl DataProviderInterface for MoodleClient {
     async fn get_user(&self, token: &str) -> Result<User, Error> {
-        let url = format!(
-            "{}wstoken={}&wsfunction=core_webservice_get_site_info{}",
-            self.base_url, token, self.format
-        );
-        let response = self.client.get(&url).send().await?;
-        response.json::<User>().await
+        self.fetch(token, &self.functions.site_info, "").await
     }
 
     async fn valid_token(&self, token: &str) -> Result<(), Error> {
-        let url = format!(
-            "{}wstoken={}&wsfunction=core_webservice_get_site_info{}",
-            self.base_url, token, self.format
-        );
-        let response = self.client.get(&url).send().await?;
-        response.json::<User>().await?;
+        self.fetch::<User>(token, &self.functions.site_info, "")
+            .await?;
         Ok(())
     }
 
     async fn get_courses(&self, token: &str, user_id: i64) -> Result<Vec<Course>, Error> {
-        let url = format!(
-            "{}wstoken={}&wsfunction=core_enrol_get_users_courses{}&userid={}",
-            self.base_url, token, self.format, user_id,
-        );
-        let response = self.client.get(&url).send().await?;
-        response.json::<Vec<Course>>().await
+        self.fetch(
+            token,
+            &self.functions.users_courses,
+            &format!("&userid={}", user_id),
+        )
+        .await
     }
 
     async fn get_grades_by_course_id(
@@ -63,12 +150,12 @@ impl DataProviderInterface for MoodleClient {
         user_id: i64,
         course_id: i64,
     ) -> Result<UserGrades, Error> {
-        let url = format!(
-            "{}wstoken={}&wsfunction=gradereport_user_get_grade_items{}&userid={}&courseid={}",
-            self.base_url, token, self.format, user_id, course_id
-        );
-        let response = self.client.get(&url).send().await?;
-        response.json::<UserGrades>().await
+        self.fetch(
+            token,
+            &self.functions.grade_items,
+            &format!("&userid={}&courseid={}", user_id, course_id),
+        )
+        .await
     }
 
     async fn get_deadline_by_course_id(
@@ -76,20 +163,164 @@ impl DataProviderInterface for MoodleClient {
         token: &str,
         course_id: i64,
     ) -> Result<Events, Error> {
-        let url = format!(
-            "{}wstoken={}&wsfunction=core_calendar_get_action_events_by_course{}&courseid={}",
-            self.base_url, token, self.format, course_id,
-        );
-        let response = self.client.get(&url).send().await?;
-        response.json::<Events>().await
+        self.fetch(
+            token,
+            &self.functions.course_events,
+            &format!("&courseid={}", course_id),
+        )
+        .await
     }
 
     async fn get_grades_overview(&self, token: &str) -> Result<GradesOverview, Error> {
-        let url = format!(
-            "{}wstoken={}&wsfunction=gradereport_overview_get_course_grades{}",
-            self.base_url, token, self.format
+        self.fetch(token, &self.functions.grades_overview, "").await
+    }
+
+    async fn get_course_contents(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Vec<ContentSection>, Error> {
+        self.fetch(
+            token,
+            &self.functions.course_contents,
+            &format!("&courseid={}", course_id),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// Serves a single HTTP response and hands back the raw request it received.
+    fn serve_once(body: &'static str) -> (String, JoinHandle<String>) {
+        serve_bytes_once(body.as_bytes().to_vec(), "")
+    }
+
+    fn serve_bytes_once(
+        body: Vec<u8>,
+        extra_headers: &'static str,
+    ) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 4096];
+            let read = stream.read(&mut buffer).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                extra_headers,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+            String::from_utf8_lossy(&buffer[..read]).to_string()
+        });
+        (
+            format!("http://{}/webservice/rest/server.php?", address),
+            handle,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_custom_function_mapping_is_used_in_request() {
+        let (base_url, server) = serve_once(r#"{"usergrades":[]}"#);
+        let functions = ProviderFunctions::from_mapping(
+            "gradereport_user_get_grade_items=local_aitu_get_grade_items",
+        )
+        .unwrap();
+        let client = MoodleClient::new(
+            base_url,
+            "&moodlewsrestformat=json".to_string(),
+            functions,
+            true,
+            Arc::new(ProviderTracer::default()),
+            Arc::new(Metrics::new()),
+        );
+
+        let grades = client.get_grades_by_course_id("token", 1, 2).await.unwrap();
+        let request = server.join().unwrap();
+
+        assert!(grades.usergrades.is_empty());
+        assert!(request.starts_with(
+            "GET /webservice/rest/server.php?wstoken=token&wsfunction=local_aitu_get_grade_items&moodlewsrestformat=json&userid=1&courseid=2 "
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_gzip_encoded_response_is_decompressed() {
+        let body = r#"{"usergrades":[{"coursename":null,"courseid":2,"gradeitems":[{"id":1,"itemname":"Quiz 1","percentageformatted":"95.00 %"}]}]}"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        let (base_url, server) =
+            serve_bytes_once(encoder.finish().unwrap(), "Content-Encoding: gzip\r\n");
+        let client = MoodleClient::new(
+            base_url,
+            "&moodlewsrestformat=json".to_string(),
+            ProviderFunctions::default(),
+            true,
+            Arc::new(ProviderTracer::default()),
+            Arc::new(Metrics::new()),
         );
-        let response = self.client.get(&url).send().await?;
-        response.json::<GradesOverview>().await
+
+        let grades = client.get_grades_by_course_id("token", 1, 2).await.unwrap();
+        let request = server.join().unwrap().to_ascii_lowercase();
+
+        assert!(request.contains("accept-encoding: gzip"));
+        assert_eq!(
+            grades.usergrades[0].gradeitems[0].percentageformatted,
+            "95.00 %"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gzip_can_be_disabled() {
+        let (base_url, server) = serve_once(r#"{"usergrades":[]}"#);
+        let client = MoodleClient::new(
+            base_url,
+            "&moodlewsrestformat=json".to_string(),
+            ProviderFunctions::default(),
+            false,
+            Arc::new(ProviderTracer::default()),
+            Arc::new(Metrics::new()),
+        );
+
+        client.get_grades_by_course_id("token", 1, 2).await.unwrap();
+        let request = server.join().unwrap().to_ascii_lowercase();
+
+        assert!(!request.contains("accept-encoding"));
+    }
+
+    #[tokio::test]
+    async fn test_provider_call_is_traced_with_size_and_duration() {
+        let body = r#"{"usergrades":[]}"#;
+        let (base_url, server) = serve_once(body);
+        let tracer = Arc::new(ProviderTracer::default());
+        let client = MoodleClient::new(
+            base_url,
+            "&moodlewsrestformat=json".to_string(),
+            ProviderFunctions::default(),
+            true,
+            Arc::clone(&tracer),
+            Arc::new(Metrics::new()),
+        );
+
+        client.get_grades_by_course_id("token", 1, 2).await.unwrap();
+        server.join().unwrap();
+
+        let histograms = tracer.histograms();
+        assert_eq!(histograms.len(), 1);
+        let histogram = &histograms[0];
+        assert_eq!(histogram.endpoint, "gradereport_user_get_grade_items");
+        assert_eq!((histogram.calls, histogram.errors), (1, 0));
+        assert_eq!(histogram.total_bytes, body.len() as u64);
+        let observed: u64 = histogram.duration_buckets.iter().map(|b| b.count).sum();
+        assert_eq!(observed, 1);
     }
 }
@@ -0,0 +1,79 @@
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderFunctions {
+    pub site_info: String,
+    pub users_courses: String,
+    pub grade_items: String,
+    pub course_events: String,
+    pub grades_overview: String,
+    pub course_contents: String,
+}
+
+impl Default for ProviderFunctions {
+    fn default() -> Self {
+        Self {
+            site_info: "core_webservice_get_site_info".to_string(),
+            users_courses: "core_enrol_get_users_courses".to_string(),
+            grade_items: "gradereport_user_get_grade_items".to_string(),
+            course_events: "core_calendar_get_action_events_by_course".to_string(),
+            grades_overview: "gradereport_overview_get_course_grades".to_string(),
+            course_contents: "core_course_get_contents".to_string(),
+        }
+    }
+}
+
+impl ProviderFunctions {
+    /// Parses overrides in the form `default_name=custom_name,...`, keyed by
+    /// the standard Moodle function names.
+    pub fn from_mapping(mapping: &str) -> Result<Self, String> {
+        let mut functions = Self::default();
+        for pair in mapping.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (default_name, custom_name) = pair
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .filter(|(_, value)| !value.is_empty())
+                .ok_or_else(|| format!("Invalid function mapping: {}", pair))?;
+
+            let function = match default_name {
+                "core_webservice_get_site_info" => &mut functions.site_info,
+                "core_enrol_get_users_courses" => &mut functions.users_courses,
+                "gradereport_user_get_grade_items" => &mut functions.grade_items,
+                "core_calendar_get_action_events_by_course" => &mut functions.course_events,
+                "gradereport_overview_get_course_grades" => &mut functions.grades_overview,
+                "core_course_get_contents" => &mut functions.course_contents,
+                _ => return Err(format!("Unknown provider function: {}", default_name)),
+            };
+            *function = custom_name.to_string();
+        }
+        Ok(functions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_mapping_keeps_defaults() {
+        assert_eq!(
+            ProviderFunctions::from_mapping("").unwrap(),
+            ProviderFunctions::default()
+        );
+    }
+
+    #[test]
+    fn test_mapping_overrides_function() {
+        let functions = ProviderFunctions::from_mapping(
+            "gradereport_user_get_grade_items = local_aitu_get_grade_items",
+        )
+        .unwrap();
+        assert_eq!(functions.grade_items, "local_aitu_get_grade_items");
+        assert_eq!(functions.site_info, "core_webservice_get_site_info");
+    }
+
+    #[test]
+    fn test_mapping_rejects_unknown_and_malformed_entries() {
+        assert!(ProviderFunctions::from_mapping("core_unknown=custom").is_err());
+        assert!(ProviderFunctions::from_mapping("gradereport_user_get_grade_items").is_err());
+        assert!(ProviderFunctions::from_mapping("gradereport_user_get_grade_items=").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Upper bounds of the duration buckets, in milliseconds.
+const DURATION_BUCKETS_MS: [u64; 7] = [50, 100, 250, 500, 1000, 2500, 5000];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderCall {
+    pub endpoint: String,
+    /// Request URL with the token redacted.
+    pub url: String,
+    pub status: Option<u16>,
+    pub response_bytes: usize,
+    pub duration: Duration,
+}
+
+impl ProviderCall {
+    pub fn new(
+        endpoint: &str,
+        url: &str,
+        status: Option<u16>,
+        response_bytes: usize,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            url: redact_token(url),
+            status,
+            response_bytes,
+            duration,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DurationBucket {
+    /// `None` for the overflow bucket.
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EndpointHistogram {
+    pub endpoint: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub total_bytes: u64,
+    pub max_bytes: u64,
+    pub duration_buckets: Vec<DurationBucket>,
+}
+
+impl EndpointHistogram {
+    fn new(endpoint: &str) -> Self {
+        let duration_buckets = DURATION_BUCKETS_MS
+            .iter()
+            .map(|&le_ms| Some(le_ms))
+            .chain([None])
+            .map(|le_ms| DurationBucket { le_ms, count: 0 })
+            .collect();
+        Self {
+            endpoint: endpoint.to_string(),
+            calls: 0,
+            errors: 0,
+            total_bytes: 0,
+            max_bytes: 0,
+            duration_buckets,
+        }
+    }
+
+    fn observe(&mut self, call: &ProviderCall) {
+        self.calls += 1;
+        if !call
+            .status
+            .is_some_and(|status| (200..300).contains(&status))
+        {
+            self.errors += 1;
+        }
+        self.total_bytes += call.response_bytes as u64;
+        self.max_bytes = self.max_bytes.max(call.response_bytes as u64);
+
+        let duration_ms = call.duration.as_millis() as u64;
+        if let Some(bucket) = self
+            .duration_buckets
+            .iter_mut()
+            .find(|bucket| bucket.le_ms.is_none_or(|le_ms| duration_ms <= le_ms))
+        {
+            bucket.count += 1;
+        }
+    }
+}
+
+/// Logs every provider call and keeps a per-endpoint histogram for capacity planning.
+#[derive(Default)]
+pub struct ProviderTracer {
+    histograms: Mutex<BTreeMap<String, EndpointHistogram>>,
+}
+
+impl ProviderTracer {
+    pub fn record(&self, call: &ProviderCall) {
+        println!(
+            "provider_call endpoint={} url={} status={} response_bytes={} duration_ms={}",
+            call.endpoint,
+            call.url,
+            call.status
+                .map_or_else(|| "-".to_string(), |status| status.to_string()),
+            call.response_bytes,
+            call.duration.as_millis()
+        );
+        self.histograms
+            .lock()
+            .unwrap()
+            .entry(call.endpoint.clone())
+            .or_insert_with(|| EndpointHistogram::new(&call.endpoint))
+            .observe(call);
+    }
+
+    pub fn histograms(&self) -> Vec<EndpointHistogram> {
+        self.histograms.lock().unwrap().values().cloned().collect()
+    }
+}
+
+pub fn redact_token(url: &str) -> String {
+    let Some(start) = url.find("wstoken=").map(|index| index + "wstoken=".len()) else {
+        return url.to_string();
+    };
+    let end = url[start..]
+        .find('&')
+        .map_or(url.len(), |index| start + index);
+    format!("{}REDACTED{}", &url[..start], &url[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_token() {
+        assert_eq!(
+            redact_token("http://aitu/server.php?wstoken=secret&wsfunction=f"),
+            "http://aitu/server.php?wstoken=REDACTED&wsfunction=f"
+        );
+        assert_eq!(
+            redact_token("http://aitu/server.php?wsfunction=f&wstoken=secret"),
+            "http://aitu/server.php?wsfunction=f&wstoken=REDACTED"
+        );
+        assert_eq!(redact_token("http://aitu/"), "http://aitu/");
+    }
+
+    #[test]
+    fn test_histogram_buckets_durations_per_endpoint() {
+        let tracer = ProviderTracer::default();
+        for (endpoint, status, bytes, millis) in [
+            ("grades", Some(200), 100, 40),
+            ("grades", Some(200), 300, 700),
+            ("grades", None, 0, 15_000),
+            ("courses", Some(200), 50, 120),
+        ] {
+            tracer.record(&ProviderCall::new(
+                endpoint,
+                "http://aitu/?wstoken=secret",
+                status,
+                bytes,
+                Duration::from_millis(millis),
+            ));
+        }
+
+        let histograms = tracer.histograms();
+        assert_eq!(histograms.len(), 2);
+        let grades = histograms.iter().find(|h| h.endpoint == "grades").unwrap();
+        assert_eq!((grades.calls, grades.errors), (3, 1));
+        assert_eq!((grades.total_bytes, grades.max_bytes), (400, 300));
+        let counts: Vec<_> = grades.duration_buckets.iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![1, 0, 0, 0, 1, 0, 0, 1]);
+    }
+}
@@ -0,0 +1,228 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::Error;
+
+use crate::models::course::Course;
+use crate::models::course_content::ContentSection;
+use crate::models::deadline::Events;
+use crate::models::grade::{GradesOverview, UserGrades};
+use crate::models::user::User;
+use crate::services::provider_interfaces::DataProviderInterface;
+use crate::services::retry_budget::RetryBudget;
+
+/// Retries transient provider failures with exponential backoff.
+pub struct RetryingProvider {
+    inner: Arc<dyn DataProviderInterface>,
+    max_retries: u32,
+    backoff: Duration,
+    budget: Arc<RetryBudget>,
+}
+
+impl RetryingProvider {
+    pub fn new(
+        inner: Arc<dyn DataProviderInterface>,
+        max_retries: u32,
+        backoff: Duration,
+        budget: Arc<RetryBudget>,
+    ) -> Self {
+        Self {
+            inner,
+            max_retries,
+            backoff,
+            budget,
+        }
+    }
+
+    async fn retry<T, F, Fut>(&self, token: &str, call: F) -> Result<T, Error>
+    where
+        F: Fn() -> Fut + Send,
+        Fut: Future<Output = Result<T, Error>> + Send,
+    {
+        let mut attempt = 0;
+        loop {
+            match call().await {
+                Err(e)
+                    if is_transient(&e)
+                        && attempt < self.max_retries
+                        && self.budget.try_take(token) =>
+                {
+                    self.backoff_delay(attempt).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn backoff_delay(&self, attempt: u32) {
+        tokio::time::sleep(self.backoff * 2u32.saturating_pow(attempt)).await;
+    }
+}
+
+/// Moodle reports its own errors in a 200 body, so only network failures are retried.
+fn is_transient(e: &Error) -> bool {
+    e.is_timeout() || e.is_connect()
+}
+
+#[async_trait]
+impl DataProviderInterface for RetryingProvider {
+    async fn get_user(&self, token: &str) -> Result<User, Error> {
+        self.retry(token, || self.inner.get_user(token)).await
+    }
+
+    async fn valid_token(&self, token: &str) -> Result<(), Error> {
+        self.retry(token, || self.inner.valid_token(token)).await
+    }
+
+    async fn get_courses(&self, token: &str, user_id: i64) -> Result<Vec<Course>, Error> {
+        self.retry(token, || self.inner.get_courses(token, user_id))
+            .await
+    }
+
+    async fn get_grades_by_course_id(
+        &self,
+        token: &str,
+        user_id: i64,
+        course_id: i64,
+    ) -> Result<UserGrades, Error> {
+        self.retry(token, || {
+            self.inner
+                .get_grades_by_course_id(token, user_id, course_id)
+        })
+        .await
+    }
+
+    async fn get_deadline_by_course_id(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Events, Error> {
+        self.retry(token, || {
+            self.inner.get_deadline_by_course_id(token, course_id)
+        })
+        .await
+    }
+
+    async fn get_grades_overview(&self, token: &str) -> Result<GradesOverview, Error> {
+        self.retry(token, || self.inner.get_grades_overview(token))
+            .await
+    }
+
+    async fn get_course_contents(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Vec<ContentSection>, Error> {
+        self.retry(token, || self.inner.get_course_contents(token, course_id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Every call fails with a refused connection.
+    #[derive(Default)]
+    struct UnreachableProvider {
+        attempts: AtomicUsize,
+    }
+
+    impl UnreachableProvider {
+        async fn fail<T>(&self) -> Result<T, Error> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            Err(reqwest::get("http://127.0.0.1:9").await.unwrap_err())
+        }
+    }
+
+    #[async_trait]
+    impl DataProviderInterface for UnreachableProvider {
+        async fn get_user(&self, _token: &str) -> Result<User, Error> {
+            self.fail().await
+        }
+
+        async fn valid_token(&self, _token: &str) -> Result<(), Error> {
+            self.fail().await
+        }
+
+        async fn get_courses(&self, _token: &str, _user_id: i64) -> Result<Vec<Course>, Error> {
+            self.fail().await
+        }
+
+        async fn get_grades_by_course_id(
+            &self,
+            _token: &str,
+            _user_id: i64,
+            _course_id: i64,
+        ) -> Result<UserGrades, Error> {
+            self.fail().await
+        }
+
+        async fn get_deadline_by_course_id(
+            &self,
+            _token: &str,
+            _course_id: i64,
+        ) -> Result<Events, Error> {
+            self.fail().await
+        }
+
+        async fn get_grades_overview(&self, _token: &str) -> Result<GradesOverview, Error> {
+            self.fail().await
+        }
+
+        async fn get_course_contents(
+            &self,
+            _token: &str,
+            _course_id: i64,
+        ) -> Result<Vec<ContentSection>, Error> {
+            self.fail().await
+        }
+    }
+
+    fn retrying_provider(
+        max_retries: u32,
+        budget: u32,
+    ) -> (Arc<UnreachableProvider>, RetryingProvider) {
+        let inner = Arc::new(UnreachableProvider::default());
+        let provider = RetryingProvider::new(
+            Arc::clone(&inner) as Arc<dyn DataProviderInterface>,
+            max_retries,
+            Duration::ZERO,
+            Arc::new(RetryBudget::new(budget)),
+        );
+        (inner, provider)
+    }
+
+    #[tokio::test]
+    async fn test_retries_stop_once_cycle_budget_is_spent() {
+        let (inner, provider) = retrying_provider(5, 3);
+        provider.budget.start("token");
+
+        assert!(provider
+            .get_courses("token", 1)
+            .await
+            .unwrap_err()
+            .is_connect());
+        assert_eq!(inner.attempts.load(Ordering::SeqCst), 4);
+
+        // Budget exhausted: later calls in the same cycle fail fast
+        assert!(provider.get_grades_overview("token").await.is_err());
+        assert!(provider
+            .get_grades_by_course_id("token", 1, 2)
+            .await
+            .is_err());
+        assert_eq!(inner.attempts.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_calls_outside_cycle_use_per_call_limit() {
+        let (inner, provider) = retrying_provider(2, 0);
+
+        assert!(provider.get_user("token").await.is_err());
+        assert_eq!(inner.attempts.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::services::health::HealthCheck;
+use async_trait::async_trait;
 use mongodb::bson::doc;
 use mongodb::options::{ClientOptions, ServerApi, ServerApiVersion};
 use mongodb::{Client, Database};
@@ -19,3 +21,28 @@ pub async fn connect(db_env: &str) -> mongodb::error::Result<Database> {
 
     Ok(db)
 }
+
+pub struct MongoHealthCheck {
+    db: Database,
+}
+
+impl MongoHealthCheck {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for MongoHealthCheck {
+    fn name(&self) -> &'static str {
+        "mongodb"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        self.db
+            .run_command(doc! { "ping": 1 })
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,336 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use crate::models::notification::{Notification, Priority};
+use crate::services::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
+
+/// Apple rejects provider tokens older than an hour and throttles refreshes
+/// more often than every 20 minutes.
+const PROVIDER_TOKEN_LIFETIME_SECS: i64 = 50 * 60;
+
+/// Token-based (p8) APNs credentials.
+#[derive(Debug, Clone)]
+pub struct ApnsSettings {
+    pub key_file: String,
+    pub key_id: String,
+    pub team_id: String,
+    /// The app's bundle id.
+    pub topic: String,
+    pub sandbox: bool,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    iat: i64,
+}
+
+struct ProviderToken {
+    value: String,
+    issued_at: i64,
+}
+
+/// Why APNs refused a notification, from the `reason` of its response.
+#[derive(Debug, PartialEq)]
+pub enum ApnsError {
+    /// The app was removed from the device.
+    Unregistered,
+    /// The provider token expired or was rejected; it is signed again.
+    ProviderToken,
+    TooManyRequests,
+    Unavailable,
+    Other(StatusCode, String),
+}
+
+impl ApnsError {
+    /// `BadDeviceToken` and `DeviceTokenNotForTopic` also come back when the
+    /// host or topic is misconfigured, so only `Unregistered` and 410 retire
+    /// a token.
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        let error: Value = serde_json::from_str(body).unwrap_or_default();
+        let reason = error["reason"].as_str().unwrap_or_default();
+        match (reason, status) {
+            ("Unregistered", _) | (_, StatusCode::GONE) => ApnsError::Unregistered,
+            ("ExpiredProviderToken" | "InvalidProviderToken", _) => ApnsError::ProviderToken,
+            (_, StatusCode::TOO_MANY_REQUESTS) => ApnsError::TooManyRequests,
+            (_, status) if status.is_server_error() => ApnsError::Unavailable,
+            (reason, status) => ApnsError::Other(status, reason.to_string()),
+        }
+    }
+}
+
+impl From<ApnsError> for ProduceError {
+    fn from(err: ApnsError) -> Self {
+        let message = format!("APNs {:?}", err);
+        match err {
+            ApnsError::ProviderToken | ApnsError::TooManyRequests | ApnsError::Unavailable => {
+                ProduceError::Transient(message)
+            }
+            ApnsError::Unregistered => ProduceError::Unregistered(message),
+            _ => ProduceError::Permanent(message),
+        }
+    }
+}
+
+/// Sends notifications straight to Apple Push Notification service over
+/// HTTP/2, signing requests with a p8 key.
+pub struct ApnsProducer {
+    client: Client,
+    settings: ApnsSettings,
+    key: EncodingKey,
+    provider_token: Mutex<Option<ProviderToken>>,
+}
+
+impl ApnsProducer {
+    pub fn new(settings: ApnsSettings) -> Result<Self> {
+        let pem = std::fs::read(&settings.key_file)
+            .with_context(|| format!("Cannot read APNs key file {}", settings.key_file))?;
+        let key = EncodingKey::from_ec_pem(&pem).context("Invalid APNs key")?;
+        Ok(Self {
+            client: Client::builder()
+                .http2_prior_knowledge()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            settings,
+            key,
+            provider_token: Mutex::new(None),
+        })
+    }
+
+    fn url(&self, device_token: &str) -> String {
+        let host = if self.settings.sandbox {
+            "api.sandbox.push.apple.com"
+        } else {
+            "api.push.apple.com"
+        };
+        format!("https://{}/3/device/{}", host, device_token)
+    }
+
+    async fn provider_token(&self) -> Result<String> {
+        let mut cached = self.provider_token.lock().await;
+        let now = Utc::now().timestamp();
+        if let Some(token) = cached.as_ref() {
+            if token.issued_at + PROVIDER_TOKEN_LIFETIME_SECS > now {
+                return Ok(token.value.clone());
+            }
+        }
+
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(self.settings.key_id.clone());
+        let claims = Claims {
+            iss: &self.settings.team_id,
+            iat: now,
+        };
+        let value = jsonwebtoken::encode(&header, &claims, &self.key)?;
+        *cached = Some(ProviderToken {
+            value: value.clone(),
+            issued_at: now,
+        });
+        Ok(value)
+    }
+
+    async fn forget_provider_token(&self) {
+        *self.provider_token.lock().await = None;
+    }
+
+    async fn try_send(&self, msg: &Notification) -> Result<Receipt, ApnsError> {
+        let provider_token = self.provider_token().await.map_err(|e| {
+            eprintln!("Error signing APNs provider token: {:?}", e);
+            ApnsError::ProviderToken
+        })?;
+        // Apple only accepts background pushes at priority 5
+        let (push_type, priority) = if msg.silent {
+            ("background", "5")
+        } else {
+            ("alert", apns_priority(msg.priority))
+        };
+        let mut request = self
+            .client
+            .post(self.url(&msg.device_token))
+            .bearer_auth(provider_token)
+            .header("apns-topic", &self.settings.topic)
+            .header("apns-push-type", push_type)
+            .header("apns-priority", priority)
+            .json(&apns_payload(msg));
+        if let (Some(collapse_key), false) = (&msg.collapse_key, msg.silent) {
+            request = request.header("apns-collapse-id", collapse_key);
+        }
+        let response = request.send().await.map_err(|e| {
+            eprintln!("Error reaching APNs: {:?}", e);
+            ApnsError::Unavailable
+        })?;
+        let status = response.status();
+        if status.is_success() {
+            let apns_id = response
+                .headers()
+                .get("apns-id")
+                .and_then(|id| id.to_str().ok())
+                .map(str::to_string);
+            return Ok(Receipt::Delivered(apns_id));
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(ApnsError::from_response(status, &body))
+    }
+}
+
+/// Priority 5 lets iOS hold the push back to save power.
+fn apns_priority(priority: Priority) -> &'static str {
+    match priority {
+        Priority::High | Priority::Normal => "10",
+        Priority::Low => "5",
+    }
+}
+
+/// The APNs request body; custom keys sit next to `aps`.
+pub fn apns_payload(msg: &Notification) -> Value {
+    if msg.silent {
+        let mut payload = json!({"aps": {"content-available": 1}});
+        for (key, value) in &msg.data {
+            payload[key] = json!(value);
+        }
+        return payload;
+    }
+    let mut payload = json!({
+        "aps": {
+            "alert": {"title": msg.title, "body": msg.body},
+        },
+        "kind": msg.kind.as_str(),
+    });
+    // Time-sensitive alerts break through Focus; passive ones don't light the screen
+    match msg.priority {
+        Priority::High => payload["aps"]["interruption-level"] = json!("time-sensitive"),
+        Priority::Normal => {}
+        Priority::Low => payload["aps"]["interruption-level"] = json!("passive"),
+    }
+    if let Some(image_url) = &msg.image_url {
+        payload["aps"]["mutable-content"] = json!(1);
+        payload["image_url"] = json!(image_url);
+    }
+    if let Some(category) = msg.action_category() {
+        payload["aps"]["category"] = json!(category);
+        payload["actions"] = json!(msg.actions);
+    }
+    if let Some(thread_id) = &msg.thread_id {
+        payload["aps"]["thread-id"] = json!(thread_id);
+    }
+    if let Some(sound) = &msg.sound {
+        payload["aps"]["sound"] = json!(sound);
+    }
+    if let Some(badge) = msg.badge {
+        payload["aps"]["badge"] = json!(badge);
+    }
+    if let Some(key) = &msg.idempotency_key {
+        payload["idempotency_key"] = json!(key);
+    }
+    for (key, value) in &msg.data {
+        payload[key] = json!(value);
+    }
+    payload
+}
+
+#[async_trait]
+impl EventProducerInterface for ApnsProducer {
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+        let mut result = self.try_send(msg).await;
+        if result == Err(ApnsError::ProviderToken) {
+            self.forget_provider_token().await;
+            result = self.try_send(msg).await;
+        }
+        Ok(result?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::NotificationKind;
+    use crate::models::token::{Device, Platform};
+
+    #[test]
+    fn test_payload_has_alert_and_kind() {
+        let device = Device {
+            token: "apns-token".to_string(),
+            platform: Some(Platform::Ios),
+        };
+        let notification = Notification::new(
+            NotificationKind::Deadline,
+            "New deadline".to_string(),
+            "Task: Essay".to_string(),
+        )
+        .for_device(&device);
+
+        let payload = apns_payload(&notification);
+
+        assert_eq!(payload["aps"]["alert"]["title"], "New deadline");
+        assert_eq!(payload["aps"]["alert"]["body"], "Task: Essay");
+        assert_eq!(payload["kind"], "deadline");
+        assert_eq!(payload["screen"], "deadline");
+        assert!(payload.get("idempotency_key").is_none());
+        assert!(payload["aps"].get("interruption-level").is_none());
+        assert_eq!(payload["aps"]["sound"], "default");
+        assert!(payload["aps"].get("badge").is_none());
+
+        let styled = apns_payload(&notification.clone().with_alert_style(None, Some(3)));
+        assert!(styled["aps"].get("sound").is_none());
+        assert_eq!(styled["aps"]["badge"], 3);
+
+        let urgent = apns_payload(&notification.clone().with_priority(Priority::High));
+        assert_eq!(urgent["aps"]["interruption-level"], "time-sensitive");
+        assert_eq!(apns_priority(Priority::Low), "5");
+
+        let rich = apns_payload(
+            &notification
+                .with_image(Some("https://example.com/essay.png".to_string()))
+                .with_action("open_course", "Open course".to_string()),
+        );
+        assert_eq!(rich["aps"]["mutable-content"], 1);
+        assert_eq!(rich["aps"]["category"], "open_course");
+        assert_eq!(rich["image_url"], "https://example.com/essay.png");
+        assert_eq!(rich["actions"][0]["title"], "Open course");
+
+        let silent = apns_payload(&Notification::background_sync());
+        assert_eq!(silent["aps"], json!({"content-available": 1}));
+        assert_eq!(silent["type"], "sync");
+    }
+
+    #[test]
+    fn test_error_response_mapping() {
+        assert_eq!(
+            ApnsError::from_response(StatusCode::BAD_REQUEST, r#"{"reason": "BadDeviceToken"}"#),
+            ApnsError::Other(StatusCode::BAD_REQUEST, "BadDeviceToken".to_string())
+        );
+        assert_eq!(
+            ApnsError::from_response(
+                StatusCode::BAD_REQUEST,
+                r#"{"reason": "DeviceTokenNotForTopic"}"#
+            ),
+            ApnsError::Other(
+                StatusCode::BAD_REQUEST,
+                "DeviceTokenNotForTopic".to_string()
+            )
+        );
+        assert_eq!(
+            ApnsError::from_response(StatusCode::GONE, r#"{"reason": "Unregistered"}"#),
+            ApnsError::Unregistered
+        );
+        assert_eq!(
+            ApnsError::from_response(
+                StatusCode::FORBIDDEN,
+                r#"{"reason": "ExpiredProviderToken"}"#
+            ),
+            ApnsError::ProviderToken
+        );
+        assert_eq!(
+            ApnsError::from_response(StatusCode::BAD_REQUEST, r#"{"reason": "PayloadEmpty"}"#),
+            ApnsError::Other(StatusCode::BAD_REQUEST, "PayloadEmpty".to_string())
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use crate::models::notification::{Notification, Priority};
+use crate::services::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
+
+const MESSAGING_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+const TOKEN_LIFETIME_SECS: i64 = 3600;
+/// Access tokens are renewed this long before Google expires them.
+const TOKEN_EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// The fields of a Firebase service-account key file that signing needs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceAccount {
+    pub project_id: String,
+    pub client_email: String,
+    pub private_key: String,
+    pub token_uri: String,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
+
+struct AccessToken {
+    value: String,
+    expires_at: i64,
+}
+
+/// Why FCM refused a message.
+#[derive(Debug, PartialEq)]
+pub enum FcmError {
+    /// The device token is no longer registered with FCM.
+    Unregistered,
+    InvalidArgument(String),
+    /// The access token was rejected; it is dropped and fetched again.
+    Unauthenticated,
+    SenderIdMismatch,
+    QuotaExceeded,
+    Unavailable,
+    Other(StatusCode, String),
+}
+
+impl FcmError {
+    /// Maps an HTTP v1 error response, preferring the FCM error code over the
+    /// status. Only `UNREGISTERED` retires a token: a bare 404 is also what a
+    /// wrong project id or endpoint returns.
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        let error: Value = serde_json::from_str(body).unwrap_or_default();
+        let message = error["error"]["message"]
+            .as_str()
+            .unwrap_or(body)
+            .to_string();
+        let fcm_code = error["error"]["details"].as_array().and_then(|details| {
+            details
+                .iter()
+                .find_map(|detail| detail["errorCode"].as_str())
+        });
+        match (fcm_code, status) {
+            (Some("UNREGISTERED"), _) => FcmError::Unregistered,
+            (Some("SENDER_ID_MISMATCH"), _) => FcmError::SenderIdMismatch,
+            (Some("QUOTA_EXCEEDED"), _) | (None, StatusCode::TOO_MANY_REQUESTS) => {
+                FcmError::QuotaExceeded
+            }
+            (Some("UNAVAILABLE" | "INTERNAL"), _) => FcmError::Unavailable,
+            (Some("INVALID_ARGUMENT"), _) | (None, StatusCode::BAD_REQUEST) => {
+                FcmError::InvalidArgument(message)
+            }
+            (_, StatusCode::UNAUTHORIZED) => FcmError::Unauthenticated,
+            (_, status) if status.is_server_error() => FcmError::Unavailable,
+            (_, status) => FcmError::Other(status, message),
+        }
+    }
+}
+
+impl From<FcmError> for ProduceError {
+    fn from(err: FcmError) -> Self {
+        let message = format!("FCM {:?}", err);
+        match err {
+            FcmError::QuotaExceeded | FcmError::Unavailable | FcmError::Unauthenticated => {
+                ProduceError::Transient(message)
+            }
+            FcmError::Unregistered => ProduceError::Unregistered(message),
+            _ => ProduceError::Permanent(message),
+        }
+    }
+}
+
+/// Sends notifications straight to Firebase Cloud Messaging over the HTTP v1
+/// API, authenticating with a service account.
+pub struct FcmProducer {
+    client: Client,
+    account: ServiceAccount,
+    key: EncodingKey,
+    access_token: Mutex<Option<AccessToken>>,
+}
+
+impl FcmProducer {
+    pub fn new(account: ServiceAccount) -> Result<Self> {
+        let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+            .context("Invalid service account private key")?;
+        Ok(Self {
+            client: Client::builder().timeout(Duration::from_secs(10)).build()?,
+            account,
+            key,
+            access_token: Mutex::new(None),
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read service account file {}", path))?;
+        Self::new(serde_json::from_str(&contents)?)
+    }
+
+    fn send_url(&self) -> String {
+        format!(
+            "https://fcm.googleapis.com/v1/projects/{}/messages:send",
+            self.account.project_id
+        )
+    }
+
+    /// A cached access token, exchanged for a new signed assertion when close to expiry.
+    async fn access_token(&self) -> Result<String> {
+        let mut cached = self.access_token.lock().await;
+        let now = Utc::now().timestamp();
+        if let Some(token) = cached.as_ref() {
+            if token.expires_at - TOKEN_EXPIRY_MARGIN_SECS > now {
+                return Ok(token.value.clone());
+            }
+        }
+
+        let claims = Claims {
+            iss: &self.account.client_email,
+            scope: MESSAGING_SCOPE,
+            aud: &self.account.token_uri,
+            iat: now,
+            exp: now + TOKEN_LIFETIME_SECS,
+        };
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)?;
+        let response: TokenResponse = self
+            .client
+            .post(&self.account.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let value = response.access_token.clone();
+        *cached = Some(AccessToken {
+            value: response.access_token,
+            expires_at: now + response.expires_in,
+        });
+        Ok(value)
+    }
+
+    async fn forget_access_token(&self) {
+        *self.access_token.lock().await = None;
+    }
+
+    async fn try_send(&self, message: &Value) -> Result<Receipt, FcmError> {
+        let access_token = self.access_token().await.map_err(|e| {
+            eprintln!("Error fetching FCM access token: {:?}", e);
+            FcmError::Unauthenticated
+        })?;
+        let response = self
+            .client
+            .post(self.send_url())
+            .bearer_auth(access_token)
+            .json(message)
+            .send()
+            .await
+            .map_err(|e| {
+                eprintln!("Error reaching FCM: {:?}", e);
+                FcmError::Unavailable
+            })?;
+        let status = response.status();
+        if status.is_success() {
+            // The message resource name, e.g. `projects/app/messages/0:1500415314455276`
+            let message: Value = response.json().await.unwrap_or_default();
+            let name = message["name"].as_str().map(str::to_string);
+            return Ok(Receipt::Delivered(name));
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(FcmError::from_response(status, &body))
+    }
+}
+
+/// The HTTP v1 request body for a notification; FCM data values must be strings.
+pub fn fcm_message(msg: &Notification) -> Value {
+    let mut data = json!({"kind": msg.kind.as_str()});
+    if let Some(key) = &msg.idempotency_key {
+        data["idempotency_key"] = json!(key);
+    }
+    for (key, value) in &msg.data {
+        data[key] = json!(value);
+    }
+    if !msg.actions.is_empty() {
+        data["actions"] = json!(json!(msg.actions).to_string());
+    }
+    if msg.silent {
+        // Data only, so the app handles it without showing anything
+        return json!({
+            "message": {
+                "token": msg.device_token,
+                "data": data,
+                "android": {"priority": "NORMAL"},
+                "apns": {
+                    "headers": {"apns-priority": "5", "apns-push-type": "background"},
+                    "payload": {"aps": {"content-available": 1}},
+                },
+            }
+        });
+    }
+    // Normal priority may be delayed while an Android device dozes
+    let (android_priority, apns_priority) = match msg.priority {
+        Priority::High => ("HIGH", "10"),
+        Priority::Normal => ("NORMAL", "10"),
+        Priority::Low => ("NORMAL", "5"),
+    };
+    let mut message = json!({
+        "message": {
+            "token": msg.device_token,
+            "notification": {"title": msg.title, "body": msg.body},
+            "data": data,
+            "android": {"priority": android_priority},
+            "apns": {"headers": {"apns-priority": apns_priority}},
+        }
+    });
+    if let Some(image_url) = &msg.image_url {
+        message["message"]["notification"]["image"] = json!(image_url);
+        // Lets the app's notification service extension attach the image on iOS
+        message["message"]["apns"]["payload"]["aps"]["mutable-content"] = json!(1);
+    }
+    if let Some(category) = msg.action_category() {
+        message["message"]["apns"]["payload"]["aps"]["category"] = json!(category);
+    }
+    if let Some(thread_id) = &msg.thread_id {
+        // Android has no thread field; the app groups on the data key
+        message["message"]["data"]["thread_id"] = json!(thread_id);
+        message["message"]["apns"]["payload"]["aps"]["thread-id"] = json!(thread_id);
+    }
+    if let Some(sound) = &msg.sound {
+        message["message"]["android"]["notification"]["sound"] = json!(sound);
+        message["message"]["apns"]["payload"]["aps"]["sound"] = json!(sound);
+    }
+    if let Some(badge) = msg.badge {
+        message["message"]["android"]["notification"]["notification_count"] = json!(badge);
+        message["message"]["apns"]["payload"]["aps"]["badge"] = json!(badge);
+    }
+    if let Some(collapse_key) = &msg.collapse_key {
+        message["message"]["android"]["collapse_key"] = json!(collapse_key);
+        // Replaces a shown notification with the same tag
+        message["message"]["android"]["notification"]["tag"] = json!(collapse_key);
+        message["message"]["apns"]["headers"]["apns-collapse-id"] = json!(collapse_key);
+    }
+    message
+}
+
+#[async_trait]
+impl EventProducerInterface for FcmProducer {
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+        let message = fcm_message(msg);
+        let mut result = self.try_send(&message).await;
+        if result == Err(FcmError::Unauthenticated) {
+            // Revoked or rotated credentials; one retry with a fresh token
+            self.forget_access_token().await;
+            result = self.try_send(&message).await;
+        }
+        Ok(result?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::NotificationKind;
+    use crate::models::token::{Device, Platform};
+
+    #[test]
+    fn test_message_carries_token_notification_and_kind() {
+        let device = Device {
+            token: "fcm-token".to_string(),
+            platform: Some(Platform::Android),
+        };
+        let notification = Notification::new(
+            NotificationKind::Grade,
+            "Math".to_string(),
+            "New grade | 90 %".to_string(),
+        )
+        .with_change("token", Some(1), Some(2), "90 %")
+        .for_device(&device);
+
+        let message = fcm_message(&notification);
+
+        assert_eq!(message["message"]["token"], "fcm-token");
+        assert_eq!(message["message"]["notification"]["title"], "Math");
+        assert_eq!(message["message"]["data"]["kind"], "grade");
+        assert_eq!(message["message"]["data"]["grade_item_id"], "2");
+        assert_eq!(message["message"]["android"]["priority"], "NORMAL");
+        assert_eq!(message["message"]["data"]["thread_id"], "course-1");
+        assert_eq!(
+            message["message"]["apns"]["payload"]["aps"]["thread-id"],
+            "course-1"
+        );
+        assert_eq!(
+            message["message"]["android"]["notification"]["tag"],
+            message["message"]["apns"]["headers"]["apns-collapse-id"]
+        );
+        let urgent = fcm_message(&notification.clone().with_priority(Priority::High));
+        assert_eq!(urgent["message"]["android"]["priority"], "HIGH");
+        assert!(message["message"]["data"].get("actions").is_none());
+
+        let styled = fcm_message(
+            &notification
+                .clone()
+                .with_alert_style(Some("chime.caf".to_string()), Some(4)),
+        )["message"]
+            .clone();
+        assert_eq!(styled["android"]["notification"]["sound"], "chime.caf");
+        assert_eq!(styled["android"]["notification"]["notification_count"], 4);
+        assert_eq!(styled["apns"]["payload"]["aps"]["badge"], 4);
+    }
+
+    #[test]
+    fn test_silent_message_is_data_only() {
+        let message = fcm_message(&Notification::background_sync())["message"].clone();
+
+        assert!(message.get("notification").is_none());
+        assert_eq!(message["data"]["kind"], "sync");
+        assert_eq!(message["apns"]["headers"]["apns-push-type"], "background");
+        assert_eq!(message["apns"]["payload"]["aps"]["content-available"], 1);
+    }
+
+    #[test]
+    fn test_message_carries_image_and_actions() {
+        let notification = Notification::new(
+            NotificationKind::Deadline,
+            "Deadline reminder".to_string(),
+            "Task: Essay".to_string(),
+        )
+        .with_image(Some("https://example.com/essay.png".to_string()))
+        .with_action("open_course", "Open course".to_string())
+        .with_action("snooze_1h", "Snooze 1h".to_string());
+
+        let message = fcm_message(&notification)["message"].clone();
+
+        assert_eq!(
+            message["notification"]["image"],
+            "https://example.com/essay.png"
+        );
+        assert_eq!(message["apns"]["payload"]["aps"]["mutable-content"], 1);
+        assert_eq!(
+            message["apns"]["payload"]["aps"]["category"],
+            "open_course+snooze_1h"
+        );
+        let actions: Value =
+            serde_json::from_str(message["data"]["actions"].as_str().unwrap()).unwrap();
+        assert_eq!(actions[1]["id"], "snooze_1h");
+        assert_eq!(actions[1]["title"], "Snooze 1h");
+        assert_eq!(
+            message["message"]["data"]["idempotency_key"],
+            json!(notification.idempotency_key)
+        );
+    }
+
+    #[test]
+    fn test_error_response_mapping() {
+        let unregistered = r#"{"error": {"code": 404, "message": "Requested entity was not found.",
+            "status": "NOT_FOUND", "details": [{"@type": "type.googleapis.com/google.firebase.fcm.v1.FcmError",
+            "errorCode": "UNREGISTERED"}]}}"#;
+        assert_eq!(
+            FcmError::from_response(StatusCode::NOT_FOUND, unregistered),
+            FcmError::Unregistered
+        );
+        assert_eq!(
+            FcmError::from_response(
+                StatusCode::BAD_REQUEST,
+                r#"{"error": {"message": "Invalid registration"}}"#
+            ),
+            FcmError::InvalidArgument("Invalid registration".to_string())
+        );
+        assert_eq!(
+            FcmError::from_response(StatusCode::NOT_FOUND, "Not Found"),
+            FcmError::Other(StatusCode::NOT_FOUND, "Not Found".to_string())
+        );
+        assert!(
+            !ProduceError::from(FcmError::Other(StatusCode::NOT_FOUND, String::new()))
+                .is_unregistered()
+        );
+        assert_eq!(
+            FcmError::from_response(StatusCode::UNAUTHORIZED, ""),
+            FcmError::Unauthenticated
+        );
+        assert_eq!(
+            FcmError::from_response(StatusCode::SERVICE_UNAVAILABLE, "busy"),
+            FcmError::Unavailable
+        );
+        assert!(ProduceError::from(FcmError::Unregistered).is_unregistered());
+    }
+}
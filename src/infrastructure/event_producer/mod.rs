@@ -1 +1,7 @@
+pub mod apns;
+pub mod fcm;
 pub mod producer;
+pub mod retrying_producer;
+pub mod telegram;
+pub mod transport_router;
+pub mod webhook;
@@ -4,10 +4,12 @@ use rdkafka::{
     ClientConfig,
 };
 
-use crate::services::event_producer_interface::EventProducerInterface;
+use crate::models::notification::Notification;
+use crate::services::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
 
 pub struct EventProducer {
     pub producer: FutureProducer,
+    topic: String,
 }
 
 impl EventProducer {
@@ -17,28 +19,37 @@ impl EventProducer {
 
         let producer = config.create().expect("Failure in creating producer");
 
-        Self { producer }
+        Self {
+            producer,
+            topic: "notification".to_string(),
+        }
+    }
+
+    /// Shares the Kafka connection but publishes to another topic.
+    pub fn with_topic(&self, topic: &str) -> Self {
+        Self {
+            producer: self.producer.clone(),
+            topic: topic.to_string(),
+        }
     }
 }
 
 #[async_trait]
 impl EventProducerInterface for EventProducer {
-    async fn produce_notification(&self, msg: &crate::models::notification::Notification) {
-        let json_payload = match serde_json::to_string(msg) {
-            Ok(json) => json,
-            Err(e) => {
-                eprintln!("Ошибка сериализации: {:?}", e);
-                return;
-            }
-        };
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+        let json_payload =
+            serde_json::to_string(msg).map_err(|e| ProduceError::Permanent(e.to_string()))?;
 
-        let record = FutureRecord::to("notification")
+        let record = FutureRecord::to(&self.topic)
             .payload(&json_payload)
             .key("notification-key");
 
         match self.producer.send(record, None).await {
-            Ok(report) => println!("Message sent: {:?}", report),
-            Err(e) => eprintln!("Error producing: {:?}", e),
+            Ok(report) => {
+                println!("Message sent: {:?}", report);
+                Ok(Receipt::Sent)
+            }
+            Err((e, _)) => Err(ProduceError::Transient(e.to_string())),
         }
     }
 }
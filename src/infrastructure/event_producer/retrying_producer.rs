@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rand::Rng;
+
+use crate::models::notification::Notification;
+use crate::services::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
+
+/// Retries transient delivery failures with exponential backoff and jitter.
+pub struct RetryingProducer {
+    inner: Box<dyn EventProducerInterface>,
+    max_retries: u32,
+    backoff: Duration,
+}
+
+impl RetryingProducer {
+    pub fn new(
+        inner: Box<dyn EventProducerInterface>,
+        max_retries: u32,
+        backoff: Duration,
+    ) -> Self {
+        Self {
+            inner,
+            max_retries,
+            backoff,
+        }
+    }
+
+    /// Half the exponential delay plus a random share of the other half, so
+    /// retries of a burst don't all land at once.
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let delay = self.backoff * 2u32.saturating_pow(attempt);
+        delay / 2 + delay.mul_f64(rand::thread_rng().gen::<f64>() / 2.0)
+    }
+}
+
+#[async_trait]
+impl EventProducerInterface for RetryingProducer {
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+        let mut attempt = 0;
+        loop {
+            match self.inner.produce_notification(msg).await {
+                Err(e) if e.is_transient() && attempt < self.max_retries => {
+                    tokio::time::sleep(self.backoff_delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::NotificationKind;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Fails with `error` for the first `failures` attempts.
+    struct FlakyProducer {
+        attempts: Arc<AtomicU32>,
+        failures: u32,
+        error: ProduceError,
+    }
+
+    #[async_trait]
+    impl EventProducerInterface for FlakyProducer {
+        async fn produce_notification(&self, _msg: &Notification) -> Result<Receipt, ProduceError> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(self.error.clone());
+            }
+            Ok(Receipt::Sent)
+        }
+    }
+
+    async fn produce(failures: u32, error: ProduceError) -> (Result<Receipt, ProduceError>, u32) {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let producer = RetryingProducer::new(
+            Box::new(FlakyProducer {
+                attempts: Arc::clone(&attempts),
+                failures,
+                error,
+            }),
+            2,
+            Duration::from_millis(1),
+        );
+        let notification = Notification::new(
+            NotificationKind::Course,
+            "New course".to_string(),
+            "Math".to_string(),
+        );
+        let result = producer.produce_notification(&notification).await;
+        (result, attempts.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_retried_up_to_limit() {
+        let transient = ProduceError::Transient("unavailable".to_string());
+        assert_eq!(produce(2, transient.clone()).await, (Ok(Receipt::Sent), 3));
+        assert_eq!(produce(5, transient.clone()).await, (Err(transient), 3));
+    }
+
+    #[tokio::test]
+    async fn test_permanent_failure_not_retried() {
+        let permanent = ProduceError::Permanent("unregistered".to_string());
+        assert_eq!(produce(1, permanent.clone()).await, (Err(permanent), 1));
+    }
+
+    #[test]
+    fn test_backoff_grows_with_jitter_bounds() {
+        let producer = RetryingProducer::new(
+            Box::new(FlakyProducer {
+                attempts: Arc::default(),
+                failures: 0,
+                error: ProduceError::Permanent(String::new()),
+            }),
+            3,
+            Duration::from_millis(100),
+        );
+        for attempt in 0..3 {
+            let full = Duration::from_millis(100 * 2u64.pow(attempt));
+            let delay = producer.backoff_delay(attempt);
+            assert!(delay >= full / 2 && delay <= full);
+        }
+    }
+}
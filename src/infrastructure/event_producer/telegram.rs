@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+
+use crate::models::notification::{Notification, Priority};
+use crate::services::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
+
+/// Why the Bot API refused a message, from its `error_code` and `description`.
+#[derive(Debug, PartialEq)]
+pub enum TelegramError {
+    /// The user blocked the bot, or the chat is gone.
+    Blocked,
+    ChatNotFound,
+    /// The bot token was rejected; pushes resume once it is fixed.
+    Unauthorized,
+    TooManyRequests,
+    Unavailable,
+    Other(StatusCode, String),
+}
+
+impl TelegramError {
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        let error: Value = serde_json::from_str(body).unwrap_or_default();
+        let description = error["description"].as_str().unwrap_or(body);
+        match status {
+            StatusCode::FORBIDDEN => TelegramError::Blocked,
+            StatusCode::BAD_REQUEST if description.contains("chat not found") => {
+                TelegramError::ChatNotFound
+            }
+            StatusCode::UNAUTHORIZED => TelegramError::Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS => TelegramError::TooManyRequests,
+            status if status.is_server_error() => TelegramError::Unavailable,
+            status => TelegramError::Other(status, description.to_string()),
+        }
+    }
+}
+
+impl From<TelegramError> for ProduceError {
+    fn from(err: TelegramError) -> Self {
+        let message = format!("Telegram {:?}", err);
+        match err {
+            TelegramError::Unauthorized
+            | TelegramError::TooManyRequests
+            | TelegramError::Unavailable => ProduceError::Transient(message),
+            TelegramError::Blocked | TelegramError::ChatNotFound => {
+                ProduceError::Unregistered(message)
+            }
+            TelegramError::Other(..) => ProduceError::Permanent(message),
+        }
+    }
+}
+
+/// Sends notifications as messages from a Telegram bot to the chat id a user
+/// linked as their device token.
+pub struct TelegramProducer {
+    client: Client,
+    bot_token: String,
+}
+
+impl TelegramProducer {
+    pub fn new(bot_token: String) -> Result<Self> {
+        Ok(Self {
+            client: Client::builder().timeout(Duration::from_secs(10)).build()?,
+            bot_token,
+        })
+    }
+
+    fn url(&self, method: &str) -> String {
+        format!("https://api.telegram.org/bot{}/{}", self.bot_token, method)
+    }
+}
+
+/// The Bot API method and request body for a notification: a photo with a
+/// caption when it has an image, a plain message otherwise.
+pub fn telegram_message(msg: &Notification) -> (&'static str, Value) {
+    let text = format!("{}\n\n{}", msg.title, msg.body);
+    // Low priority arrives without a sound
+    let silent = msg.priority == Priority::Low;
+    match &msg.image_url {
+        Some(image_url) => (
+            "sendPhoto",
+            json!({
+                "chat_id": msg.device_token,
+                "photo": image_url,
+                "caption": text,
+                "disable_notification": silent,
+            }),
+        ),
+        None => (
+            "sendMessage",
+            json!({
+                "chat_id": msg.device_token,
+                "text": text,
+                "disable_notification": silent,
+            }),
+        ),
+    }
+}
+
+#[async_trait]
+impl EventProducerInterface for TelegramProducer {
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+        let (method, message) = telegram_message(msg);
+        let response = self
+            .client
+            .post(self.url(method))
+            .json(&message)
+            .send()
+            .await
+            .map_err(|e| {
+                // The URL carries the bot token
+                eprintln!("Error reaching Telegram: {:?}", e.without_url());
+                TelegramError::Unavailable
+            })?;
+        let status = response.status();
+        if status.is_success() {
+            let sent: Value = response.json().await.unwrap_or_default();
+            let message_id = sent["result"]["message_id"].as_i64();
+            return Ok(Receipt::Delivered(message_id.map(|id| id.to_string())));
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(TelegramError::from_response(status, &body).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::NotificationKind;
+    use crate::models::token::{Device, Platform};
+
+    #[test]
+    fn test_message_goes_to_linked_chat() {
+        let device = Device {
+            token: "-100123".to_string(),
+            platform: Some(Platform::Telegram),
+        };
+        let notification = Notification::new(
+            NotificationKind::Grade,
+            "Math".to_string(),
+            "New grade | 90 %".to_string(),
+        )
+        .for_device(&device);
+
+        let (method, message) = telegram_message(&notification);
+        assert_eq!(method, "sendMessage");
+        assert_eq!(message["chat_id"], "-100123");
+        assert_eq!(message["text"], "Math\n\nNew grade | 90 %");
+        assert_eq!(message["disable_notification"], false);
+
+        let quiet = notification
+            .with_priority(Priority::Low)
+            .with_image(Some("https://example.com/math.png".to_string()));
+        let (method, message) = telegram_message(&quiet);
+        assert_eq!(method, "sendPhoto");
+        assert_eq!(message["photo"], "https://example.com/math.png");
+        assert_eq!(message["disable_notification"], true);
+    }
+
+    #[test]
+    fn test_error_response_mapping() {
+        let blocked = r#"{"ok": false, "error_code": 403,
+            "description": "Forbidden: bot was blocked by the user"}"#;
+        assert_eq!(
+            TelegramError::from_response(StatusCode::FORBIDDEN, blocked),
+            TelegramError::Blocked
+        );
+        let missing =
+            r#"{"ok": false, "error_code": 400, "description": "Bad Request: chat not found"}"#;
+        assert_eq!(
+            TelegramError::from_response(StatusCode::BAD_REQUEST, missing),
+            TelegramError::ChatNotFound
+        );
+        let throttled = r#"{"ok": false, "error_code": 429, "parameters": {"retry_after": 5}}"#;
+        let err = TelegramError::from_response(StatusCode::TOO_MANY_REQUESTS, throttled);
+        assert!(ProduceError::from(err).is_transient());
+        assert!(ProduceError::from(TelegramError::Blocked).is_unregistered());
+    }
+}
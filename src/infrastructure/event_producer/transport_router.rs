@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::models::notification::Notification;
+use crate::models::token::Platform;
+use crate::services::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
+
+/// Sends each notification through the transport configured for its platform,
+/// falling back to the default one.
+pub struct TransportRouter {
+    default: Box<dyn EventProducerInterface>,
+    platforms: HashMap<Platform, Box<dyn EventProducerInterface>>,
+}
+
+impl TransportRouter {
+    pub fn new(default: Box<dyn EventProducerInterface>) -> Self {
+        Self {
+            default,
+            platforms: HashMap::new(),
+        }
+    }
+
+    pub fn route(mut self, platform: Platform, transport: Box<dyn EventProducerInterface>) -> Self {
+        self.platforms.insert(platform, transport);
+        self
+    }
+}
+
+#[async_trait]
+impl EventProducerInterface for TransportRouter {
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+        let transport = msg
+            .platform
+            .and_then(|platform| self.platforms.get(&platform))
+            .unwrap_or(&self.default);
+        transport.produce_notification(msg).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::NotificationKind;
+    use crate::models::token::Device;
+    use crate::services::mocks::MockEventProducer;
+
+    fn notification(platform: Option<Platform>) -> Notification {
+        let device = Device {
+            token: "device".to_string(),
+            platform,
+        };
+        Notification::new(
+            NotificationKind::Course,
+            "New course".to_string(),
+            "Math".to_string(),
+        )
+        .for_device(&device)
+    }
+
+    #[tokio::test]
+    async fn test_notifications_use_platform_transport() {
+        let default = MockEventProducer::default();
+        let ios = MockEventProducer::default();
+        let android = MockEventProducer::default();
+        let router = TransportRouter::new(Box::new(default.clone()))
+            .route(Platform::Ios, Box::new(ios.clone()))
+            .route(Platform::Android, Box::new(android.clone()));
+
+        router
+            .produce_notification(&notification(Some(Platform::Ios)))
+            .await
+            .unwrap();
+        router
+            .produce_notification(&notification(Some(Platform::Android)))
+            .await
+            .unwrap();
+        router
+            .produce_notification(&notification(Some(Platform::Android)))
+            .await
+            .unwrap();
+
+        assert_eq!(ios.sent.lock().unwrap().len(), 1);
+        assert_eq!(android.sent.lock().unwrap().len(), 2);
+        assert!(default.sent.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unrouted_platforms_fall_back_to_default() {
+        let default = MockEventProducer::default();
+        let ios = MockEventProducer::default();
+        let router = TransportRouter::new(Box::new(default.clone()))
+            .route(Platform::Ios, Box::new(ios.clone()));
+
+        router
+            .produce_notification(&notification(Some(Platform::Android)))
+            .await
+            .unwrap();
+        router
+            .produce_notification(&notification(None))
+            .await
+            .unwrap();
+
+        assert_eq!(default.sent.lock().unwrap().len(), 2);
+        assert!(ios.sent.lock().unwrap().is_empty());
+    }
+}
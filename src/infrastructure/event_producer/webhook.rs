@@ -0,0 +1,196 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{Client, StatusCode, Url};
+use serde_json::{json, Value};
+
+use crate::models::notification::Notification;
+use crate::models::webhook::{is_public_ip, sign, user_webhook_secret};
+use crate::services::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
+
+pub const SIGNATURE_HEADER: &str = "X-Keeper-Signature";
+
+/// Posts each notification as a signed JSON change event to the webhook URL a
+/// user registered as their device token.
+pub struct WebhookProducer {
+    client: Client,
+    signing_key: String,
+}
+
+impl WebhookProducer {
+    pub fn new(signing_key: String) -> Result<Self> {
+        Ok(Self {
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .redirect(reqwest::redirect::Policy::none())
+                .dns_resolver(Arc::new(PublicResolver))
+                .build()?,
+            signing_key,
+        })
+    }
+}
+
+/// A webhook host that only reaches our own network.
+#[derive(Debug)]
+struct BlockedAddress(String);
+
+impl StdError for BlockedAddress {}
+
+impl fmt::Display for BlockedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} has no public address", self.0)
+    }
+}
+
+/// Resolves webhook hosts, keeping only public addresses, so a name can't
+/// point a webhook at our own network after it was registered.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(BlockedAddress(host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// IP-literal hosts skip the resolver, so they are checked here.
+fn check_webhook_host(url: &str) -> Result<(), ProduceError> {
+    let url = Url::parse(url)
+        .map_err(|e| ProduceError::Permanent(format!("Invalid webhook URL: {}", e)))?;
+    let host = url.host_str().unwrap_or_default();
+    match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) if !is_public_ip(ip) => Err(ProduceError::Permanent(
+            BlockedAddress(host.to_string()).to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn is_blocked(e: &reqwest::Error) -> bool {
+    let mut source = e.source();
+    while let Some(e) = source {
+        if e.is::<BlockedAddress>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// The change event a user webhook receives.
+pub fn webhook_event(msg: &Notification, sent_at: i64) -> Value {
+    json!({
+        "kind": msg.kind.as_str(),
+        "title": msg.title,
+        "body": msg.body,
+        "data": msg.data,
+        "priority": msg.priority,
+        "idempotency_key": msg.idempotency_key,
+        "sent_at": sent_at,
+    })
+}
+
+/// Throttling and server errors are worth another try and 410 Gone retires
+/// the webhook; any other refusal is the user's endpoint rejecting the event.
+fn webhook_error(status: StatusCode) -> ProduceError {
+    let message = format!("Webhook responded {}", status);
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        ProduceError::Transient(message)
+    } else if status == StatusCode::GONE {
+        ProduceError::Unregistered(message)
+    } else {
+        ProduceError::Permanent(message)
+    }
+}
+
+#[async_trait]
+impl EventProducerInterface for WebhookProducer {
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+        check_webhook_host(&msg.device_token)?;
+        let body = webhook_event(msg, Utc::now().timestamp()).to_string();
+        let secret = user_webhook_secret(&self.signing_key, &msg.device_token);
+        let response = self
+            .client
+            .post(&msg.device_token)
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, sign(&secret, body.as_bytes()))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| {
+                if is_blocked(&e) {
+                    ProduceError::Permanent(format!("Webhook blocked: {}", e))
+                } else {
+                    ProduceError::Transient(format!("Webhook unreachable: {}", e))
+                }
+            })?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Receipt::Delivered(None));
+        }
+        Err(webhook_error(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::NotificationKind;
+
+    #[test]
+    fn test_event_carries_change_and_key() {
+        let notification = Notification::new(
+            NotificationKind::Grade,
+            "Math".to_string(),
+            "New grade | 90 %".to_string(),
+        )
+        .with_change("token", Some(1), Some(2), "90 %");
+
+        let event = webhook_event(&notification, 1_700_000_000);
+
+        assert_eq!(event["kind"], "grade");
+        assert_eq!(event["title"], "Math");
+        assert_eq!(event["data"]["grade_item_id"], "2");
+        assert_eq!(event["priority"], "normal");
+        assert_eq!(
+            event["idempotency_key"],
+            json!(notification.idempotency_key)
+        );
+        assert_eq!(event["sent_at"], 1_700_000_000);
+    }
+
+    #[test]
+    fn test_only_throttling_and_server_errors_are_retried() {
+        assert!(webhook_error(StatusCode::TOO_MANY_REQUESTS).is_transient());
+        assert!(webhook_error(StatusCode::BAD_GATEWAY).is_transient());
+        assert!(!webhook_error(StatusCode::NOT_FOUND).is_transient());
+        assert!(webhook_error(StatusCode::GONE).is_unregistered());
+    }
+
+    #[tokio::test]
+    async fn test_private_addresses_are_refused() {
+        assert!(check_webhook_host("https://127.0.0.1/hook").is_err());
+        assert!(check_webhook_host("https://0x7f.0.0.1/hook").is_err());
+        assert!(check_webhook_host("https://[::1]/hook").is_err());
+        assert!(check_webhook_host("https://hooks.example.com/hook").is_ok());
+
+        let resolved = PublicResolver.resolve("localhost".parse().unwrap()).await;
+        assert!(resolved.is_err());
+    }
+}
@@ -1,9 +1,14 @@
+use actix_web::middleware::{from_fn, Compress};
 use actix_web::{guard, web, App, HttpResponse, HttpServer};
 use config::Config;
 use infrastructure::app_setup::{
-    create_app_state, initialize_dependencies, spawn_background_tasks,
+    create_app_state, initialize_dependencies, spawn_background_tasks, spawn_device_token_listener,
+    spawn_retention_job,
 };
 use std::error::Error;
+use std::sync::Arc;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 mod config;
 mod controllers;
@@ -12,28 +17,48 @@ mod models;
 mod repositories;
 mod services;
 
-use crate::controllers::course_controller::course_routes;
-use crate::controllers::deadline_controller::deadline_routes;
-use crate::controllers::grade_controller::grade_routes;
-use crate::controllers::user_controller::user_routes;
+use crate::controllers::api_docs::{ApiDoc, OPENAPI_URL};
+use crate::controllers::api_version::api_routes;
+use crate::controllers::health_controller::health_routes;
+use crate::controllers::metrics_controller::metrics_routes;
+use crate::controllers::shared::api_key_auth::require_api_key;
+use crate::controllers::shared::cors::cors;
+use crate::controllers::shared::rate_limit::rate_limit;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     dotenv::dotenv().ok();
 
     let config = Config::from_env()?;
-    let deps = initialize_dependencies(&config).await?;
-    spawn_background_tasks(deps.producer_service, config.batch_size).await;
-    let app_state = create_app_state(deps.data_service);
+    if config.api_keys.is_empty() {
+        eprintln!("API_KEYS is not set; API routes accept requests without a key");
+    }
+    let mut deps = initialize_dependencies(&config).await?;
+    spawn_background_tasks(
+        Arc::clone(&deps.producer_service),
+        Arc::clone(&deps.stats_service),
+        config.batch_size,
+    )
+    .await;
+    spawn_retention_job(Arc::clone(&deps.retention_service));
+    if let Some(source) = deps.device_token_changes.take() {
+        spawn_device_token_listener(source, Arc::clone(&deps.producer_service));
+    }
+    let app_state = create_app_state(deps, &config);
+    let cors_settings = config.cors.clone();
 
     let address = format!("0.0.0.0:{}", config.port);
     HttpServer::new(move || {
         App::new()
             .app_data(app_state.clone())
-            .configure(user_routes)
-            .configure(course_routes)
-            .configure(grade_routes)
-            .configure(deadline_routes)
+            .wrap(from_fn(require_api_key))
+            .wrap(Compress::default())
+            .wrap(from_fn(rate_limit))
+            .wrap(cors(&cors_settings))
+            .configure(api_routes)
+            .configure(health_routes)
+            .configure(metrics_routes)
+            .service(SwaggerUi::new("/swagger-ui/{_:.*}").url(OPENAPI_URL, ApiDoc::openapi()))
             .default_service(
                 web::route()
                     .guard(guard::Not(guard::Get()))
@@ -0,0 +1,71 @@
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::token::Platform;
+
+/// Recipients loaded and sent to at a time.
+pub const BROADCAST_BATCH_SIZE: u64 = 500;
+
+/// An announcement for every registered device, or a segment of them.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct Broadcast {
+    pub title: String,
+    pub body: String,
+    /// An https image shown with the announcement.
+    #[serde(default)]
+    pub image_url: Option<String>,
+    #[serde(default)]
+    pub segment: BroadcastSegment,
+}
+
+impl Broadcast {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.title.trim().is_empty() {
+            return Err("title".to_string());
+        }
+        if self.body.trim().is_empty() {
+            return Err("body".to_string());
+        }
+        if self
+            .image_url
+            .as_ref()
+            .is_some_and(|url| !url.starts_with("https://"))
+        {
+            return Err("image_url".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Unset filters match every user with a device who has not turned notifications
+/// off; a platform limits the send to that platform's devices.
+#[derive(Debug, Deserialize, Default, Clone, ToSchema)]
+pub struct BroadcastSegment {
+    pub platform: Option<Platform>,
+    /// Only users enrolled in this course.
+    pub course_id: Option<i64>,
+}
+
+impl BroadcastSegment {
+    pub fn filter(&self) -> Document {
+        let mut filter = doc! {
+            "devices.0": {"$exists": true},
+            "preferences.notifications_off": {"$ne": true},
+        };
+        if let Some(platform) = self.platform {
+            filter.insert("devices.platform", platform.as_str());
+        }
+        if let Some(course_id) = self.course_id {
+            filter.insert("courses.id", course_id);
+        }
+        filter
+    }
+}
+
+#[derive(Debug, Serialize, Default, PartialEq, ToSchema)]
+pub struct BroadcastReport {
+    pub recipients: u64,
+    /// Recipients the same announcement had already reached are not sent again.
+    pub sent: u64,
+}
@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use super::deadline::Deadline;
+
+const PRODID: &str = "-//aitu-keeper//Deadlines//EN";
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CalendarLink {
+    pub feed_url: String,
+}
+
+impl CalendarLink {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            feed_url: format!("/calendar/{}.ics", secret),
+        }
+    }
+}
+
+pub struct CalendarFeed {
+    pub body: String,
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl CalendarFeed {
+    pub fn new(deadlines: &[Deadline], last_modified: Option<DateTime<Utc>>) -> Self {
+        let body = deadlines_to_ics(deadlines, last_modified.unwrap_or_default());
+        let etag = format!("{:x}", Sha256::digest(body.as_bytes()));
+        Self {
+            body,
+            etag,
+            last_modified,
+        }
+    }
+}
+
+pub fn deadlines_to_ics(deadlines: &[Deadline], stamp: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+    ];
+
+    for deadline in deadlines {
+        let Some(start) = DateTime::from_timestamp(deadline.due_at(), 0) else {
+            continue;
+        };
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@aitu-keeper", deadline.id));
+        lines.push(format!("DTSTAMP:{}", format_ics_time(stamp)));
+        lines.push(format!("DTSTART:{}", format_ics_time(start)));
+        lines.push(format!("SUMMARY:{}", escape_text(&deadline.name)));
+        if let Some(coursename) = &deadline.coursename {
+            lines.push(format!("DESCRIPTION:{}", escape_text(coursename)));
+            lines.push(format!("CATEGORIES:{}", escape_text(coursename)));
+        }
+        lines.push("STATUS:CONFIRMED".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+fn format_ics_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+    for ch in line.chars() {
+        let len = ch.len_utf8();
+        if octets + len > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(ch);
+        octets += len;
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deadline(id: i32, name: &str) -> Deadline {
+        Deadline {
+            id,
+            name: name.to_string(),
+            timeusermidnight: 1_767_225_600,
+            formattedtime: "1 January 2026 12:00".to_string(),
+            coursename: Some("Math, Algebra".to_string()),
+            courseid: None,
+            timestart: None,
+        }
+    }
+
+    #[test]
+    fn test_deadlines_to_ics() {
+        let ics = deadlines_to_ics(&[deadline(7, "Essay")], DateTime::default());
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.contains("UID:7@aitu-keeper\r\n"));
+        assert!(ics.contains("DTSTART:20260101T000000Z\r\n"));
+        assert!(ics.contains("SUMMARY:Essay\r\n"));
+        assert!(ics.contains("CATEGORIES:Math\\, Algebra\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn test_provider_timestart_is_preferred() {
+        let essay = Deadline {
+            timestart: Some(1_767_268_800),
+            ..deadline(7, "Essay")
+        };
+        let ics = deadlines_to_ics(&[essay], DateTime::default());
+        assert!(ics.contains("DTSTART:20260101T120000Z\r\n"));
+    }
+
+    #[test]
+    fn test_long_lines_are_folded() {
+        let ics = deadlines_to_ics(&[deadline(1, &"x".repeat(200))], DateTime::default());
+        assert!(ics.split("\r\n").all(|line| line.len() <= MAX_LINE_OCTETS));
+        assert!(ics.contains("\r\n x"));
+    }
+
+    #[test]
+    fn test_etag_changes_with_content() {
+        let first = CalendarFeed::new(&[deadline(1, "Essay")], None);
+        let same = CalendarFeed::new(&[deadline(1, "Essay")], None);
+        let changed = CalendarFeed::new(&[deadline(1, "Report")], None);
+        assert_eq!(first.etag, same.etag);
+        assert_ne!(first.etag, changed.etag);
+    }
+}
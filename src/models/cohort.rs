@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// Rollout group of a token; canary tokens run the comparison logic under test.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Cohort {
+    #[default]
+    Stable,
+    Canary,
+}
+
+impl Cohort {
+    /// Hash-based, so a token stays in its cohort across restarts and growing
+    /// the percentage only moves stable tokens into the canary.
+    pub fn of(token: &str, canary_percent: u8) -> Self {
+        let digest = Sha256::digest(token.as_bytes());
+        let bucket = u64::from_be_bytes(digest[..8].try_into().unwrap()) % 100;
+        if bucket < u64::from(canary_percent) {
+            Cohort::Canary
+        } else {
+            Cohort::Stable
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Cohort::Stable => "stable",
+            Cohort::Canary => "canary",
+        }
+    }
+}
+
+/// Comparison logic a cohort runs; candidates are added here as they are
+/// written and picked for the canary by name.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ComparisonKind {
+    #[default]
+    Stable,
+}
+
+impl FromStr for ComparisonKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "stable" => Ok(ComparisonKind::Stable),
+            _ => Err(format!("unknown comparison: {}", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cohort_assignment_is_stable_and_disjoint() {
+        let tokens: Vec<String> = (0..1000).map(|i| format!("token-{}", i)).collect();
+        let canary = |percent| -> Vec<&String> {
+            tokens
+                .iter()
+                .filter(|token| Cohort::of(token, percent) == Cohort::Canary)
+                .collect()
+        };
+
+        let ten = canary(10);
+        assert_eq!(ten, canary(10));
+        assert!((50..150).contains(&ten.len()));
+        assert!(ten
+            .iter()
+            .all(|token| Cohort::of(token, 10) != Cohort::Stable));
+
+        let twenty = canary(20);
+        assert!(ten.iter().all(|token| twenty.contains(token)));
+        assert!(canary(0).is_empty());
+        assert_eq!(canary(100).len(), tokens.len());
+    }
+
+    #[test]
+    fn test_comparison_kind_parsed_by_name() {
+        assert_eq!(" Stable ".parse(), Ok(ComparisonKind::Stable));
+        assert!("candidate".parse::<ComparisonKind>().is_err());
+    }
+}
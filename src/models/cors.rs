@@ -0,0 +1,22 @@
+/// Cross-origin access for browser clients; no origins keeps it closed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsSettings {
+    /// Exact origins, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub max_age_secs: usize,
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+                .map(str::to_string)
+                .to_vec(),
+            allowed_headers: ["Content-Type", "X-Api-Key"].map(str::to_string).to_vec(),
+            max_age_secs: 3600,
+        }
+    }
+}
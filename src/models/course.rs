@@ -1,11 +1,19 @@
+use async_graphql::SimpleObject;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, ToSchema, SimpleObject)]
 pub struct Course {
     pub id: i64,
     pub fullname: String,
+    #[serde(default)]
+    pub shortname: Option<String>,
     enddate: i64,
+    /// No longer listed by the provider, e.g. after unenrolment; kept so it
+    /// doesn't silently disappear.
+    #[serde(default)]
+    pub removed: bool,
 }
 
 impl Course {
@@ -15,18 +23,106 @@ impl Course {
         let current_unix_time = current_time.timestamp();
         courses.retain(|course| course.enddate > current_unix_time);
     }
+
+    pub fn delete_removed_courses(courses: &mut Vec<Course>) {
+        courses.retain(|course| !course.removed);
+    }
+}
+
+/// Case-insensitive text matched against course full and short names.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CourseSearchQuery {
+    pub q: String,
+}
+
+pub fn search_courses(courses: Vec<Course>, text: &str) -> Vec<Course> {
+    let text = text.trim().to_lowercase();
+    courses
+        .into_iter()
+        .filter(|course| {
+            course.fullname.to_lowercase().contains(&text)
+                || course
+                    .shortname
+                    .as_ref()
+                    .is_some_and(|shortname| shortname.to_lowercase().contains(&text))
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize, Default, IntoParams)]
+pub struct CourseQuery {
+    /// Leave out courses that already ended.
+    #[serde(default)]
+    pub active: bool,
+}
+
+/// Provider order varies between calls, so courses are kept sorted by id, then name.
+pub fn sort_courses(courses: &mut [Course]) {
+    courses.sort_by(|a, b| a.id.cmp(&b.id).then_with(|| a.fullname.cmp(&b.fullname)));
+}
+
+/// Shortnames were stored only later, so they don't make a course new.
+fn is_same_course(a: &Course, b: &Course) -> bool {
+    a.id == b.id && a.fullname == b.fullname && a.enddate == b.enddate
 }
 
 pub fn compare_courses<'a>(external_courses: &'a [Course], courses: &[Course]) -> Vec<&'a Course> {
     let mut new_courses = Vec::new();
     for external_course in external_courses {
-        if !courses.contains(external_course) {
+        if !courses
+            .iter()
+            .any(|course| is_same_course(course, external_course))
+        {
             new_courses.push(external_course);
         }
     }
     new_courses
 }
 
+/// Whether a stored course lacks the shortname the provider now lists, as
+/// courses stored before shortnames were kept do.
+pub fn lacks_shortnames(external_courses: &[Course], courses: &[Course]) -> bool {
+    courses.iter().any(|course| {
+        course.shortname.is_none()
+            && external_courses.iter().any(|external_course| {
+                external_course.id == course.id && external_course.shortname.is_some()
+            })
+    })
+}
+
+/// Stored courses the provider no longer lists; ones already flagged as
+/// removed aren't reported again.
+pub fn removed_courses<'a>(external_courses: &[Course], courses: &'a [Course]) -> Vec<&'a Course> {
+    courses
+        .iter()
+        .filter(|course| {
+            !course.removed
+                && !external_courses
+                    .iter()
+                    .any(|external_course| external_course.id == course.id)
+        })
+        .collect()
+}
+
+/// The provider's courses plus the stored ones it no longer lists, flagged as
+/// removed.
+pub fn with_removed_courses(external_courses: &[Course], courses: &[Course]) -> Vec<Course> {
+    let mut merged = external_courses.to_vec();
+    for course in courses {
+        if !external_courses
+            .iter()
+            .any(|external_course| external_course.id == course.id)
+        {
+            merged.push(Course {
+                removed: true,
+                ..course.clone()
+            });
+        }
+    }
+    sort_courses(&mut merged);
+    merged
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -44,7 +140,9 @@ mod tests {
         let external_courses = vec![Course {
             id: 1,
             fullname: "Math".to_string(),
+            shortname: None,
             enddate: 0,
+            removed: false,
         }];
         let courses = vec![];
         let result = compare_courses(&external_courses, &courses);
@@ -58,12 +156,16 @@ mod tests {
         let external_courses = vec![Course {
             id: 1,
             fullname: "Math".to_string(),
+            shortname: None,
             enddate: 0,
+            removed: false,
         }];
         let courses = vec![Course {
             id: 1,
             fullname: "Math".to_string(),
+            shortname: None,
             enddate: 0,
+            removed: false,
         }];
         let result = compare_courses(&external_courses, &courses);
         assert!(result.is_empty());
@@ -75,18 +177,24 @@ mod tests {
             Course {
                 id: 1,
                 fullname: "Math".to_string(),
+                shortname: None,
                 enddate: 0,
+                removed: false,
             },
             Course {
                 id: 2,
                 fullname: "Physics".to_string(),
+                shortname: None,
                 enddate: 0,
+                removed: false,
             },
         ];
         let courses = vec![Course {
             id: 1,
             fullname: "Math".to_string(),
+            shortname: None,
             enddate: 0,
+            removed: false,
         }];
         let result = compare_courses(&external_courses, &courses);
         assert_eq!(result.len(), 1);
@@ -94,23 +202,121 @@ mod tests {
         assert_eq!(result[0].fullname, "Physics");
     }
 
+    #[test]
+    fn test_compare_courses_ignores_new_shortname() {
+        let stored = Course {
+            id: 1,
+            fullname: "Math".to_string(),
+            shortname: None,
+            enddate: 0,
+            removed: false,
+        };
+        let external = Course {
+            shortname: Some("MATH101".to_string()),
+            ..stored.clone()
+        };
+        let (external, stored) = ([external], [stored]);
+        assert!(lacks_shortnames(&external, &stored));
+        assert!(compare_courses(&external, &stored).is_empty());
+        assert!(!lacks_shortnames(&external, &external));
+    }
+
+    #[test]
+    fn test_removed_courses_flagged_once() {
+        let course = |id: i64, removed: bool| Course {
+            id,
+            fullname: format!("Course {}", id),
+            shortname: None,
+            enddate: 0,
+            removed,
+        };
+        let external_courses = vec![course(1, false)];
+        let courses = vec![course(1, true), course(2, false), course(3, true)];
+
+        let removed: Vec<i64> = removed_courses(&external_courses, &courses)
+            .iter()
+            .map(|course| course.id)
+            .collect();
+        assert_eq!(removed, [2]);
+        assert_eq!(
+            with_removed_courses(&external_courses, &courses),
+            vec![course(1, false), course(2, true), course(3, true)]
+        );
+    }
+
+    #[test]
+    fn test_search_courses() {
+        let course = |id: i64, fullname: &str, shortname: Option<&str>| Course {
+            id,
+            fullname: fullname.to_string(),
+            shortname: shortname.map(str::to_string),
+            enddate: 0,
+            removed: false,
+        };
+        let courses = vec![
+            course(1, "Linear Algebra", Some("MATH101")),
+            course(2, "Physics", None),
+            course(3, "Discrete Mathematics", None),
+        ];
+        let ids = |text| {
+            search_courses(courses.clone(), text)
+                .iter()
+                .map(|course| course.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(" math "), [1, 3]);
+        assert_eq!(ids("PHYS"), [2]);
+        assert!(ids("chemistry").is_empty());
+    }
+
+    #[test]
+    fn test_sort_courses() {
+        let course = |id: i64, fullname: &str| Course {
+            id,
+            fullname: fullname.to_string(),
+            shortname: None,
+            enddate: 0,
+            removed: false,
+        };
+        let mut courses = vec![
+            course(2, "Physics"),
+            course(1, "Math"),
+            course(1, "Algebra"),
+        ];
+        sort_courses(&mut courses);
+        assert_eq!(
+            courses,
+            vec![
+                course(1, "Algebra"),
+                course(1, "Math"),
+                course(2, "Physics")
+            ]
+        );
+    }
+
     #[test]
     fn test_delete_past_courses() {
         let mut courses = vec![
             Course {
                 id: 1,
                 fullname: String::from("Course 1"),
+                shortname: None,
                 enddate: 1733011200,
+                removed: false,
             },
             Course {
                 id: 2,
                 fullname: String::from("Course 2"),
+                shortname: None,
                 enddate: 1733011200,
+                removed: false,
             },
             Course {
                 id: 3,
                 fullname: String::from("Course 3"),
+                shortname: None,
                 enddate: 1733011200,
+                removed: false,
             },
         ];
 
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+/// A course section as listed by `core_course_get_contents`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ContentSection {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub modules: Vec<ContentModule>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ContentModule {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub contents: Vec<ContentFile>,
+    #[serde(default)]
+    pub contentsinfo: Option<ContentsInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ContentFile {
+    #[serde(default)]
+    pub timemodified: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ContentsInfo {
+    #[serde(default)]
+    pub lastmodified: i64,
+}
+
+impl ContentModule {
+    /// When any of the module's files last changed; 0 for modules without files.
+    fn timemodified(&self) -> i64 {
+        self.contents
+            .iter()
+            .map(|file| file.timemodified)
+            .chain(self.contentsinfo.as_ref().map(|info| info.lastmodified))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// A section, or a module within it, of a course as last seen.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CourseContent {
+    pub course_id: i64,
+    pub section_id: i64,
+    /// `None` for the section itself.
+    #[serde(default)]
+    pub module_id: Option<i64>,
+    pub name: String,
+    #[serde(default)]
+    pub timemodified: i64,
+}
+
+impl CourseContent {
+    pub fn from_sections(course_id: i64, sections: &[ContentSection]) -> Vec<CourseContent> {
+        let mut contents = Vec::new();
+        for section in sections {
+            contents.push(CourseContent {
+                course_id,
+                section_id: section.id,
+                module_id: None,
+                name: section.name.clone(),
+                timemodified: 0,
+            });
+            contents.extend(section.modules.iter().map(|module| CourseContent {
+                course_id,
+                section_id: section.id,
+                module_id: Some(module.id),
+                name: module.name.clone(),
+                timemodified: module.timemodified(),
+            }));
+        }
+        contents
+    }
+
+    fn is_same_item(&self, other: &CourseContent) -> bool {
+        self.course_id == other.course_id
+            && self.section_id == other.section_id
+            && self.module_id == other.module_id
+    }
+}
+
+/// Sections and modules that are new, or whose files changed since they were
+/// stored. A course with nothing stored yet reports nothing, so the first
+/// sync doesn't list everything already there.
+pub fn changed_course_contents<'a>(
+    external: &'a [CourseContent],
+    stored: &[CourseContent],
+    course_id: i64,
+) -> Vec<&'a CourseContent> {
+    if !stored.iter().any(|content| content.course_id == course_id) {
+        return Vec::new();
+    }
+    external
+        .iter()
+        .filter(|content| content.course_id == course_id)
+        .filter(|content| {
+            stored
+                .iter()
+                .find(|stored| stored.is_same_item(content))
+                .is_none_or(|stored| content.timemodified > stored.timemodified)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sections(files: &[i64]) -> Vec<ContentSection> {
+        vec![ContentSection {
+            id: 10,
+            name: "Week 1".to_string(),
+            modules: files
+                .iter()
+                .enumerate()
+                .map(|(i, timemodified)| ContentModule {
+                    id: i as i64 + 1,
+                    name: format!("Lecture {}", i + 1),
+                    contents: vec![ContentFile {
+                        timemodified: *timemodified,
+                    }],
+                    contentsinfo: None,
+                })
+                .collect(),
+        }]
+    }
+
+    #[test]
+    fn test_changed_course_contents_reports_new_and_updated_items() {
+        let stored = CourseContent::from_sections(1, &sections(&[100, 100]));
+        let external = CourseContent::from_sections(1, &sections(&[100, 200, 300]));
+
+        let names: Vec<_> = changed_course_contents(&external, &stored, 1)
+            .iter()
+            .map(|content| content.name.as_str())
+            .collect();
+        assert_eq!(names, ["Lecture 2", "Lecture 3"]);
+        assert!(changed_course_contents(&external, &[], 1).is_empty());
+        assert!(changed_course_contents(&external, &external, 1).is_empty());
+    }
+}
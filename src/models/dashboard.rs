@@ -0,0 +1,103 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::course::Course;
+use super::deadline::{Deadline, DeadlineQuery};
+use super::grade::{Grade, GradeOverview};
+use super::user::User;
+
+pub const RECENT_GRADES: usize = 5;
+
+/// Deadlines shown on the dashboard: the next few within two weeks.
+pub fn dashboard_deadlines_query() -> DeadlineQuery {
+    DeadlineQuery {
+        days: Some(14),
+        limit: Some(5),
+    }
+}
+
+/// Everything the app shows on launch, in one response.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Dashboard {
+    pub user: User,
+    pub courses: Vec<Course>,
+    pub upcoming_deadlines: Vec<Deadline>,
+    pub recent_grades: Vec<RecentGrade>,
+    pub grades_overview: Vec<GradeOverview>,
+}
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct RecentGrade {
+    pub courseid: i64,
+    pub coursename: Option<String>,
+    pub itemname: String,
+    pub percentageformatted: String,
+    pub gradedategraded: i64,
+}
+
+/// Graded items, most recently graded first.
+pub fn recent_grades(grades: &[Grade], limit: usize) -> Vec<RecentGrade> {
+    let mut recent: Vec<RecentGrade> = grades
+        .iter()
+        .flat_map(|grade| {
+            grade.gradeitems.iter().filter_map(|item| {
+                Some(RecentGrade {
+                    courseid: grade.courseid,
+                    coursename: grade.coursename.clone(),
+                    itemname: item.itemname.clone(),
+                    percentageformatted: item.percentageformatted.clone(),
+                    gradedategraded: item.gradedategraded?,
+                })
+            })
+        })
+        .collect();
+    recent.sort_by_key(|grade| std::cmp::Reverse(grade.gradedategraded));
+    recent.truncate(limit);
+    recent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::grade::GradeItems;
+
+    fn item(id: i64, gradedategraded: Option<i64>) -> GradeItems {
+        GradeItems {
+            id,
+            itemname: format!("Item {}", id),
+            percentageformatted: "80.00 %".to_string(),
+            itemtype: None,
+            grademax: None,
+            rangeformatted: None,
+            gradedategraded,
+            gradeislocked: false,
+            feedback: None,
+        }
+    }
+
+    #[test]
+    fn test_recent_grades_newest_first_and_limited() {
+        let grades = vec![
+            Grade {
+                coursename: Some("Math".to_string()),
+                courseid: 1,
+                gradeitems: vec![item(1, Some(100)), item(2, None)],
+            },
+            Grade {
+                coursename: Some("Physics".to_string()),
+                courseid: 2,
+                gradeitems: vec![item(3, Some(300)), item(4, Some(200))],
+            },
+        ];
+
+        let recent = recent_grades(&grades, 2);
+        assert_eq!(
+            recent
+                .iter()
+                .map(|grade| (grade.courseid, grade.itemname.as_str()))
+                .collect::<Vec<_>>(),
+            [(2, "Item 3"), (2, "Item 4")]
+        );
+        assert_eq!(recent_grades(&grades, RECENT_GRADES).len(), 3);
+    }
+}
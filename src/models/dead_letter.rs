@@ -0,0 +1,45 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::notification::Notification;
+
+/// Dead letters re-sent per re-drive request, oldest first.
+pub const REDRIVE_BATCH_SIZE: i64 = 500;
+
+/// A push to one device that still failed after every retry, kept until an
+/// admin re-drives it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeadLetter {
+    #[serde(rename = "_id", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub token: String,
+    pub notification: Notification,
+    /// The last delivery error.
+    pub error: String,
+    pub failed_at: DateTime,
+    /// Re-drives that failed again.
+    #[serde(default)]
+    pub redrives: u32,
+}
+
+impl DeadLetter {
+    pub fn new(token: &str, notification: &Notification, error: String) -> Self {
+        Self {
+            id: None,
+            token: token.to_string(),
+            notification: notification.clone(),
+            error,
+            failed_at: DateTime::now(),
+            redrives: 0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Default, PartialEq, ToSchema)]
+pub struct RedriveReport {
+    /// Delivered and removed from the dead letters.
+    pub delivered: u64,
+    /// Failed again; kept with the new error.
+    pub failed: u64,
+}
@@ -1,33 +1,56 @@
 use std::collections::HashSet;
 
 use anyhow::Result;
+use async_graphql::SimpleObject;
 use chrono::Timelike;
-use chrono::{NaiveTime, Utc};
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Serialize, Deserialize)]
+use super::i18n::Text;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Events {
     pub events: Vec<Deadline>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, ToSchema, SimpleObject)]
 pub struct Deadline {
     pub id: i32,
     pub name: String,
     pub timeusermidnight: i64,
     pub formattedtime: String,
     pub coursename: Option<String>,
+    #[serde(default)]
+    pub courseid: Option<i64>,
+    /// Exact due time from the provider, when it sends one.
+    #[serde(default)]
+    pub timestart: Option<i64>,
 }
 
 impl Deadline {
-    pub fn create_body_message_deadline(&self) -> String {
-        format!(
-            "Course: {}\nTask: {}\nUntil {}",
-            self.coursename.clone().unwrap_or("-".to_string()),
-            self.name,
-            self.formattedtime
-        )
+    /// Unix seconds the deadline is due, preferring the provider's exact time.
+    pub fn due_at(&self) -> i64 {
+        self.timestart.unwrap_or(self.timeusermidnight)
+    }
+
+    /// Whether the provider moved the stored deadline to another time.
+    pub fn is_moved_from(&self, stored: &Deadline) -> bool {
+        match (self.timestart, stored.timestart) {
+            (Some(due), Some(stored_due)) => due != stored_due,
+            // Stored before exact times were kept
+            _ => self.timeusermidnight != stored.timeusermidnight,
+        }
+    }
+
+    pub fn body_text(&self) -> Text<'_> {
+        Text::DeadlineBody {
+            course: self.coursename.as_deref().unwrap_or("-"),
+            task: &self.name,
+            until: &self.formattedtime,
+        }
     }
 }
 
@@ -59,10 +82,48 @@ pub fn sort_deadlines(deadlines: &mut [Deadline]) -> Result<Vec<Deadline>> {
         }
         sorted_deadlines.push(deadline.clone())
     }
-    sorted_deadlines.sort_by(|a, b| a.timeusermidnight.cmp(&b.timeusermidnight));
+    sorted_deadlines.sort_by_key(|d| d.timeusermidnight);
     Ok(sorted_deadlines)
 }
 
+/// Furthest ahead, in days, deadlines can be asked for.
+pub const MAX_DEADLINE_DAYS: i64 = 366;
+
+/// With `days`, only deadlines due within that many days from now are kept.
+#[derive(Debug, Deserialize, Default, IntoParams)]
+pub struct DeadlineQuery {
+    pub days: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+impl DeadlineQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .days
+            .is_some_and(|days| !(0..=MAX_DEADLINE_DAYS).contains(&days))
+        {
+            return Err("days".to_string());
+        }
+        Ok(())
+    }
+
+    /// The latest due time kept at `now`, in unix seconds.
+    pub fn until(&self, now: i64) -> Option<i64> {
+        self.days
+            .map(|days| now.saturating_add(days.saturating_mul(86400)))
+    }
+}
+
+/// Filters deadlines sorted by due time; `now` is a unix timestamp.
+pub fn filter_deadlines(deadlines: &mut Vec<Deadline>, now: i64, query: &DeadlineQuery) {
+    if let Some(until) = query.until(now) {
+        deadlines.retain(|d| d.timeusermidnight >= now && d.timeusermidnight <= until);
+    }
+    if let Some(limit) = query.limit {
+        deadlines.truncate(limit);
+    }
+}
+
 pub fn extract_time(date_str: &str) -> Option<String> {
     let re = Regex::new(r"\b(\d{1,2}:\d{2})\b").ok()?;
     if let Some(captures) = re.captures(date_str) {
@@ -102,6 +163,54 @@ pub fn compare_deadlines<'a>(
         .collect()
 }
 
+/// Fetched deadlines already stored under another due time, each with its
+/// stored copy.
+pub fn moved_deadlines<'a, 'b>(
+    external_deadlines: &'a [Deadline],
+    deadlines: &'b [Deadline],
+) -> Vec<(&'a Deadline, &'b Deadline)> {
+    external_deadlines
+        .iter()
+        .filter_map(|external| {
+            deadlines
+                .iter()
+                .find(|d| d.id == external.id)
+                .filter(|stored| external.is_moved_from(stored))
+                .map(|stored| (external, stored))
+        })
+        .collect()
+}
+
+/// A due time, in unix seconds, as the user's local date and time.
+pub fn format_due(due: i64, timezone: Tz) -> String {
+    DateTime::from_timestamp(due, 0)
+        .map(|due| {
+            due.with_timezone(&timezone)
+                .format("%d.%m.%Y %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// Stored deadlines of the course still ahead of `now` that the provider no
+/// longer returns, i.e. cancelled or moved out of its window. Passed ones drop
+/// out on their own and aren't counted.
+pub fn removed_deadlines<'a>(
+    external_deadlines: &[Deadline],
+    deadlines: &'a [Deadline],
+    course_id: i64,
+    now: i64,
+) -> Vec<&'a Deadline> {
+    let external_ids: HashSet<i32> = external_deadlines.iter().map(|d| d.id).collect();
+
+    deadlines
+        .iter()
+        .filter(|d| {
+            d.courseid == Some(course_id) && d.due_at() > now && !external_ids.contains(&d.id)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,6 +249,8 @@ mod tests {
             timeusermidnight: 1678886400,
             formattedtime: "2024-02-01 12:00".to_string(),
             coursename: Some("Math".to_string()),
+            courseid: None,
+            timestart: None,
         }];
         let result = compare_deadlines(&external_deadlines, &deadlines);
         assert!(result.is_empty());
@@ -153,6 +264,8 @@ mod tests {
             timeusermidnight: 1678886400,
             formattedtime: "2024-02-01 12:00".to_string(),
             coursename: Some("Math".to_string()),
+            courseid: None,
+            timestart: None,
         }];
         let deadlines = vec![];
         let result = compare_deadlines(&external_deadlines, &deadlines);
@@ -167,6 +280,8 @@ mod tests {
             timeusermidnight: 1678886400,
             formattedtime: "2024-02-01 12:00".to_string(),
             coursename: Some("Math".to_string()),
+            courseid: None,
+            timestart: None,
         }];
 
         let deadlines = vec![Deadline {
@@ -175,11 +290,70 @@ mod tests {
             timeusermidnight: 1678886400,
             formattedtime: "2024".to_string(),
             coursename: Some("Chemistry".to_string()),
+            courseid: None,
+            timestart: None,
         }];
         let result = compare_deadlines(&external_deadlines, &deadlines);
         assert!(result.is_empty());
     }
 
+    #[test]
+    fn test_moved_deadlines_prefer_exact_times() {
+        let deadline = |id, timeusermidnight, timestart| Deadline {
+            id,
+            name: format!("Task {}", id),
+            timeusermidnight,
+            formattedtime: String::new(),
+            coursename: None,
+            courseid: Some(7),
+            timestart,
+        };
+        let external_deadlines = vec![
+            deadline(1, 1000, Some(1500)),
+            deadline(2, 1000, Some(1500)),
+            deadline(3, 2000, None),
+            deadline(4, 1000, None),
+        ];
+        let deadlines = vec![
+            deadline(1, 1000, Some(1200)),
+            deadline(2, 1000, Some(1500)),
+            deadline(3, 1000, None),
+        ];
+        let moved = moved_deadlines(&external_deadlines, &deadlines);
+        let ids: Vec<(i32, i64)> = moved
+            .iter()
+            .map(|(external, stored)| (external.id, stored.due_at()))
+            .collect();
+        assert_eq!(ids, [(1, 1200), (3, 1000)]);
+        assert_eq!(
+            format_due(1_704_088_800, chrono_tz::Asia::Almaty),
+            "01.01.2024 12:00"
+        );
+    }
+
+    #[test]
+    fn test_removed_deadlines_skips_passed_and_other_courses() {
+        let stored = |id, courseid, timeusermidnight| Deadline {
+            id,
+            name: format!("Task {}", id),
+            timeusermidnight,
+            formattedtime: String::new(),
+            coursename: Some("Math".to_string()),
+            courseid: Some(courseid),
+            timestart: None,
+        };
+        let external_deadlines = vec![stored(1, 7, 2000)];
+        let deadlines = vec![
+            stored(1, 7, 2000),
+            stored(2, 7, 2000),
+            stored(3, 7, 500),
+            stored(4, 8, 2000),
+        ];
+        let removed = removed_deadlines(&external_deadlines, &deadlines, 7, 1000);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, 2);
+    }
+
     #[test]
     fn test_sort_deadlines_empty() -> Result<()> {
         let mut deadlines: Vec<Deadline> = Vec::new();
@@ -196,6 +370,8 @@ mod tests {
             timeusermidnight: 1678886400,
             formattedtime: "<a href=\"some link\">Some Date</a>, 12:00".to_string(),
             coursename: Some("Math".to_string()),
+            courseid: None,
+            timestart: None,
         }];
 
         let result = sort_deadlines(&mut deadlines)?;
@@ -203,4 +379,57 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_filter_deadlines_by_days_and_limit() {
+        let deadline = |id, due| Deadline {
+            id,
+            name: format!("Task {}", id),
+            timeusermidnight: due,
+            formattedtime: String::new(),
+            coursename: None,
+            courseid: None,
+            timestart: None,
+        };
+        let now = 1_000_000;
+        let all = vec![
+            deadline(1, now - 10),
+            deadline(2, now + 3600),
+            deadline(3, now + 2 * 86400),
+            deadline(4, now + 8 * 86400),
+        ];
+
+        let mut within_week = all.clone();
+        let query = DeadlineQuery {
+            days: Some(7),
+            limit: None,
+        };
+        filter_deadlines(&mut within_week, now, &query);
+        assert_eq!(within_week.iter().map(|d| d.id).collect::<Vec<_>>(), [2, 3]);
+
+        let mut limited = all.clone();
+        let query = DeadlineQuery {
+            days: Some(7),
+            limit: Some(1),
+        };
+        filter_deadlines(&mut limited, now, &query);
+        assert_eq!(limited.iter().map(|d| d.id).collect::<Vec<_>>(), [2]);
+
+        let mut unfiltered = all.clone();
+        filter_deadlines(&mut unfiltered, now, &DeadlineQuery::default());
+        assert_eq!(unfiltered, all);
+
+        let huge = DeadlineQuery {
+            days: Some(i64::MAX),
+            limit: None,
+        };
+        assert_eq!(huge.until(now), Some(i64::MAX));
+        assert_eq!(huge.validate(), Err("days".to_string()));
+        let mut unbounded = all.clone();
+        filter_deadlines(&mut unbounded, now, &huge);
+        assert_eq!(
+            unbounded.iter().map(|d| d.id).collect::<Vec<_>>(),
+            [2, 3, 4]
+        );
+    }
 }
@@ -1,6 +1,8 @@
 use actix_web::{HttpResponse, ResponseError};
 use derive_more::Display;
 use serde::Serialize;
+use serde_json::{json, Value};
+use utoipa::ToSchema;
 
 use crate::services::errors::ServiceError;
 
@@ -12,24 +14,82 @@ pub enum ApiError {
     #[display("User already exist")]
     UserAlreadyExist,
 
+    #[display("Unauthorized")]
+    Unauthorized,
+
     #[display("Data not found: {field}")]
     DataNotFound { field: String },
 
     #[display("Data is empty: {field}")]
     DataIsEmpty { field: String },
 
+    #[display("Invalid input: {field}")]
+    InvalidInput { field: String },
+
+    #[display("Device token is registered to another user")]
+    DeviceTokenInUse,
+
+    #[display("Too many requests")]
+    TooManyRequests,
+
+    #[display("The course provider is unavailable. Please try again later.")]
+    ProviderUnavailable,
+
     #[display("An internal error occurred. Please try again later.")]
     InternalServerError,
 }
 
+/// Body of every error response.
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct ErrorBody {
+    /// Stable, machine-readable error code.
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidToken => "invalid_token",
+            ApiError::UserAlreadyExist => "user_already_exists",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::DataNotFound { .. } => "data_not_found",
+            ApiError::DataIsEmpty { .. } => "data_is_empty",
+            ApiError::InvalidInput { .. } => "invalid_input",
+            ApiError::DeviceTokenInUse => "device_token_in_use",
+            ApiError::TooManyRequests => "too_many_requests",
+            ApiError::ProviderUnavailable => "provider_unavailable",
+            ApiError::InternalServerError => "internal_error",
+        }
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        let details = match self {
+            ApiError::DataNotFound { field }
+            | ApiError::DataIsEmpty { field }
+            | ApiError::InvalidInput { field } => Some(json!({ "field": field })),
+            _ => None,
+        };
+        ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            details,
+        }
+    }
+}
+
 impl From<ServiceError> for ApiError {
     fn from(err: ServiceError) -> Self {
         match err {
             ServiceError::InvalidToken => ApiError::InvalidToken,
             ServiceError::DataNotFound(field) => ApiError::DataNotFound { field },
             ServiceError::DataIsEmpty(field) => ApiError::DataIsEmpty { field },
+            ServiceError::InvalidInput(field) => ApiError::InvalidInput { field },
+            ServiceError::DeviceTokenInUse => ApiError::DeviceTokenInUse,
             ServiceError::DatabaseError(_msg) => ApiError::InternalServerError,
-            ServiceError::ProviderError(_msg) => ApiError::InternalServerError,
+            ServiceError::ProviderError(_msg) => ApiError::ProviderUnavailable,
             ServiceError::UserAlreayExist => ApiError::UserAlreadyExist,
         }
     }
@@ -37,16 +97,74 @@ impl From<ServiceError> for ApiError {
 
 impl ResponseError for ApiError {
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).body(self.to_string())
+        HttpResponse::build(self.status_code()).json(self.body())
     }
 
     fn status_code(&self) -> actix_web::http::StatusCode {
         match self {
-            ApiError::InvalidToken => actix_web::http::StatusCode::BAD_REQUEST,
+            ApiError::InvalidToken => actix_web::http::StatusCode::UNAUTHORIZED,
             ApiError::DataNotFound { field: _ } => actix_web::http::StatusCode::NOT_FOUND,
             ApiError::DataIsEmpty { field: _ } => actix_web::http::StatusCode::NOT_FOUND,
+            ApiError::InvalidInput { field: _ } => actix_web::http::StatusCode::BAD_REQUEST,
+            ApiError::DeviceTokenInUse => actix_web::http::StatusCode::CONFLICT,
+            ApiError::TooManyRequests => actix_web::http::StatusCode::TOO_MANY_REQUESTS,
+            ApiError::ProviderUnavailable => actix_web::http::StatusCode::BAD_GATEWAY,
             ApiError::InternalServerError => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::UserAlreadyExist => actix_web::http::StatusCode::FOUND,
+            ApiError::Unauthorized => actix_web::http::StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+
+    #[test]
+    fn test_service_errors_map_to_codes_and_statuses() {
+        let cases = [
+            (
+                ServiceError::InvalidToken,
+                StatusCode::UNAUTHORIZED,
+                "invalid_token",
+            ),
+            (
+                ServiceError::DataIsEmpty("Grades".to_string()),
+                StatusCode::NOT_FOUND,
+                "data_is_empty",
+            ),
+            (
+                ServiceError::ProviderError("timeout".to_string()),
+                StatusCode::BAD_GATEWAY,
+                "provider_unavailable",
+            ),
+            (
+                ServiceError::DatabaseError("down".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+            ),
+        ];
+        for (service_error, status, code) in cases {
+            let error = ApiError::from(service_error);
+            assert_eq!(error.status_code(), status);
+            assert_eq!(error.code(), code);
+        }
+    }
+
+    #[test]
+    fn test_body_carries_field_details() {
+        let body = ApiError::DataNotFound {
+            field: "User".to_string(),
         }
+        .body();
+        assert_eq!(
+            serde_json::to_value(body).unwrap(),
+            json!({
+                "code": "data_not_found",
+                "message": "Data not found: User",
+                "details": {"field": "User"},
+            })
+        );
     }
 }
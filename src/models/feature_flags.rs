@@ -0,0 +1,60 @@
+use serde::Serialize;
+
+use super::cohort::ComparisonKind;
+
+/// Per-environment toggles, loaded once at startup.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FeatureFlags {
+    /// Check course totals for changes on every pass.
+    pub grade_overview: bool,
+    /// Skip the course total notification when an item grade of the course was just sent.
+    pub suppress_overview_after_grade: bool,
+    /// Minimum rise in percentage points before a grade item is notified again.
+    pub grade_notify_threshold: Option<f64>,
+    pub validate_device_tokens: bool,
+    /// Re-read saved data after registration and report mismatches.
+    pub verify_registration: bool,
+    /// Keep a registration whose grades or deadlines failed to load and backfill them later.
+    pub best_effort_registration: bool,
+    /// Let an empty grade set from the provider overwrite a course's stored grades.
+    pub allow_clearing_grades: bool,
+    /// Grade notifications sent per course in one pass; the rest collapse into a summary.
+    pub grade_notifications_per_course: Option<usize>,
+    /// Share of tokens, in percent, compared with the canary logic.
+    pub canary_percent: u8,
+    /// Comparison logic the canary cohort runs.
+    pub canary_comparison: ComparisonKind,
+    /// Process users right after their device token is set; needs a Mongo replica set.
+    pub watch_device_tokens: bool,
+    /// Remind about stored deadlines due within this many hours; off when unset.
+    pub deadline_reminder_hours: Option<i64>,
+    /// Remind at escalating tiers, 7 days down to 30 minutes by default,
+    /// before each stored deadline is due.
+    pub scheduled_reminders: bool,
+    /// Pushes a user gets per hour; a pass's excess folds into one "and N more" push.
+    pub notifications_per_hour: Option<usize>,
+    /// Send the app a silent push after every successful pass so it refreshes its cache.
+    pub background_sync_pushes: bool,
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            grade_overview: true,
+            suppress_overview_after_grade: false,
+            grade_notify_threshold: None,
+            validate_device_tokens: true,
+            verify_registration: false,
+            best_effort_registration: false,
+            allow_clearing_grades: false,
+            grade_notifications_per_course: None,
+            canary_percent: 0,
+            canary_comparison: ComparisonKind::Stable,
+            watch_device_tokens: false,
+            deadline_reminder_hours: None,
+            scheduled_reminders: false,
+            notifications_per_hour: None,
+            background_sync_pushes: false,
+        }
+    }
+}
@@ -1,37 +1,128 @@
+use async_graphql::SimpleObject;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Grader feedback longer than this many characters is cut in notifications.
+const FEEDBACK_SNIPPET_CHARS: usize = 120;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserGrades {
     pub usergrades: Vec<Grade>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, SimpleObject)]
 pub struct Grade {
     pub coursename: Option<String>,
     pub courseid: i64,
     pub gradeitems: Vec<GradeItems>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema, SimpleObject)]
 pub struct GradeItems {
-    id: i64,
+    pub id: i64,
+    pub itemname: String,
+    pub percentageformatted: String,
+    #[serde(default)]
+    pub itemtype: Option<String>,
+    #[serde(default)]
+    pub grademax: Option<f64>,
+    #[serde(default)]
+    pub rangeformatted: Option<String>,
+    /// Unix time the item was graded.
+    #[serde(default)]
+    pub gradedategraded: Option<i64>,
+    #[serde(default)]
+    pub gradeislocked: bool,
+    /// The grader's comment, as HTML; Moodle sends an empty string for none.
+    #[serde(default)]
+    pub feedback: Option<String>,
+}
+
+impl GradeItems {
+    /// The grader's comment as plain text, cut to fit a notification.
+    pub fn feedback_snippet(&self) -> Option<String> {
+        let feedback = self.feedback.as_deref()?;
+        let tags = Regex::new(r"<[^>]*>").ok()?;
+        let text = tags
+            .replace_all(feedback, " ")
+            .replace("&nbsp;", " ")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&");
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            return None;
+        }
+        if text.chars().count() <= FEEDBACK_SNIPPET_CHARS {
+            return Some(text);
+        }
+        let cut: String = text.chars().take(FEEDBACK_SNIPPET_CHARS - 1).collect();
+        Some(format!("{}…", cut.trim_end()))
+    }
+}
+
+/// A grade item of one course, with `percentageformatted` parsed into a number.
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct CourseGradeItem {
+    pub id: i64,
     pub itemname: String,
     pub percentageformatted: String,
+    pub percentage: Option<f64>,
+    pub itemtype: Option<String>,
+    pub grademax: Option<f64>,
+    pub rangeformatted: Option<String>,
+    pub gradeislocked: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+impl From<GradeItems> for CourseGradeItem {
+    fn from(item: GradeItems) -> Self {
+        CourseGradeItem {
+            percentage: parse_percentage(&item.percentageformatted),
+            id: item.id,
+            itemname: item.itemname,
+            percentageformatted: item.percentageformatted,
+            itemtype: item.itemtype,
+            grademax: item.grademax,
+            rangeformatted: item.rangeformatted,
+            gradeislocked: item.gradeislocked,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct GradesOverview {
     pub grades: Vec<GradeOverview>,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, ToSchema, SimpleObject)]
 pub struct GradeOverview {
     pub course_name: Option<String>,
     pub courseid: i64,
     pub grade: String,
+    #[graphql(skip)]
     rawgrade: String,
 }
 
+/// Whether a stored item lacks the grading time the provider now lists, as
+/// grades stored before it was kept do.
+pub fn lacks_graded_dates(external_grades: &[Grade], grades: &[Grade]) -> bool {
+    grades.iter().any(|grade| {
+        grade.gradeitems.iter().any(|item| {
+            item.gradedategraded.is_none()
+                && external_grades
+                    .iter()
+                    .filter(|external_grade| external_grade.courseid == grade.courseid)
+                    .flat_map(|external_grade| &external_grade.gradeitems)
+                    .any(|external_item| {
+                        external_item.id == item.id && external_item.gradedategraded.is_some()
+                    })
+        })
+    })
+}
+
 pub fn compare_grades<'a>(
     external_grades: &'a mut [Grade],
     grades: &'a mut [Grade],
@@ -71,6 +162,96 @@ pub fn compare_grades<'a>(
     new_and_old_grades
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GradeMark {
+    pub itemid: i64,
+    pub percentage: f64,
+}
+
+pub fn parse_percentage(percentage: &str) -> Option<f64> {
+    percentage
+        .trim()
+        .trim_end_matches('%')
+        .trim()
+        .replace(',', ".")
+        .parse::<f64>()
+        .ok()
+}
+
+/// Returns whether a changed grade item should be notified: only when it rises
+/// above the last notified percentage by more than `threshold`, or when the
+/// grade is locked as final. The mark is raised whenever a notification fires.
+pub fn passes_grade_mark(marks: &mut Vec<GradeMark>, item: &GradeItems, threshold: f64) -> bool {
+    let Some(percentage) = parse_percentage(&item.percentageformatted) else {
+        return true;
+    };
+    let mark = marks.iter_mut().find(|mark| mark.itemid == item.id);
+
+    match mark {
+        Some(mark) if item.gradeislocked || percentage > mark.percentage + threshold => {
+            mark.percentage = mark.percentage.max(percentage);
+            true
+        }
+        Some(_) => false,
+        None => {
+            marks.push(GradeMark {
+                itemid: item.id,
+                percentage,
+            });
+            true
+        }
+    }
+}
+
+/// One row per grade item: course, item, percentage and range.
+pub fn grades_to_csv(grades: &[Grade]) -> String {
+    let mut csv = String::from("course,item,percentage,range\r\n");
+    for grade in grades {
+        let course = grade.coursename.as_deref().unwrap_or_default();
+        for item in &grade.gradeitems {
+            let range = item
+                .rangeformatted
+                .as_deref()
+                .unwrap_or_default()
+                .replace("&ndash;", "-");
+            let row = [course, &item.itemname, &item.percentageformatted, &range];
+            csv.push_str(&row.map(csv_field).join(","));
+            csv.push_str("\r\n");
+        }
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn has_items(grades: &[Grade], course_id: i64) -> bool {
+    grades
+        .iter()
+        .any(|grade| grade.courseid == course_id && !grade.gradeitems.is_empty())
+}
+
+/// Keeps the stored grades of courses the provider suddenly returned no grade items for.
+pub fn keep_stored_grades(grades: &mut Vec<Grade>, stored_grades: &[Grade], course_ids: &[i64]) {
+    for &course_id in course_ids {
+        if has_items(grades, course_id) || !has_items(stored_grades, course_id) {
+            continue;
+        }
+        grades.retain(|grade| grade.courseid != course_id);
+        grades.extend(
+            stored_grades
+                .iter()
+                .filter(|grade| grade.courseid == course_id)
+                .cloned(),
+        );
+    }
+}
+
 pub fn sort_grades_overview(grades_overview: &mut Vec<GradeOverview>) {
     grades_overview
         .retain(|grade_overview| grade_overview.grade != "0.00" && grade_overview.grade != "0,00");
@@ -128,6 +309,12 @@ mod tests {
                 id: 1,
                 itemname: "Homework 1".to_string(),
                 percentageformatted: "50.00%".to_string(),
+                itemtype: None,
+                grademax: None,
+                rangeformatted: None,
+                gradedategraded: None,
+                gradeislocked: false,
+                feedback: None,
             }],
         }];
         let mut grades = vec![Grade {
@@ -137,6 +324,12 @@ mod tests {
                 id: 1,
                 itemname: "Homework 1".to_string(),
                 percentageformatted: "60.00%".to_string(),
+                itemtype: None,
+                grademax: None,
+                rangeformatted: None,
+                gradedategraded: None,
+                gradeislocked: false,
+                feedback: None,
             }],
         }];
 
@@ -155,11 +348,121 @@ mod tests {
                 id: 1,
                 itemname: "Homework 1".to_string(),
                 percentageformatted: "50.00%".to_string(),
+                itemtype: None,
+                grademax: None,
+                rangeformatted: None,
+                gradedategraded: None,
+                gradeislocked: false,
+                feedback: None,
             }],
         }];
         let mut grades = external_grades.clone();
+        assert!(!lacks_graded_dates(&external_grades, &grades));
+        external_grades[0].gradeitems[0].gradedategraded = Some(1_700_000_000);
+        assert!(lacks_graded_dates(&external_grades, &grades));
 
         let result = compare_grades(&mut external_grades, &mut grades);
         assert!(result.is_empty());
     }
+
+    fn grade_item(percentageformatted: &str, gradeislocked: bool) -> GradeItems {
+        GradeItems {
+            id: 1,
+            itemname: "Quiz".to_string(),
+            percentageformatted: percentageformatted.to_string(),
+            itemtype: None,
+            grademax: None,
+            rangeformatted: None,
+            gradedategraded: None,
+            gradeislocked,
+            feedback: None,
+        }
+    }
+
+    #[test]
+    fn test_feedback_snippet_strips_html_and_truncates() {
+        let with_feedback = |feedback: &str| GradeItems {
+            feedback: Some(feedback.to_string()),
+            ..grade_item("50.00 %", false)
+        };
+        assert_eq!(grade_item("50.00 %", false).feedback_snippet(), None);
+        assert_eq!(with_feedback("").feedback_snippet(), None);
+        assert_eq!(with_feedback("<p> </p>").feedback_snippet(), None);
+        assert_eq!(
+            with_feedback(
+                "<p>Good work,&nbsp;but cite <b>sources</b> &amp; check</p>\n<p>units</p>"
+            )
+            .feedback_snippet()
+            .unwrap(),
+            "Good work, but cite sources & check units"
+        );
+
+        let long = with_feedback(&"word ".repeat(50))
+            .feedback_snippet()
+            .unwrap();
+        assert_eq!(long.chars().count(), FEEDBACK_SNIPPET_CHARS);
+        assert!(long.ends_with("word…"));
+    }
+
+    #[test]
+    fn test_grades_to_csv() {
+        let grades = vec![Grade {
+            coursename: Some("Math, Algebra".to_string()),
+            courseid: 1,
+            gradeitems: vec![GradeItems {
+                itemname: "Quiz \"A\"".to_string(),
+                rangeformatted: Some("0&ndash;100".to_string()),
+                ..grade_item("50.00 %", false)
+            }],
+        }];
+        assert_eq!(
+            grades_to_csv(&grades),
+            "course,item,percentage,range\r\n\"Math, Algebra\",\"Quiz \"\"A\"\"\",50.00 %,0-100\r\n"
+        );
+        assert_eq!(grades_to_csv(&[]), "course,item,percentage,range\r\n");
+    }
+
+    #[test]
+    fn test_parse_percentage() {
+        assert_eq!(parse_percentage("50.00 %"), Some(50.0));
+        assert_eq!(parse_percentage("72,50 %"), Some(72.5));
+        assert_eq!(parse_percentage("-"), None);
+    }
+
+    #[test]
+    fn test_grade_mark_oscillating_grade_notifies_only_increases() {
+        let mut marks = Vec::new();
+        let notified: Vec<&str> = [
+            "50.00 %", "60.00 %", "55.00 %", "60.00 %", "65.00 %", "62.00 %",
+        ]
+        .into_iter()
+        .filter(|value| passes_grade_mark(&mut marks, &grade_item(value, false), 0.0))
+        .collect();
+        assert_eq!(notified, vec!["50.00 %", "60.00 %", "65.00 %"]);
+        assert_eq!(marks[0].percentage, 65.0);
+    }
+
+    #[test]
+    fn test_grade_mark_threshold_and_finalization() {
+        let mut marks = vec![GradeMark {
+            itemid: 1,
+            percentage: 60.0,
+        }];
+        assert!(!passes_grade_mark(
+            &mut marks,
+            &grade_item("64.00 %", false),
+            5.0
+        ));
+        assert!(passes_grade_mark(
+            &mut marks,
+            &grade_item("66.00 %", false),
+            5.0
+        ));
+        assert!(passes_grade_mark(
+            &mut marks,
+            &grade_item("58.00 %", true),
+            5.0
+        ));
+        assert_eq!(marks[0].percentage, 66.0);
+    }
 }
@@ -0,0 +1,474 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::i18n::{Language, Text};
+use super::notification::{Notification, NotificationKind};
+use super::templates::NotificationTemplates;
+
+/// Local hour at which the daily digest goes out.
+pub const DIGEST_HOUR: u32 = 8;
+/// Changes listed one by one in the digest; the rest are only counted.
+const DIGEST_LINES: usize = 5;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HistoryEntry {
+    #[serde(rename = "_id", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub token: String,
+    pub key: String,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub course_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Entries written before delivery was tracked count as sent.
+    #[serde(default)]
+    pub delivery: DeliveryStatus,
+    /// Message ids the push providers returned.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provider_ids: Vec<String>,
+    /// Why a device wasn't reached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub sent_at: DateTime,
+    /// When the delivery last moved on from its first state.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime>,
+    #[serde(default)]
+    pub read: bool,
+}
+
+/// Where a notification is in its lifecycle: queued -> sent -> delivered or failed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for the user's quiet hours to end.
+    Queued,
+    /// Handed to a broker that pushes it downstream.
+    #[default]
+    Sent,
+    /// Accepted by the push provider.
+    Delivered,
+    Failed,
+    /// Not pushed; kept for the user's daily digest.
+    Held,
+    /// Not pushed; counted in an "and N more updates" push instead.
+    Folded,
+    /// Not pushed; no device takes it, e.g. for live-only users.
+    Skipped,
+}
+
+/// What became of a notification across the user's devices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub status: DeliveryStatus,
+    pub provider_ids: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl From<DeliveryStatus> for Delivery {
+    fn from(status: DeliveryStatus) -> Self {
+        Self {
+            status,
+            provider_ids: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl HistoryEntry {
+    pub fn new(
+        token: &str,
+        key: &str,
+        notification: &Notification,
+        delivery: Delivery,
+        sent_at: DateTime,
+    ) -> Self {
+        let change = notification.change.as_ref();
+        Self {
+            id: None,
+            token: token.to_string(),
+            key: key.to_string(),
+            kind: notification.kind,
+            title: notification.title.clone(),
+            body: notification.body.clone(),
+            course_id: change.and_then(|change| change.course_id),
+            item_id: change.and_then(|change| change.item_id),
+            value: change.map(|change| change.value.clone()),
+            delivery: delivery.status,
+            provider_ids: delivery.provider_ids,
+            error: delivery.error,
+            sent_at,
+            updated_at: None,
+            read: false,
+        }
+    }
+}
+
+/// Bounds are unix seconds; the item filters are optional.
+#[derive(Debug, Deserialize, Default)]
+pub struct HistoryDiffQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub course_id: Option<i64>,
+    pub item_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ValueChange {
+    /// `None` for the first value inside the requested range.
+    pub from: Option<String>,
+    pub to: String,
+    pub at: i64,
+}
+
+/// Notified values of a single item, oldest first.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ItemHistory {
+    pub kind: NotificationKind,
+    pub course_id: Option<i64>,
+    pub item_id: Option<i64>,
+    pub changes: Vec<ValueChange>,
+}
+
+/// Window of a delivery stats query, in unix seconds.
+#[derive(Debug, Deserialize, Default, IntoParams)]
+pub struct DeliveryStatsQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Default, ToSchema)]
+pub struct DeliveryStats {
+    pub sent: u32,
+    pub failed: u32,
+}
+
+/// Delivered notifications the user hasn't read on any device.
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct UnreadNotifications {
+    pub unread: u64,
+}
+
+/// A delivered notification as the app's inbox shows it.
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct InboxNotification {
+    /// Id for marking the notification read.
+    pub id: String,
+    pub title: String,
+    pub body: String,
+    pub category: NotificationKind,
+    /// Unix seconds.
+    pub sent_at: i64,
+    pub read: bool,
+}
+
+/// One notification's delivery, for tracking down a push that never arrived.
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct NotificationDelivery {
+    pub id: String,
+    pub token: String,
+    pub category: NotificationKind,
+    pub title: String,
+    pub body: String,
+    pub status: DeliveryStatus,
+    pub provider_ids: Vec<String>,
+    pub error: Option<String>,
+    /// Unix seconds.
+    pub created_at: i64,
+    /// Unix seconds of the last status change.
+    pub updated_at: i64,
+}
+
+impl From<HistoryEntry> for NotificationDelivery {
+    fn from(entry: HistoryEntry) -> Self {
+        Self {
+            id: entry.id.map(|id| id.to_hex()).unwrap_or_default(),
+            token: entry.token,
+            category: entry.kind,
+            title: entry.title,
+            body: entry.body,
+            status: entry.delivery,
+            provider_ids: entry.provider_ids,
+            error: entry.error,
+            created_at: entry.sent_at.timestamp_millis() / 1000,
+            updated_at: entry.updated_at.unwrap_or(entry.sent_at).timestamp_millis() / 1000,
+        }
+    }
+}
+
+impl From<HistoryEntry> for InboxNotification {
+    fn from(entry: HistoryEntry) -> Self {
+        Self {
+            id: entry.id.map(|id| id.to_hex()).unwrap_or_default(),
+            title: entry.title,
+            body: entry.body,
+            category: entry.kind,
+            sent_at: entry.sent_at.timestamp_millis() / 1000,
+            read: entry.read,
+        }
+    }
+}
+
+pub fn delivery_stats(entries: &[HistoryEntry]) -> DeliveryStats {
+    let mut stats = DeliveryStats::default();
+    for entry in entries {
+        match entry.delivery {
+            DeliveryStatus::Sent | DeliveryStatus::Delivered => stats.sent += 1,
+            DeliveryStatus::Failed => stats.failed += 1,
+            DeliveryStatus::Queued
+            | DeliveryStatus::Held
+            | DeliveryStatus::Folded
+            | DeliveryStatus::Skipped => {}
+        }
+    }
+    stats
+}
+
+/// The body of the daily digest for the held entries, or `None` when nothing
+/// changed.
+pub fn digest_body(
+    entries: &[HistoryEntry],
+    templates: &NotificationTemplates,
+    language: Language,
+) -> Option<String> {
+    if entries.is_empty() {
+        return None;
+    }
+    let count = |kinds: &[NotificationKind]| {
+        entries
+            .iter()
+            .filter(|entry| kinds.contains(&entry.kind))
+            .count()
+    };
+    let mut parts = Vec::new();
+    match count(&[NotificationKind::Grade, NotificationKind::GradeOverview]) {
+        0 => {}
+        n => parts.push(templates.render(Text::NewGrades(n), language)),
+    }
+    match count(&[NotificationKind::Deadline]) {
+        0 => {}
+        n => parts.push(templates.render(Text::NewDeadlines(n), language)),
+    }
+    // Held during an exam period, other kinds only show up in the lines below
+    let mut lines = Vec::new();
+    if !parts.is_empty() {
+        lines.push(parts.join(", "));
+    }
+    for entry in entries.iter().take(DIGEST_LINES) {
+        let summary = entry.body.lines().next().unwrap_or_default();
+        lines.push(format!("{}: {}", entry.title, summary));
+    }
+    if entries.len() > DIGEST_LINES {
+        lines.push(templates.render(Text::AndMore(entries.len() - DIGEST_LINES), language));
+    }
+    Some(lines.join("\n"))
+}
+
+/// The body of the weekly report for a week of grade entries sorted by
+/// `sent_at`, or `None` when nothing was graded. A regraded item counts once,
+/// at its latest value.
+pub fn weekly_report_body(
+    entries: &[HistoryEntry],
+    templates: &NotificationTemplates,
+    language: Language,
+) -> Option<String> {
+    let mut latest: Vec<&HistoryEntry> = Vec::new();
+    for entry in entries {
+        if entry.kind != NotificationKind::Grade || entry.item_id.is_none() {
+            continue;
+        }
+        match latest
+            .iter_mut()
+            .find(|latest| latest.course_id == entry.course_id && latest.item_id == entry.item_id)
+        {
+            Some(latest) => *latest = entry,
+            None => latest.push(entry),
+        }
+    }
+    if latest.is_empty() {
+        return None;
+    }
+    let percentages: Vec<f64> = latest
+        .iter()
+        .filter_map(|entry| {
+            entry
+                .value
+                .as_deref()?
+                .trim_end_matches('%')
+                .trim()
+                .parse()
+                .ok()
+        })
+        .collect();
+    let average = (!percentages.is_empty())
+        .then(|| percentages.iter().sum::<f64>() / percentages.len() as f64);
+    Some(templates.render(
+        Text::WeeklyReportBody {
+            grades: latest.len(),
+            average,
+        },
+        language,
+    ))
+}
+
+/// Groups entries sorted by `sent_at` into per-item value changes.
+pub fn diff_history(entries: &[HistoryEntry]) -> Vec<ItemHistory> {
+    let mut items: Vec<ItemHistory> = Vec::new();
+    for entry in entries {
+        let Some(value) = &entry.value else {
+            continue;
+        };
+        let index = match items.iter().position(|item| {
+            item.kind == entry.kind
+                && item.course_id == entry.course_id
+                && item.item_id == entry.item_id
+        }) {
+            Some(index) => index,
+            None => {
+                items.push(ItemHistory {
+                    kind: entry.kind,
+                    course_id: entry.course_id,
+                    item_id: entry.item_id,
+                    changes: Vec::new(),
+                });
+                items.len() - 1
+            }
+        };
+
+        let changes = &mut items[index].changes;
+        let from = changes.last().map(|change| change.to.clone());
+        if from.as_ref() == Some(value) {
+            continue;
+        }
+        changes.push(ValueChange {
+            from,
+            to: value.clone(),
+            at: entry.sent_at.timestamp_millis() / 1000,
+        });
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(item_id: i64, value: &str, sent_at: i64) -> HistoryEntry {
+        HistoryEntry {
+            id: None,
+            token: "token".to_string(),
+            key: format!("{}-{}", item_id, value),
+            kind: NotificationKind::Grade,
+            title: "Math".to_string(),
+            body: "New grade".to_string(),
+            course_id: Some(1),
+            item_id: Some(item_id),
+            value: Some(value.to_string()),
+            delivery: DeliveryStatus::Sent,
+            provider_ids: Vec::new(),
+            error: None,
+            sent_at: DateTime::from_millis(sent_at * 1000),
+            updated_at: None,
+            read: false,
+        }
+    }
+
+    #[test]
+    fn test_diff_history_tracks_oscillating_item() {
+        let entries = vec![
+            entry(10, "80.00 %", 100),
+            entry(11, "50.00 %", 150),
+            entry(10, "90.00 %", 200),
+            entry(10, "90.00 %", 250),
+            entry(10, "80.00 %", 300),
+        ];
+
+        let items = diff_history(&entries);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].item_id, Some(10));
+        assert_eq!(
+            items[0].changes,
+            vec![
+                ValueChange {
+                    from: None,
+                    to: "80.00 %".to_string(),
+                    at: 100
+                },
+                ValueChange {
+                    from: Some("80.00 %".to_string()),
+                    to: "90.00 %".to_string(),
+                    at: 200
+                },
+                ValueChange {
+                    from: Some("90.00 %".to_string()),
+                    to: "80.00 %".to_string(),
+                    at: 300
+                },
+            ]
+        );
+        assert_eq!(items[1].changes.len(), 1);
+    }
+
+    #[test]
+    fn test_digest_body_counts_and_lists_changes() {
+        assert_eq!(
+            digest_body(&[], &NotificationTemplates::default(), Language::En),
+            None
+        );
+
+        let mut entries: Vec<HistoryEntry> = (0..6)
+            .map(|i| HistoryEntry {
+                body: format!("New grade | Quiz {}\n- -> 90 %", i),
+                ..entry(i, "90 %", i)
+            })
+            .collect();
+        entries.push(HistoryEntry {
+            kind: NotificationKind::Deadline,
+            title: "New deadline".to_string(),
+            body: "Essay".to_string(),
+            ..entry(20, "1000", 10)
+        });
+
+        let body = digest_body(&entries, &NotificationTemplates::default(), Language::En).unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines[0], "6 new grades, 1 new deadline");
+        assert_eq!(lines[1], "Math: New grade | Quiz 0");
+        assert_eq!(lines.len(), 1 + DIGEST_LINES + 1);
+        assert_eq!(lines.last(), Some(&"and 2 more"));
+    }
+
+    #[test]
+    fn test_weekly_report_averages_latest_value_per_item() {
+        assert_eq!(
+            weekly_report_body(&[], &NotificationTemplates::default(), Language::En),
+            None
+        );
+
+        let entries = vec![
+            entry(10, "60.00 %", 100),
+            entry(11, "80.00 %", 150),
+            entry(10, "90.00 %", 200),
+            entry(12, "-", 250),
+        ];
+        assert_eq!(
+            weekly_report_body(&entries, &NotificationTemplates::default(), Language::En)
+                .as_deref(),
+            Some("This week: 3 new grades, average 85%")
+        );
+        assert_eq!(
+            weekly_report_body(
+                &entries[3..],
+                &NotificationTemplates::default(),
+                Language::Ru
+            )
+            .as_deref(),
+            Some("На этой неделе: 1 новая оценка")
+        );
+    }
+}
@@ -0,0 +1,445 @@
+use serde_json::{json, Value};
+
+/// Languages notifications are rendered in; see `SUPPORTED_LANGUAGES`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    En,
+    Ru,
+    Kk,
+}
+
+impl Language {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::Ru => "ru",
+            Language::Kk => "kk",
+        }
+    }
+
+    /// Unknown codes fall back to English.
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "ru" => Language::Ru,
+            "kk" => Language::Kk,
+            _ => Language::En,
+        }
+    }
+}
+
+/// Picks the Russian plural form for `n`: 1 оценка, 2 оценки, 5 оценок.
+fn ru_plural<'a>(n: usize, one: &'a str, few: &'a str, many: &'a str) -> &'a str {
+    match (n % 10, n % 100) {
+        (1, rem) if rem != 11 => one,
+        (2..=4, rem) if !(12..=14).contains(&rem) => few,
+        _ => many,
+    }
+}
+
+/// Every piece of notification text, with the values it is filled in with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Text<'a> {
+    UserInfoTitle,
+    UserInfoBody {
+        email: &'a str,
+        fullname: &'a str,
+        user_id: i64,
+    },
+    CourseTitle,
+    CourseRemovedTitle,
+    MaterialTitle,
+    MaterialBody {
+        course: &'a str,
+        name: &'a str,
+    },
+    DeadlineTitle,
+    DeadlineBody {
+        course: &'a str,
+        task: &'a str,
+        until: &'a str,
+    },
+    DeadlineRemovedTitle,
+    DeadlineMovedTitle,
+    DeadlineMovedBody {
+        course: &'a str,
+        task: &'a str,
+        from: &'a str,
+        to: &'a str,
+    },
+    DeadlineReminderTitle,
+    DeadlineInDays(i64),
+    DeadlineInHours(i64),
+    DeadlineInMinutes(i64),
+    GradeBody {
+        item: &'a str,
+        from: &'a str,
+        to: &'a str,
+    },
+    GradeFeedback(&'a str),
+    GradesAdded {
+        course: &'a str,
+        count: usize,
+    },
+    CourseTotalBody(&'a str),
+    DigestTitle,
+    NewGrades(usize),
+    NewDeadlines(usize),
+    AndMore(usize),
+    WeeklyReportTitle,
+    WeeklyReportBody {
+        grades: usize,
+        average: Option<f64>,
+    },
+    OpenCourseAction,
+    SnoozeAction,
+    MoreUpdatesTitle,
+    MoreUpdates(usize),
+}
+
+impl Text<'_> {
+    /// The name a configured template overrides this text under.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Text::UserInfoTitle => "user_info_title",
+            Text::UserInfoBody { .. } => "user_info_body",
+            Text::CourseTitle => "course_title",
+            Text::CourseRemovedTitle => "course_removed_title",
+            Text::MaterialTitle => "material_title",
+            Text::MaterialBody { .. } => "material_body",
+            Text::DeadlineTitle => "deadline_title",
+            Text::DeadlineBody { .. } => "deadline_body",
+            Text::DeadlineRemovedTitle => "deadline_removed_title",
+            Text::DeadlineMovedTitle => "deadline_moved_title",
+            Text::DeadlineMovedBody { .. } => "deadline_moved_body",
+            Text::DeadlineReminderTitle => "deadline_reminder_title",
+            Text::DeadlineInDays(_) => "deadline_in_days",
+            Text::DeadlineInHours(_) => "deadline_in_hours",
+            Text::DeadlineInMinutes(_) => "deadline_in_minutes",
+            Text::GradeBody { .. } => "grade_body",
+            Text::GradeFeedback(_) => "grade_feedback",
+            Text::GradesAdded { .. } => "grades_added",
+            Text::CourseTotalBody(_) => "course_total_body",
+            Text::DigestTitle => "digest_title",
+            Text::NewGrades(_) => "new_grades",
+            Text::NewDeadlines(_) => "new_deadlines",
+            Text::AndMore(_) => "and_more",
+            Text::WeeklyReportTitle => "weekly_report_title",
+            Text::WeeklyReportBody { .. } => "weekly_report_body",
+            Text::OpenCourseAction => "open_course_action",
+            Text::SnoozeAction => "snooze_action",
+            Text::MoreUpdatesTitle => "more_updates_title",
+            Text::MoreUpdates(_) => "more_updates",
+        }
+    }
+
+    /// The values a template can refer to.
+    pub fn data(&self) -> Value {
+        match *self {
+            Text::UserInfoBody {
+                email,
+                fullname,
+                user_id,
+            } => json!({"email": email, "fullname": fullname, "user_id": user_id}),
+            Text::MaterialBody { course, name } => json!({"course": course, "name": name}),
+            Text::DeadlineBody {
+                course,
+                task,
+                until,
+            } => json!({"course": course, "task": task, "until": until}),
+            Text::DeadlineMovedBody {
+                course,
+                task,
+                from,
+                to,
+            } => json!({"course": course, "task": task, "from": from, "to": to}),
+            Text::DeadlineInDays(days) => json!({"days": days}),
+            Text::DeadlineInHours(hours) => json!({"hours": hours}),
+            Text::DeadlineInMinutes(minutes) => json!({"minutes": minutes}),
+            Text::GradeBody { item, from, to } => json!({"item": item, "from": from, "to": to}),
+            Text::GradeFeedback(feedback) => json!({"feedback": feedback}),
+            Text::GradesAdded { course, count } => json!({"course": course, "count": count}),
+            Text::CourseTotalBody(grade) => json!({"grade": grade}),
+            Text::NewGrades(count)
+            | Text::NewDeadlines(count)
+            | Text::AndMore(count)
+            | Text::MoreUpdates(count) => {
+                json!({"count": count})
+            }
+            Text::WeeklyReportBody { grades, average } => json!({
+                "grades": grades,
+                "average": average.map(|average| format!("{:.0}", average)),
+            }),
+            _ => json!({}),
+        }
+    }
+
+    /// The built-in wording.
+    pub fn render(&self, language: Language) -> String {
+        use Language::{En, Kk, Ru};
+        match (*self, language) {
+            (Text::UserInfoTitle, En) => "New user info".to_string(),
+            (Text::UserInfoTitle, Ru) => "Новые данные профиля".to_string(),
+            (Text::UserInfoTitle, Kk) => "Жаңа профиль деректері".to_string(),
+            (
+                Text::UserInfoBody {
+                    email,
+                    fullname,
+                    user_id,
+                },
+                language,
+            ) => {
+                let labels = match language {
+                    En => ["Email", "Fullname", "User_id"],
+                    Ru => ["Почта", "ФИО", "ID пользователя"],
+                    Kk => ["Пошта", "Аты-жөні", "Пайдаланушы ID"],
+                };
+                format!(
+                    "{}: {}\n{}: {}\n{}: {}",
+                    labels[0], email, labels[1], fullname, labels[2], user_id
+                )
+            }
+            (Text::CourseTitle, En) => "New course".to_string(),
+            (Text::CourseTitle, Ru) => "Новый курс".to_string(),
+            (Text::CourseTitle, Kk) => "Жаңа курс".to_string(),
+            (Text::CourseRemovedTitle, En) => "Course removed".to_string(),
+            (Text::CourseRemovedTitle, Ru) => "Курс удалён".to_string(),
+            (Text::CourseRemovedTitle, Kk) => "Курс жойылды".to_string(),
+            (Text::MaterialTitle, En) => "New course material".to_string(),
+            (Text::MaterialTitle, Ru) => "Новые материалы курса".to_string(),
+            (Text::MaterialTitle, Kk) => "Курстың жаңа материалдары".to_string(),
+            (Text::MaterialBody { course, name }, En) => {
+                format!("Course: {}\nMaterial: {}", course, name)
+            }
+            (Text::MaterialBody { course, name }, Ru) => {
+                format!("Курс: {}\nМатериал: {}", course, name)
+            }
+            (Text::MaterialBody { course, name }, Kk) => {
+                format!("Курс: {}\nМатериал: {}", course, name)
+            }
+            (Text::DeadlineTitle, En) => "New deadline".to_string(),
+            (Text::DeadlineTitle, Ru) => "Новый дедлайн".to_string(),
+            (Text::DeadlineTitle, Kk) => "Жаңа дедлайн".to_string(),
+            (
+                Text::DeadlineBody {
+                    course,
+                    task,
+                    until,
+                },
+                En,
+            ) => {
+                format!("Course: {}\nTask: {}\nUntil {}", course, task, until)
+            }
+            (
+                Text::DeadlineBody {
+                    course,
+                    task,
+                    until,
+                },
+                Ru,
+            ) => {
+                format!("Курс: {}\nЗадание: {}\nДо {}", course, task, until)
+            }
+            (
+                Text::DeadlineBody {
+                    course,
+                    task,
+                    until,
+                },
+                Kk,
+            ) => {
+                format!("Курс: {}\nТапсырма: {}\n{} дейін", course, task, until)
+            }
+            (Text::DeadlineRemovedTitle, En) => "Deadline removed".to_string(),
+            (Text::DeadlineRemovedTitle, Ru) => "Дедлайн отменён".to_string(),
+            (Text::DeadlineRemovedTitle, Kk) => "Дедлайн алынып тасталды".to_string(),
+            (Text::DeadlineMovedTitle, En) => "Deadline moved".to_string(),
+            (Text::DeadlineMovedTitle, Ru) => "Дедлайн перенесён".to_string(),
+            (Text::DeadlineMovedTitle, Kk) => "Дедлайн ауыстырылды".to_string(),
+            (
+                Text::DeadlineMovedBody {
+                    course,
+                    task,
+                    from,
+                    to,
+                },
+                En,
+            ) => {
+                format!(
+                    "Course: {}\nTask: {}\nMoved from {} to {}",
+                    course, task, from, to
+                )
+            }
+            (
+                Text::DeadlineMovedBody {
+                    course,
+                    task,
+                    from,
+                    to,
+                },
+                Ru,
+            ) => {
+                format!(
+                    "Курс: {}\nЗадание: {}\nПеренесён с {} на {}",
+                    course, task, from, to
+                )
+            }
+            (
+                Text::DeadlineMovedBody {
+                    course,
+                    task,
+                    from,
+                    to,
+                },
+                Kk,
+            ) => {
+                format!(
+                    "Курс: {}\nТапсырма: {}\n{} күнінен {} күніне ауыстырылды",
+                    course, task, from, to
+                )
+            }
+            (Text::DeadlineReminderTitle, En) => "Deadline reminder".to_string(),
+            (Text::DeadlineReminderTitle, Ru) => "Напоминание о дедлайне".to_string(),
+            (Text::DeadlineReminderTitle, Kk) => "Дедлайн туралы еске салу".to_string(),
+            (Text::DeadlineInDays(1), En) => "Deadline in 1 day".to_string(),
+            (Text::DeadlineInDays(days), En) => format!("Deadline in {} days", days),
+            (Text::DeadlineInDays(days), Ru) => {
+                let unit = ru_plural(days.unsigned_abs() as usize, "день", "дня", "дней");
+                format!("Дедлайн через {} {}", days, unit)
+            }
+            (Text::DeadlineInDays(days), Kk) => format!("Дедлайнға {} күн қалды", days),
+            (Text::DeadlineInMinutes(1), En) => "Deadline in 1 minute".to_string(),
+            (Text::DeadlineInMinutes(minutes), En) => format!("Deadline in {} minutes", minutes),
+            (Text::DeadlineInMinutes(minutes), Ru) => {
+                let unit = ru_plural(minutes.unsigned_abs() as usize, "минуту", "минуты", "минут");
+                format!("Дедлайн через {} {}", minutes, unit)
+            }
+            (Text::DeadlineInMinutes(minutes), Kk) => {
+                format!("Дедлайнға {} минут қалды", minutes)
+            }
+            (Text::DeadlineInHours(1), En) => "Deadline in 1 hour".to_string(),
+            (Text::DeadlineInHours(hours), En) => format!("Deadline in {} hours", hours),
+            (Text::DeadlineInHours(hours), Ru) => {
+                let unit = ru_plural(hours.unsigned_abs() as usize, "час", "часа", "часов");
+                format!("Дедлайн через {} {}", hours, unit)
+            }
+            (Text::DeadlineInHours(hours), Kk) => format!("Дедлайнға {} сағат қалды", hours),
+            (Text::GradeBody { item, from, to }, En) => {
+                format!("New grade | {}\n{} -> {}", item, from, to)
+            }
+            (Text::GradeBody { item, from, to }, Ru) => {
+                format!("Новая оценка | {}\n{} -> {}", item, from, to)
+            }
+            (Text::GradeBody { item, from, to }, Kk) => {
+                format!("Жаңа баға | {}\n{} -> {}", item, from, to)
+            }
+            (Text::GradeFeedback(feedback), En) => format!("Feedback: {}", feedback),
+            (Text::GradeFeedback(feedback), Ru) => format!("Отзыв: {}", feedback),
+            (Text::GradeFeedback(feedback), Kk) => format!("Пікір: {}", feedback),
+            (Text::GradesAdded { course, count }, En) => {
+                format!("{}: {} grades added", course, count)
+            }
+            (Text::GradesAdded { course, count }, Ru) => {
+                format!("{}: добавлено оценок: {}", course, count)
+            }
+            (Text::GradesAdded { course, count }, Kk) => {
+                format!("{}: {} баға қосылды", course, count)
+            }
+            (Text::CourseTotalBody(grade), En) => format!("New course total grade | {}", grade),
+            (Text::CourseTotalBody(grade), Ru) => {
+                format!("Новая итоговая оценка курса | {}", grade)
+            }
+            (Text::CourseTotalBody(grade), Kk) => {
+                format!("Курстың жаңа қорытынды бағасы | {}", grade)
+            }
+            (Text::DigestTitle, En) => "Your daily digest".to_string(),
+            (Text::DigestTitle, Ru) => "Ваша ежедневная сводка".to_string(),
+            (Text::DigestTitle, Kk) => "Күнделікті шолу".to_string(),
+            (Text::NewGrades(1), En) => "1 new grade".to_string(),
+            (Text::NewGrades(n), En) => format!("{} new grades", n),
+            (Text::NewGrades(n), Ru) => format!(
+                "{} {}",
+                n,
+                ru_plural(n, "новая оценка", "новые оценки", "новых оценок")
+            ),
+            (Text::NewGrades(n), Kk) => format!("{} жаңа баға", n),
+            (Text::NewDeadlines(1), En) => "1 new deadline".to_string(),
+            (Text::NewDeadlines(n), En) => format!("{} new deadlines", n),
+            (Text::NewDeadlines(n), Ru) => format!(
+                "{} {}",
+                n,
+                ru_plural(n, "новый дедлайн", "новых дедлайна", "новых дедлайнов")
+            ),
+            (Text::NewDeadlines(n), Kk) => format!("{} жаңа дедлайн", n),
+            (Text::AndMore(n), En) => format!("and {} more", n),
+            (Text::AndMore(n), Ru) => format!("и ещё {}", n),
+            (Text::AndMore(n), Kk) => format!("тағы {}", n),
+            (Text::WeeklyReportTitle, En) => "Weekly grade report".to_string(),
+            (Text::WeeklyReportTitle, Ru) => "Еженедельный отчёт об оценках".to_string(),
+            (Text::WeeklyReportTitle, Kk) => "Апталық баға есебі".to_string(),
+            (Text::WeeklyReportBody { grades, average }, language) => {
+                let grades = Text::NewGrades(grades).render(language);
+                match (average, language) {
+                    (None, En) => format!("This week: {}", grades),
+                    (None, Ru) => format!("На этой неделе: {}", grades),
+                    (None, Kk) => format!("Осы аптада: {}", grades),
+                    (Some(average), En) => {
+                        format!("This week: {}, average {:.0}%", grades, average)
+                    }
+                    (Some(average), Ru) => {
+                        format!("На этой неделе: {}, средний балл {:.0}%", grades, average)
+                    }
+                    (Some(average), Kk) => {
+                        format!("Осы аптада: {}, орташа {:.0}%", grades, average)
+                    }
+                }
+            }
+            (Text::OpenCourseAction, En) => "Open course".to_string(),
+            (Text::OpenCourseAction, Ru) => "Открыть курс".to_string(),
+            (Text::OpenCourseAction, Kk) => "Курсты ашу".to_string(),
+            (Text::SnoozeAction, En) => "Snooze 1h".to_string(),
+            (Text::SnoozeAction, Ru) => "Отложить на 1 ч".to_string(),
+            (Text::SnoozeAction, Kk) => "1 сағатқа кейінге қалдыру".to_string(),
+            (Text::MoreUpdatesTitle, En) => "More updates".to_string(),
+            (Text::MoreUpdatesTitle, Ru) => "Новые обновления".to_string(),
+            (Text::MoreUpdatesTitle, Kk) => "Жаңа жаңартулар".to_string(),
+            (Text::MoreUpdates(1), En) => "And 1 more update".to_string(),
+            (Text::MoreUpdates(n), En) => format!("And {} more updates", n),
+            (Text::MoreUpdates(n), Ru) => format!(
+                "И ещё {} {}",
+                n,
+                ru_plural(n, "обновление", "обновления", "обновлений")
+            ),
+            (Text::MoreUpdates(n), Kk) => format!("Тағы {} жаңарту", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_language_falls_back_to_english() {
+        assert_eq!(Language::from_code("kk"), Language::Kk);
+        assert_eq!(Language::from_code("de"), Language::En);
+        assert_eq!(Text::DeadlineTitle.render(Language::Kk), "Жаңа дедлайн");
+    }
+
+    #[test]
+    fn test_russian_counts_use_plural_forms() {
+        let grades = |n| Text::NewGrades(n).render(Language::Ru);
+        assert_eq!(grades(1), "1 новая оценка");
+        assert_eq!(grades(3), "3 новые оценки");
+        assert_eq!(grades(11), "11 новых оценок");
+        assert_eq!(grades(22), "22 новые оценки");
+        assert_eq!(
+            Text::DeadlineInHours(24).render(Language::Ru),
+            "Дедлайн через 24 часа"
+        );
+        assert_eq!(
+            Text::DeadlineInHours(1).render(Language::En),
+            "Deadline in 1 hour"
+        );
+    }
+}
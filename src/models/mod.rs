@@ -1,7 +1,29 @@
+pub mod broadcast;
+pub mod calendar;
+pub mod cohort;
+pub mod cors;
 pub mod course;
+pub mod course_content;
+pub mod dashboard;
+pub mod dead_letter;
 pub mod deadline;
 pub mod errors;
+pub mod feature_flags;
 pub mod grade;
+pub mod history;
+pub mod i18n;
 pub mod notification;
+pub mod pagination;
+pub mod preferences;
+pub mod rate_limit;
+pub mod registration;
+pub mod reminder;
+pub mod settings;
+pub mod stats;
+pub mod sync;
+pub mod templates;
 pub mod token;
+pub mod unread;
 pub mod user;
+pub mod user_list;
+pub mod webhook;
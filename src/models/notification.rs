@@ -1,18 +1,430 @@
-use serde::Serialize;
+use std::collections::BTreeMap;
 
-#[derive(Serialize)]
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use super::settings::DEFAULT_SOUND;
+use super::token::{Device, Platform};
+
+/// Reminders of deadlines due within this many seconds go out as high priority.
+const URGENT_DEADLINE_SECONDS: i64 = 3600;
+/// Opens the course in `data`.
+pub const OPEN_COURSE_ACTION: &str = "open_course";
+/// Snoozes reminders of the deadline in `data` for an hour.
+pub const SNOOZE_ACTION: &str = "snooze_1h";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    UserInfo,
+    Course,
+    /// New or updated files and sections in a course.
+    Material,
+    Deadline,
+    Grade,
+    GradeOverview,
+    Announcement,
+    Digest,
+    WeeklyReport,
+    /// Stands in for the pushes folded away by the hourly cap.
+    MoreUpdates,
+    /// A silent push telling the app to refresh its cache in the background.
+    Sync,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::UserInfo => "user_info",
+            NotificationKind::Course => "course",
+            NotificationKind::Material => "material",
+            NotificationKind::Deadline => "deadline",
+            NotificationKind::Grade => "grade",
+            NotificationKind::GradeOverview => "grade_overview",
+            NotificationKind::Announcement => "announcement",
+            NotificationKind::Digest => "digest",
+            NotificationKind::WeeklyReport => "weekly_report",
+            NotificationKind::MoreUpdates => "more_updates",
+            NotificationKind::Sync => "sync",
+        }
+    }
+
+    /// The app screen a tapped notification of this kind opens.
+    pub fn screen(&self) -> &'static str {
+        match self {
+            NotificationKind::UserInfo => "profile",
+            NotificationKind::Course | NotificationKind::Material => "course",
+            NotificationKind::Deadline => "deadline",
+            NotificationKind::Grade
+            | NotificationKind::GradeOverview
+            | NotificationKind::WeeklyReport => "grades",
+            NotificationKind::Announcement
+            | NotificationKind::Digest
+            | NotificationKind::MoreUpdates
+            | NotificationKind::Sync => "inbox",
+        }
+    }
+
+    pub fn priority(&self) -> Priority {
+        match self {
+            NotificationKind::UserInfo
+            | NotificationKind::Course
+            | NotificationKind::Material
+            | NotificationKind::Digest
+            | NotificationKind::WeeklyReport
+            | NotificationKind::Sync => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    /// The data key the reported item's id goes under.
+    fn item_key(&self) -> &'static str {
+        match self {
+            NotificationKind::Deadline => "deadline_id",
+            NotificationKind::Grade => "grade_item_id",
+            _ => "item_id",
+        }
+    }
+}
+
+/// How urgently the device should show the notification; high priority wakes
+/// devices that are saving power.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    /// High for a deadline due within the hour at `now`, in unix seconds.
+    pub fn for_deadline(due: i64, now: i64) -> Self {
+        if due - now <= URGENT_DEADLINE_SECONDS {
+            Priority::High
+        } else {
+            Priority::Normal
+        }
+    }
+}
+
+/// A button under the notification; the app runs `id` against the ids in the
+/// notification's data.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NotificationAction {
+    pub id: String,
+    pub title: String,
+}
+
+/// A notification as produced, addressed to one device by [`Notification::for_device`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Notification {
     pub device_token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    pub kind: NotificationKind,
+    #[serde(default)]
+    pub priority: Priority,
     pub title: String,
     pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// String pairs the app deep-links with: the event type, the screen to
+    /// open and the ids of what changed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<NotificationAction>,
+    /// Content-available only: wakes the app without showing an alert.
+    #[serde(default)]
+    pub silent: bool,
+    /// Notifications with the same thread stack together on the device, one
+    /// thread per course.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    /// A newer notification with the same key replaces the older one on the device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collapse_key: Option<String>,
+    /// The sound the alert plays; `None` arrives silently.
+    #[serde(default = "default_sound")]
+    pub sound: Option<String>,
+    /// The unread count the app icon shows once this notification lands.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub badge: Option<u64>,
+    #[serde(skip)]
+    pub change: Option<Change>,
+}
+
+/// A detected change as streamed to live clients.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ChangeEvent {
+    pub category: NotificationKind,
+    pub title: String,
+    pub body: String,
+}
+
+impl From<&Notification> for ChangeEvent {
+    fn from(notification: &Notification) -> Self {
+        Self {
+            category: notification.kind,
+            title: notification.title.clone(),
+            body: notification.body.clone(),
+        }
+    }
+}
+
+/// What a notification reports, kept in the notification history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub course_id: Option<i64>,
+    pub item_id: Option<i64>,
+    pub value: String,
 }
 
 impl Notification {
-    pub fn new(device_token: String, title: String, body: String) -> Self {
+    pub fn new(kind: NotificationKind, title: String, body: String) -> Self {
         Self {
-            device_token,
+            device_token: String::new(),
+            platform: None,
+            kind,
+            priority: kind.priority(),
             title,
             body,
+            idempotency_key: None,
+            data: BTreeMap::from([
+                ("type".to_string(), kind.as_str().to_string()),
+                ("screen".to_string(), kind.screen().to_string()),
+            ]),
+            image_url: None,
+            actions: Vec::new(),
+            silent: false,
+            thread_id: None,
+            collapse_key: None,
+            sound: default_sound(),
+            badge: None,
+            change: None,
+        }
+    }
+
+    /// The silent push sent after a sync so the app refreshes its cache.
+    pub fn background_sync() -> Self {
+        Self {
+            silent: true,
+            ..Self::new(NotificationKind::Sync, String::new(), String::new())
+        }
+    }
+
+    /// Tags the notification with the change it reports, deriving its idempotency key.
+    pub fn with_change(
+        mut self,
+        token: &str,
+        course_id: Option<i64>,
+        item_id: Option<i64>,
+        value: &str,
+    ) -> Self {
+        self.idempotency_key = Some(idempotency_key(token, self.kind, course_id, item_id, value));
+        // Later changes of the same item replace the earlier notification
+        self.collapse_key = Some(format!(
+            "{}:{}:{}",
+            self.kind.as_str(),
+            course_id.map(|id| id.to_string()).unwrap_or_default(),
+            item_id.map(|id| id.to_string()).unwrap_or_default(),
+        ));
+        if let Some(course_id) = course_id {
+            self = self.with_thread(course_id);
+        }
+        if let Some(item_id) = item_id {
+            self.data
+                .insert(self.kind.item_key().to_string(), item_id.to_string());
+        }
+        self.change = Some(Change {
+            course_id,
+            item_id,
+            value: value.to_string(),
+        });
+        self
+    }
+
+    /// Files the notification under the course, for the app to open and the
+    /// device to stack it with the course's other notifications.
+    pub fn with_thread(mut self, course_id: i64) -> Self {
+        self.data
+            .insert("course_id".to_string(), course_id.to_string());
+        self.thread_id = Some(format!("course-{}", course_id));
+        self
+    }
+
+    /// Carries both due times of a moved deadline, in unix seconds.
+    pub fn with_due_change(mut self, from: i64, to: i64) -> Self {
+        self.data.insert("due_from".to_string(), from.to_string());
+        self.data.insert("due_to".to_string(), to.to_string());
+        self
+    }
+
+    pub fn with_image(mut self, image_url: Option<String>) -> Self {
+        self.image_url = image_url;
+        self
+    }
+
+    pub fn with_action(mut self, id: &str, title: String) -> Self {
+        self.actions.push(NotificationAction {
+            id: id.to_string(),
+            title,
+        });
+        self
+    }
+
+    /// The iOS category for the buttons, one the app registers per set of
+    /// action ids, e.g. `open_course+snooze_1h`.
+    pub fn action_category(&self) -> Option<String> {
+        if self.actions.is_empty() {
+            return None;
+        }
+        let ids: Vec<&str> = self
+            .actions
+            .iter()
+            .map(|action| action.id.as_str())
+            .collect();
+        Some(ids.join("+"))
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Keys a summary of several changes by the keys of the changes it sums
+    /// up, so the same set is only summed up once.
+    pub fn with_summary_key(mut self, token: &str, keys: &[&str]) -> Self {
+        let content = keys.join(",");
+        self.idempotency_key = Some(idempotency_key(token, self.kind, None, None, &content));
+        self
+    }
+
+    /// Urgent deadlines, and the digest holding everything else, still go out
+    /// during an exam period.
+    pub fn passes_exam_period(&self) -> bool {
+        match self.kind {
+            NotificationKind::Digest => true,
+            NotificationKind::Deadline => self.priority == Priority::High,
+            _ => false,
+        }
+    }
+
+    /// Plays the user's chosen sound and badges the app icon with their
+    /// unread count, when they keep badges on.
+    pub fn with_alert_style(mut self, sound: Option<String>, badge: Option<u64>) -> Self {
+        self.sound = sound;
+        self.badge = badge;
+        self
+    }
+
+    pub fn for_device(&self, device: &Device) -> Self {
+        Self {
+            device_token: device.token.clone(),
+            platform: device.platform,
+            ..self.clone()
         }
     }
 }
+
+fn default_sound() -> Option<String> {
+    Some(DEFAULT_SOUND.to_string())
+}
+
+/// Deterministic key for a change, so consumers and the history check can drop re-sends.
+pub fn idempotency_key(
+    token: &str,
+    kind: NotificationKind,
+    course_id: Option<i64>,
+    item_id: Option<i64>,
+    value: &str,
+) -> String {
+    let course_id = course_id.map(|id| id.to_string()).unwrap_or_default();
+    let item_id = item_id.map(|id| id.to_string()).unwrap_or_default();
+    let source = [token, kind.as_str(), &course_id, &item_id, value].join("\u{1f}");
+    format!("{:x}", Sha256::digest(source.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idempotency_key_is_deterministic() {
+        let key = idempotency_key("token", NotificationKind::Grade, Some(1), Some(2), "90 %");
+        assert_eq!(
+            key,
+            idempotency_key("token", NotificationKind::Grade, Some(1), Some(2), "90 %")
+        );
+        assert_ne!(
+            key,
+            idempotency_key("token", NotificationKind::Grade, Some(1), Some(2), "95 %")
+        );
+        assert_ne!(
+            key,
+            idempotency_key("other", NotificationKind::Grade, Some(1), Some(2), "90 %")
+        );
+        assert_ne!(
+            idempotency_key("token", NotificationKind::Grade, Some(12), None, ""),
+            idempotency_key("token", NotificationKind::Grade, Some(1), Some(2), "")
+        );
+    }
+
+    #[test]
+    fn test_data_carries_screen_and_changed_ids() {
+        let notification = Notification::new(
+            NotificationKind::Deadline,
+            "New deadline".to_string(),
+            "Essay".to_string(),
+        )
+        .with_change("token", Some(7), Some(42), "1000");
+
+        assert_eq!(
+            notification.data,
+            BTreeMap::from([
+                ("course_id".to_string(), "7".to_string()),
+                ("deadline_id".to_string(), "42".to_string()),
+                ("screen".to_string(), "deadline".to_string()),
+                ("type".to_string(), "deadline".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_course_changes_share_a_thread_and_items_collapse() {
+        let grade = |item_id, value| {
+            Notification::new(NotificationKind::Grade, "Math".to_string(), String::new())
+                .with_change("token", Some(7), Some(item_id), value)
+        };
+
+        assert_eq!(grade(1, "80 %").thread_id.as_deref(), Some("course-7"));
+        assert_eq!(grade(1, "80 %").thread_id, grade(2, "90 %").thread_id);
+        assert_eq!(grade(1, "80 %").collapse_key, grade(1, "90 %").collapse_key);
+        assert_ne!(grade(1, "80 %").collapse_key, grade(2, "80 %").collapse_key);
+        let profile = Notification::new(NotificationKind::UserInfo, String::new(), String::new());
+        assert_eq!(profile.thread_id, None);
+    }
+
+    #[test]
+    fn test_priority_follows_kind_and_deadline_urgency() {
+        let priority = |kind| Notification::new(kind, String::new(), String::new()).priority;
+        assert_eq!(priority(NotificationKind::Course), Priority::Low);
+        assert_eq!(priority(NotificationKind::Grade), Priority::Normal);
+
+        assert_eq!(
+            Priority::for_deadline(10_000, 10_000 - 3600),
+            Priority::High
+        );
+        assert_eq!(
+            Priority::for_deadline(10_000, 10_000 - 3601),
+            Priority::Normal
+        );
+    }
+}
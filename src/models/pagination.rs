@@ -0,0 +1,75 @@
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+pub const DEFAULT_PER_PAGE: u64 = 20;
+pub const MAX_PER_PAGE: u64 = 100;
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// Page counted from 1; `per_page` is kept within `1..=MAX_PER_PAGE`.
+#[derive(Debug, Deserialize, Default, Clone, Copy, IntoParams)]
+pub struct PageQuery {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
+impl PageQuery {
+    /// Whether the caller asked for a page rather than the whole list.
+    pub fn is_requested(&self) -> bool {
+        self.page.is_some() || self.per_page.is_some()
+    }
+
+    pub fn per_page(&self) -> u64 {
+        self.per_page
+            .unwrap_or(DEFAULT_PER_PAGE)
+            .clamp(1, MAX_PER_PAGE)
+    }
+
+    /// Saturates instead of overflowing on huge pages, which are simply empty.
+    pub fn skip(&self) -> u64 {
+        skip(self.page.unwrap_or(1), self.per_page())
+    }
+}
+
+/// Items before `page`, counted from 1, when each holds `per_page`; kept
+/// within `i64::MAX`, which is as far as MongoDB skips.
+pub fn skip(page: u64, per_page: u64) -> u64 {
+    page.saturating_sub(1)
+        .saturating_mul(per_page)
+        .min(i64::MAX as u64)
+}
+
+/// One page of a list with the size of the whole list.
+#[derive(Debug, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_bounds() {
+        let query = PageQuery {
+            page: Some(3),
+            per_page: Some(1000),
+        };
+        assert_eq!(query.per_page(), MAX_PER_PAGE);
+        assert_eq!(query.skip(), 2 * MAX_PER_PAGE);
+
+        let query = PageQuery {
+            page: Some(0),
+            per_page: Some(0),
+        };
+        assert_eq!((query.per_page(), query.skip()), (1, 0));
+        assert!(!PageQuery::default().is_requested());
+        assert_eq!(PageQuery::default().per_page(), DEFAULT_PER_PAGE);
+
+        let query = PageQuery {
+            page: Some(u64::MAX),
+            per_page: Some(MAX_PER_PAGE),
+        };
+        assert_eq!(query.skip(), i64::MAX as u64);
+    }
+}
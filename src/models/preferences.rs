@@ -0,0 +1,498 @@
+use chrono::{DateTime, Datelike, NaiveTime, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::grade::GradeItems;
+use super::notification::{NotificationKind, Priority};
+use super::reminder::{MAX_REMINDER_OFFSET_MINUTES, MAX_REMINDER_TIERS, REMINDER_TIERS_MINUTES};
+use super::token::Channel;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, ToSchema)]
+pub struct Preferences {
+    #[serde(default)]
+    pub min_grademax: Option<f64>,
+    #[serde(default)]
+    pub only_final_grades: bool,
+    #[serde(default)]
+    pub ignore_courses: Vec<i64>,
+    #[serde(default)]
+    pub pause: Option<NotificationPause>,
+    #[serde(default)]
+    pub categories: NotificationCategories,
+    /// Master switch: nothing is sent while data keeps syncing.
+    #[serde(default)]
+    pub notifications_off: bool,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// Grade and deadline changes are held for one morning summary instead of
+    /// being pushed as they happen.
+    #[serde(default)]
+    pub digest: bool,
+    #[serde(default)]
+    pub weekly_report: Option<WeeklyReport>,
+    #[serde(default)]
+    pub channels: ChannelPreferences,
+    #[serde(default)]
+    pub exam_period: Option<ExamPeriod>,
+    /// Minutes before each deadline at which escalating reminders go out;
+    /// unset keeps the default tiers and an empty list turns them off.
+    #[serde(default)]
+    pub reminder_tiers: Option<Vec<i64>>,
+}
+
+/// The lowest priority each channel gets, e.g. `"telegram": "high"` only
+/// sends urgent deadlines to Telegram; every channel gets everything by default.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+#[serde(default)]
+pub struct ChannelPreferences {
+    pub push: Priority,
+    pub telegram: Priority,
+    pub webhook: Priority,
+}
+
+impl Default for ChannelPreferences {
+    fn default() -> Self {
+        Self {
+            push: Priority::Low,
+            telegram: Priority::Low,
+            webhook: Priority::Low,
+        }
+    }
+}
+
+impl ChannelPreferences {
+    pub fn min_priority(&self, channel: Channel) -> Priority {
+        match channel {
+            Channel::Push => self.push,
+            Channel::Telegram => self.telegram,
+            Channel::Webhook => self.webhook,
+        }
+    }
+}
+
+/// Per-category toggles; a category missing from stored preferences stays on,
+/// except course materials, which are opt-in.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+#[serde(default)]
+pub struct NotificationCategories {
+    pub grades: bool,
+    pub deadlines: bool,
+    pub courses: bool,
+    pub user_info: bool,
+    /// New files and sections in a course; their contents are only fetched
+    /// while this is on.
+    pub materials: bool,
+}
+
+impl Default for NotificationCategories {
+    fn default() -> Self {
+        Self {
+            grades: true,
+            deadlines: true,
+            courses: true,
+            user_info: true,
+            materials: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, ToSchema)]
+pub struct NotificationPause {
+    #[serde(default)]
+    pub until: Option<i64>,
+}
+
+/// Local times, as `HH:MM`, between which pushes are held back and sent once
+/// quiet hours end; a `start` later than `end` spans midnight.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct QuietHours {
+    #[schema(example = "23:00")]
+    pub start: String,
+    #[schema(example = "07:00")]
+    pub end: String,
+}
+
+impl QuietHours {
+    fn bounds(&self) -> Option<(NaiveTime, NaiveTime)> {
+        let parse = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").ok();
+        Some((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    /// Whether `now`, in unix seconds, falls within quiet hours in the timezone.
+    pub fn contains(&self, timezone: Tz, now: i64) -> bool {
+        let (Some((start, end)), Some(now)) = (self.bounds(), DateTime::from_timestamp(now, 0))
+        else {
+            return false;
+        };
+        let local = now.with_timezone(&timezone).time();
+        if start <= end {
+            start <= local && local < end
+        } else {
+            local >= start || local < end
+        }
+    }
+}
+
+/// Unix seconds between which only deadline-critical pushes go out; the
+/// rest is held for the morning digest. Set per user, or by admins for
+/// everyone.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct ExamPeriod {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl ExamPeriod {
+    pub fn is_valid(&self) -> bool {
+        self.start < self.end
+    }
+
+    pub fn contains(&self, now: i64) -> bool {
+        self.start <= now && now < self.end
+    }
+
+    /// Whether changes held during the period may still await the digest:
+    /// while it runs and for a day after.
+    pub fn holds_digest(&self, now: i64) -> bool {
+        self.start <= now && now < self.end + 24 * 3600
+    }
+}
+
+/// When the weekly grade report goes out, as a weekday name and a local
+/// `HH:MM` time.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct WeeklyReport {
+    #[schema(example = "sunday")]
+    pub weekday: String,
+    #[schema(example = "18:00")]
+    pub time: String,
+}
+
+impl WeeklyReport {
+    fn schedule(&self) -> Option<(Weekday, NaiveTime)> {
+        Some((
+            self.weekday.parse().ok()?,
+            NaiveTime::parse_from_str(&self.time, "%H:%M").ok()?,
+        ))
+    }
+
+    /// Whether the report is due at `now`, in unix seconds: on its weekday in
+    /// the timezone, from its time on.
+    pub fn is_due(&self, timezone: Tz, now: i64) -> bool {
+        let (Some((weekday, time)), Some(now)) =
+            (self.schedule(), DateTime::from_timestamp(now, 0))
+        else {
+            return false;
+        };
+        let local = now.with_timezone(&timezone);
+        local.weekday() == weekday && local.time() >= time
+    }
+}
+
+impl Preferences {
+    /// Paused notifications resume on their own once `until` has passed.
+    pub fn is_paused(&self, now: i64) -> bool {
+        self.pause
+            .as_ref()
+            .is_some_and(|pause| pause.until.is_none_or(|until| until > now))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(min_grademax) = self.min_grademax {
+            if !min_grademax.is_finite() || min_grademax < 0.0 {
+                return Err("min_grademax".to_string());
+            }
+        }
+        if self
+            .quiet_hours
+            .as_ref()
+            .is_some_and(|quiet_hours| quiet_hours.bounds().is_none())
+        {
+            return Err("quiet_hours".to_string());
+        }
+        if self
+            .weekly_report
+            .as_ref()
+            .is_some_and(|report| report.schedule().is_none())
+        {
+            return Err("weekly_report".to_string());
+        }
+        if self
+            .exam_period
+            .as_ref()
+            .is_some_and(|period| !period.is_valid())
+        {
+            return Err("exam_period".to_string());
+        }
+        if self.reminder_tiers.as_ref().is_some_and(|tiers| {
+            tiers.len() > MAX_REMINDER_TIERS
+                || tiers
+                    .iter()
+                    .any(|tier| !(1..=MAX_REMINDER_OFFSET_MINUTES).contains(tier))
+        }) {
+            return Err("reminder_tiers".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the user wants notifications of this kind; announcements and the
+    /// digest have no toggle.
+    pub fn allows(&self, kind: NotificationKind) -> bool {
+        match kind {
+            NotificationKind::UserInfo => self.categories.user_info,
+            NotificationKind::Course => self.categories.courses,
+            NotificationKind::Material => self.categories.materials,
+            NotificationKind::Deadline => self.categories.deadlines,
+            NotificationKind::Grade
+            | NotificationKind::GradeOverview
+            | NotificationKind::WeeklyReport => self.categories.grades,
+            NotificationKind::Announcement
+            | NotificationKind::Digest
+            | NotificationKind::MoreUpdates
+            | NotificationKind::Sync => true,
+        }
+    }
+
+    pub fn reminder_tiers(&self) -> &[i64] {
+        self.reminder_tiers
+            .as_deref()
+            .unwrap_or(&REMINDER_TIERS_MINUTES)
+    }
+
+    /// Muted courses send no grade or deadline notifications.
+    pub fn is_course_muted(&self, course_id: i64) -> bool {
+        self.ignore_courses.contains(&course_id)
+    }
+
+    pub fn set_course_muted(&mut self, course_id: i64, muted: bool) {
+        self.ignore_courses.retain(|id| *id != course_id);
+        if muted {
+            self.ignore_courses.push(course_id);
+        }
+    }
+
+    pub fn allows_grade(&self, course_id: i64, item: &GradeItems) -> bool {
+        if !self.allows(NotificationKind::Grade) || self.is_course_muted(course_id) {
+            return false;
+        }
+        if self.only_final_grades && item.itemtype.as_deref() == Some("category") {
+            return false;
+        }
+        match (self.min_grademax, item.grademax) {
+            (Some(min_grademax), Some(grademax)) => grademax >= min_grademax,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grade_item(itemtype: &str, grademax: Option<f64>) -> GradeItems {
+        GradeItems {
+            id: 1,
+            itemname: "Quiz 1".to_string(),
+            percentageformatted: "100.00 %".to_string(),
+            itemtype: Some(itemtype.to_string()),
+            grademax,
+            rangeformatted: None,
+            gradedategraded: None,
+            gradeislocked: false,
+            feedback: None,
+        }
+    }
+
+    #[test]
+    fn test_default_preferences_allow_everything() {
+        let preferences = Preferences::default();
+        assert!(preferences.allows_grade(1, &grade_item("mod", Some(1.0))));
+        assert!(preferences.allows_grade(1, &grade_item("category", None)));
+        assert!(preferences.validate().is_ok());
+    }
+
+    #[test]
+    fn test_min_grademax_filters_small_items() {
+        let preferences = Preferences {
+            min_grademax: Some(10.0),
+            ..Default::default()
+        };
+        assert!(!preferences.allows_grade(1, &grade_item("mod", Some(1.0))));
+        assert!(preferences.allows_grade(1, &grade_item("mod", Some(10.0))));
+        assert!(preferences.allows_grade(1, &grade_item("mod", None)));
+    }
+
+    #[test]
+    fn test_only_final_grades_skips_categories() {
+        let preferences = Preferences {
+            only_final_grades: true,
+            ..Default::default()
+        };
+        assert!(!preferences.allows_grade(1, &grade_item("category", Some(100.0))));
+        assert!(preferences.allows_grade(1, &grade_item("course", Some(100.0))));
+    }
+
+    #[test]
+    fn test_ignore_courses() {
+        let preferences = Preferences {
+            ignore_courses: vec![2],
+            ..Default::default()
+        };
+        assert!(!preferences.allows_grade(2, &grade_item("mod", Some(100.0))));
+        assert!(preferences.allows_grade(1, &grade_item("mod", Some(100.0))));
+    }
+
+    #[test]
+    fn test_validate_rejects_negative_min_grademax() {
+        let preferences = Preferences {
+            min_grademax: Some(-1.0),
+            ..Default::default()
+        };
+        assert!(preferences.validate().is_err());
+    }
+
+    #[test]
+    fn test_pause_indefinite_and_expiring() {
+        let mut preferences = Preferences::default();
+        assert!(!preferences.is_paused(100));
+
+        preferences.pause = Some(NotificationPause { until: None });
+        assert!(preferences.is_paused(100));
+
+        preferences.pause = Some(NotificationPause { until: Some(200) });
+        assert!(preferences.is_paused(100));
+        assert!(!preferences.is_paused(200));
+    }
+
+    #[test]
+    fn test_set_course_muted_is_idempotent() {
+        let mut preferences = Preferences::default();
+        preferences.set_course_muted(3, true);
+        preferences.set_course_muted(3, true);
+        assert_eq!(preferences.ignore_courses, vec![3]);
+        assert!(preferences.is_course_muted(3));
+        preferences.set_course_muted(3, false);
+        assert!(!preferences.is_course_muted(3));
+    }
+
+    #[test]
+    fn test_categories_default_to_enabled() {
+        let preferences: Preferences =
+            serde_json::from_str(r#"{"categories": {"grades": false}}"#).unwrap();
+        assert!(!preferences.categories.grades);
+        assert!(preferences.categories.deadlines);
+        assert!(preferences.categories.courses);
+        assert!(preferences.categories.user_info);
+        assert!(!preferences.categories.materials);
+
+        let preferences: Preferences = serde_json::from_str("{}").unwrap();
+        assert_eq!(preferences.categories, NotificationCategories::default());
+        assert!(!preferences.notifications_off);
+    }
+
+    #[test]
+    fn test_quiet_hours_span_midnight_in_user_timezone() {
+        let quiet_hours = |start: &str, end: &str| QuietHours {
+            start: start.to_string(),
+            end: end.to_string(),
+        };
+        let almaty: Tz = "Asia/Almaty".parse().unwrap();
+        // 2024-01-01 22:30 UTC, 04:30 in Almaty
+        let night = 1_704_148_200;
+        // 2024-01-01 06:00 UTC, 12:00 in Almaty
+        let noon = 1_704_088_800;
+
+        let overnight = quiet_hours("23:00", "07:00");
+        assert!(overnight.contains(almaty, night));
+        assert!(!overnight.contains(almaty, noon));
+        assert!(!overnight.contains(Tz::UTC, night));
+        assert!(quiet_hours("09:00", "17:00").contains(almaty, noon));
+
+        let preferences = Preferences {
+            quiet_hours: Some(quiet_hours("25:00", "07:00")),
+            ..Default::default()
+        };
+        assert_eq!(preferences.validate(), Err("quiet_hours".to_string()));
+    }
+
+    #[test]
+    fn test_disabled_category_blocks_its_kinds() {
+        let preferences = Preferences {
+            categories: NotificationCategories {
+                grades: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(!preferences.allows(NotificationKind::Grade));
+        assert!(!preferences.allows(NotificationKind::GradeOverview));
+        assert!(!preferences.allows_grade(1, &grade_item("mod", Some(100.0))));
+        assert!(preferences.allows(NotificationKind::Deadline));
+        assert!(preferences.allows(NotificationKind::Announcement));
+    }
+
+    #[test]
+    fn test_weekly_report_due_from_its_time_on_its_weekday() {
+        let report = WeeklyReport {
+            weekday: "sunday".to_string(),
+            time: "18:00".to_string(),
+        };
+        // 2025-03-09 is a Sunday; Tokyo is UTC+9
+        let at = |day, hour| {
+            chrono::NaiveDate::from_ymd_opt(2025, 3, day)
+                .and_then(|date| date.and_hms_opt(hour, 0, 0))
+                .unwrap()
+                .and_utc()
+                .timestamp()
+        };
+        assert!(!report.is_due(chrono_tz::Asia::Tokyo, at(9, 8)));
+        assert!(report.is_due(chrono_tz::Asia::Tokyo, at(9, 9)));
+        assert!(!report.is_due(chrono_tz::Asia::Tokyo, at(9, 15)));
+        assert!(!report.is_due(chrono_tz::UTC, at(8, 19)));
+
+        let preferences = Preferences {
+            weekly_report: Some(WeeklyReport {
+                weekday: "someday".to_string(),
+                ..report
+            }),
+            ..Default::default()
+        };
+        assert_eq!(preferences.validate(), Err("weekly_report".to_string()));
+    }
+
+    #[test]
+    fn test_exam_period_holds_digest_for_a_day_after_it_ends() {
+        let period = ExamPeriod {
+            start: 1000,
+            end: 2000,
+        };
+        assert!(!period.contains(999));
+        assert!(period.contains(1000));
+        assert!(!period.contains(2000));
+        assert!(period.holds_digest(2000 + 24 * 3600 - 1));
+        assert!(!period.holds_digest(2000 + 24 * 3600));
+
+        let preferences = Preferences {
+            exam_period: Some(ExamPeriod {
+                start: 2000,
+                end: 1000,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(preferences.validate(), Err("exam_period".to_string()));
+    }
+
+    #[test]
+    fn test_reminder_tiers_default_and_validate() {
+        let mut preferences = Preferences::default();
+        assert_eq!(preferences.reminder_tiers(), REMINDER_TIERS_MINUTES);
+
+        preferences.reminder_tiers = Some(vec![60, 15]);
+        assert_eq!(preferences.reminder_tiers(), [60, 15]);
+        assert!(preferences.validate().is_ok());
+
+        preferences.reminder_tiers = Some(vec![60, 0]);
+        assert_eq!(preferences.validate(), Err("reminder_tiers".to_string()));
+    }
+}
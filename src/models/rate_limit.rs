@@ -0,0 +1,121 @@
+/// Token bucket limits per client IP; every configured route prefix gets its
+/// own bucket, all other routes share the default one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitSettings {
+    /// Requests per minute, also the burst size; 0 disables the limit.
+    pub per_minute: u32,
+    pub routes: Vec<(String, u32)>,
+    /// Proxies in front of the server that append to `X-Forwarded-For`; with
+    /// none the peer address is the client.
+    pub trusted_proxies: usize,
+}
+
+/// The route whose limit paces Moodle registrations; batches take one token
+/// from it per registration.
+pub const REGISTRATION_ROUTE: &str = "/users/create_user";
+
+pub const DEFAULT_ROUTE_LIMITS: &str = "/users/create_user=10,/health=0,/metrics=0";
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            per_minute: 120,
+            routes: parse_route_limits(DEFAULT_ROUTE_LIMITS).unwrap(),
+            trusted_proxies: 0,
+        }
+    }
+}
+
+impl RateLimitSettings {
+    /// The bucket a path falls into and its limit; the longest prefix wins.
+    pub fn limit_for<'a>(&'a self, path: &str) -> (&'a str, u32) {
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(("*", self.per_minute), |(prefix, limit)| {
+                (prefix.as_str(), *limit)
+            })
+    }
+}
+
+/// The client address behind `trusted_proxies` proxies: the entry that many
+/// hops from the right of `X-Forwarded-For`, as everything left of it can be
+/// forged by the client. Falls back to the peer when the header is shorter.
+pub fn client_address(
+    peer: Option<&str>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: usize,
+) -> Option<String> {
+    let hops: Vec<&str> = forwarded_for
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|hop| !hop.is_empty())
+        .collect();
+    match hops.len().checked_sub(trusted_proxies) {
+        Some(index) if trusted_proxies > 0 => Some(hops[index].to_string()),
+        _ => peer.map(str::to_string),
+    }
+}
+
+/// Parses `prefix=per_minute,...`, e.g. `/users/create_user=10`.
+pub fn parse_route_limits(mapping: &str) -> Result<Vec<(String, u32)>, String> {
+    mapping
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            let (prefix, limit) = pair
+                .split_once('=')
+                .map(|(prefix, limit)| (prefix.trim(), limit.trim()))
+                .filter(|(prefix, _)| prefix.starts_with('/'))
+                .ok_or_else(|| format!("Invalid route limit: {}", pair))?;
+            let limit = limit
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid route limit: {}", pair))?;
+            Ok((prefix.to_string(), limit))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_configured_prefix_picks_the_bucket() {
+        let settings = RateLimitSettings {
+            routes: parse_route_limits("/users=30, /users/create_user=5").unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.limit_for("/users/create_user"),
+            ("/users/create_user", 5)
+        );
+        assert_eq!(settings.limit_for("/users/get_user/abc"), ("/users", 30));
+        assert_eq!(settings.limit_for("/grades/abc"), ("*", 120));
+        assert!(parse_route_limits("/users=x").is_err());
+        assert!(parse_route_limits("users=1").is_err());
+    }
+
+    #[test]
+    fn test_client_address_ignores_forged_hops() {
+        let forwarded = Some("6.6.6.6, 1.1.1.1, 10.0.0.2");
+        assert_eq!(
+            client_address(Some("10.0.0.1"), forwarded, 0).as_deref(),
+            Some("10.0.0.1")
+        );
+        assert_eq!(
+            client_address(Some("10.0.0.1"), forwarded, 2).as_deref(),
+            Some("1.1.1.1")
+        );
+        assert_eq!(
+            client_address(Some("10.0.0.1"), Some("1.1.1.1"), 2).as_deref(),
+            Some("10.0.0.1")
+        );
+        assert_eq!(
+            client_address(Some("10.0.0.1"), None, 1).as_deref(),
+            Some("10.0.0.1")
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+use super::token::DeviceTokenPolicy;
+
+#[derive(Debug, Clone)]
+pub struct RegistrationSettings {
+    pub concurrency: usize,
+    pub device_token_policy: DeviceTokenPolicy,
+}
+
+impl Default for RegistrationSettings {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            device_token_policy: DeviceTokenPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationStatus {
+    Created,
+    AlreadyRegistered,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegistrationReport {
+    pub token: String,
+    pub status: RegistrationStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<BackfillResource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<RegistrationVerification>,
+}
+
+impl RegistrationReport {
+    pub fn new(token: String, status: RegistrationStatus, error: Option<String>) -> Self {
+        Self {
+            token,
+            status,
+            error,
+            pending: Vec::new(),
+            verification: None,
+        }
+    }
+}
+
+/// Data left out of a best-effort registration, fetched on the next cycle.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackfillResource {
+    Grades,
+    Deadlines,
+    GradesOverview,
+}
+
+#[derive(Debug, Default)]
+pub struct RegistrationOutcome {
+    pub pending: Vec<BackfillResource>,
+    pub verification: Option<RegistrationVerification>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct VerificationMismatch {
+    pub data: &'static str,
+    pub fetched: usize,
+    pub saved: usize,
+}
+
+/// Outcome of re-reading freshly registered data from the repository.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RegistrationVerification {
+    pub verified: bool,
+    pub mismatches: Vec<VerificationMismatch>,
+}
+
+impl RegistrationVerification {
+    /// Takes `(data, fetched, saved)` counts.
+    pub fn from_counts(counts: &[(&'static str, usize, usize)]) -> Self {
+        let mismatches: Vec<_> = counts
+            .iter()
+            .filter(|(_, fetched, saved)| fetched != saved)
+            .map(|&(data, fetched, saved)| VerificationMismatch {
+                data,
+                fetched,
+                saved,
+            })
+            .collect();
+        Self {
+            verified: mismatches.is_empty(),
+            mismatches,
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::deadline::Deadline;
+use super::i18n::Text;
+
+/// Longest accepted snooze: one week.
+pub const MAX_SNOOZE_MINUTES: i64 = 7 * 24 * 60;
+/// Furthest a custom reminder may be set before its deadline: 30 days.
+pub const MAX_REMINDER_OFFSET_MINUTES: i64 = 30 * 24 * 60;
+/// Minutes before a deadline is due at which escalating reminders go out,
+/// unless the user picked their own tiers.
+pub const REMINDER_TIERS_MINUTES: [i64; 4] = [7 * 24 * 60, 24 * 60, 3 * 60, 30];
+/// Most reminder tiers a user may pick.
+pub const MAX_REMINDER_TIERS: usize = 8;
+
+/// Reminders for the deadline are held back until `until`, a unix timestamp.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct DeadlineSnooze {
+    pub deadline_id: i32,
+    pub until: i64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SnoozeRequest {
+    pub minutes: i64,
+}
+
+impl SnoozeRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_SNOOZE_MINUTES).contains(&self.minutes) {
+            return Err("minutes".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A scheduled reminder that went out, kept until its deadline passes.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(from = "StoredSentReminder")]
+pub struct SentReminder {
+    pub deadline_id: i32,
+    /// Unix seconds; a moved deadline is reminded about again.
+    pub due: i64,
+    pub lead_minutes: i64,
+}
+
+/// A sent reminder as stored, including ones kept before leads were in minutes.
+#[derive(Deserialize)]
+struct StoredSentReminder {
+    deadline_id: i32,
+    due: i64,
+    #[serde(default)]
+    lead_minutes: Option<i64>,
+    #[serde(default)]
+    lead_hours: Option<i64>,
+}
+
+impl From<StoredSentReminder> for SentReminder {
+    fn from(stored: StoredSentReminder) -> Self {
+        Self {
+            deadline_id: stored.deadline_id,
+            due: stored.due,
+            lead_minutes: stored
+                .lead_minutes
+                .or(stored.lead_hours.map(|hours| hours * 60))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// A user-defined reminder, stored in its own collection until it is sent.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReminderEntry {
+    #[serde(rename = "_id", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub token: String,
+    pub deadline_id: i32,
+    pub offset_minutes: i64,
+    /// Unix seconds, as of when the reminder was set.
+    pub remind_at: i64,
+}
+
+impl ReminderEntry {
+    /// When to remind about a deadline due at `due`; recomputed on dispatch
+    /// so a moved deadline takes its reminder along.
+    pub fn remind_at_before(&self, due: i64) -> i64 {
+        due - self.offset_minutes * 60
+    }
+}
+
+/// Remind `days`, `hours` and `minutes` before the deadline, added together.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReminderRequest {
+    pub deadline_id: i32,
+    #[serde(default)]
+    pub days: i64,
+    #[serde(default)]
+    pub hours: i64,
+    #[serde(default)]
+    pub minutes: i64,
+}
+
+impl ReminderRequest {
+    /// The offset before the deadline, or the name of the field that is out of range.
+    pub fn offset_minutes(&self) -> Result<i64, String> {
+        let parts = [
+            ("days", self.days, 24 * 60),
+            ("hours", self.hours, 60),
+            ("minutes", self.minutes, 1),
+        ];
+        let mut offset = 0;
+        for (field, value, unit) in parts {
+            if !(0..=MAX_REMINDER_OFFSET_MINUTES / unit).contains(&value) {
+                return Err(field.to_string());
+            }
+            offset += value * unit;
+        }
+        if !(1..=MAX_REMINDER_OFFSET_MINUTES).contains(&offset) {
+            return Err("offset".to_string());
+        }
+        Ok(offset)
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct Reminder {
+    pub id: String,
+    pub deadline_id: i32,
+    pub offset_minutes: i64,
+    /// Unix seconds.
+    pub remind_at: i64,
+}
+
+impl From<ReminderEntry> for Reminder {
+    fn from(entry: ReminderEntry) -> Self {
+        Self {
+            id: entry.id.map(|id| id.to_hex()).unwrap_or_default(),
+            deadline_id: entry.deadline_id,
+            offset_minutes: entry.offset_minutes,
+            remind_at: entry.remind_at,
+        }
+    }
+}
+
+/// Replaces any earlier snooze of the deadline and drops the expired ones.
+pub fn add_snooze(snoozes: &mut Vec<DeadlineSnooze>, snooze: DeadlineSnooze, now: i64) {
+    snoozes.retain(|s| s.deadline_id != snooze.deadline_id && s.until > now);
+    snoozes.push(snooze);
+}
+
+/// Deadlines due within `lead` seconds from `now` whose reminder is not snoozed.
+pub fn due_reminders<'a>(
+    deadlines: &'a [Deadline],
+    snoozes: &[DeadlineSnooze],
+    now: i64,
+    lead: i64,
+) -> Vec<&'a Deadline> {
+    deadlines
+        .iter()
+        .filter(|deadline| (now + 1..=now + lead).contains(&deadline.timeusermidnight))
+        .filter(|deadline| {
+            !snoozes
+                .iter()
+                .any(|s| s.deadline_id == deadline.id && s.until > now)
+        })
+        .collect()
+}
+
+/// The scheduled reminder each deadline is due for at `now`: the shortest of
+/// the `tiers`, in minutes, whose window has opened, unless that reminder
+/// already went out.
+pub fn scheduled_reminders<'a>(
+    deadlines: &'a [Deadline],
+    tiers: &[i64],
+    sent: &[SentReminder],
+    now: i64,
+) -> Vec<(&'a Deadline, SentReminder)> {
+    deadlines
+        .iter()
+        .filter_map(|deadline| {
+            let due = deadline.due_at();
+            let lead_minutes = tiers
+                .iter()
+                .copied()
+                .filter(|lead| (due - lead * 60..due).contains(&now))
+                .min()?;
+            let reminder = SentReminder {
+                deadline_id: deadline.id,
+                due,
+                lead_minutes,
+            };
+            (!sent.contains(&reminder)).then_some((deadline, reminder))
+        })
+        .collect()
+}
+
+/// The title of a reminder `lead_minutes` ahead, in the largest whole unit.
+pub fn lead_text(lead_minutes: i64) -> Text<'static> {
+    if lead_minutes >= 2 * 24 * 60 && lead_minutes % (24 * 60) == 0 {
+        Text::DeadlineInDays(lead_minutes / (24 * 60))
+    } else if lead_minutes % 60 == 0 {
+        Text::DeadlineInHours(lead_minutes / 60)
+    } else {
+        Text::DeadlineInMinutes(lead_minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deadline(id: i32, due: i64) -> Deadline {
+        Deadline {
+            id,
+            name: format!("Task {}", id),
+            timeusermidnight: due,
+            formattedtime: String::new(),
+            coursename: None,
+            courseid: Some(1),
+            timestart: None,
+        }
+    }
+
+    #[test]
+    fn test_snoozed_deadline_skipped_until_snooze_expires() {
+        let deadlines = [deadline(1, 1_000), deadline(2, 1_500), deadline(3, 9_000)];
+        let snoozes = [DeadlineSnooze {
+            deadline_id: 2,
+            until: 600,
+        }];
+
+        let ids = |now| {
+            due_reminders(&deadlines, &snoozes, now, 3_600)
+                .iter()
+                .map(|deadline| deadline.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(500), [1]);
+        assert_eq!(ids(600), [1, 2]);
+        assert_eq!(ids(1_000), [2]);
+    }
+
+    #[test]
+    fn test_add_snooze_replaces_and_prunes() {
+        let snooze = |deadline_id, until| DeadlineSnooze { deadline_id, until };
+        let mut snoozes = vec![snooze(1, 100), snooze(2, 500), snooze(3, 50)];
+
+        add_snooze(&mut snoozes, snooze(2, 900), 80);
+
+        assert_eq!(snoozes, [snooze(1, 100), snooze(2, 900)]);
+        assert!(SnoozeRequest { minutes: 0 }.validate().is_err());
+        assert!(SnoozeRequest { minutes: 30 }.validate().is_ok());
+    }
+
+    #[test]
+    fn test_scheduled_reminders_pick_shortest_open_lead_once() {
+        let due = 100_000;
+        let deadlines = [Deadline {
+            timestart: Some(due),
+            ..deadline(1, 0)
+        }];
+        let leads = |sent: &[SentReminder], now| {
+            scheduled_reminders(&deadlines, &REMINDER_TIERS_MINUTES, sent, now)
+                .into_iter()
+                .map(|(_, reminder)| reminder.lead_minutes)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(leads(&[], due - 8 * 86400).is_empty());
+        assert_eq!(leads(&[], due - 6 * 86400), [7 * 24 * 60]);
+        assert_eq!(leads(&[], due - 23 * 3600), [24 * 60]);
+        assert_eq!(leads(&[], due - 2 * 3600), [3 * 60]);
+        assert_eq!(leads(&[], due - 1200), [30]);
+        assert!(leads(&[], due).is_empty());
+
+        let sent = [SentReminder {
+            deadline_id: 1,
+            due,
+            lead_minutes: 24 * 60,
+        }];
+        assert!(leads(&sent, due - 4 * 3600).is_empty());
+        assert_eq!(leads(&sent, due - 1200), [30]);
+    }
+
+    #[test]
+    fn test_sent_reminders_stored_in_hours_read_as_minutes() {
+        let stored: SentReminder = serde_json::from_value(serde_json::json!({
+            "deadline_id": 1,
+            "due": 100,
+            "lead_hours": 24,
+        }))
+        .unwrap();
+        assert_eq!(stored.lead_minutes, 24 * 60);
+        assert_eq!(lead_text(7 * 24 * 60), Text::DeadlineInDays(7));
+        assert_eq!(lead_text(24 * 60), Text::DeadlineInHours(24));
+        assert_eq!(lead_text(30), Text::DeadlineInMinutes(30));
+    }
+
+    #[test]
+    fn test_reminder_offset_adds_up_parts() {
+        let request = |days, hours, minutes| ReminderRequest {
+            deadline_id: 1,
+            days,
+            hours,
+            minutes,
+        };
+        assert_eq!(request(3, 2, 0).offset_minutes(), Ok(3 * 24 * 60 + 120));
+        assert_eq!(request(0, 0, 0).offset_minutes(), Err("offset".to_string()));
+        assert_eq!(request(0, -1, 0).offset_minutes(), Err("hours".to_string()));
+        assert_eq!(
+            request(30, 1, 0).offset_minutes(),
+            Err("offset".to_string())
+        );
+        assert_eq!(
+            request(0, 0, i64::MAX).offset_minutes(),
+            Err("minutes".to_string())
+        );
+    }
+}
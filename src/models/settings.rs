@@ -0,0 +1,108 @@
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::i18n::Language;
+
+pub const SUPPORTED_LANGUAGES: [&str; 3] = ["en", "ru", "kk"];
+/// The platform's own notification sound.
+pub const DEFAULT_SOUND: &str = "default";
+
+/// Language and IANA timezone used to localize notifications and place
+/// quiet hours, and how pushes sound and badge the app icon; users who never
+/// saved settings get the university's defaults.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+#[serde(default)]
+pub struct UserSettings {
+    pub language: String,
+    pub timezone: String,
+    /// A sound file the app bundles, e.g. `chime.caf`, or `default`; `null`
+    /// mutes pushes.
+    pub sound: Option<String>,
+    /// Whether pushes set the app icon badge to the unread count.
+    pub badge: bool,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            language: "en".to_string(),
+            timezone: "Asia/Almaty".to_string(),
+            sound: Some(DEFAULT_SOUND.to_string()),
+            badge: true,
+        }
+    }
+}
+
+impl UserSettings {
+    /// Falls back to the default timezone for settings saved before validation.
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(chrono_tz::Asia::Almaty)
+    }
+
+    pub fn lang(&self) -> Language {
+        Language::from_code(&self.language)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !SUPPORTED_LANGUAGES.contains(&self.language.as_str()) {
+            return Err("language".to_string());
+        }
+        if self.timezone.parse::<Tz>().is_err() {
+            return Err("timezone".to_string());
+        }
+        if let Some(sound) = &self.sound {
+            if !is_sound_name(sound) {
+                return Err("sound".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A plain file name, so it can't point outside the app's sounds.
+fn is_sound_name(sound: &str) -> bool {
+    !sound.is_empty()
+        && sound.len() <= 64
+        && !sound.starts_with('.')
+        && sound
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_language_and_timezone() {
+        assert!(UserSettings::default().validate().is_ok());
+        let settings = |language: &str, timezone: &str| UserSettings {
+            language: language.to_string(),
+            timezone: timezone.to_string(),
+            ..UserSettings::default()
+        };
+        assert!(settings("kk", "Europe/Berlin").validate().is_ok());
+        assert_eq!(
+            settings("de", "Asia/Almaty").validate(),
+            Err("language".to_string())
+        );
+        assert_eq!(
+            settings("ru", "GMT+6").validate(),
+            Err("timezone".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_sound_name() {
+        let sound = |sound: Option<&str>| UserSettings {
+            sound: sound.map(str::to_string),
+            ..UserSettings::default()
+        };
+        assert!(sound(Some("chime.caf")).validate().is_ok());
+        assert!(sound(None).validate().is_ok());
+        for invalid in ["", "../chime.caf", "chime caf", ".hidden"] {
+            assert_eq!(sound(Some(invalid)).validate(), Err("sound".to_string()));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use chrono::Utc;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::AddAssign;
+
+use super::cohort::Cohort;
+use super::notification::NotificationKind;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BatchReport {
+    pub tokens_processed: i64,
+    pub errors: i64,
+}
+
+impl AddAssign for BatchReport {
+    fn add_assign(&mut self, other: Self) {
+        self.tokens_processed += other.tokens_processed;
+        self.errors += other.errors;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CycleReport {
+    pub started_at: DateTime,
+    pub finished_at: DateTime,
+    pub duration_ms: i64,
+    pub tokens_processed: i64,
+    pub errors: i64,
+}
+
+impl CycleReport {
+    pub fn new(started_at: DateTime, finished_at: DateTime, batch: BatchReport) -> Self {
+        Self {
+            started_at,
+            finished_at,
+            duration_ms: finished_at.timestamp_millis() - started_at.timestamp_millis(),
+            tokens_processed: batch.tokens_processed,
+            errors: batch.errors,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Default, Clone, PartialEq)]
+pub struct UserStats {
+    pub total: u64,
+    pub with_device: u64,
+    /// Users whose last sync failed.
+    pub failing_sync: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DailyNotificationCount {
+    pub day: String,
+    pub kind: NotificationKind,
+    /// Counts recorded before cohorts existed belong to the stable cohort.
+    #[serde(default)]
+    pub cohort: Cohort,
+    pub count: i64,
+    /// Count per UTC hour ("00".."23") of the day.
+    #[serde(default, skip_serializing)]
+    pub hours: HashMap<String, i64>,
+}
+
+/// Sums hourly counts from the hour of `since` onwards.
+pub fn notifications_since(counts: &[DailyNotificationCount], since: chrono::DateTime<Utc>) -> i64 {
+    let since_hour = since.format("%Y-%m-%dT%H").to_string();
+    counts
+        .iter()
+        .flat_map(|count| {
+            count
+                .hours
+                .iter()
+                .map(move |(hour, n)| (format!("{}T{}", count.day, hour), *n))
+        })
+        .filter(|(hour, _)| *hour >= since_hour)
+        .map(|(_, n)| n)
+        .sum()
+}
+
+#[derive(Debug, Serialize, Default, PartialEq)]
+pub struct CycleSummary {
+    pub cycles: i64,
+    pub average_duration_ms: f64,
+    /// Cycle time spent per synced token, failed syncs included.
+    pub average_token_sync_ms: f64,
+    pub tokens_processed: i64,
+    pub errors: i64,
+    pub provider_error_rate: f64,
+}
+
+impl CycleSummary {
+    pub fn from_reports(reports: &[CycleReport]) -> Self {
+        if reports.is_empty() {
+            return Self::default();
+        }
+        let cycles = reports.len() as i64;
+        let total_duration: i64 = reports.iter().map(|r| r.duration_ms).sum();
+        let tokens_processed: i64 = reports.iter().map(|r| r.tokens_processed).sum();
+        let errors: i64 = reports.iter().map(|r| r.errors).sum();
+        let attempts = tokens_processed + errors;
+        Self {
+            cycles,
+            average_duration_ms: total_duration as f64 / cycles as f64,
+            average_token_sync_ms: if attempts == 0 {
+                0.0
+            } else {
+                total_duration as f64 / attempts as f64
+            },
+            tokens_processed,
+            errors,
+            provider_error_rate: if attempts == 0 {
+                0.0
+            } else {
+                errors as f64 / attempts as f64
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminStats {
+    pub period_days: i64,
+    pub users: UserStats,
+    pub notifications: Vec<DailyNotificationCount>,
+    pub notifications_last_24h: i64,
+    pub cycles: CycleSummary,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(duration_ms: i64, tokens_processed: i64, errors: i64) -> CycleReport {
+        CycleReport {
+            started_at: DateTime::from_millis(0),
+            finished_at: DateTime::from_millis(duration_ms),
+            duration_ms,
+            tokens_processed,
+            errors,
+        }
+    }
+
+    #[test]
+    fn test_cycle_summary_empty() {
+        assert_eq!(CycleSummary::from_reports(&[]), CycleSummary::default());
+    }
+
+    #[test]
+    fn test_cycle_summary_averages_and_error_rate() {
+        let summary = CycleSummary::from_reports(&[report(1000, 9, 1), report(3000, 10, 0)]);
+        assert_eq!(summary.cycles, 2);
+        assert_eq!(summary.average_duration_ms, 2000.0);
+        assert_eq!(summary.average_token_sync_ms, 200.0);
+        assert_eq!(summary.tokens_processed, 19);
+        assert_eq!(summary.errors, 1);
+        assert_eq!(summary.provider_error_rate, 0.05);
+    }
+
+    #[test]
+    fn test_cycle_report_duration() {
+        let mut batch = BatchReport {
+            tokens_processed: 2,
+            errors: 0,
+        };
+        batch += BatchReport {
+            tokens_processed: 3,
+            errors: 1,
+        };
+        let report = CycleReport::new(
+            DateTime::from_millis(1_000),
+            DateTime::from_millis(4_500),
+            batch,
+        );
+        assert_eq!(report.duration_ms, 3_500);
+        assert_eq!(report.tokens_processed, 5);
+        assert_eq!(report.errors, 1);
+    }
+
+    #[test]
+    fn test_notifications_since_counts_whole_hours_across_days() {
+        let count = |day: &str, hours: &[(&str, i64)]| DailyNotificationCount {
+            day: day.to_string(),
+            kind: NotificationKind::Grade,
+            cohort: Cohort::Stable,
+            count: hours.iter().map(|(_, n)| n).sum(),
+            hours: hours.iter().map(|(h, n)| (h.to_string(), *n)).collect(),
+        };
+        let counts = [
+            count("2026-10-15", &[("09", 1), ("10", 2), ("23", 3)]),
+            count("2026-10-16", &[("00", 4), ("10", 5)]),
+        ];
+        let since = chrono::DateTime::parse_from_rfc3339("2026-10-15T10:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(notifications_since(&counts, since), 14);
+    }
+}
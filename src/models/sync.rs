@@ -0,0 +1,17 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::course::Course;
+use super::deadline::Deadline;
+use super::grade::{Grade, GradeOverview};
+use super::user::User;
+
+/// Everything stored for a user right after a sync with the provider.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncedData {
+    pub user: User,
+    pub courses: Vec<Course>,
+    pub grades: Vec<Grade>,
+    pub grades_overview: Vec<GradeOverview>,
+    pub deadlines: Vec<Deadline>,
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use handlebars::Handlebars;
+
+use super::i18n::{Language, Text};
+use super::settings::SUPPORTED_LANGUAGES;
+
+/// Handlebars overrides of the built-in notification wording, keyed by text
+/// name and then language code:
+/// `{"deadline_title": {"en": "Due soon: {{task}}"}}`. Text without an
+/// override keeps its built-in wording.
+pub struct NotificationTemplates {
+    registry: Handlebars<'static>,
+}
+
+impl Default for NotificationTemplates {
+    fn default() -> Self {
+        let mut registry = Handlebars::new();
+        // Pushes are plain text
+        registry.register_escape_fn(handlebars::no_escape);
+        Self { registry }
+    }
+}
+
+impl NotificationTemplates {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let templates: HashMap<String, HashMap<String, String>> =
+            serde_json::from_str(json).context("Invalid notification templates")?;
+        let mut engine = Self::default();
+        for (name, translations) in templates {
+            for (language, template) in translations {
+                if !SUPPORTED_LANGUAGES.contains(&language.as_str()) {
+                    bail!("Unsupported language {} in template {}", language, name);
+                }
+                engine
+                    .registry
+                    .register_template_string(&format!("{}.{}", name, language), template)
+                    .with_context(|| format!("Invalid template {}", name))?;
+            }
+        }
+        Ok(engine)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read notification templates {}", path))?;
+        Self::from_json(&contents)
+    }
+
+    /// Renders the configured template for the text, falling back to the
+    /// built-in wording when there is none or it fails.
+    pub fn render(&self, text: Text, language: Language) -> String {
+        let key = format!("{}.{}", text.name(), language.code());
+        if self.registry.has_template(&key) {
+            match self.registry.render(&key, &text.data()) {
+                Ok(rendered) => return rendered,
+                Err(e) => eprintln!("Error rendering template {}: {}", key, e),
+            }
+        }
+        text.render(language)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_template_overrides_one_language() {
+        let templates = NotificationTemplates::from_json(
+            r#"{"deadline_body": {"en": "{{task}} & more, due {{until}}"}}"#,
+        )
+        .unwrap();
+        let text = Text::DeadlineBody {
+            course: "Math",
+            task: "Essay",
+            until: "Monday",
+        };
+
+        assert_eq!(
+            templates.render(text, Language::En),
+            "Essay & more, due Monday"
+        );
+        assert_eq!(
+            templates.render(text, Language::Ru),
+            text.render(Language::Ru)
+        );
+        assert_eq!(
+            templates.render(Text::DeadlineTitle, Language::En),
+            "New deadline"
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates_rejected() {
+        assert!(
+            NotificationTemplates::from_json(r#"{"deadline_title": {"de": "Frist"}}"#).is_err()
+        );
+        assert!(
+            NotificationTemplates::from_json(r#"{"deadline_title": {"en": "{{#if"}}"#).is_err()
+        );
+    }
+}
@@ -1,10 +1,140 @@
-use serde::Deserialize;
+use std::net::IpAddr;
+use std::str::FromStr;
 
-#[derive(Debug, Deserialize, Clone)]
+use mongodb::bson::{from_bson, Document};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Ios,
+    Android,
+    /// A Telegram chat the bot messages; the device token is the chat id.
+    Telegram,
+    /// A user's own endpoint that change events are posted to; the device token is its URL.
+    Webhook,
+}
+
+/// How a device is reached; users choose per channel what it gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Push,
+    Telegram,
+    Webhook,
+}
+
+impl Platform {
+    /// Whether the platform is the mobile app, which can take silent pushes.
+    pub fn is_app(&self) -> bool {
+        matches!(self, Platform::Ios | Platform::Android)
+    }
+
+    pub fn channel(&self) -> Channel {
+        match self {
+            Platform::Ios | Platform::Android => Channel::Push,
+            Platform::Telegram => Channel::Telegram,
+            Platform::Webhook => Channel::Webhook,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Platform::Ios => "ios",
+            Platform::Android => "android",
+            Platform::Telegram => "telegram",
+            Platform::Webhook => "webhook",
+        }
+    }
+
+    /// APNs tokens are hex strings, often copied with spaces or angle brackets;
+    /// FCM registration tokens are opaque url-safe strings; Telegram chat ids
+    /// are integers, negative for groups; webhooks are public https URLs.
+    pub fn normalize_device_token(&self, device_token: &str) -> Result<String, String> {
+        let normalized = match self {
+            Platform::Ios => device_token
+                .chars()
+                .filter(|c| !c.is_whitespace() && *c != '<' && *c != '>')
+                .collect::<String>()
+                .to_ascii_lowercase(),
+            Platform::Android | Platform::Telegram | Platform::Webhook => {
+                device_token.trim().to_string()
+            }
+        };
+
+        let valid = match self {
+            Platform::Ios => {
+                (64..=200).contains(&normalized.len())
+                    && normalized.len() % 2 == 0
+                    && normalized.chars().all(|c| c.is_ascii_hexdigit())
+            }
+            Platform::Android => {
+                (32..=4096).contains(&normalized.len())
+                    && normalized
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'))
+            }
+            Platform::Telegram => normalized
+                .strip_prefix('-')
+                .unwrap_or(&normalized)
+                .parse::<u64>()
+                .is_ok_and(|id| id > 0),
+            Platform::Webhook => normalized.len() <= 2048 && is_public_https_url(&normalized),
+        };
+
+        if valid {
+            Ok(normalized)
+        } else {
+            Err("device_token".to_string())
+        }
+    }
+}
+
+/// Keeps webhooks off loopback and IP-literal hosts, which would reach our own
+/// network. A numeric last label makes URL parsers read the host as IPv4, as
+/// in `0x7f.0.0.1`. Where names resolve to is checked when connecting.
+fn is_public_https_url(url: &str) -> bool {
+    let Some(rest) = url.strip_prefix("https://") else {
+        return false;
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    let last_label = host
+        .trim_end_matches('.')
+        .rsplit('.')
+        .next()
+        .unwrap_or_default();
+    let numeric_label = last_label.chars().all(|c| c.is_ascii_digit())
+        || last_label
+            .get(..2)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("0x"));
+    !url.contains(char::is_whitespace)
+        && !host.contains('%')
+        && host.contains('.')
+        && !numeric_label
+        && !host.eq_ignore_ascii_case("localhost")
+        && host.parse::<IpAddr>().is_err()
+}
+
+/// Devices kept per account; registering another drops the least recently added.
+pub const MAX_DEVICES: usize = 10;
+
+/// A user as registered or read back; `device_token` is the device being
+/// registered, `devices` every device stored for the account.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
 pub struct Token {
     pub token: String,
     pub device_token: Option<String>,
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    #[serde(skip)]
+    pub devices: Vec<Device>,
 }
 
 impl Token {
@@ -12,6 +142,235 @@ impl Token {
         Self {
             token,
             device_token,
+            platform: None,
+            devices: Vec::new(),
+        }
+    }
+
+    pub fn device(&self) -> Option<Device> {
+        self.device_token.as_ref().map(|device_token| Device {
+            token: device_token.clone(),
+            platform: self.platform,
+        })
+    }
+
+    /// Tokens registered without a platform are only trimmed.
+    pub fn normalize_device_token(&mut self) -> Result<(), String> {
+        if let Some(device_token) = &self.device_token {
+            let normalized = match self.platform {
+                Some(platform) => platform.normalize_device_token(device_token)?,
+                None => device_token.trim().to_string(),
+            };
+            if normalized.is_empty() {
+                return Err("device_token".to_string());
+            }
+            self.device_token = Some(normalized);
+        }
+        Ok(())
+    }
+}
+
+/// Device token to add, or to swap in for `previous_device_token` after an
+/// APNs/FCM refresh; without a platform the replaced device's is kept.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeviceTokenUpdate {
+    pub device_token: String,
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    #[serde(default)]
+    pub previous_device_token: Option<String>,
+}
+
+/// What to do when a device token is already registered to another account.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DeviceTokenPolicy {
+    Reject,
+    #[default]
+    Transfer,
+    Allow,
+}
+
+impl FromStr for DeviceTokenPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "reject" => Ok(DeviceTokenPolicy::Reject),
+            "transfer" => Ok(DeviceTokenPolicy::Transfer),
+            "allow" => Ok(DeviceTokenPolicy::Allow),
+            _ => Err(format!("unknown device token policy: {}", value)),
+        }
+    }
+}
+
+/// Notification target of a registered user.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Device {
+    pub token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+}
+
+impl Device {
+    /// Devices registered without a platform are the app's.
+    pub fn channel(&self) -> Channel {
+        self.platform
+            .map_or(Channel::Push, |platform| platform.channel())
+    }
+}
+
+/// The `devices` of a user document; malformed entries are skipped.
+pub fn devices_from_document(doc: &Document) -> Vec<Device> {
+    doc.get_array("devices")
+        .map(|devices| {
+            devices
+                .iter()
+                .filter_map(|device| from_bson(device.clone()).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The device to store in place of the one it `replaces`, or of an older entry
+/// of the same token, inheriting that device's platform when it has none.
+pub fn replacing_device(devices: &[Device], mut device: Device, replaces: Option<&str>) -> Device {
+    let previous = devices
+        .iter()
+        .find(|d| Some(d.token.as_str()) == replaces || d.token == device.token);
+    if let Some(previous) = previous {
+        device.platform = device.platform.or(previous.platform);
+    }
+    device
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APNS_TOKEN: &str = "740f4707bebcf74f9b7c25d48e3358945f6aa01da5ddb387462c7eaf61bb78ad";
+    const FCM_TOKEN: &str =
+        "dQw4w9WgXcQ:APA91bHun4MxP5egoKMwt2KZFBaFUH-1RYqx6YPqGfZrW8T5d0kxMZxsl2Jq9rV7bgL1nM3oK";
+
+    fn token(device_token: &str, platform: Option<Platform>) -> Token {
+        Token {
+            platform,
+            ..Token::new("token".to_string(), Some(device_token.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_ios_device_token_is_normalized() {
+        let mut ios = token(
+            "<740F4707 BEBCF74F 9B7C25D4 8E335894 5F6AA01D A5DDB387 462C7EAF 61BB78AD>",
+            Some(Platform::Ios),
+        );
+        assert!(ios.normalize_device_token().is_ok());
+        assert_eq!(ios.device_token.as_deref(), Some(APNS_TOKEN));
+    }
+
+    #[test]
+    fn test_ios_device_token_rejects_invalid_format() {
+        assert!(token("abc123", Some(Platform::Ios))
+            .normalize_device_token()
+            .is_err());
+        assert!(token(FCM_TOKEN, Some(Platform::Ios))
+            .normalize_device_token()
+            .is_err());
+    }
+
+    #[test]
+    fn test_android_device_token_validation() {
+        let mut android = token(&format!("  {}\n", FCM_TOKEN), Some(Platform::Android));
+        assert!(android.normalize_device_token().is_ok());
+        assert_eq!(android.device_token.as_deref(), Some(FCM_TOKEN));
+
+        assert!(token("short:token", Some(Platform::Android))
+            .normalize_device_token()
+            .is_err());
+        assert!(token(
+            &format!("{} {}", FCM_TOKEN, FCM_TOKEN),
+            Some(Platform::Android)
+        )
+        .normalize_device_token()
+        .is_err());
+    }
+
+    #[test]
+    fn test_telegram_chat_id_validation() {
+        let mut group = token(" -1001234567890 ", Some(Platform::Telegram));
+        assert!(group.normalize_device_token().is_ok());
+        assert_eq!(group.device_token.as_deref(), Some("-1001234567890"));
+
+        for invalid in ["@aitu_student", "0", "12a"] {
+            assert!(token(invalid, Some(Platform::Telegram))
+                .normalize_device_token()
+                .is_err());
         }
     }
+
+    #[test]
+    fn test_webhook_url_must_be_public_https() {
+        let mut webhook = token(
+            " https://hooks.example.com/aitu?id=1 ",
+            Some(Platform::Webhook),
+        );
+        assert!(webhook.normalize_device_token().is_ok());
+        assert_eq!(
+            webhook.device_token.as_deref(),
+            Some("https://hooks.example.com/aitu?id=1")
+        );
+
+        for invalid in [
+            "http://hooks.example.com/aitu",
+            "https://localhost:8080/hook",
+            "https://127.0.0.1/hook",
+            "https://[::1]/hook",
+            "https://user@10.0.0.1/hook",
+            "https://intranet/hook",
+            "https://0x7f.0.0.1/hook",
+            "https://127.1/hook",
+            "https://%31%32%37.0.0.1/hook",
+        ] {
+            assert!(
+                token(invalid, Some(Platform::Webhook))
+                    .normalize_device_token()
+                    .is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_replacing_device_keeps_platform_of_refreshed_token() {
+        let device = |token: &str, platform| Device {
+            token: token.to_string(),
+            platform,
+        };
+        let devices = vec![
+            device("phone", Some(Platform::Android)),
+            device("ipad", Some(Platform::Ios)),
+        ];
+
+        assert_eq!(
+            replacing_device(&devices, device("phone-2", None), Some("phone")),
+            device("phone-2", Some(Platform::Android))
+        );
+        assert_eq!(
+            replacing_device(&devices, device("ipad", Some(Platform::Webhook)), None),
+            device("ipad", Some(Platform::Webhook))
+        );
+        assert_eq!(
+            replacing_device(&devices, device("laptop", None), None),
+            device("laptop", None)
+        );
+    }
+
+    #[test]
+    fn test_device_token_without_platform_is_trimmed() {
+        let mut legacy = token(" legacy ", None);
+        assert!(legacy.normalize_device_token().is_ok());
+        assert_eq!(legacy.device_token.as_deref(), Some("legacy"));
+        assert!(token("   ", None).normalize_device_token().is_err());
+    }
 }
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::notification::NotificationKind;
+
+/// Changes in a course the user has been notified about but not acknowledged.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct UnreadCourse {
+    pub courseid: i64,
+    #[serde(default)]
+    pub grades: u32,
+    #[serde(default)]
+    pub deadlines: u32,
+}
+
+#[derive(Debug, Deserialize, Default, ToSchema)]
+pub struct UnreadAck {
+    /// Courses to acknowledge; all of them when omitted.
+    #[serde(default)]
+    pub course_ids: Option<Vec<i64>>,
+}
+
+pub fn add_unread(
+    unread: &mut Vec<UnreadCourse>,
+    course_id: i64,
+    kind: NotificationKind,
+    count: u32,
+) {
+    let index = match unread
+        .iter()
+        .position(|course| course.courseid == course_id)
+    {
+        Some(index) => index,
+        None => {
+            unread.push(UnreadCourse {
+                courseid: course_id,
+                grades: 0,
+                deadlines: 0,
+            });
+            unread.len() - 1
+        }
+    };
+    let course = &mut unread[index];
+    match kind {
+        NotificationKind::Grade => course.grades += count,
+        NotificationKind::Deadline => course.deadlines += count,
+        _ => {}
+    }
+}
+
+pub fn ack_unread(unread: &mut Vec<UnreadCourse>, course_ids: Option<&[i64]>) {
+    match course_ids {
+        Some(course_ids) => unread.retain(|course| !course_ids.contains(&course.courseid)),
+        None => unread.clear(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_unread_accumulates_per_course() {
+        let mut unread = Vec::new();
+        add_unread(&mut unread, 1, NotificationKind::Grade, 2);
+        add_unread(&mut unread, 1, NotificationKind::Deadline, 1);
+        add_unread(&mut unread, 2, NotificationKind::Grade, 1);
+        add_unread(&mut unread, 1, NotificationKind::Grade, 1);
+
+        assert_eq!(
+            unread,
+            vec![
+                UnreadCourse {
+                    courseid: 1,
+                    grades: 3,
+                    deadlines: 1
+                },
+                UnreadCourse {
+                    courseid: 2,
+                    grades: 1,
+                    deadlines: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ack_unread() {
+        let mut unread = Vec::new();
+        add_unread(&mut unread, 1, NotificationKind::Grade, 1);
+        add_unread(&mut unread, 2, NotificationKind::Grade, 1);
+
+        ack_unread(&mut unread, Some(&[1]));
+        assert_eq!(unread.len(), 1);
+        assert_eq!(unread[0].courseid, 2);
+
+        ack_unread(&mut unread, None);
+        assert!(unread.is_empty());
+    }
+}
@@ -1,6 +1,10 @@
+use async_graphql::SimpleObject;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+use super::i18n::Text;
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, ToSchema, SimpleObject)]
 pub struct User {
     username: String,
     fullname: String,
@@ -8,26 +12,28 @@ pub struct User {
 }
 
 impl User {
-    pub fn create_body_message_user(&self) -> String {
-        format!(
-            "Email: {}\nFullname: {}\nUser_id: {}",
-            self.username, self.fullname, self.userid
-        )
+    pub fn body_text(&self) -> Text<'_> {
+        Text::UserInfoBody {
+            email: &self.username,
+            fullname: &self.fullname,
+            user_id: self.userid,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::i18n::Language;
 
     #[test]
-    fn test_create_body_message_user() {
+    fn test_body_text() {
         let user = User {
             username: "testuser".to_string(),
             fullname: "Test User".to_string(),
             userid: 123,
         };
         let expected_message = "Email: testuser\nFullname: Test User\nUser_id: 123";
-        assert_eq!(user.create_body_message_user(), expected_message);
+        assert_eq!(user.body_text().render(Language::En), expected_message);
     }
 }
@@ -0,0 +1,106 @@
+use mongodb::bson::{doc, DateTime, Document};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+use super::pagination::skip;
+use super::token::Platform;
+
+pub const USERS_PAGE_SIZE: u64 = 50;
+
+/// Outcome of the last background sync of a user.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SyncStatus {
+    pub at: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Filters of the admin user list; unset filters match everyone.
+#[derive(Debug, Deserialize, Default, IntoParams)]
+pub struct UserListQuery {
+    /// Page counted from 1.
+    pub page: Option<u64>,
+    pub has_device_token: Option<bool>,
+    /// Unix seconds; users never synced count as synced before any time.
+    pub synced_before: Option<i64>,
+    /// Whether the last sync failed.
+    pub failing: Option<bool>,
+}
+
+impl UserListQuery {
+    pub fn skip(&self) -> u64 {
+        skip(self.page.unwrap_or(1), USERS_PAGE_SIZE)
+    }
+
+    pub fn filter(&self) -> Document {
+        let mut conditions = Vec::new();
+        if let Some(has_device_token) = self.has_device_token {
+            let has = doc! {"devices.0": {"$exists": true}};
+            conditions.push(if has_device_token {
+                has
+            } else {
+                doc! {"$nor": [has]}
+            });
+        }
+        if let Some(before) = self.synced_before {
+            conditions.push(doc! {"$or": [
+                {"sync.at": {"$lt": DateTime::from_millis(before * 1000)}},
+                {"sync": {"$exists": false}},
+            ]});
+        }
+        if let Some(failing) = self.failing {
+            let failed = doc! {"sync.error": {"$type": "string"}};
+            conditions.push(if failing {
+                failed
+            } else {
+                doc! {"$nor": [failed]}
+            });
+        }
+        if conditions.is_empty() {
+            doc! {}
+        } else {
+            doc! {"$and": conditions}
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RegisteredUser {
+    pub token: String,
+    pub has_device_token: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userid: Option<i64>,
+    /// Unix seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_sync_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync_error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_combines_set_filters_only() {
+        assert_eq!(UserListQuery::default().filter(), doc! {});
+        assert_eq!(UserListQuery::default().skip(), 0);
+
+        let query = UserListQuery {
+            page: Some(3),
+            has_device_token: Some(false),
+            synced_before: None,
+            failing: Some(true),
+        };
+        assert_eq!(query.skip(), 2 * USERS_PAGE_SIZE);
+        assert_eq!(
+            query.filter(),
+            doc! {"$and": [
+                {"$nor": [{"devices.0": {"$exists": true}}]},
+                {"sync.error": {"$type": "string"}},
+            ]}
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use utoipa::ToSchema;
+
+pub const SIGNATURE_PREFIX: &str = "sha256=";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderEventKind {
+    User,
+    Course,
+    Grade,
+    Deadline,
+}
+
+/// Change pushed by the provider for a single Moodle user.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct ProviderEvent {
+    pub userid: i64,
+    pub kind: ProviderEventKind,
+    #[serde(default)]
+    pub courseid: Option<i64>,
+}
+
+/// Where a user wants their change events posted.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UserWebhookRequest {
+    pub url: String,
+}
+
+/// A registered user webhook and the secret its events are signed with.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserWebhook {
+    pub url: String,
+    pub secret: String,
+}
+
+impl UserWebhook {
+    pub fn new(signing_key: &str, url: String) -> Self {
+        Self {
+            secret: user_webhook_secret(signing_key, &url),
+            url,
+        }
+    }
+}
+
+/// The secret for a user webhook, derived from the URL so that senders only
+/// need the signing key; registering another URL rotates it.
+pub fn user_webhook_secret(signing_key: &str, url: &str) -> String {
+    hex::encode(hmac_sha256(signing_key, url.as_bytes()))
+}
+
+/// A `sha256=<hex>` HMAC of the body.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    format!(
+        "{}{}",
+        SIGNATURE_PREFIX,
+        hex::encode(hmac_sha256(secret, body))
+    )
+}
+
+fn hmac_sha256(secret: &str, body: &[u8]) -> Vec<u8> {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Checks a `sha256=<hex>` HMAC of the raw request body.
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(signature) = signature
+        .strip_prefix(SIGNATURE_PREFIX)
+        .and_then(|hex_digest| hex::decode(hex_digest).ok())
+    else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Whether a webhook may connect to `ip`: not loopback, private, link-local
+/// (cloud metadata included), carrier-grade NAT, unique local or otherwise
+/// reserved.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || first == 0x2001 && ip.segments()[1] == 0x0db8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_signature() {
+        let body = br#"{"userid":1,"kind":"grade","courseid":2}"#;
+        let signature = sign("secret", body);
+
+        assert!(verify_signature("secret", body, &signature));
+        assert!(!verify_signature("other", body, &signature));
+        assert!(!verify_signature(
+            "secret",
+            br#"{"userid":2,"kind":"grade","courseid":2}"#,
+            &signature
+        ));
+        assert!(!verify_signature(
+            "secret",
+            body,
+            signature.trim_start_matches(SIGNATURE_PREFIX)
+        ));
+        assert!(!verify_signature("secret", body, "sha256=not-hex"));
+    }
+
+    #[test]
+    fn test_provider_event_deserialization() {
+        let event: ProviderEvent =
+            serde_json::from_str(r#"{"userid":1,"kind":"deadline"}"#).unwrap();
+        assert_eq!(event.kind, ProviderEventKind::Deadline);
+        assert_eq!(event.courseid, None);
+    }
+
+    #[test]
+    fn test_user_webhook_secret_signs_events() {
+        let webhook = UserWebhook::new("key", "https://example.com/a".to_string());
+        assert_eq!(webhook.secret.len(), 64);
+        assert_ne!(
+            webhook.secret,
+            user_webhook_secret("key", "https://example.com/b")
+        );
+
+        let body = br#"{"kind":"grade"}"#;
+        assert!(verify_signature(
+            &webhook.secret,
+            body,
+            &sign(&webhook.secret, body)
+        ));
+    }
+
+    #[test]
+    fn test_only_public_addresses_pass() {
+        for blocked in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.100.100.200",
+            "0.0.0.0",
+            "::1",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(blocked.parse().unwrap()), "{}", blocked);
+        }
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:2800:220:1::".parse().unwrap()));
+    }
+}
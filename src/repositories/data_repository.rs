@@ -1,18 +1,40 @@
+use crate::models::broadcast::BroadcastSegment;
 use crate::models::course::Course;
+use crate::models::course_content::CourseContent;
 use crate::models::deadline::Deadline;
-use crate::models::grade::{Grade, GradeOverview, GradesOverview};
-use crate::models::token::Token;
+use crate::models::grade::{Grade, GradeMark, GradeOverview, GradesOverview};
+use crate::models::notification::Notification;
+use crate::models::pagination::Page;
+use crate::models::preferences::Preferences;
+use crate::models::registration::BackfillResource;
+use crate::models::reminder::{DeadlineSnooze, SentReminder};
+use crate::models::settings::UserSettings;
+use crate::models::token::{devices_from_document, Device, Token, MAX_DEVICES};
+use crate::models::unread::UnreadCourse;
 use crate::models::user::User;
+use crate::models::user_list::{RegisteredUser, SyncStatus, UserListQuery, USERS_PAGE_SIZE};
 use crate::services::data_service::{
     CourseRepositoryInterface, DeadlineRepositoryInterface, GradeRepositoryInterface,
-    RepositoryInterfaces, TokenRepositoryInterface, UserRepositoryInterface,
+    PreferencesRepositoryInterface, RepositoryInterfaces, TokenRepositoryInterface,
+    UnreadRepositoryInterface, UserRepositoryInterface,
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
 use mongodb::bson::{doc, from_bson, to_bson, Bson, Document};
-use mongodb::{bson, Collection, Cursor};
+use mongodb::options::IndexOptions;
+use mongodb::{bson, Collection, IndexModel};
+use serde::de::DeserializeOwned;
 
 use super::errors::RepositoryError;
 
+/// Reads the token fields of a user document; `None` without an `_id`.
+pub fn token_from_document(doc: &Document) -> Option<Token> {
+    let mut token = Token::new(doc.get_str("_id").ok()?.to_string(), None);
+    token.devices = devices_from_document(doc);
+    Some(token)
+}
+
 pub struct DataRepository {
     collection: Collection<Document>,
 }
@@ -21,6 +43,78 @@ impl DataRepository {
     pub fn new(collection: Collection<Document>) -> Self {
         Self { collection }
     }
+
+    /// Moves the single `device_token`/`platform` of older user documents into
+    /// `devices`; documents already migrated are left alone.
+    pub async fn migrate_device_tokens(&self) -> Result<u64, RepositoryError> {
+        let legacy_device = doc! {
+            "token": "$device_token",
+            "platform": {"$ifNull": ["$platform", "$$REMOVE"]},
+        };
+        let result = self
+            .collection
+            .update_many(
+                doc! {"device_token": {"$exists": true}},
+                vec![
+                    doc! {"$set": {"devices": {"$cond": [
+                        {"$eq": [{"$type": "$device_token"}, "string"]},
+                        [legacy_device],
+                        [],
+                    ]}}},
+                    doc! {"$unset": ["device_token", "platform"]},
+                ],
+            )
+            .await?;
+        Ok(result.modified_count)
+    }
+
+    pub async fn create_indexes(&self) -> Result<(), RepositoryError> {
+        let devices = IndexModel::builder()
+            .keys(doc! {"devices.token": 1})
+            .build();
+        self.collection.create_index(devices).await?;
+        // Feeds are looked up by secret; a collision would serve another user's
+        let calendar_secret = IndexModel::builder()
+            .keys(doc! {"calendar_secret": 1})
+            .options(IndexOptions::builder().unique(true).sparse(true).build())
+            .build();
+        self.collection.create_index(calendar_secret).await?;
+        Ok(())
+    }
+
+    /// Slices a stored array server-side so only the requested page leaves the database.
+    async fn find_array_page<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        field: &str,
+        array: Bson,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Page<T>, RepositoryError> {
+        // $slice takes 32-bit positions; anything further is past the end anyway
+        let skip = skip.min(i32::MAX as u64) as i64;
+        let pipeline = [
+            doc! {"$match": {"_id": token, field: {"$type": "array"}}},
+            doc! {"$project": {
+                "items": {"$slice": [array.clone(), skip, limit as i64]},
+                "total": {"$size": array},
+            }},
+        ];
+        let doc = self
+            .collection
+            .aggregate(pipeline)
+            .await?
+            .try_next()
+            .await?
+            .ok_or_else(|| RepositoryError::DataNotFound(field.to_string()))?;
+        let items = from_bson(doc.get("items").cloned().unwrap_or(Bson::Array(Vec::new())))?;
+        let total = match doc.get("total") {
+            Some(Bson::Int32(total)) => *total as u64,
+            Some(Bson::Int64(total)) => *total as u64,
+            _ => 0,
+        };
+        Ok(Page { items, total })
+    }
 }
 
 #[async_trait]
@@ -36,7 +130,10 @@ impl TokenRepositoryInterface for DataRepository {
         Ok(())
     }
     async fn save_tokens(&self, token: &Token) -> Result<(), RepositoryError> {
-        let doc = doc! {"_id": &token.token, "device_token": &token.device_token };
+        let doc = doc! {
+            "_id": &token.token,
+            "devices": to_bson(&token.device().into_iter().collect::<Vec<_>>())?,
+        };
         self.find_token(token).await?;
 
         self.collection.insert_one(doc).await?;
@@ -47,11 +144,19 @@ impl TokenRepositoryInterface for DataRepository {
         &self,
         limit: i64,
         skip: u64,
-    ) -> Result<Cursor<Document>, RepositoryError> {
+    ) -> Result<Vec<Token>, RepositoryError> {
         let filter = doc! {"_id": {"$exists": true}};
 
-        let cursor = self.collection.find(filter).limit(limit).skip(skip).await?;
-        Ok(cursor)
+        let docs: Vec<Document> = self
+            .collection
+            .find(filter)
+            .projection(doc! {"_id": 1, "devices": 1})
+            .limit(limit)
+            .skip(skip)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(docs.iter().filter_map(token_from_document).collect())
     }
 
     async fn delete(&self, token: &str) -> Result<(), RepositoryError> {
@@ -65,6 +170,221 @@ impl TokenRepositoryInterface for DataRepository {
         self.collection.delete_one(doc).await?;
         Ok(())
     }
+
+    async fn find_calendar_secret(&self, token: &str) -> Result<Option<String>, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": token}).await?;
+        if let Some(doc) = doc {
+            Ok(doc.get_str("calendar_secret").ok().map(str::to_string))
+        } else {
+            Err(RepositoryError::DataNotFound("User".to_string()))
+        }
+    }
+
+    async fn save_calendar_secret(&self, token: &str, secret: &str) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {
+                    "$set": {"calendar_secret": secret}
+                },
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn find_token_by_calendar_secret(&self, secret: &str) -> Result<String, RepositoryError> {
+        let doc = self
+            .collection
+            .find_one(doc! {"calendar_secret": secret})
+            .await?;
+        match doc.as_ref().and_then(|doc| doc.get_str("_id").ok()) {
+            Some(token) => Ok(token.to_string()),
+            None => Err(RepositoryError::DataNotFound("Calendar".to_string())),
+        }
+    }
+
+    async fn find_tokens_by_device_token(
+        &self,
+        device_token: &str,
+    ) -> Result<Vec<String>, RepositoryError> {
+        let docs: Vec<Document> = self
+            .collection
+            .find(doc! {"devices.token": device_token})
+            .projection(doc! {"_id": 1})
+            .await?
+            .try_collect()
+            .await?;
+        Ok(docs
+            .iter()
+            .filter_map(|doc| doc.get_str("_id").ok().map(str::to_string))
+            .collect())
+    }
+
+    async fn find_devices(&self, token: &str) -> Result<Vec<Device>, RepositoryError> {
+        let doc = self
+            .collection
+            .find_one(doc! {"_id": token})
+            .projection(doc! {"devices": 1})
+            .await?;
+        match doc {
+            Some(doc) => Ok(devices_from_document(&doc)),
+            None => Err(RepositoryError::DataNotFound("User".to_string())),
+        }
+    }
+
+    async fn pull_devices(
+        &self,
+        token: &str,
+        device_tokens: &[&str],
+    ) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {"$pull": {"devices": {"token": {"$in": device_tokens}}}},
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn push_device(&self, token: &str, device: &Device) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {"$push": {"devices": {
+                    "$each": [to_bson(device)?],
+                    "$slice": -(MAX_DEVICES as i64),
+                }}},
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn find_pending_backfill(
+        &self,
+        token: &str,
+    ) -> Result<Vec<BackfillResource>, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": token}).await?;
+        match doc {
+            Some(doc) => match doc.get("pending_backfill") {
+                Some(pending) => Ok(from_bson(pending.clone())?),
+                None => Ok(Vec::new()),
+            },
+            None => Err(RepositoryError::DataNotFound("User".to_string())),
+        }
+    }
+
+    async fn save_pending_backfill(
+        &self,
+        token: &str,
+        pending: &[BackfillResource],
+    ) -> Result<(), RepositoryError> {
+        let update = if pending.is_empty() {
+            doc! {"$unset": {"pending_backfill": ""}}
+        } else {
+            doc! {"$set": {"pending_backfill": to_bson(pending)?}}
+        };
+        self.collection
+            .update_one(doc! {"_id": token}, update)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_tokens_by_user_id(&self, userid: i64) -> Result<Vec<Token>, RepositoryError> {
+        let docs: Vec<Document> = self
+            .collection
+            .find(doc! {"user.userid": userid})
+            .projection(doc! {"_id": 1, "devices": 1})
+            .await?
+            .try_collect()
+            .await?;
+        Ok(docs.iter().filter_map(token_from_document).collect())
+    }
+
+    async fn find_broadcast_recipients(
+        &self,
+        segment: &BroadcastSegment,
+        after: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<Token>, RepositoryError> {
+        let mut filter = segment.filter();
+        if let Some(after) = after {
+            filter.insert("_id", doc! {"$gt": after});
+        }
+        let docs: Vec<Document> = self
+            .collection
+            .find(filter)
+            .projection(doc! {"_id": 1, "devices": 1})
+            .sort(doc! {"_id": 1})
+            .limit(limit as i64)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(docs.iter().filter_map(token_from_document).collect())
+    }
+
+    async fn save_sync_status(
+        &self,
+        token: &str,
+        status: &SyncStatus,
+    ) -> Result<(), RepositoryError> {
+        self.collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {"$set": {"sync": to_bson(status)?}},
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn find_registered_users(
+        &self,
+        query: &UserListQuery,
+    ) -> Result<Vec<RegisteredUser>, RepositoryError> {
+        let docs: Vec<Document> = self
+            .collection
+            .find(query.filter())
+            .projection(doc! {"_id": 1, "devices": 1, "user.userid": 1, "sync": 1})
+            .sort(doc! {"_id": 1})
+            .skip(query.skip())
+            .limit(USERS_PAGE_SIZE as i64)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(docs
+            .iter()
+            .filter_map(registered_user_from_document)
+            .collect())
+    }
+}
+
+fn registered_user_from_document(doc: &Document) -> Option<RegisteredUser> {
+    let token = token_from_document(doc)?;
+    let sync: Option<SyncStatus> = doc
+        .get("sync")
+        .and_then(|sync| from_bson(sync.clone()).ok());
+    Some(RegisteredUser {
+        has_device_token: !token.devices.is_empty(),
+        platform: token.devices.first().and_then(|device| device.platform),
+        userid: doc
+            .get_document("user")
+            .ok()
+            .and_then(|user| user.get_i64("userid").ok()),
+        last_sync_at: sync.as_ref().map(|sync| sync.at.timestamp_millis() / 1000),
+        sync_error: sync.and_then(|sync| sync.error),
+        token: token.token,
+    })
 }
 
 #[async_trait]
@@ -127,6 +447,39 @@ impl CourseRepositoryInterface for DataRepository {
             Err(RepositoryError::DataNotFound("Courses".to_string()))
         }
     }
+
+    async fn find_course_contents(
+        &self,
+        token: &str,
+    ) -> Result<Vec<CourseContent>, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": token}).await?;
+        if let Some(doc) = doc {
+            match doc.get_array("course_contents").ok() {
+                Some(contents) => Ok(from_bson(Bson::Array(contents.clone()))?),
+                None => Ok(Vec::new()),
+            }
+        } else {
+            Err(RepositoryError::DataNotFound("User".to_string()))
+        }
+    }
+
+    async fn save_course_contents(
+        &self,
+        token: &str,
+        contents: &[CourseContent],
+    ) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {"$set": {"course_contents": to_bson(contents)?}},
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -163,6 +516,16 @@ impl GradeRepositoryInterface for DataRepository {
         }
     }
 
+    async fn find_grades_page(
+        &self,
+        token: &str,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Page<Grade>, RepositoryError> {
+        self.find_array_page(token, "grades", Bson::from("$grades"), skip, limit)
+            .await
+    }
+
     async fn save_grades_overview(
         &self,
         token: &str,
@@ -201,6 +564,41 @@ impl GradeRepositoryInterface for DataRepository {
             Err(RepositoryError::DataNotFound("Grades".to_string()))
         }
     }
+
+    async fn find_grade_marks_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Vec<GradeMark>, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": token}).await?;
+
+        if let Some(doc) = doc {
+            if let Some(Bson::Array(marks_array)) = doc.get("grade_marks") {
+                let bson = Bson::from(marks_array);
+                Ok(from_bson::<Vec<GradeMark>>(bson)?)
+            } else {
+                Ok(Vec::new())
+            }
+        } else {
+            Err(RepositoryError::DataNotFound("User".to_string()))
+        }
+    }
+
+    async fn save_grade_marks(
+        &self,
+        token: &str,
+        marks: &[GradeMark],
+    ) -> Result<(), RepositoryError> {
+        let marks_doc = to_bson(marks)?;
+        self.collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {
+                    "$set": {"grade_marks": marks_doc}
+                },
+            )
+            .await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -215,7 +613,10 @@ impl DeadlineRepositoryInterface for DataRepository {
             .update_one(
                 doc! {"_id": token},
                 doc! {
-                    "$set": {"deadlines": deadlines_doc}
+                    "$set": {
+                        "deadlines": deadlines_doc,
+                        "deadlines_updated_at": mongodb::bson::DateTime::now()
+                    }
                 },
             )
             .await?;
@@ -239,4 +640,239 @@ impl DeadlineRepositoryInterface for DataRepository {
             Err(RepositoryError::DataNotFound("Deadlines".to_string()))
         }
     }
+
+    async fn find_deadlines_page(
+        &self,
+        token: &str,
+        due: Option<(i64, i64)>,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Page<Deadline>, RepositoryError> {
+        let deadlines = match due {
+            Some((from, to)) => Bson::from(doc! {"$filter": {
+                "input": "$deadlines",
+                "cond": {"$and": [
+                    {"$gte": ["$$this.timeusermidnight", from]},
+                    {"$lte": ["$$this.timeusermidnight", to]},
+                ]},
+            }}),
+            None => Bson::from("$deadlines"),
+        };
+        self.find_array_page(token, "deadlines", deadlines, skip, limit)
+            .await
+    }
+
+    async fn find_deadlines_updated_at(
+        &self,
+        token: &str,
+    ) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": token}).await?;
+        Ok(doc
+            .as_ref()
+            .and_then(|doc| doc.get_datetime("deadlines_updated_at").ok())
+            .and_then(|updated_at| DateTime::from_timestamp_millis(updated_at.timestamp_millis())))
+    }
+
+    async fn find_snoozes(&self, token: &str) -> Result<Vec<DeadlineSnooze>, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": token}).await?;
+        if let Some(doc) = doc {
+            match doc.get_array("snoozes").ok() {
+                Some(snoozes) => Ok(from_bson(Bson::Array(snoozes.clone()))?),
+                None => Ok(Vec::new()),
+            }
+        } else {
+            Err(RepositoryError::DataNotFound("User".to_string()))
+        }
+    }
+
+    async fn save_snoozes(
+        &self,
+        token: &str,
+        snoozes: &[DeadlineSnooze],
+    ) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {"$set": {"snoozes": to_bson(snoozes)?}},
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn find_sent_reminders(&self, token: &str) -> Result<Vec<SentReminder>, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": token}).await?;
+        if let Some(doc) = doc {
+            match doc.get_array("sent_reminders").ok() {
+                Some(sent) => Ok(from_bson(Bson::Array(sent.clone()))?),
+                None => Ok(Vec::new()),
+            }
+        } else {
+            Err(RepositoryError::DataNotFound("User".to_string()))
+        }
+    }
+
+    async fn save_sent_reminders(
+        &self,
+        token: &str,
+        sent: &[SentReminder],
+    ) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {"$set": {"sent_reminders": to_bson(sent)?}},
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PreferencesRepositoryInterface for DataRepository {
+    async fn find_preferences_by_token(&self, token: &str) -> Result<Preferences, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": token}).await?;
+        if let Some(doc) = doc {
+            match doc.get_document("preferences").ok() {
+                Some(doc) => {
+                    let preferences: Preferences = bson::from_document(doc.clone())?;
+                    Ok(preferences)
+                }
+                None => Ok(Preferences::default()),
+            }
+        } else {
+            Err(RepositoryError::DataNotFound("User".to_string()))
+        }
+    }
+
+    async fn save_preferences(
+        &self,
+        token: &str,
+        preferences: &Preferences,
+    ) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {
+                    "$set": {"preferences": to_bson(preferences)?}
+                },
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn find_settings_by_token(&self, token: &str) -> Result<UserSettings, RepositoryError> {
+        let doc = self
+            .collection
+            .find_one(doc! {"_id": token})
+            .await?
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        match doc.get_document("settings") {
+            Ok(settings) => Ok(bson::from_document(settings.clone())?),
+            Err(_) => Ok(UserSettings::default()),
+        }
+    }
+
+    async fn save_settings(
+        &self,
+        token: &str,
+        settings: &UserSettings,
+    ) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {"$set": {"settings": to_bson(settings)?}},
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn push_queued_notification(
+        &self,
+        token: &str,
+        notification: &Notification,
+    ) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {"$push": {"queued_notifications": to_bson(notification)?}},
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn take_queued_notifications(
+        &self,
+        token: &str,
+    ) -> Result<Vec<Notification>, RepositoryError> {
+        // Only users with something queued are written to
+        let doc = self
+            .collection
+            .find_one_and_update(
+                doc! {"_id": token, "queued_notifications.0": {"$exists": true}},
+                doc! {"$unset": {"queued_notifications": ""}},
+            )
+            .projection(doc! {"queued_notifications": 1})
+            .await?;
+        match doc.as_ref().and_then(|doc| doc.get("queued_notifications")) {
+            Some(queued) => Ok(from_bson(queued.clone())?),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl UnreadRepositoryInterface for DataRepository {
+    async fn find_unread_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Vec<UnreadCourse>, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": token}).await?;
+        if let Some(doc) = doc {
+            match doc.get_array("unread").ok() {
+                Some(unread) => Ok(from_bson(Bson::Array(unread.clone()))?),
+                None => Ok(Vec::new()),
+            }
+        } else {
+            Err(RepositoryError::DataNotFound("User".to_string()))
+        }
+    }
+
+    async fn save_unread(
+        &self,
+        token: &str,
+        unread: &[UnreadCourse],
+    ) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": token},
+                doc! {
+                    "$set": {"unread": to_bson(unread)?}
+                },
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        Ok(())
+    }
 }
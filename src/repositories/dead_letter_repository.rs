@@ -0,0 +1,67 @@
+use crate::models::dead_letter::DeadLetter;
+use crate::services::producer_service::DeadLetterRepositoryInterface;
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_document, Document};
+use mongodb::{Collection, IndexModel};
+
+use super::errors::RepositoryError;
+
+pub struct DeadLetterRepository {
+    collection: Collection<Document>,
+}
+
+impl DeadLetterRepository {
+    pub fn new(collection: Collection<Document>) -> Self {
+        Self { collection }
+    }
+
+    pub async fn create_indexes(&self) -> Result<(), RepositoryError> {
+        let oldest = IndexModel::builder().keys(doc! {"failed_at": 1}).build();
+        self.collection.create_index(oldest).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DeadLetterRepositoryInterface for DeadLetterRepository {
+    async fn save_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), RepositoryError> {
+        self.collection
+            .insert_one(to_document(dead_letter)?)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_oldest(&self, limit: i64) -> Result<Vec<DeadLetter>, RepositoryError> {
+        let docs: Vec<Document> = self
+            .collection
+            .find(doc! {})
+            .sort(doc! {"failed_at": 1})
+            .limit(limit)
+            .await?
+            .try_collect()
+            .await?;
+        docs.into_iter()
+            .map(|doc| from_document(doc).map_err(Into::into))
+            .collect()
+    }
+
+    async fn delete_dead_letter(&self, id: ObjectId) -> Result<(), RepositoryError> {
+        self.collection.delete_one(doc! {"_id": id}).await?;
+        Ok(())
+    }
+
+    async fn record_redrive_failure(
+        &self,
+        id: ObjectId,
+        error: &str,
+    ) -> Result<(), RepositoryError> {
+        self.collection
+            .update_one(
+                doc! {"_id": id},
+                doc! {"$set": {"error": error}, "$inc": {"redrives": 1}},
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use crate::models::preferences::ExamPeriod;
+use crate::services::producer_service::ExamPeriodRepositoryInterface;
+use async_trait::async_trait;
+use mongodb::bson::{doc, from_document, to_document, Document};
+use mongodb::Collection;
+
+use super::errors::RepositoryError;
+
+/// The one exam period admins set for everyone is kept under this id.
+const GLOBAL_ID: &str = "global";
+
+pub struct ExamPeriodRepository {
+    collection: Collection<Document>,
+}
+
+impl ExamPeriodRepository {
+    pub fn new(collection: Collection<Document>) -> Self {
+        Self { collection }
+    }
+}
+
+#[async_trait]
+impl ExamPeriodRepositoryInterface for ExamPeriodRepository {
+    async fn find_global(&self) -> Result<Option<ExamPeriod>, RepositoryError> {
+        let doc = self.collection.find_one(doc! {"_id": GLOBAL_ID}).await?;
+        Ok(doc.map(from_document).transpose()?)
+    }
+
+    async fn save_global(&self, period: Option<&ExamPeriod>) -> Result<(), RepositoryError> {
+        match period {
+            Some(period) => {
+                self.collection
+                    .replace_one(doc! {"_id": GLOBAL_ID}, to_document(period)?)
+                    .upsert(true)
+                    .await?;
+            }
+            None => {
+                self.collection.delete_one(doc! {"_id": GLOBAL_ID}).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use crate::services::retention_service::HistoryArchiveRepositoryInterface;
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, DateTime, Document};
+use mongodb::error::{ErrorKind, InsertManyError};
+use mongodb::options::IndexOptions;
+use mongodb::{Collection, IndexModel};
+use std::time::Duration;
+
+use super::errors::RepositoryError;
+
+/// Duplicate key: the entry was archived by a run that stopped before deleting it.
+const DUPLICATE_KEY: i32 = 11000;
+
+pub struct HistoryArchiveRepository {
+    history: Collection<Document>,
+    archive: Collection<Document>,
+}
+
+impl HistoryArchiveRepository {
+    pub fn new(history: Collection<Document>, archive: Collection<Document>) -> Self {
+        Self { history, archive }
+    }
+
+    pub async fn create_indexes(&self, retention_days: u64) -> Result<(), RepositoryError> {
+        let expiry = IndexModel::builder()
+            .keys(doc! {"archived_at": 1})
+            .options(
+                IndexOptions::builder()
+                    .expire_after(Duration::from_secs(retention_days * 24 * 60 * 60))
+                    .build(),
+            )
+            .build();
+        self.archive.create_index(expiry).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HistoryArchiveRepositoryInterface for HistoryArchiveRepository {
+    async fn archive_before(&self, before: DateTime, limit: i64) -> Result<u64, RepositoryError> {
+        let mut docs: Vec<Document> = self
+            .history
+            .find(doc! {"sent_at": {"$lt": before}})
+            .sort(doc! {"sent_at": 1})
+            .limit(limit)
+            .await?
+            .try_collect()
+            .await?;
+        if docs.is_empty() {
+            return Ok(0);
+        }
+        let archived_at = DateTime::now();
+        for doc in &mut docs {
+            doc.insert("archived_at", archived_at);
+        }
+        let ids: Vec<_> = docs
+            .iter()
+            .filter_map(|doc| doc.get("_id").cloned())
+            .collect();
+
+        match self.archive.insert_many(&docs).ordered(false).await {
+            Ok(_) => {}
+            Err(e) => match *e.kind {
+                ErrorKind::InsertMany(InsertManyError {
+                    write_errors: Some(ref errors),
+                    write_concern_error: None,
+                    ..
+                }) if errors.iter().all(|error| error.code == DUPLICATE_KEY) => {}
+                _ => return Err(e.into()),
+            },
+        }
+        let deleted = self.history.delete_many(doc! {"_id": {"$in": ids}}).await?;
+        Ok(deleted.deleted_count)
+    }
+}
@@ -0,0 +1,166 @@
+use crate::models::history::{Delivery, DeliveryStatus, HistoryEntry};
+use crate::services::history_service::HistoryRepositoryInterface;
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_bson, to_document, DateTime, Document};
+use mongodb::{Collection, IndexModel};
+
+use super::errors::RepositoryError;
+
+pub struct HistoryRepository {
+    collection: Collection<Document>,
+}
+
+impl HistoryRepository {
+    pub fn new(collection: Collection<Document>) -> Self {
+        Self { collection }
+    }
+
+    pub async fn create_indexes(&self) -> Result<(), RepositoryError> {
+        let lookup = IndexModel::builder()
+            .keys(doc! {"token": 1, "key": 1, "sent_at": -1})
+            .build();
+        let inbox = IndexModel::builder()
+            .keys(doc! {"token": 1, "sent_at": -1})
+            .build();
+        self.collection.create_indexes([lookup, inbox]).await?;
+
+        // Old entries are archived before they are deleted; the TTL index of
+        // earlier versions would delete them first
+        let indexes: Vec<IndexModel> = self.collection.list_indexes().await?.try_collect().await?;
+        for options in indexes.into_iter().filter_map(|index| index.options) {
+            if let (Some(_), Some(name)) = (options.expire_after, options.name) {
+                self.collection.drop_index(name).await?;
+            }
+        }
+        let age = IndexModel::builder().keys(doc! {"sent_at": 1}).build();
+        self.collection.create_index(age).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HistoryRepositoryInterface for HistoryRepository {
+    async fn save_entry(&self, entry: &HistoryEntry) -> Result<(), RepositoryError> {
+        self.collection.insert_one(to_document(entry)?).await?;
+        Ok(())
+    }
+
+    async fn update_delivery(
+        &self,
+        token: &str,
+        key: &str,
+        from: DeliveryStatus,
+        delivery: &Delivery,
+    ) -> Result<(), RepositoryError> {
+        self.collection
+            .find_one_and_update(
+                doc! {"token": token, "key": key, "delivery": to_bson(&from)?},
+                doc! {"$set": {
+                    "delivery": to_bson(&delivery.status)?,
+                    "provider_ids": delivery.provider_ids.clone(),
+                    "error": delivery.error.clone(),
+                    "updated_at": DateTime::now(),
+                }},
+            )
+            .sort(doc! {"sent_at": -1})
+            .await?;
+        Ok(())
+    }
+
+    async fn find_entry(&self, id: ObjectId) -> Result<Option<HistoryEntry>, RepositoryError> {
+        match self.collection.find_one(doc! {"_id": id}).await? {
+            Some(doc) => Ok(Some(from_document(doc)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn exists_since(
+        &self,
+        token: &str,
+        key: &str,
+        since: DateTime,
+    ) -> Result<bool, RepositoryError> {
+        // A failed delivery doesn't block sending the change again
+        let entry = self
+            .collection
+            .find_one(doc! {
+                "token": token,
+                "key": key,
+                "sent_at": {"$gte": since},
+                "delivery": {"$ne": "failed"},
+            })
+            .await?;
+        Ok(entry.is_some())
+    }
+
+    async fn find_entries(
+        &self,
+        token: &str,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<Vec<HistoryEntry>, RepositoryError> {
+        let docs: Vec<Document> = self
+            .collection
+            .find(doc! {"token": token, "sent_at": {"$gte": from, "$lte": to}})
+            .sort(doc! {"sent_at": 1})
+            .await?
+            .try_collect()
+            .await?;
+        docs.into_iter()
+            .map(|doc| from_document(doc).map_err(Into::into))
+            .collect()
+    }
+
+    async fn find_delivered(
+        &self,
+        token: &str,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Vec<HistoryEntry>, RepositoryError> {
+        let docs: Vec<Document> = self
+            .collection
+            .find(doc! {"token": token, "delivery": {"$ne": "failed"}})
+            .sort(doc! {"sent_at": -1})
+            .skip(skip)
+            .limit(limit as i64)
+            .await?
+            .try_collect()
+            .await?;
+        docs.into_iter()
+            .map(|doc| from_document(doc).map_err(Into::into))
+            .collect()
+    }
+
+    async fn count_delivered(&self, token: &str) -> Result<u64, RepositoryError> {
+        Ok(self
+            .collection
+            .count_documents(doc! {"token": token, "delivery": {"$ne": "failed"}})
+            .await?)
+    }
+
+    async fn mark_read(&self, token: &str, id: ObjectId) -> Result<(), RepositoryError> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": id, "token": token},
+                doc! {"$set": {"read": true}},
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(RepositoryError::DataNotFound("Notification".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn count_unread(&self, token: &str) -> Result<u64, RepositoryError> {
+        Ok(self
+            .collection
+            .count_documents(doc! {
+                "token": token,
+                "delivery": {"$ne": "failed"},
+                "read": {"$ne": true},
+            })
+            .await?)
+    }
+}
@@ -1,2 +1,9 @@
 pub mod data_repository;
+pub mod dead_letter_repository;
 pub mod errors;
+pub mod exam_period_repository;
+pub mod history_archive_repository;
+pub mod history_repository;
+pub mod reminder_repository;
+pub mod stats_repository;
+pub mod token_change_stream;
@@ -0,0 +1,53 @@
+use crate::models::reminder::ReminderEntry;
+use crate::services::reminder_service::ReminderRepositoryInterface;
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_document, Document};
+use mongodb::{Collection, IndexModel};
+
+use super::errors::RepositoryError;
+
+pub struct ReminderRepository {
+    collection: Collection<Document>,
+}
+
+impl ReminderRepository {
+    pub fn new(collection: Collection<Document>) -> Self {
+        Self { collection }
+    }
+
+    pub async fn create_indexes(&self) -> Result<(), RepositoryError> {
+        let token = IndexModel::builder().keys(doc! {"token": 1}).build();
+        self.collection.create_index(token).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ReminderRepositoryInterface for ReminderRepository {
+    async fn save_reminder(&self, reminder: &ReminderEntry) -> Result<ObjectId, RepositoryError> {
+        let result = self.collection.insert_one(to_document(reminder)?).await?;
+        result
+            .inserted_id
+            .as_object_id()
+            .ok_or_else(|| RepositoryError::DataNotFound("Reminder".to_string()))
+    }
+
+    async fn find_pending(&self, token: &str) -> Result<Vec<ReminderEntry>, RepositoryError> {
+        let docs: Vec<Document> = self
+            .collection
+            .find(doc! {"token": token})
+            .sort(doc! {"remind_at": 1})
+            .await?
+            .try_collect()
+            .await?;
+        docs.into_iter()
+            .map(|doc| from_document(doc).map_err(Into::into))
+            .collect()
+    }
+
+    async fn delete_reminder(&self, id: ObjectId) -> Result<(), RepositoryError> {
+        self.collection.delete_one(doc! {"_id": id}).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use crate::models::cohort::Cohort;
+use crate::models::notification::NotificationKind;
+use crate::models::stats::{CycleReport, DailyNotificationCount, UserStats};
+use crate::services::stats_service::StatsRepositoryInterface;
+use async_trait::async_trait;
+use chrono::Utc;
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, from_document, to_document, DateTime, Document};
+use mongodb::options::IndexOptions;
+use mongodb::{Collection, IndexModel};
+use std::time::Duration;
+
+use super::errors::RepositoryError;
+
+pub struct StatsRepository {
+    users: Collection<Document>,
+    notification_stats: Collection<Document>,
+    cycle_reports: Collection<Document>,
+}
+
+impl StatsRepository {
+    pub fn new(
+        users: Collection<Document>,
+        notification_stats: Collection<Document>,
+        cycle_reports: Collection<Document>,
+    ) -> Self {
+        Self {
+            users,
+            notification_stats,
+            cycle_reports,
+        }
+    }
+
+    pub async fn create_indexes(&self, retention_days: u64) -> Result<(), RepositoryError> {
+        let expire_after = Duration::from_secs(retention_days * 24 * 60 * 60);
+        for (collection, field) in [
+            (&self.cycle_reports, "finished_at"),
+            (&self.notification_stats, "updated_at"),
+        ] {
+            let index = IndexModel::builder()
+                .keys(doc! { field: 1 })
+                .options(IndexOptions::builder().expire_after(expire_after).build())
+                .build();
+            collection.create_index(index).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StatsRepositoryInterface for StatsRepository {
+    async fn stats(&self) -> Result<UserStats, RepositoryError> {
+        let total = self.users.count_documents(doc! {}).await?;
+        let with_device = self
+            .users
+            .count_documents(doc! {"devices.0": {"$exists": true}})
+            .await?;
+        let failing_sync = self
+            .users
+            .count_documents(doc! {"sync.error": {"$type": "string"}})
+            .await?;
+        Ok(UserStats {
+            total,
+            with_device,
+            failing_sync,
+        })
+    }
+
+    async fn increment_notification_count(
+        &self,
+        day: &str,
+        kind: NotificationKind,
+        cohort: Cohort,
+    ) -> Result<(), RepositoryError> {
+        self.notification_stats
+            .update_one(
+                doc! {"_id": format!("{}:{}:{}", day, kind.as_str(), cohort.as_str())},
+                doc! {
+                    "$set": {
+                        "day": day,
+                        "kind": kind.as_str(),
+                        "cohort": cohort.as_str(),
+                        "updated_at": DateTime::now(),
+                    },
+                    "$inc": {
+                        "count": 1_i64,
+                        format!("hours.{}", Utc::now().format("%H")): 1_i64,
+                    }
+                },
+            )
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_notification_counts(
+        &self,
+        since_day: &str,
+    ) -> Result<Vec<DailyNotificationCount>, RepositoryError> {
+        let mut cursor = self
+            .notification_stats
+            .find(doc! {"day": {"$gte": since_day}})
+            .sort(doc! {"day": 1, "kind": 1, "cohort": 1})
+            .await?;
+
+        let mut counts = Vec::new();
+        while let Some(doc) = cursor.try_next().await? {
+            counts.push(from_document::<DailyNotificationCount>(doc)?);
+        }
+        Ok(counts)
+    }
+
+    async fn save_cycle_report(&self, report: &CycleReport) -> Result<(), RepositoryError> {
+        self.cycle_reports.insert_one(to_document(report)?).await?;
+        Ok(())
+    }
+
+    async fn find_cycle_reports(
+        &self,
+        since: DateTime,
+    ) -> Result<Vec<CycleReport>, RepositoryError> {
+        let mut cursor = self
+            .cycle_reports
+            .find(doc! {"finished_at": {"$gte": since}})
+            .await?;
+
+        let mut reports = Vec::new();
+        while let Some(doc) = cursor.try_next().await? {
+            reports.push(from_document::<CycleReport>(doc)?);
+        }
+        Ok(reports)
+    }
+}
@@ -0,0 +1,55 @@
+use crate::models::token::Token;
+use crate::services::change_listener::DeviceTokenChangeSource;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::change_stream::event::ChangeStreamEvent;
+use mongodb::change_stream::ChangeStream;
+use mongodb::options::FullDocumentType;
+use mongodb::Collection;
+
+use super::data_repository::token_from_document;
+use super::errors::RepositoryError;
+
+/// Watches the users collection for devices being registered. Needs a replica set.
+pub struct TokenChangeStream {
+    stream: ChangeStream<ChangeStreamEvent<Document>>,
+}
+
+impl TokenChangeStream {
+    pub async fn watch(collection: &Collection<Document>) -> Result<Self, RepositoryError> {
+        let device_token_set = doc! {
+            "$match": {
+                "$or": [
+                    {
+                        "operationType": {"$in": ["insert", "replace"]},
+                        "fullDocument.devices.0": {"$exists": true},
+                    },
+                    {
+                        "operationType": "update",
+                        "updateDescription.updatedFields.devices.0": {"$exists": true},
+                    },
+                ]
+            }
+        };
+        let stream = collection
+            .watch()
+            .pipeline([device_token_set])
+            .full_document(FullDocumentType::UpdateLookup)
+            .await?;
+        Ok(Self { stream })
+    }
+}
+
+#[async_trait]
+impl DeviceTokenChangeSource for TokenChangeStream {
+    async fn next_change(&mut self) -> Result<Option<Token>, RepositoryError> {
+        while let Some(event) = self.stream.next().await {
+            // The user may be deleted before the lookup; skip such events
+            if let Some(token) = event?.full_document.as_ref().and_then(token_from_document) {
+                return Ok(Some(token));
+            }
+        }
+        Ok(None)
+    }
+}
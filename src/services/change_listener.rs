@@ -0,0 +1,98 @@
+use crate::models::token::Token;
+use crate::repositories::errors::RepositoryError;
+use crate::services::producer_service_interfaces::ProducerServiceInterface;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+#[async_trait]
+pub trait DeviceTokenChangeSource: Send {
+    /// Next user whose device token was set; `None` once the source is closed.
+    async fn next_change(&mut self) -> Result<Option<Token>, RepositoryError>;
+}
+
+/// Processes users as soon as their device token is set instead of waiting
+/// for their batch. Stops on a source error; the batch loop still covers them.
+pub async fn listen_device_token_changes(
+    mut source: Box<dyn DeviceTokenChangeSource>,
+    producer_service: Arc<dyn ProducerServiceInterface>,
+) {
+    loop {
+        let tokens = match source.next_change().await {
+            Ok(Some(tokens)) => tokens,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Device token change stream stopped: {}", e);
+                break;
+            }
+        };
+        if tokens.devices.is_empty() {
+            continue;
+        }
+        if let Err(e) = producer_service
+            .process_producing(&tokens.token, &tokens.devices)
+            .await
+        {
+            eprintln!("Error processing device token change: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::feature_flags::FeatureFlags;
+    use crate::models::registration::RegistrationSettings;
+    use crate::services::data_service::DataService;
+    use crate::services::mocks::{
+        device, MockEventProducer, MockHistoryService, MockProvider, MockRepository,
+        MockStatsService,
+    };
+    use crate::services::producer_service::ProducerService;
+    use crate::services::retry_budget::RetryBudget;
+    use std::collections::VecDeque;
+
+    struct MockChangeSource {
+        changes: VecDeque<Token>,
+    }
+
+    #[async_trait]
+    impl DeviceTokenChangeSource for MockChangeSource {
+        async fn next_change(&mut self) -> Result<Option<Token>, RepositoryError> {
+            Ok(self.changes.pop_front())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_device_token_set_triggers_processing() {
+        let provider = MockProvider::default();
+        let repository = MockRepository::with_tokens(&["token", "other"]);
+        let data_service = DataService::new(
+            Arc::new(provider.clone()),
+            Box::new(repository.clone()),
+            RegistrationSettings::default(),
+            FeatureFlags::default(),
+        );
+        let producer_service = ProducerService::new(
+            Box::new(MockEventProducer::default()),
+            Arc::new(provider.clone()),
+            Arc::new(data_service),
+            Arc::new(MockStatsService::default()),
+            Arc::new(MockHistoryService::default()),
+            FeatureFlags::default(),
+            Arc::new(RetryBudget::new(0)),
+        );
+        let source = MockChangeSource {
+            changes: VecDeque::from([
+                Token {
+                    devices: vec![device("device", None)],
+                    ..Token::new("token".to_string(), None)
+                },
+                Token::new("other".to_string(), None),
+            ]),
+        };
+
+        listen_device_token_changes(Box::new(source), Arc::new(producer_service)).await;
+
+        assert_eq!(provider.calls_to("get_user"), 1);
+    }
+}
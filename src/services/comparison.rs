@@ -0,0 +1,44 @@
+use crate::models::cohort::ComparisonKind;
+use crate::models::deadline::{compare_deadlines, Deadline};
+use crate::models::grade::{compare_grades, Grade, GradeItems};
+
+/// Decides which fetched items are new to the user; swapped per cohort to
+/// roll out comparison changes gradually.
+pub trait ComparisonStrategy: Send + Sync {
+    fn compare_grades<'a>(
+        &self,
+        external_grades: &'a mut [Grade],
+        grades: &'a mut [Grade],
+    ) -> Vec<(&'a GradeItems, &'a GradeItems)>;
+    fn compare_deadlines<'a>(
+        &self,
+        external_deadlines: &'a [Deadline],
+        deadlines: &[Deadline],
+    ) -> Vec<&'a Deadline>;
+}
+
+pub struct StableComparison;
+
+pub fn comparison_strategy(kind: ComparisonKind) -> Box<dyn ComparisonStrategy> {
+    match kind {
+        ComparisonKind::Stable => Box::new(StableComparison),
+    }
+}
+
+impl ComparisonStrategy for StableComparison {
+    fn compare_grades<'a>(
+        &self,
+        external_grades: &'a mut [Grade],
+        grades: &'a mut [Grade],
+    ) -> Vec<(&'a GradeItems, &'a GradeItems)> {
+        compare_grades(external_grades, grades)
+    }
+
+    fn compare_deadlines<'a>(
+        &self,
+        external_deadlines: &'a [Deadline],
+        deadlines: &[Deadline],
+    ) -> Vec<&'a Deadline> {
+        compare_deadlines(external_deadlines, deadlines)
+    }
+}
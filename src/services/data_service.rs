@@ -1,18 +1,49 @@
-use crate::models::course::Course;
-use crate::models::deadline::{sort_deadlines, Deadline};
-use crate::models::grade::{sort_grades_overview, Grade, GradeOverview, GradesOverview};
-use crate::models::token::Token;
+use crate::models::broadcast::BroadcastSegment;
+use crate::models::calendar::CalendarFeed;
+use crate::models::course::{
+    search_courses, sort_courses, with_removed_courses, Course, CourseSearchQuery,
+};
+use crate::models::course_content::CourseContent;
+use crate::models::dashboard::{
+    dashboard_deadlines_query, recent_grades, Dashboard, RECENT_GRADES,
+};
+use crate::models::deadline::{filter_deadlines, sort_deadlines, Deadline, DeadlineQuery};
+use crate::models::feature_flags::FeatureFlags;
+use crate::models::grade::{
+    keep_stored_grades, sort_grades_overview, CourseGradeItem, Grade, GradeMark, GradeOverview,
+    GradesOverview,
+};
+use crate::models::notification::{Notification, NotificationKind};
+use crate::models::pagination::{Page, PageQuery};
+use crate::models::preferences::{NotificationPause, Preferences};
+use crate::models::registration::{
+    BackfillResource, RegistrationOutcome, RegistrationReport, RegistrationSettings,
+    RegistrationStatus, RegistrationVerification,
+};
+use crate::models::reminder::{add_snooze, DeadlineSnooze, SentReminder, SnoozeRequest};
+use crate::models::settings::UserSettings;
+use crate::models::sync::SyncedData;
+use crate::models::token::{
+    replacing_device, Device, DeviceTokenPolicy, DeviceTokenUpdate, Platform, Token,
+};
+use crate::models::unread::{ack_unread, add_unread, UnreadCourse};
 use crate::models::user::User;
+use crate::models::user_list::{RegisteredUser, SyncStatus, UserListQuery};
 use crate::repositories::errors::RepositoryError;
+use crate::services::data_service_interfaces::CalendarServiceInterface;
 use crate::services::data_service_interfaces::CourseServiceInterface;
 use crate::services::data_service_interfaces::DeadlineServiceInterface;
 use crate::services::data_service_interfaces::GradeServiceInterface;
+use crate::services::data_service_interfaces::PreferencesServiceInterface;
 use crate::services::data_service_interfaces::TokenServiceInterface;
+use crate::services::data_service_interfaces::UnreadServiceInterface;
 use crate::services::data_service_interfaces::UserServiceInterface;
 use crate::services::provider_interfaces::DataProviderInterface;
 use async_trait::async_trait;
-use mongodb::bson::Document;
-use mongodb::Cursor;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use rand::Rng;
 use std::result::Result::Ok;
 use std::sync::Arc;
 
@@ -26,6 +57,8 @@ pub trait RepositoryInterfaces:
     + CourseRepositoryInterface
     + DeadlineRepositoryInterface
     + GradeRepositoryInterface
+    + PreferencesRepositoryInterface
+    + UnreadRepositoryInterface
     + Send
     + Sync
 {
@@ -39,8 +72,52 @@ pub trait TokenRepositoryInterface {
         &self,
         limit: i64,
         skip: u64,
-    ) -> Result<Cursor<Document>, RepositoryError>;
+    ) -> Result<Vec<Token>, RepositoryError>;
     async fn delete(&self, token: &str) -> Result<(), RepositoryError>;
+    async fn find_calendar_secret(&self, token: &str) -> Result<Option<String>, RepositoryError>;
+    async fn save_calendar_secret(&self, token: &str, secret: &str) -> Result<(), RepositoryError>;
+    async fn find_token_by_calendar_secret(&self, secret: &str) -> Result<String, RepositoryError>;
+    async fn find_tokens_by_device_token(
+        &self,
+        device_token: &str,
+    ) -> Result<Vec<String>, RepositoryError>;
+    async fn find_devices(&self, token: &str) -> Result<Vec<Device>, RepositoryError>;
+    /// Removes the user's devices with any of the given tokens.
+    async fn pull_devices(
+        &self,
+        token: &str,
+        device_tokens: &[&str],
+    ) -> Result<(), RepositoryError>;
+    /// Appends the device, keeping only the `MAX_DEVICES` most recent.
+    async fn push_device(&self, token: &str, device: &Device) -> Result<(), RepositoryError>;
+    async fn find_tokens_by_user_id(&self, userid: i64) -> Result<Vec<Token>, RepositoryError>;
+    /// Users in the segment that have a device token, ordered by token and
+    /// starting after the token `after`.
+    async fn find_broadcast_recipients(
+        &self,
+        segment: &BroadcastSegment,
+        after: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<Token>, RepositoryError>;
+    async fn save_sync_status(
+        &self,
+        token: &str,
+        status: &SyncStatus,
+    ) -> Result<(), RepositoryError>;
+    /// One page of users matching the query, ordered by token.
+    async fn find_registered_users(
+        &self,
+        query: &UserListQuery,
+    ) -> Result<Vec<RegisteredUser>, RepositoryError>;
+    async fn find_pending_backfill(
+        &self,
+        token: &str,
+    ) -> Result<Vec<BackfillResource>, RepositoryError>;
+    async fn save_pending_backfill(
+        &self,
+        token: &str,
+        pending: &[BackfillResource],
+    ) -> Result<(), RepositoryError>;
 }
 
 #[async_trait]
@@ -53,6 +130,15 @@ pub trait UserRepositoryInterface {
 pub trait CourseRepositoryInterface {
     async fn save_courses(&self, token: &str, courses: &[Course]) -> Result<(), RepositoryError>;
     async fn find_courses_by_token(&self, token: &str) -> Result<Vec<Course>, RepositoryError>;
+    async fn find_course_contents(
+        &self,
+        token: &str,
+    ) -> Result<Vec<CourseContent>, RepositoryError>;
+    async fn save_course_contents(
+        &self,
+        token: &str,
+        contents: &[CourseContent],
+    ) -> Result<(), RepositoryError>;
 }
 
 #[async_trait]
@@ -63,12 +149,42 @@ pub trait DeadlineRepositoryInterface {
         deadlines: &[Deadline],
     ) -> Result<(), RepositoryError>;
     async fn find_deadlines_by_token(&self, token: &str) -> Result<Vec<Deadline>, RepositoryError>;
+    /// A slice of the stored deadlines, limited to those due within `due` when given.
+    async fn find_deadlines_page(
+        &self,
+        token: &str,
+        due: Option<(i64, i64)>,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Page<Deadline>, RepositoryError>;
+    async fn find_deadlines_updated_at(
+        &self,
+        token: &str,
+    ) -> Result<Option<DateTime<Utc>>, RepositoryError>;
+    async fn find_snoozes(&self, token: &str) -> Result<Vec<DeadlineSnooze>, RepositoryError>;
+    async fn save_snoozes(
+        &self,
+        token: &str,
+        snoozes: &[DeadlineSnooze],
+    ) -> Result<(), RepositoryError>;
+    async fn find_sent_reminders(&self, token: &str) -> Result<Vec<SentReminder>, RepositoryError>;
+    async fn save_sent_reminders(
+        &self,
+        token: &str,
+        sent: &[SentReminder],
+    ) -> Result<(), RepositoryError>;
 }
 
 #[async_trait]
 pub trait GradeRepositoryInterface {
     async fn save_grades(&self, token: &str, grades: &[Grade]) -> Result<(), RepositoryError>;
     async fn find_grades_by_token(&self, token: &str) -> Result<Vec<Grade>, RepositoryError>;
+    async fn find_grades_page(
+        &self,
+        token: &str,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Page<Grade>, RepositoryError>;
     async fn save_grades_overview(
         &self,
         token: &str,
@@ -78,23 +194,231 @@ pub trait GradeRepositoryInterface {
         &self,
         token: &str,
     ) -> Result<Vec<GradeOverview>, RepositoryError>;
+    async fn find_grade_marks_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Vec<GradeMark>, RepositoryError>;
+    async fn save_grade_marks(
+        &self,
+        token: &str,
+        marks: &[GradeMark],
+    ) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+pub trait PreferencesRepositoryInterface {
+    async fn find_preferences_by_token(&self, token: &str) -> Result<Preferences, RepositoryError>;
+    async fn save_preferences(
+        &self,
+        token: &str,
+        preferences: &Preferences,
+    ) -> Result<(), RepositoryError>;
+    async fn find_settings_by_token(&self, token: &str) -> Result<UserSettings, RepositoryError>;
+    async fn save_settings(
+        &self,
+        token: &str,
+        settings: &UserSettings,
+    ) -> Result<(), RepositoryError>;
+    async fn push_queued_notification(
+        &self,
+        token: &str,
+        notification: &Notification,
+    ) -> Result<(), RepositoryError>;
+    /// Removes and returns the notifications held back, oldest first.
+    async fn take_queued_notifications(
+        &self,
+        token: &str,
+    ) -> Result<Vec<Notification>, RepositoryError>;
+}
+
+#[async_trait]
+pub trait UnreadRepositoryInterface {
+    async fn find_unread_by_token(&self, token: &str)
+        -> Result<Vec<UnreadCourse>, RepositoryError>;
+    async fn save_unread(
+        &self,
+        token: &str,
+        unread: &[UnreadCourse],
+    ) -> Result<(), RepositoryError>;
 }
 
 pub struct DataService {
     data_provider: Arc<dyn DataProviderInterface>,
     data_repositories: Box<dyn RepositoryInterfaces>,
+    registration: RegistrationSettings,
+    flags: FeatureFlags,
 }
 
 impl DataService {
     pub fn new(
         data_provider: Arc<dyn DataProviderInterface>,
         data_repositories: Box<dyn RepositoryInterfaces>,
+        registration: RegistrationSettings,
+        flags: FeatureFlags,
     ) -> Self {
         Self {
             data_provider,
             data_repositories,
+            registration: RegistrationSettings {
+                concurrency: registration.concurrency.max(1),
+                ..registration
+            },
+            flags,
         }
     }
+
+    async fn register_and_report(&self, token: &Token) -> RegistrationReport {
+        match self.register_user(token).await {
+            Ok(outcome) => RegistrationReport {
+                pending: outcome.pending,
+                verification: outcome.verification,
+                ..RegistrationReport::new(token.token.clone(), RegistrationStatus::Created, None)
+            },
+            Err(ServiceError::UserAlreayExist) => RegistrationReport::new(
+                token.token.clone(),
+                RegistrationStatus::AlreadyRegistered,
+                None,
+            ),
+            Err(e) => RegistrationReport::new(
+                token.token.clone(),
+                RegistrationStatus::Failed,
+                Some(e.to_string()),
+            ),
+        }
+    }
+
+    /// In best-effort mode a failed fetch is queued for backfill instead of failing.
+    fn best_effort<T>(
+        &self,
+        result: Result<T, ServiceError>,
+        resource: BackfillResource,
+        pending: &mut Vec<BackfillResource>,
+    ) -> Result<Option<T>, ServiceError> {
+        match result {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if self.flags.best_effort_registration => {
+                eprintln!("Registration continues without {:?}: {}", resource, e);
+                pending.push(resource);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Deadlines no longer listed, by the provider or with their course, are
+    /// left out, so subscribed calendars drop them.
+    async fn deadlines_feed(&self, token: &str) -> Result<CalendarFeed, ServiceError> {
+        let mut deadlines = or_empty(self.data_repositories.find_deadlines_by_token(token).await)?;
+        let removed_courses: Vec<i64> =
+            or_empty(self.data_repositories.find_courses_by_token(token).await)?
+                .into_iter()
+                .filter(|course| course.removed)
+                .map(|course| course.id)
+                .collect();
+        deadlines.retain(|deadline| {
+            !deadline
+                .courseid
+                .is_some_and(|id| removed_courses.contains(&id))
+        });
+        let last_modified = self
+            .data_repositories
+            .find_deadlines_updated_at(token)
+            .await?;
+        Ok(CalendarFeed::new(&deadlines, last_modified))
+    }
+
+    async fn stored_grades(&self, token: &str) -> Result<Vec<Grade>, ServiceError> {
+        or_empty(self.data_repositories.find_grades_by_token(token).await)
+    }
+
+    async fn remove_device(&self, token: &str, device_token: &str) -> Result<(), ServiceError> {
+        self.data_repositories
+            .pull_devices(token, &[device_token])
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Enforces the policy for a device token already held by other users.
+    async fn apply_device_token_policy(&self, tokens: &Token) -> Result<(), ServiceError> {
+        let Some(device_token) = &tokens.device_token else {
+            return Ok(());
+        };
+        if self.registration.device_token_policy == DeviceTokenPolicy::Allow {
+            return Ok(());
+        }
+
+        let holders: Vec<String> = self
+            .data_repositories
+            .find_tokens_by_device_token(device_token)
+            .await?
+            .into_iter()
+            .filter(|holder| holder != &tokens.token)
+            .collect();
+        if holders.is_empty() {
+            return Ok(());
+        }
+
+        match self.registration.device_token_policy {
+            DeviceTokenPolicy::Reject => Err(ServiceError::DeviceTokenInUse),
+            DeviceTokenPolicy::Transfer => {
+                for holder in holders {
+                    self.remove_device(&holder, device_token).await?;
+                }
+                Ok(())
+            }
+            DeviceTokenPolicy::Allow => Ok(()),
+        }
+    }
+
+    async fn verify_registration(
+        &self,
+        token: &str,
+        courses: &[Course],
+        grades: &[Grade],
+        deadlines: &[Deadline],
+        grades_overview: &[GradeOverview],
+    ) -> RegistrationVerification {
+        let repositories = &self.data_repositories;
+        let saved_user = usize::from(repositories.find_user_by_token(token).await.is_ok());
+        let saved_courses = saved_count(repositories.find_courses_by_token(token).await);
+        let saved_grades = saved_count(repositories.find_grades_by_token(token).await);
+        let saved_deadlines = saved_count(repositories.find_deadlines_by_token(token).await);
+        let saved_grades_overview =
+            saved_count(repositories.find_grades_overview_by_token(token).await);
+
+        let verification = RegistrationVerification::from_counts(&[
+            ("user", 1, saved_user),
+            ("courses", courses.len(), saved_courses),
+            ("grades", grades.len(), saved_grades),
+            ("deadlines", deadlines.len(), saved_deadlines),
+            (
+                "grades_overview",
+                grades_overview.len(),
+                saved_grades_overview,
+            ),
+        ]);
+        if !verification.verified {
+            eprintln!(
+                "Registration verification failed: {:?}",
+                verification.mismatches
+            );
+        }
+        verification
+    }
+}
+
+/// Empty or missing data counts as nothing saved.
+fn saved_count<T>(result: Result<Vec<T>, RepositoryError>) -> usize {
+    result.map(|data| data.len()).unwrap_or(0)
+}
+
+/// Empty or missing data reads as an empty list; other failures propagate.
+fn or_empty<T>(result: Result<Vec<T>, RepositoryError>) -> Result<Vec<T>, ServiceError> {
+    match result {
+        Ok(data) => Ok(data),
+        Err(RepositoryError::DataIsEmpty(_) | RepositoryError::DataNotFound(_)) => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
 }
 #[async_trait]
 impl DataServiceInterfaces for DataService {}
@@ -108,11 +432,80 @@ impl TokenServiceInterface for DataService {
             .map_err(Into::into)
     }
 
-    async fn find_all_tokens(
+    async fn unregister_device(&self, token: &str, device_token: &str) -> Result<(), ServiceError> {
+        let holders = self
+            .data_repositories
+            .find_tokens_by_device_token(device_token)
+            .await?;
+        if !holders.iter().any(|holder| holder == token) {
+            return Err(ServiceError::DataNotFound("Device".to_string()));
+        }
+        self.remove_device(token, device_token).await
+    }
+
+    async fn update_device_token(
         &self,
-        limit: i64,
-        skip: u64,
-    ) -> Result<Cursor<Document>, ServiceError> {
+        token: &str,
+        update: &DeviceTokenUpdate,
+    ) -> Result<(), ServiceError> {
+        let mut tokens = Token::new(token.to_string(), Some(update.device_token.clone()));
+        tokens.platform = update.platform;
+        // Webhook URLs are always checked: the server posts to them
+        if self.flags.validate_device_tokens || tokens.platform == Some(Platform::Webhook) {
+            tokens
+                .normalize_device_token()
+                .map_err(ServiceError::InvalidInput)?;
+        }
+        let devices = self.data_repositories.find_devices(token).await?;
+        self.apply_device_token_policy(&tokens).await?;
+        let Some(device) = tokens.device() else {
+            return Ok(());
+        };
+        let replaces = update.previous_device_token.as_deref();
+        let device = replacing_device(&devices, device, replaces);
+        // Separate atomic updates, as a document can't $pull and $push the
+        // same array at once; concurrent registrations are never lost
+        let mut stale = vec![device.token.as_str()];
+        stale.extend(replaces);
+        self.data_repositories.pull_devices(token, &stale).await?;
+        self.data_repositories
+            .push_device(token, &device)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn set_webhook(&self, token: &str, url: &str) -> Result<String, ServiceError> {
+        let url = Platform::Webhook
+            .normalize_device_token(url)
+            .map_err(|_| ServiceError::InvalidInput("url".to_string()))?;
+        let previous = self
+            .data_repositories
+            .find_devices(token)
+            .await?
+            .into_iter()
+            .find(|device| device.platform == Some(Platform::Webhook))
+            .map(|device| device.token);
+        let update = DeviceTokenUpdate {
+            device_token: url.clone(),
+            platform: Some(Platform::Webhook),
+            previous_device_token: previous,
+        };
+        self.update_device_token(token, &update).await?;
+        Ok(url)
+    }
+
+    async fn remove_webhook(&self, token: &str) -> Result<(), ServiceError> {
+        let webhook = self
+            .data_repositories
+            .find_devices(token)
+            .await?
+            .into_iter()
+            .find(|device| device.platform == Some(Platform::Webhook))
+            .ok_or_else(|| ServiceError::DataNotFound("Webhook".to_string()))?;
+        self.remove_device(token, &webhook.token).await
+    }
+
+    async fn find_all_tokens(&self, limit: i64, skip: u64) -> Result<Vec<Token>, ServiceError> {
         self.data_repositories
             .find_all_device_tokens(limit, skip)
             .await
@@ -128,7 +521,31 @@ impl TokenServiceInterface for DataService {
         Ok(())
     }
 
-    async fn register_user(&self, tokens: &Token) -> Result<(), ServiceError> {
+    async fn refresh_data(&self, token: &str) -> Result<SyncedData, ServiceError> {
+        self.get_user(token).await?;
+        self.fetch_and_update_data(token).await?;
+        Ok(SyncedData {
+            user: self.get_user(token).await?,
+            courses: or_empty(self.data_repositories.find_courses_by_token(token).await)?,
+            grades: self.stored_grades(token).await?,
+            grades_overview: or_empty(
+                self.data_repositories
+                    .find_grades_overview_by_token(token)
+                    .await,
+            )?,
+            deadlines: or_empty(self.data_repositories.find_deadlines_by_token(token).await)?,
+        })
+    }
+
+    async fn register_user(&self, tokens: &Token) -> Result<RegistrationOutcome, ServiceError> {
+        let mut tokens = tokens.clone();
+        if self.flags.validate_device_tokens || tokens.platform == Some(Platform::Webhook) {
+            tokens
+                .normalize_device_token()
+                .map_err(ServiceError::InvalidInput)?;
+        }
+        let tokens = &tokens;
+
         self.data_provider
             .valid_token(&tokens.token)
             .await
@@ -141,15 +558,30 @@ impl TokenServiceInterface for DataService {
             .get_user(&tokens.token)
             .await
             .map_err(ServiceError::from)?;
-        let courses = self
+        let mut courses = self
             .data_provider
             .get_courses(&tokens.token, user.userid)
             .await
             .map_err(ServiceError::from)?;
-        let grades = self.fetch_grades(&tokens.token, &user, &courses).await?;
-        let deadlines = self.fetch_deadlines(&tokens.token, &courses).await?;
-        let grades_overview = self.fetch_grades_overview(&tokens.token, &courses).await?;
+        sort_courses(&mut courses);
+        let mut pending = Vec::new();
+        let grades = self.best_effort(
+            self.fetch_grades(&tokens.token, &user, &courses).await,
+            BackfillResource::Grades,
+            &mut pending,
+        )?;
+        let deadlines = self.best_effort(
+            self.fetch_deadlines(&tokens.token, &courses).await,
+            BackfillResource::Deadlines,
+            &mut pending,
+        )?;
+        let grades_overview = self.best_effort(
+            self.fetch_grades_overview(&tokens.token, &courses).await,
+            BackfillResource::GradesOverview,
+            &mut pending,
+        )?;
 
+        self.apply_device_token_policy(tokens).await?;
         self.data_repositories.save_tokens(tokens).await?;
 
         self.data_repositories
@@ -160,19 +592,127 @@ impl TokenServiceInterface for DataService {
             .save_courses(&tokens.token, &courses)
             .await?;
 
+        if let Some(grades) = &grades {
+            self.data_repositories
+                .save_grades(&tokens.token, grades)
+                .await?;
+        }
+
+        if let Some(grades_overview) = &grades_overview {
+            self.data_repositories
+                .save_grades_overview(&tokens.token, grades_overview)
+                .await?;
+        }
+
+        if let Some(deadlines) = &deadlines {
+            self.data_repositories
+                .save_deadlines(&tokens.token, deadlines)
+                .await?;
+        }
+
+        if !pending.is_empty() {
+            self.data_repositories
+                .save_pending_backfill(&tokens.token, &pending)
+                .await?;
+        }
+
+        let verification = if self.flags.verify_registration {
+            Some(
+                self.verify_registration(
+                    &tokens.token,
+                    &courses,
+                    grades.as_deref().unwrap_or_default(),
+                    deadlines.as_deref().unwrap_or_default(),
+                    grades_overview
+                        .as_ref()
+                        .map_or(&[][..], |grades_overview| &grades_overview.grades),
+                )
+                .await,
+            )
+        } else {
+            None
+        };
+        Ok(RegistrationOutcome {
+            pending,
+            verification,
+        })
+    }
+
+    async fn register_users(&self, tokens: &[Token]) -> Vec<RegistrationReport> {
+        let registrations: Vec<_> = tokens
+            .iter()
+            .map(|token| self.register_and_report(token))
+            .collect();
+        stream::iter(registrations)
+            .buffered(self.registration.concurrency)
+            .collect()
+            .await
+    }
+
+    async fn backfill_pending(&self, token: &str) -> Result<(), ServiceError> {
+        let pending = self.data_repositories.find_pending_backfill(token).await?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let user = self.get_user(token).await?;
+        let mut courses = self.get_courses(token).await?;
+        Course::delete_removed_courses(&mut courses);
+        let mut remaining = Vec::new();
+        for resource in pending {
+            let result = match resource {
+                BackfillResource::Grades => self.update_grades(token, &user, &courses).await,
+                BackfillResource::Deadlines => self.update_deadlines(token, &courses).await,
+                BackfillResource::GradesOverview => {
+                    self.update_grades_overview(token, &courses).await
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Error backfilling {:?}: {}", resource, e);
+                remaining.push(resource);
+            }
+        }
         self.data_repositories
-            .save_grades(&tokens.token, &grades)
+            .save_pending_backfill(token, &remaining)
             .await?;
+        Ok(())
+    }
 
+    async fn find_tokens_by_user_id(&self, userid: i64) -> Result<Vec<Token>, ServiceError> {
         self.data_repositories
-            .save_grades_overview(&tokens.token, &grades_overview)
-            .await?;
+            .find_tokens_by_user_id(userid)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn find_broadcast_recipients(
+        &self,
+        segment: &BroadcastSegment,
+        after: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<Token>, ServiceError> {
+        self.data_repositories
+            .find_broadcast_recipients(segment, after, limit)
+            .await
+            .map_err(Into::into)
+    }
 
+    async fn record_sync(&self, token: &str, error: Option<String>) -> Result<(), ServiceError> {
+        let status = SyncStatus {
+            at: mongodb::bson::DateTime::now(),
+            error,
+        };
         self.data_repositories
-            .save_deadlines(&tokens.token, &deadlines)
-            .await?;
+            .save_sync_status(token, &status)
+            .await
+            .map_err(Into::into)
+    }
 
-        Ok(())
+    async fn list_users(&self, query: &UserListQuery) -> Result<Vec<RegisteredUser>, ServiceError> {
+        self.data_repositories
+            .find_registered_users(query)
+            .await
+            .map_err(Into::into)
     }
 }
 
@@ -194,21 +734,103 @@ impl UserServiceInterface for DataService {
             .await
             .map_err(Into::into)
     }
+
+    async fn get_dashboard(&self, token: &str) -> Result<Dashboard, ServiceError> {
+        let user = self.get_user(token).await?;
+        let mut courses = or_empty(self.data_repositories.find_courses_by_token(token).await)?;
+        sort_courses(&mut courses);
+        let mut upcoming_deadlines =
+            or_empty(self.data_repositories.find_deadlines_by_token(token).await)?;
+        filter_deadlines(
+            &mut upcoming_deadlines,
+            Utc::now().timestamp(),
+            &dashboard_deadlines_query(),
+        );
+        let grades = self.stored_grades(token).await?;
+        let grades_overview = or_empty(
+            self.data_repositories
+                .find_grades_overview_by_token(token)
+                .await,
+        )?;
+        Ok(Dashboard {
+            user,
+            courses,
+            upcoming_deadlines,
+            recent_grades: recent_grades(&grades, RECENT_GRADES),
+            grades_overview,
+        })
+    }
 }
 
 #[async_trait]
 impl CourseServiceInterface for DataService {
     async fn get_courses(&self, token: &str) -> Result<Vec<Course>, ServiceError> {
+        let mut courses = self.data_repositories.find_courses_by_token(token).await?;
+        sort_courses(&mut courses);
+        Ok(courses)
+    }
+
+    async fn search_courses(
+        &self,
+        token: &str,
+        query: &CourseSearchQuery,
+    ) -> Result<Vec<Course>, ServiceError> {
+        if query.q.trim().is_empty() {
+            return Err(ServiceError::InvalidInput("q".to_string()));
+        }
+        let courses = or_empty(self.data_repositories.find_courses_by_token(token).await)?;
+        let mut courses = search_courses(courses, &query.q);
+        sort_courses(&mut courses);
+        Ok(courses)
+    }
+
+    async fn update_courses(&self, token: &str, user: &User) -> Result<Vec<Course>, ServiceError> {
+        let mut courses = self.data_provider.get_courses(token, user.userid).await?;
+        sort_courses(&mut courses);
+        let stored = or_empty(self.data_repositories.find_courses_by_token(token).await)?;
         self.data_repositories
-            .find_courses_by_token(token)
+            .save_courses(token, &with_removed_courses(&courses, &stored))
+            .await?;
+        Ok(courses)
+    }
+
+    async fn get_course_contents(&self, token: &str) -> Result<Vec<CourseContent>, ServiceError> {
+        self.data_repositories
+            .find_course_contents(token)
             .await
             .map_err(Into::into)
     }
 
-    async fn update_courses(&self, token: &str, user: &User) -> Result<Vec<Course>, ServiceError> {
-        let courses = self.data_provider.get_courses(token, user.userid).await?;
-        self.data_repositories.save_courses(token, &courses).await?;
-        Ok(courses)
+    async fn update_course_contents(
+        &self,
+        token: &str,
+        course_id: i64,
+        contents: &[CourseContent],
+    ) -> Result<(), ServiceError> {
+        let mut stored = self.data_repositories.find_course_contents(token).await?;
+        stored.retain(|content| content.course_id != course_id);
+        stored.extend_from_slice(contents);
+        self.data_repositories
+            .save_course_contents(token, &stored)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn retain_course_contents(
+        &self,
+        token: &str,
+        course_ids: &[i64],
+    ) -> Result<(), ServiceError> {
+        let mut stored = self.data_repositories.find_course_contents(token).await?;
+        let count = stored.len();
+        stored.retain(|content| course_ids.contains(&content.course_id));
+        if stored.len() == count {
+            return Ok(());
+        }
+        self.data_repositories
+            .save_course_contents(token, &stored)
+            .await
+            .map_err(Into::into)
     }
 }
 
@@ -221,6 +843,31 @@ impl GradeServiceInterface for DataService {
             .map_err(Into::into)
     }
 
+    async fn get_grades_page(
+        &self,
+        token: &str,
+        query: &PageQuery,
+    ) -> Result<Page<Grade>, ServiceError> {
+        self.data_repositories
+            .find_grades_page(token, query.skip(), query.per_page())
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_course_grades(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Vec<CourseGradeItem>, ServiceError> {
+        let grade = self
+            .get_grades(token)
+            .await?
+            .into_iter()
+            .find(|grade| grade.courseid == course_id)
+            .ok_or_else(|| ServiceError::DataNotFound("Course grades".to_string()))?;
+        Ok(grade.gradeitems.into_iter().map(Into::into).collect())
+    }
+
     async fn fetch_grades(
         &self,
         token: &str,
@@ -249,12 +896,38 @@ impl GradeServiceInterface for DataService {
         user: &User,
         courses: &[Course],
     ) -> Result<(), ServiceError> {
-        let grades = self.fetch_grades(token, user, courses).await?;
+        let mut grades = self.fetch_grades(token, user, courses).await?;
+
+        if !self.flags.allow_clearing_grades {
+            let stored_grades = self.stored_grades(token).await?;
+            let course_ids: Vec<i64> = courses.iter().map(|course| course.id).collect();
+            keep_stored_grades(&mut grades, &stored_grades, &course_ids);
+        }
 
         self.data_repositories.save_grades(token, &grades).await?;
         Ok(())
     }
 
+    async fn update_grades_for_course(
+        &self,
+        token: &str,
+        course_id: i64,
+        grades: &[Grade],
+    ) -> Result<(), ServiceError> {
+        let mut stored_grades = self.stored_grades(token).await?;
+        let mut grades = grades.to_vec();
+        if !self.flags.allow_clearing_grades {
+            keep_stored_grades(&mut grades, &stored_grades, &[course_id]);
+        }
+        stored_grades.retain(|grade| grade.courseid != course_id);
+        stored_grades.extend(grades);
+
+        self.data_repositories
+            .save_grades(token, &stored_grades)
+            .await?;
+        Ok(())
+    }
+
     async fn get_grades_overview(&self, token: &str) -> Result<Vec<GradeOverview>, ServiceError> {
         self.data_repositories
             .find_grades_overview_by_token(token)
@@ -292,6 +965,24 @@ impl GradeServiceInterface for DataService {
             .await?;
         Ok(())
     }
+
+    async fn get_grade_marks(&self, token: &str) -> Result<Vec<GradeMark>, ServiceError> {
+        self.data_repositories
+            .find_grade_marks_by_token(token)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn update_grade_marks(
+        &self,
+        token: &str,
+        marks: &[GradeMark],
+    ) -> Result<(), ServiceError> {
+        self.data_repositories
+            .save_grade_marks(token, marks)
+            .await
+            .map_err(Into::into)
+    }
 }
 
 #[async_trait]
@@ -303,6 +994,97 @@ impl DeadlineServiceInterface for DataService {
             .map_err(Into::into)
     }
 
+    async fn get_upcoming_deadlines(
+        &self,
+        token: &str,
+        query: &DeadlineQuery,
+    ) -> Result<Vec<Deadline>, ServiceError> {
+        query.validate().map_err(ServiceError::InvalidInput)?;
+        let mut deadlines = self.get_deadlines(token).await?;
+        filter_deadlines(&mut deadlines, Utc::now().timestamp(), query);
+        Ok(deadlines)
+    }
+
+    async fn get_deadlines_page(
+        &self,
+        token: &str,
+        query: &DeadlineQuery,
+        page: &PageQuery,
+    ) -> Result<Page<Deadline>, ServiceError> {
+        query.validate().map_err(ServiceError::InvalidInput)?;
+        let now = Utc::now().timestamp();
+        let due = query.until(now).map(|until| (now, until));
+        self.data_repositories
+            .find_deadlines_page(token, due, page.skip(), page.per_page())
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn snooze_deadline(
+        &self,
+        token: &str,
+        deadline_id: i32,
+        request: &SnoozeRequest,
+    ) -> Result<DeadlineSnooze, ServiceError> {
+        request.validate().map_err(ServiceError::InvalidInput)?;
+        let deadlines = or_empty(self.data_repositories.find_deadlines_by_token(token).await)?;
+        if !deadlines.iter().any(|deadline| deadline.id == deadline_id) {
+            return Err(ServiceError::DataNotFound("Deadline".to_string()));
+        }
+
+        let now = Utc::now().timestamp();
+        let snooze = DeadlineSnooze {
+            deadline_id,
+            until: now + request.minutes * 60,
+        };
+        let mut snoozes = self.data_repositories.find_snoozes(token).await?;
+        add_snooze(&mut snoozes, snooze.clone(), now);
+        self.data_repositories.save_snoozes(token, &snoozes).await?;
+        Ok(snooze)
+    }
+
+    async fn get_snoozes(&self, token: &str) -> Result<Vec<DeadlineSnooze>, ServiceError> {
+        self.data_repositories
+            .find_snoozes(token)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_sent_reminders(&self, token: &str) -> Result<Vec<SentReminder>, ServiceError> {
+        self.data_repositories
+            .find_sent_reminders(token)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn mark_reminders_sent(
+        &self,
+        token: &str,
+        reminders: &[SentReminder],
+    ) -> Result<(), ServiceError> {
+        let now = Utc::now().timestamp();
+        let mut sent = self.data_repositories.find_sent_reminders(token).await?;
+        sent.retain(|reminder| reminder.due > now);
+        sent.extend_from_slice(reminders);
+        self.data_repositories
+            .save_sent_reminders(token, &sent)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_course_deadlines(
+        &self,
+        token: &str,
+        course_id: i64,
+        query: &DeadlineQuery,
+    ) -> Result<Vec<Deadline>, ServiceError> {
+        query.validate().map_err(ServiceError::InvalidInput)?;
+        let mut deadlines = self.get_deadlines(token).await?;
+        deadlines.retain(|deadline| deadline.courseid == Some(course_id));
+        filter_deadlines(&mut deadlines, Utc::now().timestamp(), query);
+        Ok(deadlines)
+    }
+
     async fn fetch_deadlines(
         &self,
         token: &str,
@@ -318,6 +1100,7 @@ impl DeadlineServiceInterface for DataService {
                 .events;
             for mut deadline in external_deadlines {
                 deadline.coursename = Option::from(course.fullname.clone());
+                deadline.courseid = Some(course.id);
                 deadlines.push(deadline);
             }
         }
@@ -334,3 +1117,928 @@ impl DeadlineServiceInterface for DataService {
         Ok(())
     }
 }
+
+#[async_trait]
+impl PreferencesServiceInterface for DataService {
+    async fn get_preferences(&self, token: &str) -> Result<Preferences, ServiceError> {
+        let mut preferences = self
+            .data_repositories
+            .find_preferences_by_token(token)
+            .await?;
+        if !preferences.is_paused(Utc::now().timestamp()) {
+            preferences.pause = None;
+        }
+        Ok(preferences)
+    }
+
+    async fn set_course_muted(
+        &self,
+        token: &str,
+        course_id: i64,
+        muted: bool,
+    ) -> Result<Preferences, ServiceError> {
+        let mut preferences = self
+            .data_repositories
+            .find_preferences_by_token(token)
+            .await?;
+        preferences.set_course_muted(course_id, muted);
+        self.data_repositories
+            .save_preferences(token, &preferences)
+            .await?;
+        self.get_preferences(token).await
+    }
+
+    async fn get_settings(&self, token: &str) -> Result<UserSettings, ServiceError> {
+        self.data_repositories
+            .find_settings_by_token(token)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn update_settings(
+        &self,
+        token: &str,
+        settings: &UserSettings,
+    ) -> Result<UserSettings, ServiceError> {
+        settings.validate().map_err(ServiceError::InvalidInput)?;
+        self.data_repositories
+            .save_settings(token, settings)
+            .await?;
+        Ok(settings.clone())
+    }
+
+    async fn queue_notification(
+        &self,
+        token: &str,
+        notification: &Notification,
+    ) -> Result<(), ServiceError> {
+        self.data_repositories
+            .push_queued_notification(token, notification)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn take_queued_notifications(
+        &self,
+        token: &str,
+    ) -> Result<Vec<Notification>, ServiceError> {
+        self.data_repositories
+            .take_queued_notifications(token)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn update_preferences(
+        &self,
+        token: &str,
+        preferences: &Preferences,
+    ) -> Result<Preferences, ServiceError> {
+        preferences.validate().map_err(ServiceError::InvalidInput)?;
+        let current = self.get_preferences(token).await?;
+        let preferences = Preferences {
+            pause: current.pause,
+            ..preferences.clone()
+        };
+        self.data_repositories
+            .save_preferences(token, &preferences)
+            .await?;
+        Ok(preferences)
+    }
+
+    async fn pause_notifications(
+        &self,
+        token: &str,
+        until: Option<i64>,
+    ) -> Result<Preferences, ServiceError> {
+        if until.is_some_and(|until| until <= Utc::now().timestamp()) {
+            return Err(ServiceError::InvalidInput("until".to_string()));
+        }
+        let mut preferences = self.get_preferences(token).await?;
+        preferences.pause = Some(NotificationPause { until });
+        self.data_repositories
+            .save_preferences(token, &preferences)
+            .await?;
+        Ok(preferences)
+    }
+
+    async fn resume_notifications(&self, token: &str) -> Result<Preferences, ServiceError> {
+        let mut preferences = self.get_preferences(token).await?;
+        preferences.pause = None;
+        self.data_repositories
+            .save_preferences(token, &preferences)
+            .await?;
+        Ok(preferences)
+    }
+}
+
+#[async_trait]
+impl UnreadServiceInterface for DataService {
+    async fn get_unread_courses(&self, token: &str) -> Result<Vec<UnreadCourse>, ServiceError> {
+        Ok(self.data_repositories.find_unread_by_token(token).await?)
+    }
+
+    async fn add_unread(
+        &self,
+        token: &str,
+        course_id: i64,
+        kind: NotificationKind,
+        count: u32,
+    ) -> Result<(), ServiceError> {
+        let mut unread = self.data_repositories.find_unread_by_token(token).await?;
+        add_unread(&mut unread, course_id, kind, count);
+        self.data_repositories.save_unread(token, &unread).await?;
+        Ok(())
+    }
+
+    async fn ack_unread(
+        &self,
+        token: &str,
+        course_ids: Option<&[i64]>,
+    ) -> Result<Vec<UnreadCourse>, ServiceError> {
+        let mut unread = self.data_repositories.find_unread_by_token(token).await?;
+        ack_unread(&mut unread, course_ids);
+        self.data_repositories.save_unread(token, &unread).await?;
+        Ok(unread)
+    }
+}
+
+#[async_trait]
+impl CalendarServiceInterface for DataService {
+    async fn get_calendar_secret(&self, token: &str) -> Result<String, ServiceError> {
+        match self.data_repositories.find_calendar_secret(token).await? {
+            Some(secret) => Ok(secret),
+            None => self.rotate_calendar_secret(token).await,
+        }
+    }
+
+    async fn rotate_calendar_secret(&self, token: &str) -> Result<String, ServiceError> {
+        let secret = URL_SAFE_NO_PAD.encode(rand::thread_rng().gen::<[u8; 32]>());
+        self.data_repositories
+            .save_calendar_secret(token, &secret)
+            .await?;
+        Ok(secret)
+    }
+
+    async fn get_calendar_feed(&self, secret: &str) -> Result<CalendarFeed, ServiceError> {
+        let token = self
+            .data_repositories
+            .find_token_by_calendar_secret(secret)
+            .await?;
+        self.deadlines_feed(&token).await
+    }
+
+    async fn get_deadlines_calendar(&self, token: &str) -> Result<CalendarFeed, ServiceError> {
+        self.get_user(token).await?;
+        self.deadlines_feed(token).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::registration::VerificationMismatch;
+    use crate::models::token::{Platform, MAX_DEVICES};
+    use crate::services::mocks::{
+        course, deadline, device, grade, user, MockProvider, MockRepository,
+    };
+
+    fn data_service(provider: &MockProvider, repository: &MockRepository) -> DataService {
+        DataService::new(
+            Arc::new(provider.clone()),
+            Box::new(repository.clone()),
+            RegistrationSettings {
+                concurrency: 2,
+                ..Default::default()
+            },
+            FeatureFlags::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_register_users_mixed_new_and_existing() {
+        let provider = MockProvider::default();
+        provider
+            .invalid_tokens
+            .lock()
+            .unwrap()
+            .insert("invalid".to_string());
+        let repository = MockRepository::with_tokens(&["existing"]);
+        let service = data_service(&provider, &repository);
+
+        let tokens = vec![
+            Token::new("new1".to_string(), Some("device1".to_string())),
+            Token::new("existing".to_string(), None),
+            Token::new("invalid".to_string(), None),
+            Token::new("new2".to_string(), None),
+        ];
+        let reports = service.register_users(&tokens).await;
+
+        let statuses: Vec<_> = reports.iter().map(|r| &r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                &RegistrationStatus::Created,
+                &RegistrationStatus::AlreadyRegistered,
+                &RegistrationStatus::Failed,
+                &RegistrationStatus::Created,
+            ]
+        );
+        assert!(reports[2].error.is_some());
+        assert_eq!(
+            repository.stored("new1").unwrap().device_tokens(),
+            ["device1"]
+        );
+        assert!(repository.stored("new2").unwrap().user.is_some());
+        assert!(repository.stored("invalid").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_calendar_feed_secret_rotation() {
+        let provider = MockProvider::default();
+        let repository = MockRepository::with_tokens(&["token"]);
+        let service = data_service(&provider, &repository);
+
+        let secret = service.get_calendar_secret("token").await.unwrap();
+        assert_eq!(service.get_calendar_secret("token").await.unwrap(), secret);
+        let feed = service.get_calendar_feed(&secret).await.unwrap();
+        assert!(feed.body.starts_with("BEGIN:VCALENDAR"));
+        assert!(!feed.body.contains("token"));
+
+        let rotated = service.rotate_calendar_secret("token").await.unwrap();
+        assert_ne!(rotated, secret);
+        assert!(matches!(
+            service.get_calendar_feed(&secret).await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+        assert!(service.get_calendar_feed(&rotated).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_deadlines_calendar_by_token() {
+        let provider = MockProvider::default();
+        let due = Utc::now().timestamp() + 86400;
+        provider
+            .deadlines
+            .lock()
+            .unwrap()
+            .insert(1, vec![deadline(10, due)]);
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .user = Some(user(1));
+        let service = data_service(&provider, &repository);
+        service
+            .update_deadlines("token", &[course(1)])
+            .await
+            .unwrap();
+
+        let feed = service.get_deadlines_calendar("token").await.unwrap();
+        assert!(feed.body.contains("UID:10@aitu-keeper"));
+        assert!(feed.body.contains("DESCRIPTION:Course 1"));
+
+        let mut removed = course(1);
+        removed.removed = true;
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .courses = Some(vec![removed]);
+        let feed = service.get_deadlines_calendar("token").await.unwrap();
+        assert!(!feed.body.contains("BEGIN:VEVENT"));
+        assert!(matches!(
+            service.get_deadlines_calendar("missing").await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pause_survives_preferences_update_until_resumed() {
+        let provider = MockProvider::default();
+        let repository = MockRepository::with_tokens(&["token"]);
+        let service = data_service(&provider, &repository);
+
+        assert!(matches!(
+            service.pause_notifications("token", Some(0)).await,
+            Err(ServiceError::InvalidInput(_))
+        ));
+        service.pause_notifications("token", None).await.unwrap();
+
+        let updated = service
+            .update_preferences(
+                "token",
+                &Preferences {
+                    only_final_grades: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(updated.only_final_grades);
+        assert_eq!(updated.pause, Some(NotificationPause { until: None }));
+
+        let resumed = service.resume_notifications("token").await.unwrap();
+        assert_eq!(resumed.pause, None);
+        assert!(resumed.only_final_grades);
+    }
+
+    #[tokio::test]
+    async fn test_register_user_validates_device_token_for_platform() {
+        let provider = MockProvider::default();
+        let repository = MockRepository::default();
+        let service = data_service(&provider, &repository);
+
+        let invalid = Token {
+            platform: Some(Platform::Ios),
+            ..Token::new("ios".to_string(), Some("not-a-token".to_string()))
+        };
+        assert!(matches!(
+            service.register_user(&invalid).await,
+            Err(ServiceError::InvalidInput(_))
+        ));
+        assert!(repository.stored("ios").is_none());
+        assert!(provider.calls.lock().unwrap().is_empty());
+
+        let valid = Token {
+            platform: Some(Platform::Ios),
+            ..Token::new("ios".to_string(), Some(format!("<{}>", "AB".repeat(32))))
+        };
+        service.register_user(&valid).await.unwrap();
+        let stored = repository.stored("ios").unwrap();
+        assert_eq!(
+            stored.devices,
+            [device(&"ab".repeat(32), Some(Platform::Ios))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registration_verification_flags_dropped_save() {
+        let provider = MockProvider::default();
+        provider
+            .courses
+            .lock()
+            .unwrap()
+            .extend([course(1), course(2)]);
+        provider
+            .grades
+            .lock()
+            .unwrap()
+            .insert(1, vec![grade(1, &[(10, "50.00 %")])]);
+        let repository = MockRepository::default();
+        let service = DataService::new(
+            Arc::new(provider.clone()),
+            Box::new(repository.clone()),
+            RegistrationSettings::default(),
+            FeatureFlags {
+                verify_registration: true,
+                ..Default::default()
+            },
+        );
+
+        let verification = service
+            .register_user(&Token::new("first".to_string(), None))
+            .await
+            .unwrap()
+            .verification
+            .unwrap();
+        assert!(verification.verified);
+
+        repository.dropped_saves.lock().unwrap().insert("grades");
+        let verification = service
+            .register_user(&Token::new("second".to_string(), None))
+            .await
+            .unwrap()
+            .verification
+            .unwrap();
+        assert!(!verification.verified);
+        assert_eq!(
+            verification.mismatches,
+            vec![VerificationMismatch {
+                data: "grades",
+                fetched: 1,
+                saved: 0,
+            }]
+        );
+    }
+
+    async fn register_with_shared_device(
+        policy: DeviceTokenPolicy,
+    ) -> (MockRepository, Result<RegistrationOutcome, ServiceError>) {
+        let provider = MockProvider::default();
+        let repository = MockRepository::default();
+        let service = DataService::new(
+            Arc::new(provider),
+            Box::new(repository.clone()),
+            RegistrationSettings {
+                device_token_policy: policy,
+                ..Default::default()
+            },
+            FeatureFlags::default(),
+        );
+        let device = Some("shared-device".to_string());
+        service
+            .register_user(&Token::new("first".to_string(), device.clone()))
+            .await
+            .unwrap();
+
+        let result = service
+            .register_user(&Token::new("second".to_string(), device))
+            .await;
+        (repository, result)
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_device_token_rejected() {
+        let (repository, result) = register_with_shared_device(DeviceTokenPolicy::Reject).await;
+        assert!(matches!(result, Err(ServiceError::DeviceTokenInUse)));
+        assert!(repository.stored("second").is_none());
+        assert_eq!(
+            repository.stored("first").unwrap().device_tokens(),
+            ["shared-device"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_device_token_transferred_to_newest_user() {
+        let (repository, result) = register_with_shared_device(DeviceTokenPolicy::Transfer).await;
+        assert!(result.is_ok());
+        assert!(repository.stored("first").unwrap().devices.is_empty());
+        assert_eq!(
+            repository.stored("second").unwrap().device_tokens(),
+            ["shared-device"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_device_token_allowed() {
+        let (repository, result) = register_with_shared_device(DeviceTokenPolicy::Allow).await;
+        assert!(result.is_ok());
+        for token in ["first", "second"] {
+            assert_eq!(
+                repository.stored(token).unwrap().device_tokens(),
+                ["shared-device"]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_registration_backfills_failed_grades() {
+        let provider = MockProvider::default();
+        provider.courses.lock().unwrap().push(course(1));
+        provider
+            .grades
+            .lock()
+            .unwrap()
+            .insert(1, vec![grade(1, &[(10, "50.00 %")])]);
+        provider
+            .failing_calls
+            .lock()
+            .unwrap()
+            .push("get_grades_by_course_id".to_string());
+        let repository = MockRepository::default();
+        let token = Token::new("token".to_string(), None);
+
+        let strict = data_service(&provider, &repository);
+        assert!(strict.register_user(&token).await.is_err());
+        assert!(repository.stored("token").is_none());
+
+        let service = DataService::new(
+            Arc::new(provider.clone()),
+            Box::new(repository.clone()),
+            RegistrationSettings::default(),
+            FeatureFlags {
+                best_effort_registration: true,
+                ..Default::default()
+            },
+        );
+        let outcome = service.register_user(&token).await.unwrap();
+        assert_eq!(outcome.pending, vec![BackfillResource::Grades]);
+        let stored = repository.stored("token").unwrap();
+        assert_eq!(stored.courses.unwrap().len(), 1);
+        assert!(stored.grades.is_none());
+        assert_eq!(stored.pending_backfill, vec![BackfillResource::Grades]);
+
+        provider.failing_calls.lock().unwrap().clear();
+        service.backfill_pending("token").await.unwrap();
+        let stored = repository.stored("token").unwrap();
+        assert_eq!(stored.grades.unwrap().len(), 1);
+        assert!(stored.pending_backfill.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_courses_sorted_regardless_of_provider_order() {
+        let mut orders = Vec::new();
+        for provider_order in [[3, 1, 2], [2, 3, 1]] {
+            let provider = MockProvider::default();
+            provider
+                .courses
+                .lock()
+                .unwrap()
+                .extend(provider_order.map(course));
+            let repository = MockRepository::with_tokens(&["token"]);
+            let service = data_service(&provider, &repository);
+
+            let updated = service.update_courses("token", &user(1)).await.unwrap();
+            let stored = service.get_courses("token").await.unwrap();
+            assert_eq!(updated, stored);
+            orders.push(stored.iter().map(|course| course.id).collect::<Vec<_>>());
+        }
+        assert_eq!(orders, vec![vec![1, 2, 3], vec![1, 2, 3]]);
+    }
+
+    #[tokio::test]
+    async fn test_transient_empty_grades_keep_stored_grades() {
+        let provider = MockProvider::default();
+        provider
+            .grades
+            .lock()
+            .unwrap()
+            .insert(1, vec![grade(1, &[(10, "60.00 %")])]);
+        let repository = MockRepository::with_tokens(&["token"]);
+        let stored = vec![grade(1, &[(10, "50.00 %")]), grade(2, &[(20, "70.00 %")])];
+        repository.save_grades("token", &stored).await.unwrap();
+        let courses = [course(1), course(2)];
+
+        let service = data_service(&provider, &repository);
+        service
+            .update_grades("token", &user(1), &courses)
+            .await
+            .unwrap();
+        service
+            .update_grades_for_course("token", 2, &[])
+            .await
+            .unwrap();
+        let grades = repository.stored("token").unwrap().grades.unwrap();
+        assert_eq!(grades.len(), 2);
+        assert_eq!(grades[0].gradeitems[0].percentageformatted, "60.00 %");
+        assert_eq!(grades[1].gradeitems, stored[1].gradeitems);
+
+        let clearing = DataService::new(
+            Arc::new(provider.clone()),
+            Box::new(repository.clone()),
+            RegistrationSettings::default(),
+            FeatureFlags {
+                allow_clearing_grades: true,
+                ..Default::default()
+            },
+        );
+        clearing
+            .update_grades("token", &user(1), &courses)
+            .await
+            .unwrap();
+        let grades = repository.stored("token").unwrap().grades.unwrap();
+        assert_eq!(grades.len(), 1);
+        assert_eq!(grades[0].courseid, 1);
+    }
+
+    #[tokio::test]
+    async fn test_unregister_device_keeps_user_data() {
+        let repository = MockRepository::with_tokens(&["token"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.devices = vec![device("phone", None), device("tablet", None)];
+            stored.courses = Some(vec![course(1)]);
+        }
+        let service = data_service(&MockProvider::default(), &repository);
+
+        assert!(matches!(
+            service.unregister_device("token", "watch").await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+        service.unregister_device("token", "phone").await.unwrap();
+
+        let stored = repository.stored("token").unwrap();
+        assert_eq!(stored.device_tokens(), ["tablet"]);
+        assert_eq!(stored.courses.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_replaces_earlier_one() {
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .devices = vec![device("phone", Some(Platform::Android))];
+        let service = data_service(&MockProvider::default(), &repository);
+
+        assert!(matches!(
+            service
+                .set_webhook("token", "http://example.com/hook")
+                .await,
+            Err(ServiceError::InvalidInput(_))
+        ));
+        service
+            .set_webhook("token", "https://example.com/a")
+            .await
+            .unwrap();
+        let url = service
+            .set_webhook("token", " https://example.com/b ")
+            .await
+            .unwrap();
+        assert_eq!(url, "https://example.com/b");
+        assert_eq!(
+            repository.stored("token").unwrap().devices,
+            [
+                device("phone", Some(Platform::Android)),
+                device("https://example.com/b", Some(Platform::Webhook))
+            ]
+        );
+
+        service.remove_webhook("token").await.unwrap();
+        assert_eq!(
+            repository.stored("token").unwrap().device_tokens(),
+            ["phone"]
+        );
+        assert!(matches!(
+            service.remove_webhook("token").await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_update_device_token_swaps_token_and_transfers_it() {
+        let repository = MockRepository::with_tokens(&["token", "other"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.devices = vec![
+                device("old-device", Some(Platform::Android)),
+                device("tablet", Some(Platform::Ios)),
+            ];
+            users.get_mut("other").unwrap().devices = vec![device("new-device", None)];
+        }
+        let service = data_service(&MockProvider::default(), &repository);
+        let update = DeviceTokenUpdate {
+            device_token: " new-device ".to_string(),
+            platform: None,
+            previous_device_token: Some("old-device".to_string()),
+        };
+
+        service.update_device_token("token", &update).await.unwrap();
+
+        let stored = repository.stored("token").unwrap();
+        assert_eq!(
+            stored.devices,
+            [
+                device("tablet", Some(Platform::Ios)),
+                device("new-device", Some(Platform::Android))
+            ]
+        );
+        assert!(repository.stored("other").unwrap().devices.is_empty());
+
+        let second_phone = DeviceTokenUpdate {
+            device_token: "second-phone".to_string(),
+            platform: None,
+            previous_device_token: None,
+        };
+        service
+            .update_device_token("token", &second_phone)
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.stored("token").unwrap().device_tokens(),
+            ["tablet", "new-device", "second-phone"]
+        );
+        assert!(matches!(
+            service.update_device_token("missing", &update).await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_update_device_token_keeps_most_recent_devices() {
+        let repository = MockRepository::with_tokens(&["token"]);
+        let service = data_service(&MockProvider::default(), &repository);
+
+        for i in 0..=MAX_DEVICES {
+            let update = DeviceTokenUpdate {
+                device_token: format!("device-{}", i),
+                platform: None,
+                previous_device_token: None,
+            };
+            service.update_device_token("token", &update).await.unwrap();
+        }
+
+        let stored = repository.stored("token").unwrap();
+        let tokens = stored.device_tokens();
+        assert_eq!(tokens.len(), MAX_DEVICES);
+        assert_eq!(tokens[0], "device-1");
+    }
+
+    #[tokio::test]
+    async fn test_refresh_data_syncs_and_returns_stored_data() {
+        let provider = MockProvider::default();
+        provider.courses.lock().unwrap().push(course(1));
+        provider
+            .grades
+            .lock()
+            .unwrap()
+            .insert(1, vec![grade(1, &[(10, "60.00 %")])]);
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .user = Some(user(1));
+        let service = data_service(&provider, &repository);
+
+        let data = service.refresh_data("token").await.unwrap();
+        assert_eq!(data.user, user(1));
+        assert_eq!(data.courses.len(), 1);
+        assert_eq!(data.grades[0].gradeitems[0].percentageformatted, "60.00 %");
+        assert!(data.deadlines.is_empty());
+
+        let calls = provider.calls_to("get_user");
+        assert!(matches!(
+            service.refresh_data("missing").await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+        assert_eq!(provider.calls_to("get_user"), calls);
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_reads_stored_data_only() {
+        let repository = MockRepository::with_tokens(&["token", "new"]);
+        let now = Utc::now().timestamp();
+        let mut graded = grade(1, &[(10, "60.00 %"), (11, "70.00 %")]);
+        graded.gradeitems[1].gradedategraded = Some(now);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.user = Some(user(1));
+            stored.courses = Some(vec![course(2), course(1)]);
+            stored.grades = Some(vec![graded]);
+            stored.deadlines = Some(vec![
+                deadline(1, now + 3600),
+                deadline(2, now + 30 * 86400),
+                deadline(3, now - 3600),
+            ]);
+            users.get_mut("new").unwrap().user = Some(user(2));
+        }
+        let provider = MockProvider::default();
+        let service = data_service(&provider, &repository);
+
+        let dashboard = service.get_dashboard("token").await.unwrap();
+        assert_eq!(dashboard.user, user(1));
+        assert_eq!(dashboard.courses, vec![course(1), course(2)]);
+        assert_eq!(
+            dashboard
+                .upcoming_deadlines
+                .iter()
+                .map(|d| d.id)
+                .collect::<Vec<_>>(),
+            [1]
+        );
+        assert_eq!(dashboard.recent_grades.len(), 1);
+        assert_eq!(dashboard.recent_grades[0].itemname, "Item 11");
+        assert!(dashboard.grades_overview.is_empty());
+        assert_eq!(provider.calls_to("get_user"), 0);
+
+        let empty = service.get_dashboard("new").await.unwrap();
+        assert!(empty.courses.is_empty() && empty.upcoming_deadlines.is_empty());
+        assert!(matches!(
+            service.get_dashboard("missing").await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_course_deadlines_are_filtered_by_course() {
+        let provider = MockProvider::default();
+        let due = Utc::now().timestamp() + 3 * 86400;
+        provider
+            .deadlines
+            .lock()
+            .unwrap()
+            .extend([(1, vec![deadline(10, due)]), (2, vec![deadline(20, due)])]);
+        let repository = MockRepository::with_tokens(&["token"]);
+        let service = data_service(&provider, &repository);
+        service
+            .update_deadlines("token", &[course(1), course(2)])
+            .await
+            .unwrap();
+
+        let deadlines = service
+            .get_course_deadlines("token", 2, &DeadlineQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(deadlines.len(), 1);
+        assert_eq!(deadlines[0].id, 20);
+        assert_eq!(deadlines[0].courseid, Some(2));
+        assert_eq!(deadlines[0].coursename.as_deref(), Some("Course 2"));
+        assert!(service
+            .get_course_deadlines("token", 3, &DeadlineQuery::default())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_deadlines_page_applies_due_window_before_paging() {
+        let repository = MockRepository::with_tokens(&["token"]);
+        let now = Utc::now().timestamp();
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .deadlines = Some(
+            (1..=5)
+                .map(|i| deadline(i, now + i as i64 * 86400))
+                .collect(),
+        );
+        let service = data_service(&MockProvider::default(), &repository);
+        let query = DeadlineQuery {
+            days: Some(4),
+            limit: None,
+        };
+        let page = PageQuery {
+            page: Some(2),
+            per_page: Some(3),
+        };
+
+        let deadlines = service
+            .get_deadlines_page("token", &query, &page)
+            .await
+            .unwrap();
+        assert_eq!(deadlines.total, 4);
+        assert_eq!(
+            deadlines.items.iter().map(|d| d.id).collect::<Vec<_>>(),
+            [4]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_settings_default_until_saved() {
+        let repository = MockRepository::with_tokens(&["token"]);
+        let service = data_service(&MockProvider::default(), &repository);
+        assert_eq!(
+            service.get_settings("token").await.unwrap(),
+            UserSettings::default()
+        );
+
+        let settings = UserSettings {
+            language: "kk".to_string(),
+            timezone: "Asia/Aqtobe".to_string(),
+            sound: None,
+            badge: false,
+        };
+        service.update_settings("token", &settings).await.unwrap();
+        assert_eq!(service.get_settings("token").await.unwrap(), settings);
+
+        let invalid = UserSettings {
+            timezone: "Mars/Olympus".to_string(),
+            ..settings.clone()
+        };
+        assert!(matches!(
+            service.update_settings("token", &invalid).await,
+            Err(ServiceError::InvalidInput(field)) if field == "timezone"
+        ));
+        assert!(matches!(
+            service.update_settings("missing", &settings).await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_course_grades_parse_percentages() {
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .grades = Some(vec![
+            grade(1, &[(10, "60.00 %"), (11, "-")]),
+            grade(2, &[(20, "90,5 %")]),
+        ]);
+        let service = data_service(&MockProvider::default(), &repository);
+
+        let items = service.get_course_grades("token", 1).await.unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].percentageformatted, "60.00 %");
+        assert_eq!(items[0].percentage, Some(60.0));
+        assert_eq!(items[1].percentage, None);
+        assert_eq!(
+            service.get_course_grades("token", 2).await.unwrap()[0].percentage,
+            Some(90.5)
+        );
+        assert!(matches!(
+            service.get_course_grades("token", 3).await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+    }
+}
@@ -1,11 +1,22 @@
-use crate::models::course::Course;
-use crate::models::deadline::Deadline;
-use crate::models::grade::{Grade, GradeOverview, GradesOverview};
-use crate::models::token::Token;
+use crate::models::broadcast::BroadcastSegment;
+use crate::models::calendar::CalendarFeed;
+use crate::models::course::{Course, CourseSearchQuery};
+use crate::models::course_content::CourseContent;
+use crate::models::dashboard::Dashboard;
+use crate::models::deadline::{Deadline, DeadlineQuery};
+use crate::models::grade::{CourseGradeItem, Grade, GradeMark, GradeOverview, GradesOverview};
+use crate::models::notification::{Notification, NotificationKind};
+use crate::models::pagination::{Page, PageQuery};
+use crate::models::preferences::Preferences;
+use crate::models::registration::{RegistrationOutcome, RegistrationReport};
+use crate::models::reminder::{DeadlineSnooze, SentReminder, SnoozeRequest};
+use crate::models::settings::UserSettings;
+use crate::models::sync::SyncedData;
+use crate::models::token::{DeviceTokenUpdate, Token};
+use crate::models::unread::UnreadCourse;
 use crate::models::user::User;
+use crate::models::user_list::{RegisteredUser, UserListQuery};
 use async_trait::async_trait;
-use mongodb::bson::Document;
-use mongodb::Cursor;
 
 use super::errors::ServiceError;
 
@@ -16,6 +27,9 @@ pub trait DataServiceInterfaces:
     + CourseServiceInterface
     + GradeServiceInterface
     + DeadlineServiceInterface
+    + PreferencesServiceInterface
+    + CalendarServiceInterface
+    + UnreadServiceInterface
     + Send
     + Sync
 {
@@ -24,30 +38,86 @@ pub trait DataServiceInterfaces:
 #[async_trait]
 pub trait TokenServiceInterface {
     async fn delete_one_user(&self, token: &str) -> Result<(), ServiceError>;
-    async fn find_all_tokens(
+    /// Drops one device of the user, keeping the account and its data.
+    async fn unregister_device(&self, token: &str, device_token: &str) -> Result<(), ServiceError>;
+    /// Adds the device, or swaps it in for the update's previous device token.
+    async fn update_device_token(
         &self,
-        limit: i64,
-        skip: u64,
-    ) -> Result<Cursor<Document>, ServiceError>;
+        token: &str,
+        update: &DeviceTokenUpdate,
+    ) -> Result<(), ServiceError>;
+    /// Registers the user's webhook, replacing any earlier one; returns the normalized URL.
+    async fn set_webhook(&self, token: &str, url: &str) -> Result<String, ServiceError>;
+    async fn remove_webhook(&self, token: &str) -> Result<(), ServiceError>;
+    async fn find_all_tokens(&self, limit: i64, skip: u64) -> Result<Vec<Token>, ServiceError>;
     async fn fetch_and_update_data(&self, token: &str) -> Result<(), ServiceError>;
-    async fn register_user(&self, tokens: &Token) -> Result<(), ServiceError>;
+    /// Syncs a registered user right away and returns what was stored.
+    async fn refresh_data(&self, token: &str) -> Result<SyncedData, ServiceError>;
+    async fn register_user(&self, tokens: &Token) -> Result<RegistrationOutcome, ServiceError>;
+    async fn register_users(&self, tokens: &[Token]) -> Vec<RegistrationReport>;
+    /// Fetches data a best-effort registration had to leave out.
+    async fn backfill_pending(&self, token: &str) -> Result<(), ServiceError>;
+    async fn find_tokens_by_user_id(&self, userid: i64) -> Result<Vec<Token>, ServiceError>;
+    /// Pages by the last token seen, so recipients dropping out of the
+    /// segment mid-broadcast don't shift later pages.
+    async fn find_broadcast_recipients(
+        &self,
+        segment: &BroadcastSegment,
+        after: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<Token>, ServiceError>;
+    /// Remembers when the user was last synced and whether that failed.
+    async fn record_sync(&self, token: &str, error: Option<String>) -> Result<(), ServiceError>;
+    async fn list_users(&self, query: &UserListQuery) -> Result<Vec<RegisteredUser>, ServiceError>;
 }
 
 #[async_trait]
 pub trait UserServiceInterface {
     async fn update_user(&self, token: &str) -> Result<User, ServiceError>;
     async fn get_user(&self, token: &str) -> Result<User, ServiceError>;
+    /// Stored data for the app's launch screen in one call.
+    async fn get_dashboard(&self, token: &str) -> Result<Dashboard, ServiceError>;
 }
 
 #[async_trait]
 pub trait CourseServiceInterface {
     async fn get_courses(&self, token: &str) -> Result<Vec<Course>, ServiceError>;
+    async fn search_courses(
+        &self,
+        token: &str,
+        query: &CourseSearchQuery,
+    ) -> Result<Vec<Course>, ServiceError>;
     async fn update_courses(&self, token: &str, user: &User) -> Result<Vec<Course>, ServiceError>;
+    async fn get_course_contents(&self, token: &str) -> Result<Vec<CourseContent>, ServiceError>;
+    /// Replaces the stored contents of one course.
+    async fn update_course_contents(
+        &self,
+        token: &str,
+        course_id: i64,
+        contents: &[CourseContent],
+    ) -> Result<(), ServiceError>;
+    /// Drops the stored contents of courses other than `course_ids`.
+    async fn retain_course_contents(
+        &self,
+        token: &str,
+        course_ids: &[i64],
+    ) -> Result<(), ServiceError>;
 }
 
 #[async_trait]
 pub trait GradeServiceInterface {
     async fn get_grades(&self, token: &str) -> Result<Vec<Grade>, ServiceError>;
+    /// One page of the stored grades, a course per entry.
+    async fn get_grades_page(
+        &self,
+        token: &str,
+        query: &PageQuery,
+    ) -> Result<Page<Grade>, ServiceError>;
+    async fn get_course_grades(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Vec<CourseGradeItem>, ServiceError>;
     async fn fetch_grades(
         &self,
         token: &str,
@@ -60,6 +130,12 @@ pub trait GradeServiceInterface {
         user: &User,
         courses: &[Course],
     ) -> Result<(), ServiceError>;
+    async fn update_grades_for_course(
+        &self,
+        token: &str,
+        course_id: i64,
+        grades: &[Grade],
+    ) -> Result<(), ServiceError>;
     async fn get_grades_overview(&self, token: &str) -> Result<Vec<GradeOverview>, ServiceError>;
     async fn fetch_grades_overview(
         &self,
@@ -71,15 +147,118 @@ pub trait GradeServiceInterface {
         token: &str,
         courses: &[Course],
     ) -> Result<(), ServiceError>;
+    async fn get_grade_marks(&self, token: &str) -> Result<Vec<GradeMark>, ServiceError>;
+    async fn update_grade_marks(
+        &self,
+        token: &str,
+        marks: &[GradeMark],
+    ) -> Result<(), ServiceError>;
 }
 
 #[async_trait]
 pub trait DeadlineServiceInterface {
     async fn get_deadlines(&self, token: &str) -> Result<Vec<Deadline>, ServiceError>;
+    async fn get_upcoming_deadlines(
+        &self,
+        token: &str,
+        query: &DeadlineQuery,
+    ) -> Result<Vec<Deadline>, ServiceError>;
+    /// Like `get_upcoming_deadlines`, paged in the repository; `limit` is not applied.
+    async fn get_deadlines_page(
+        &self,
+        token: &str,
+        query: &DeadlineQuery,
+        page: &PageQuery,
+    ) -> Result<Page<Deadline>, ServiceError>;
+    async fn get_course_deadlines(
+        &self,
+        token: &str,
+        course_id: i64,
+        query: &DeadlineQuery,
+    ) -> Result<Vec<Deadline>, ServiceError>;
     async fn fetch_deadlines(
         &self,
         token: &str,
         courses: &[Course],
     ) -> Result<Vec<Deadline>, ServiceError>;
     async fn update_deadlines(&self, token: &str, courses: &[Course]) -> Result<(), ServiceError>;
+    /// Holds back reminders for a stored deadline for the requested minutes.
+    async fn snooze_deadline(
+        &self,
+        token: &str,
+        deadline_id: i32,
+        request: &SnoozeRequest,
+    ) -> Result<DeadlineSnooze, ServiceError>;
+    async fn get_snoozes(&self, token: &str) -> Result<Vec<DeadlineSnooze>, ServiceError>;
+    async fn get_sent_reminders(&self, token: &str) -> Result<Vec<SentReminder>, ServiceError>;
+    /// Adds to the sent scheduled reminders, dropping those of passed deadlines.
+    async fn mark_reminders_sent(
+        &self,
+        token: &str,
+        reminders: &[SentReminder],
+    ) -> Result<(), ServiceError>;
+}
+
+#[async_trait]
+pub trait PreferencesServiceInterface {
+    async fn get_preferences(&self, token: &str) -> Result<Preferences, ServiceError>;
+    async fn update_preferences(
+        &self,
+        token: &str,
+        preferences: &Preferences,
+    ) -> Result<Preferences, ServiceError>;
+    async fn pause_notifications(
+        &self,
+        token: &str,
+        until: Option<i64>,
+    ) -> Result<Preferences, ServiceError>;
+    async fn resume_notifications(&self, token: &str) -> Result<Preferences, ServiceError>;
+    async fn set_course_muted(
+        &self,
+        token: &str,
+        course_id: i64,
+        muted: bool,
+    ) -> Result<Preferences, ServiceError>;
+    async fn get_settings(&self, token: &str) -> Result<UserSettings, ServiceError>;
+    async fn update_settings(
+        &self,
+        token: &str,
+        settings: &UserSettings,
+    ) -> Result<UserSettings, ServiceError>;
+    /// Holds a push back until the user's quiet hours end.
+    async fn queue_notification(
+        &self,
+        token: &str,
+        notification: &Notification,
+    ) -> Result<(), ServiceError>;
+    async fn take_queued_notifications(
+        &self,
+        token: &str,
+    ) -> Result<Vec<Notification>, ServiceError>;
+}
+
+#[async_trait]
+pub trait CalendarServiceInterface {
+    async fn get_calendar_secret(&self, token: &str) -> Result<String, ServiceError>;
+    async fn rotate_calendar_secret(&self, token: &str) -> Result<String, ServiceError>;
+    async fn get_calendar_feed(&self, secret: &str) -> Result<CalendarFeed, ServiceError>;
+    /// The deadlines feed addressed by the user's token instead of a feed secret.
+    async fn get_deadlines_calendar(&self, token: &str) -> Result<CalendarFeed, ServiceError>;
+}
+
+#[async_trait]
+pub trait UnreadServiceInterface {
+    async fn get_unread_courses(&self, token: &str) -> Result<Vec<UnreadCourse>, ServiceError>;
+    async fn add_unread(
+        &self,
+        token: &str,
+        course_id: i64,
+        kind: NotificationKind,
+        count: u32,
+    ) -> Result<(), ServiceError>;
+    async fn ack_unread(
+        &self,
+        token: &str,
+        course_ids: Option<&[i64]>,
+    ) -> Result<Vec<UnreadCourse>, ServiceError>;
 }
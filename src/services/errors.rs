@@ -8,6 +8,8 @@ pub enum ServiceError {
     UserAlreayExist,
     DataNotFound(String),
     DataIsEmpty(String),
+    InvalidInput(String),
+    DeviceTokenInUse,
     DatabaseError(String),
     ProviderError(String),
 }
@@ -21,6 +23,10 @@ impl fmt::Display for ServiceError {
             ServiceError::UserAlreayExist => write!(f, "User already exist"),
             ServiceError::DataNotFound(field) => write!(f, "{} not found", field),
             ServiceError::DataIsEmpty(field) => write!(f, "{} data is empty", field),
+            ServiceError::InvalidInput(field) => write!(f, "Invalid value of {}", field),
+            ServiceError::DeviceTokenInUse => {
+                write!(f, "Device token is registered to another user")
+            }
             ServiceError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
             ServiceError::ProviderError(msg) => write!(f, "Provider error: {}", msg),
         }
@@ -1,8 +1,126 @@
+use std::{error::Error as StdError, fmt};
+
 use async_trait::async_trait;
+use futures_util::future::join_all;
 
+use crate::models::history::{Delivery, DeliveryStatus};
 use crate::models::notification::Notification;
 
+/// Notifications [`EventProducerInterface::produce_notifications`] has in flight at once.
+pub const PRODUCE_CHUNK_SIZE: usize = 50;
+
+/// How far an accepted notification got.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Receipt {
+    /// Published to a broker; a downstream pusher delivers it.
+    Sent,
+    /// Accepted by the push provider, under the message id it returned.
+    Delivered(Option<String>),
+}
+
+impl From<Receipt> for Delivery {
+    fn from(receipt: Receipt) -> Self {
+        match receipt {
+            Receipt::Sent => DeliveryStatus::Sent.into(),
+            Receipt::Delivered(provider_id) => Delivery {
+                status: DeliveryStatus::Delivered,
+                provider_ids: provider_id.into_iter().collect(),
+                error: None,
+            },
+        }
+    }
+}
+
+/// Why a transport didn't accept a notification.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProduceError {
+    /// The transport may accept the notification if asked again later.
+    Transient(String),
+    /// Resending can't help, e.g. the payload was rejected.
+    Permanent(String),
+    /// The device token is no longer valid; the device should be dropped.
+    /// Only for unambiguous dead-token signals: anything a misconfigured
+    /// transport could also cause is [`ProduceError::Permanent`], or one bad
+    /// setting would drop every device.
+    Unregistered(String),
+}
+
+impl ProduceError {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ProduceError::Transient(_))
+    }
+
+    pub fn is_unregistered(&self) -> bool {
+        matches!(self, ProduceError::Unregistered(_))
+    }
+}
+
+impl StdError for ProduceError {}
+
+impl fmt::Display for ProduceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProduceError::Transient(msg) => write!(f, "Transient delivery error: {}", msg),
+            ProduceError::Permanent(msg) => write!(f, "Delivery rejected: {}", msg),
+            ProduceError::Unregistered(msg) => write!(f, "Device unregistered: {}", msg),
+        }
+    }
+}
+
 #[async_trait]
 pub trait EventProducerInterface: Send + Sync {
-    async fn produce_notification(&self, msg: &Notification);
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError>;
+
+    /// Sends the notifications concurrently, a chunk at a time; the results
+    /// are in the order of `msgs`.
+    async fn produce_notifications(
+        &self,
+        msgs: &[Notification],
+    ) -> Vec<Result<Receipt, ProduceError>> {
+        let mut results = Vec::with_capacity(msgs.len());
+        for chunk in msgs.chunks(PRODUCE_CHUNK_SIZE) {
+            results.extend(join_all(chunk.iter().map(|msg| self.produce_notification(msg))).await);
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::NotificationKind;
+    use crate::services::mocks::MockEventProducer;
+
+    struct Unbatched(MockEventProducer);
+
+    #[async_trait]
+    impl EventProducerInterface for Unbatched {
+        async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+            self.0.produce_notification(msg).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_results_keep_notification_order() {
+        let producer = MockEventProducer::default();
+        let msgs: Vec<Notification> = (0..PRODUCE_CHUNK_SIZE * 2 + 1)
+            .map(|i| Notification::new(NotificationKind::Course, i.to_string(), String::new()))
+            .collect();
+
+        let results = Unbatched(producer.clone())
+            .produce_notifications(&msgs)
+            .await;
+
+        assert_eq!(results.len(), msgs.len());
+        assert!(results.iter().all(Result::is_ok));
+        let titles: Vec<_> = producer
+            .sent
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|n| n.1.clone())
+            .collect();
+        let expected: Vec<_> = msgs.iter().map(|msg| msg.title.clone()).collect();
+        assert_eq!(titles, expected);
+    }
 }
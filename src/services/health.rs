@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A dependency the service can't work without.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn check(&self) -> Result<(), String>;
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DependencyStatus {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Readiness {
+    pub ready: bool,
+    pub dependencies: BTreeMap<&'static str, DependencyStatus>,
+}
+
+/// Runs every check concurrently; a check slower than `timeout` counts as failed.
+pub async fn check_readiness(checks: &[Arc<dyn HealthCheck>], timeout: Duration) -> Readiness {
+    let results = join_all(checks.iter().map(|check| async move {
+        let result = match tokio::time::timeout(timeout, check.check()).await {
+            Ok(result) => result,
+            Err(_) => Err("timed out".to_string()),
+        };
+        (check.name(), result)
+    }))
+    .await;
+
+    let dependencies: BTreeMap<_, _> = results
+        .into_iter()
+        .map(|(name, result)| {
+            let status = DependencyStatus {
+                ok: result.is_ok(),
+                error: result.err(),
+            };
+            (name, status)
+        })
+        .collect();
+    Readiness {
+        ready: dependencies.values().all(|status| status.ok),
+        dependencies,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StaticCheck {
+        name: &'static str,
+        result: Result<(), String>,
+        hangs: bool,
+    }
+
+    #[async_trait]
+    impl HealthCheck for StaticCheck {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn check(&self) -> Result<(), String> {
+            if self.hangs {
+                std::future::pending::<()>().await;
+            }
+            self.result.clone()
+        }
+    }
+
+    fn check(name: &'static str, result: Result<(), String>, hangs: bool) -> Arc<dyn HealthCheck> {
+        Arc::new(StaticCheck {
+            name,
+            result,
+            hangs,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_each_dependency() {
+        let timeout = Duration::from_millis(50);
+        let healthy = [
+            check("mongodb", Ok(()), false),
+            check("moodle", Ok(()), false),
+        ];
+        assert!(check_readiness(&healthy, timeout).await.ready);
+
+        let degraded = [
+            check("mongodb", Ok(()), false),
+            check("moodle", Err("connection refused".to_string()), false),
+            check("kafka", Ok(()), true),
+        ];
+        let readiness = check_readiness(&degraded, timeout).await;
+        assert!(!readiness.ready);
+        assert!(readiness.dependencies["mongodb"].ok);
+        assert_eq!(
+            readiness.dependencies["moodle"].error.as_deref(),
+            Some("connection refused")
+        );
+        assert_eq!(
+            readiness.dependencies["kafka"].error.as_deref(),
+            Some("timed out")
+        );
+    }
+}
@@ -0,0 +1,480 @@
+use crate::models::history::{
+    delivery_stats, diff_history, Delivery, DeliveryStats, DeliveryStatsQuery, DeliveryStatus,
+    HistoryDiffQuery, HistoryEntry, InboxNotification, ItemHistory, NotificationDelivery,
+    UnreadNotifications,
+};
+use crate::models::notification::{Notification, NotificationKind};
+use crate::models::pagination::{Page, PageQuery};
+use crate::repositories::errors::RepositoryError;
+use async_trait::async_trait;
+use mongodb::bson::{oid::ObjectId, DateTime};
+
+use super::errors::ServiceError;
+use super::history_service_interfaces::HistoryServiceInterface;
+
+#[async_trait]
+pub trait HistoryRepositoryInterface: Send + Sync {
+    async fn save_entry(&self, entry: &HistoryEntry) -> Result<(), RepositoryError>;
+    /// Updates the newest entry with the key that is still in the `from` state.
+    async fn update_delivery(
+        &self,
+        token: &str,
+        key: &str,
+        from: DeliveryStatus,
+        delivery: &Delivery,
+    ) -> Result<(), RepositoryError>;
+    async fn find_entry(&self, id: ObjectId) -> Result<Option<HistoryEntry>, RepositoryError>;
+    async fn exists_since(
+        &self,
+        token: &str,
+        key: &str,
+        since: DateTime,
+    ) -> Result<bool, RepositoryError>;
+    /// Entries sent within `[from, to]`, oldest first.
+    async fn find_entries(
+        &self,
+        token: &str,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<Vec<HistoryEntry>, RepositoryError>;
+    /// Delivered entries, newest first.
+    async fn find_delivered(
+        &self,
+        token: &str,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Vec<HistoryEntry>, RepositoryError>;
+    async fn count_delivered(&self, token: &str) -> Result<u64, RepositoryError>;
+    /// Fails with `DataNotFound` unless the entry belongs to the token.
+    async fn mark_read(&self, token: &str, id: ObjectId) -> Result<(), RepositoryError>;
+    async fn count_unread(&self, token: &str) -> Result<u64, RepositoryError>;
+}
+
+pub struct HistoryService {
+    history_repository: Box<dyn HistoryRepositoryInterface>,
+    dedup_window_hours: i64,
+}
+
+/// Query bounds in unix seconds; open bounds span the whole history.
+fn window(from: Option<i64>, to: Option<i64>) -> (DateTime, DateTime) {
+    let from = DateTime::from_millis(from.unwrap_or(0) * 1000);
+    let to = to.map_or_else(DateTime::now, |to| DateTime::from_millis(to * 1000));
+    (from, to)
+}
+
+impl HistoryService {
+    pub fn new(
+        history_repository: Box<dyn HistoryRepositoryInterface>,
+        dedup_window_hours: i64,
+    ) -> Self {
+        Self {
+            history_repository,
+            dedup_window_hours,
+        }
+    }
+}
+
+#[async_trait]
+impl HistoryServiceInterface for HistoryService {
+    async fn was_sent(&self, token: &str, key: &str) -> Result<bool, ServiceError> {
+        let window_millis = self.dedup_window_hours * 60 * 60 * 1000;
+        let since = DateTime::from_millis(DateTime::now().timestamp_millis() - window_millis);
+        Ok(self
+            .history_repository
+            .exists_since(token, key, since)
+            .await?)
+    }
+
+    async fn record(
+        &self,
+        token: &str,
+        notification: &Notification,
+        delivery: Delivery,
+    ) -> Result<(), ServiceError> {
+        let Some(key) = &notification.idempotency_key else {
+            return Ok(());
+        };
+        let entry = HistoryEntry::new(token, key, notification, delivery, DateTime::now());
+        Ok(self.history_repository.save_entry(&entry).await?)
+    }
+
+    async fn update_delivery(
+        &self,
+        token: &str,
+        key: &str,
+        from: DeliveryStatus,
+        delivery: Delivery,
+    ) -> Result<(), ServiceError> {
+        Ok(self
+            .history_repository
+            .update_delivery(token, key, from, &delivery)
+            .await?)
+    }
+
+    async fn get_notification(
+        &self,
+        notification_id: &str,
+    ) -> Result<NotificationDelivery, ServiceError> {
+        let not_found = || ServiceError::DataNotFound("Notification".to_string());
+        let id = ObjectId::parse_str(notification_id).map_err(|_| not_found())?;
+        let entry = self.history_repository.find_entry(id).await?;
+        entry.map(Into::into).ok_or_else(not_found)
+    }
+
+    async fn get_history_diff(
+        &self,
+        token: &str,
+        query: &HistoryDiffQuery,
+    ) -> Result<Vec<ItemHistory>, ServiceError> {
+        let (from, to) = window(query.from, query.to);
+        let entries: Vec<HistoryEntry> = self
+            .history_repository
+            .find_entries(token, from, to)
+            .await?
+            .into_iter()
+            .filter(|entry| query.course_id.is_none_or(|id| entry.course_id == Some(id)))
+            .filter(|entry| query.item_id.is_none_or(|id| entry.item_id == Some(id)))
+            .collect();
+        Ok(diff_history(&entries))
+    }
+
+    async fn get_delivery_stats(
+        &self,
+        token: &str,
+        query: &DeliveryStatsQuery,
+    ) -> Result<DeliveryStats, ServiceError> {
+        let (from, to) = window(query.from, query.to);
+        let entries = self
+            .history_repository
+            .find_entries(token, from, to)
+            .await?;
+        Ok(delivery_stats(&entries))
+    }
+
+    async fn get_inbox(
+        &self,
+        token: &str,
+        query: &PageQuery,
+    ) -> Result<Page<InboxNotification>, ServiceError> {
+        let entries = self
+            .history_repository
+            .find_delivered(token, query.skip(), query.per_page())
+            .await?;
+        Ok(Page {
+            items: entries.into_iter().map(Into::into).collect(),
+            total: self.history_repository.count_delivered(token).await?,
+        })
+    }
+
+    async fn mark_read(
+        &self,
+        token: &str,
+        notification_id: &str,
+    ) -> Result<UnreadNotifications, ServiceError> {
+        let id = ObjectId::parse_str(notification_id)
+            .map_err(|_| ServiceError::DataNotFound("Notification".to_string()))?;
+        self.history_repository.mark_read(token, id).await?;
+        self.get_unread_count(token).await
+    }
+
+    async fn get_held(&self, token: &str, since: i64) -> Result<Vec<HistoryEntry>, ServiceError> {
+        let (from, to) = window(Some(since), None);
+        Ok(self
+            .history_repository
+            .find_entries(token, from, to)
+            .await?
+            .into_iter()
+            .filter(|entry| entry.delivery == DeliveryStatus::Held)
+            .collect())
+    }
+
+    async fn get_grade_changes(
+        &self,
+        token: &str,
+        since: i64,
+    ) -> Result<Vec<HistoryEntry>, ServiceError> {
+        let (from, to) = window(Some(since), None);
+        Ok(self
+            .history_repository
+            .find_entries(token, from, to)
+            .await?
+            .into_iter()
+            .filter(|entry| entry.kind == NotificationKind::Grade)
+            .collect())
+    }
+
+    async fn get_unread_count(&self, token: &str) -> Result<UnreadNotifications, ServiceError> {
+        Ok(UnreadNotifications {
+            unread: self.history_repository.count_unread(token).await?,
+        })
+    }
+
+    async fn count_pushed(&self, token: &str, since: i64) -> Result<usize, ServiceError> {
+        let (from, to) = window(Some(since), None);
+        let entries = self
+            .history_repository
+            .find_entries(token, from, to)
+            .await?;
+        Ok(delivery_stats(&entries).sent as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::{Notification, NotificationKind};
+    use std::sync::Mutex;
+
+    struct SeededHistory {
+        entries: Mutex<Vec<HistoryEntry>>,
+    }
+
+    impl SeededHistory {
+        fn new(entries: Vec<HistoryEntry>) -> Box<Self> {
+            Box::new(Self {
+                entries: Mutex::new(entries),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl HistoryRepositoryInterface for SeededHistory {
+        async fn save_entry(&self, _entry: &HistoryEntry) -> Result<(), RepositoryError> {
+            Ok(())
+        }
+
+        async fn update_delivery(
+            &self,
+            _token: &str,
+            _key: &str,
+            _from: DeliveryStatus,
+            _delivery: &Delivery,
+        ) -> Result<(), RepositoryError> {
+            Ok(())
+        }
+
+        async fn find_entry(&self, id: ObjectId) -> Result<Option<HistoryEntry>, RepositoryError> {
+            let entries = self.entries.lock().unwrap();
+            Ok(entries.iter().find(|entry| entry.id == Some(id)).cloned())
+        }
+
+        async fn exists_since(
+            &self,
+            _token: &str,
+            _key: &str,
+            _since: DateTime,
+        ) -> Result<bool, RepositoryError> {
+            Ok(false)
+        }
+
+        async fn find_entries(
+            &self,
+            token: &str,
+            from: DateTime,
+            to: DateTime,
+        ) -> Result<Vec<HistoryEntry>, RepositoryError> {
+            Ok(self
+                .entries
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|entry| {
+                    entry.token == token && entry.sent_at >= from && entry.sent_at <= to
+                })
+                .cloned()
+                .collect())
+        }
+
+        async fn find_delivered(
+            &self,
+            token: &str,
+            skip: u64,
+            limit: u64,
+        ) -> Result<Vec<HistoryEntry>, RepositoryError> {
+            let mut entries: Vec<HistoryEntry> = self
+                .entries
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|entry| entry.token == token && entry.delivery != DeliveryStatus::Failed)
+                .cloned()
+                .collect();
+            entries.sort_by_key(|entry| std::cmp::Reverse(entry.sent_at));
+            Ok(entries
+                .into_iter()
+                .skip(skip as usize)
+                .take(limit as usize)
+                .collect())
+        }
+
+        async fn count_delivered(&self, token: &str) -> Result<u64, RepositoryError> {
+            Ok(self
+                .entries
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|entry| entry.token == token && entry.delivery != DeliveryStatus::Failed)
+                .count() as u64)
+        }
+
+        async fn mark_read(&self, token: &str, id: ObjectId) -> Result<(), RepositoryError> {
+            let mut entries = self.entries.lock().unwrap();
+            let entry = entries
+                .iter_mut()
+                .find(|entry| entry.token == token && entry.id == Some(id))
+                .ok_or_else(|| RepositoryError::DataNotFound("Notification".to_string()))?;
+            entry.read = true;
+            Ok(())
+        }
+
+        async fn count_unread(&self, token: &str) -> Result<u64, RepositoryError> {
+            Ok(self
+                .entries
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|entry| {
+                    entry.token == token && entry.delivery != DeliveryStatus::Failed && !entry.read
+                })
+                .count() as u64)
+        }
+    }
+
+    fn entry(token: &str, delivery: DeliveryStatus, sent_at: i64) -> HistoryEntry {
+        let notification = Notification::new(
+            NotificationKind::Grade,
+            "Math".to_string(),
+            "New grade".to_string(),
+        );
+        HistoryEntry {
+            id: Some(ObjectId::new()),
+            ..HistoryEntry::new(
+                token,
+                "key",
+                &notification,
+                delivery.into(),
+                DateTime::from_millis(sent_at * 1000),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delivery_stats_count_outcomes_within_window() {
+        let entries = vec![
+            entry("token", DeliveryStatus::Sent, 100),
+            entry("token", DeliveryStatus::Failed, 200),
+            entry("token", DeliveryStatus::Sent, 300),
+            entry("token", DeliveryStatus::Failed, 400),
+            entry("other", DeliveryStatus::Sent, 300),
+        ];
+        let service = HistoryService::new(SeededHistory::new(entries), 24);
+
+        let all = service
+            .get_delivery_stats("token", &DeliveryStatsQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(all, DeliveryStats { sent: 2, failed: 2 });
+
+        let query = DeliveryStatsQuery {
+            from: Some(150),
+            to: Some(350),
+        };
+        let windowed = service.get_delivery_stats("token", &query).await.unwrap();
+        assert_eq!(windowed, DeliveryStats { sent: 1, failed: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_inbox_pages_delivered_notifications_newest_first() {
+        // Every outcome but a failure is listed, as the real repository does
+        let statuses = [
+            DeliveryStatus::Sent,
+            DeliveryStatus::Delivered,
+            DeliveryStatus::Held,
+        ];
+        let mut entries: Vec<HistoryEntry> = (0..25)
+            .map(|i| entry("token", statuses[i as usize % statuses.len()], i))
+            .collect();
+        entries.push(entry("token", DeliveryStatus::Failed, 100));
+        entries.push(entry("other", DeliveryStatus::Sent, 100));
+        let service = HistoryService::new(SeededHistory::new(entries), 24);
+
+        let first = service
+            .get_inbox("token", &PageQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(first.items.len(), 20);
+        assert_eq!(first.total, 25);
+        assert_eq!(first.items[0].sent_at, 24);
+        assert_eq!(first.items[0].category, NotificationKind::Grade);
+
+        let second = service
+            .get_inbox(
+                "token",
+                &PageQuery {
+                    page: Some(2),
+                    per_page: Some(10),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            second.items.iter().map(|n| n.sent_at).collect::<Vec<_>>(),
+            [14, 13, 12, 11, 10, 9, 8, 7, 6, 5]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_state_is_kept_per_notification() {
+        let entries = vec![
+            entry("token", DeliveryStatus::Sent, 100),
+            entry("token", DeliveryStatus::Delivered, 200),
+            entry("token", DeliveryStatus::Failed, 300),
+            entry("other", DeliveryStatus::Sent, 100),
+        ];
+        let first = entries[0].id.unwrap().to_hex();
+        let service = HistoryService::new(SeededHistory::new(entries), 24);
+        assert_eq!(service.get_unread_count("token").await.unwrap().unread, 2);
+
+        let unread = service.mark_read("token", &first).await.unwrap();
+        assert_eq!(unread.unread, 1);
+        let inbox = service
+            .get_inbox("token", &PageQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            inbox.items.iter().map(|n| n.read).collect::<Vec<_>>(),
+            [false, true]
+        );
+
+        for (token, id) in [("other", first.as_str()), ("token", "not-an-id")] {
+            assert!(matches!(
+                service.mark_read(token, id).await,
+                Err(ServiceError::DataNotFound(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notification_looked_up_by_id_with_its_delivery() {
+        let mut failed = entry("token", DeliveryStatus::Failed, 100);
+        failed.error = Some("Transient delivery error: unavailable".to_string());
+        let id = failed.id.unwrap().to_hex();
+        let service = HistoryService::new(SeededHistory::new(vec![failed]), 24);
+
+        let notification = service.get_notification(&id).await.unwrap();
+        assert_eq!(notification.status, DeliveryStatus::Failed);
+        assert_eq!(notification.created_at, 100);
+        assert_eq!(notification.updated_at, 100);
+        assert_eq!(
+            notification.error.as_deref(),
+            Some("Transient delivery error: unavailable")
+        );
+
+        for id in [ObjectId::new().to_hex(), "not-an-id".to_string()] {
+            assert!(matches!(
+                service.get_notification(&id).await,
+                Err(ServiceError::DataNotFound(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use crate::models::history::{
+    Delivery, DeliveryStats, DeliveryStatsQuery, DeliveryStatus, HistoryDiffQuery, HistoryEntry,
+    InboxNotification, ItemHistory, NotificationDelivery, UnreadNotifications,
+};
+use crate::models::notification::Notification;
+use crate::models::pagination::{Page, PageQuery};
+use async_trait::async_trait;
+
+use super::errors::ServiceError;
+
+#[async_trait]
+pub trait HistoryServiceInterface: Send + Sync {
+    /// Whether a notification with this idempotency key went out within the dedup window.
+    async fn was_sent(&self, token: &str, key: &str) -> Result<bool, ServiceError>;
+    async fn record(
+        &self,
+        token: &str,
+        notification: &Notification,
+        delivery: Delivery,
+    ) -> Result<(), ServiceError>;
+    /// Moves the latest recorded notification with this key on from the
+    /// `from` state, e.g. when a queued push finally goes out.
+    async fn update_delivery(
+        &self,
+        token: &str,
+        key: &str,
+        from: DeliveryStatus,
+        delivery: Delivery,
+    ) -> Result<(), ServiceError>;
+    /// Any user's notification with its delivery state, by id.
+    async fn get_notification(
+        &self,
+        notification_id: &str,
+    ) -> Result<NotificationDelivery, ServiceError>;
+    /// Per-item value changes notified to the user within the queried range.
+    async fn get_history_diff(
+        &self,
+        token: &str,
+        query: &HistoryDiffQuery,
+    ) -> Result<Vec<ItemHistory>, ServiceError>;
+    /// Sent and failed notifications of the user within the queried window.
+    async fn get_delivery_stats(
+        &self,
+        token: &str,
+        query: &DeliveryStatsQuery,
+    ) -> Result<DeliveryStats, ServiceError>;
+    /// One page of the notifications delivered to the user, newest first.
+    async fn get_inbox(
+        &self,
+        token: &str,
+        query: &PageQuery,
+    ) -> Result<Page<InboxNotification>, ServiceError>;
+    /// Marks one of the user's notifications read and returns what is left unread.
+    async fn mark_read(
+        &self,
+        token: &str,
+        notification_id: &str,
+    ) -> Result<UnreadNotifications, ServiceError>;
+    /// Changes held for the digest since `since`, in unix seconds, oldest first.
+    async fn get_held(&self, token: &str, since: i64) -> Result<Vec<HistoryEntry>, ServiceError>;
+    /// Grade changes found since `since`, in unix seconds, oldest first.
+    async fn get_grade_changes(
+        &self,
+        token: &str,
+        since: i64,
+    ) -> Result<Vec<HistoryEntry>, ServiceError>;
+    async fn get_unread_count(&self, token: &str) -> Result<UnreadNotifications, ServiceError>;
+    /// Notifications pushed to the user since `since`, in unix seconds.
+    async fn count_pushed(&self, token: &str, since: i64) -> Result<usize, ServiceError>;
+}
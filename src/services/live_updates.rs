@@ -0,0 +1,74 @@
+use crate::models::notification::ChangeEvent;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// Events a slow client may fall behind by before it starts missing them.
+const CHANNEL_CAPACITY: usize = 32;
+
+/// Fans detected changes out to the live clients connected for a token.
+#[derive(Default)]
+pub struct LiveUpdates {
+    channels: Mutex<HashMap<String, broadcast::Sender<ChangeEvent>>>,
+}
+
+impl LiveUpdates {
+    pub fn subscribe(&self, token: &str) -> broadcast::Receiver<ChangeEvent> {
+        self.channels
+            .lock()
+            .unwrap()
+            .entry(token.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    pub fn is_watched(&self, token: &str) -> bool {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(token)
+            .is_some_and(|sender| sender.receiver_count() > 0)
+    }
+
+    pub fn publish(&self, token: &str, event: ChangeEvent) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(sender) = channels.get(token) {
+            // Sending only fails once every client is gone
+            if sender.send(event).is_err() {
+                channels.remove(token);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::NotificationKind;
+
+    fn event(title: &str) -> ChangeEvent {
+        ChangeEvent {
+            category: NotificationKind::Grade,
+            title: title.to_string(),
+            body: "New grade".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_reach_subscribers_of_the_token_only() {
+        let live_updates = LiveUpdates::default();
+        live_updates.publish("token", event("Dropped"));
+        let mut receiver = live_updates.subscribe("token");
+        let mut other = live_updates.subscribe("other");
+        assert!(live_updates.is_watched("token"));
+
+        live_updates.publish("token", event("Math"));
+        assert_eq!(receiver.recv().await.unwrap(), event("Math"));
+        assert!(other.try_recv().is_err());
+
+        drop(receiver);
+        assert!(!live_updates.is_watched("token"));
+        live_updates.publish("token", event("Math"));
+        assert!(live_updates.channels.lock().unwrap().get("token").is_none());
+    }
+}
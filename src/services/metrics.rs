@@ -0,0 +1,120 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::time::Duration;
+
+use crate::models::notification::NotificationKind;
+
+/// Process-wide Prometheus metrics, exported at `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    notifications_sent: IntCounterVec,
+    sync_errors: IntCounter,
+    provider_request_duration: HistogramVec,
+    registered_users: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let notifications_sent = IntCounterVec::new(
+            Opts::new(
+                "notifications_sent",
+                "Notifications handed to the transport",
+            ),
+            &["kind"],
+        )
+        .unwrap();
+        let sync_errors =
+            IntCounter::new("sync_errors", "Tokens whose producer pass failed").unwrap();
+        let provider_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "provider_request_duration_seconds",
+                "Duration of Moodle requests",
+            )
+            .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]),
+            &["endpoint"],
+        )
+        .unwrap();
+        let registered_users =
+            IntGauge::new("registered_users", "Users stored in the database").unwrap();
+
+        let registry = Registry::new();
+        registry
+            .register(Box::new(notifications_sent.clone()))
+            .unwrap();
+        registry.register(Box::new(sync_errors.clone())).unwrap();
+        registry
+            .register(Box::new(provider_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(registered_users.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            notifications_sent,
+            sync_errors,
+            provider_request_duration,
+            registered_users,
+        }
+    }
+
+    pub fn notification_sent(&self, kind: NotificationKind) {
+        self.notifications_sent
+            .with_label_values(&[kind.as_str()])
+            .inc();
+    }
+
+    pub fn sync_errors(&self, errors: u64) {
+        self.sync_errors.inc_by(errors);
+    }
+
+    pub fn provider_request(&self, endpoint: &str, duration: Duration) {
+        self.provider_request_duration
+            .with_label_values(&[endpoint])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn set_registered_users(&self, users: u64) {
+        self.registered_users.set(users as i64);
+    }
+
+    /// Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics are always encodable");
+        String::from_utf8(buffer).expect("text encoder writes utf-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_exports_recorded_metrics() {
+        let metrics = Metrics::new();
+        metrics.notification_sent(NotificationKind::Grade);
+        metrics.notification_sent(NotificationKind::Grade);
+        metrics.sync_errors(3);
+        metrics.provider_request("core_webservice_get_site_info", Duration::from_millis(120));
+        metrics.set_registered_users(42);
+
+        let text = metrics.encode();
+        assert!(text.contains("notifications_sent{kind=\"grade\"} 2"));
+        assert!(text.contains("sync_errors 3"));
+        assert!(text.contains(
+            "provider_request_duration_seconds_bucket{endpoint=\"core_webservice_get_site_info\",le=\"0.25\"} 1"
+        ));
+        assert!(text.contains("registered_users 42"));
+    }
+}
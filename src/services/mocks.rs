@@ -0,0 +1,1233 @@
+use crate::models::broadcast::BroadcastSegment;
+use crate::models::cohort::Cohort;
+use crate::models::course::Course;
+use crate::models::course_content::{ContentSection, CourseContent};
+use crate::models::dead_letter::DeadLetter;
+use crate::models::deadline::{Deadline, Events};
+use crate::models::grade::{
+    Grade, GradeItems, GradeMark, GradeOverview, GradesOverview, UserGrades,
+};
+use crate::models::history::{
+    delivery_stats, Delivery, DeliveryStats, DeliveryStatsQuery, DeliveryStatus, HistoryDiffQuery,
+    HistoryEntry, InboxNotification, ItemHistory, NotificationDelivery, UnreadNotifications,
+};
+use crate::models::notification::{Notification, NotificationKind};
+use crate::models::pagination::{Page, PageQuery};
+use crate::models::preferences::{ExamPeriod, Preferences};
+use crate::models::registration::BackfillResource;
+use crate::models::reminder::{DeadlineSnooze, ReminderEntry, SentReminder};
+use crate::models::settings::UserSettings;
+use crate::models::stats::{
+    notifications_since, AdminStats, CycleReport, CycleSummary, DailyNotificationCount, UserStats,
+};
+use crate::models::token::{Device, Platform, Token, MAX_DEVICES};
+use crate::models::unread::UnreadCourse;
+use crate::models::user::User;
+use crate::models::user_list::{RegisteredUser, SyncStatus, UserListQuery, USERS_PAGE_SIZE};
+use crate::repositories::errors::RepositoryError;
+use crate::services::data_service::{
+    CourseRepositoryInterface, DeadlineRepositoryInterface, GradeRepositoryInterface,
+    PreferencesRepositoryInterface, RepositoryInterfaces, TokenRepositoryInterface,
+    UnreadRepositoryInterface, UserRepositoryInterface,
+};
+use crate::services::errors::ServiceError;
+use crate::services::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
+use crate::services::history_service_interfaces::HistoryServiceInterface;
+use crate::services::producer_service::{
+    DeadLetterRepositoryInterface, ExamPeriodRepositoryInterface,
+};
+use crate::services::provider_interfaces::DataProviderInterface;
+use crate::services::reminder_service::ReminderRepositoryInterface;
+use crate::services::stats_service_interfaces::StatsServiceInterface;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+pub fn user(userid: i64) -> User {
+    serde_json::from_value(json!({
+        "username": format!("user{}", userid),
+        "fullname": format!("User {}", userid),
+        "userid": userid,
+    }))
+    .unwrap()
+}
+
+pub fn course(id: i64) -> Course {
+    serde_json::from_value(json!({
+        "id": id,
+        "fullname": format!("Course {}", id),
+        "enddate": i64::MAX,
+    }))
+    .unwrap()
+}
+
+pub fn deadline(id: i32, timeusermidnight: i64) -> Deadline {
+    Deadline {
+        id,
+        name: format!("Task {}", id),
+        timeusermidnight,
+        formattedtime: String::new(),
+        coursename: None,
+        courseid: None,
+        timestart: None,
+    }
+}
+
+pub fn grade(courseid: i64, items: &[(i64, &str)]) -> Grade {
+    Grade {
+        coursename: Some(format!("Course {}", courseid)),
+        courseid,
+        gradeitems: items
+            .iter()
+            .map(|(id, percentage)| GradeItems {
+                id: *id,
+                itemname: format!("Item {}", id),
+                percentageformatted: percentage.to_string(),
+                itemtype: Some("mod".to_string()),
+                grademax: Some(100.0),
+                rangeformatted: Some("0&ndash;100".to_string()),
+                gradedategraded: None,
+                gradeislocked: false,
+                feedback: None,
+            })
+            .collect(),
+    }
+}
+
+pub fn grade_overview(courseid: i64, grade: &str) -> GradeOverview {
+    serde_json::from_value(json!({
+        "course_name": format!("Course {}", courseid),
+        "courseid": courseid,
+        "grade": grade,
+        "rawgrade": grade,
+    }))
+    .unwrap()
+}
+
+pub fn device(token: &str, platform: Option<Platform>) -> Device {
+    Device {
+        token: token.to_string(),
+        platform,
+    }
+}
+
+/// Pages like the repository: an empty list is an empty page, not an error.
+fn page<T>(
+    items: Result<Vec<T>, RepositoryError>,
+    skip: u64,
+    limit: u64,
+) -> Result<Page<T>, RepositoryError> {
+    let items = match items {
+        Err(RepositoryError::DataIsEmpty(_)) => Vec::new(),
+        items => items?,
+    };
+    Ok(Page {
+        total: items.len() as u64,
+        items: items
+            .into_iter()
+            .skip(skip as usize)
+            .take(limit as usize)
+            .collect(),
+    })
+}
+
+pub fn provider_error() -> reqwest::Error {
+    reqwest::Client::new().get("not a url").build().unwrap_err()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StoredUser {
+    pub devices: Vec<Device>,
+    pub user: Option<User>,
+    pub courses: Option<Vec<Course>>,
+    pub grades: Option<Vec<Grade>>,
+    pub grades_overview: Option<Vec<GradeOverview>>,
+    pub deadlines: Option<Vec<Deadline>>,
+    pub preferences: Option<Preferences>,
+    pub settings: Option<UserSettings>,
+    pub queued: Vec<Notification>,
+    pub grade_marks: Vec<GradeMark>,
+    pub calendar_secret: Option<String>,
+    pub unread: Vec<UnreadCourse>,
+    pub snoozes: Vec<DeadlineSnooze>,
+    pub sent_reminders: Vec<SentReminder>,
+    pub course_contents: Vec<CourseContent>,
+    pub pending_backfill: Vec<BackfillResource>,
+    pub sync: Option<SyncStatus>,
+}
+
+impl StoredUser {
+    pub fn device_tokens(&self) -> Vec<&str> {
+        self.devices
+            .iter()
+            .map(|device| device.token.as_str())
+            .collect()
+    }
+}
+
+/// In-memory stand-in for `DataRepository`. Clones share the same storage.
+#[derive(Clone, Default)]
+pub struct MockRepository {
+    pub users: Arc<Mutex<HashMap<String, StoredUser>>>,
+    /// Saves of these fields report success without storing anything.
+    pub dropped_saves: Arc<Mutex<HashSet<&'static str>>>,
+}
+
+impl MockRepository {
+    pub fn with_tokens(tokens: &[&str]) -> Self {
+        let repository = Self::default();
+        for token in tokens {
+            repository
+                .users
+                .lock()
+                .unwrap()
+                .insert(token.to_string(), StoredUser::default());
+        }
+        repository
+    }
+
+    pub fn stored(&self, token: &str) -> Option<StoredUser> {
+        self.users.lock().unwrap().get(token).cloned()
+    }
+
+    fn drops(&self, field: &str) -> bool {
+        self.dropped_saves.lock().unwrap().contains(field)
+    }
+
+    fn update<F>(&self, token: &str, f: F) -> Result<(), RepositoryError>
+    where
+        F: FnOnce(&mut StoredUser),
+    {
+        if let Some(stored) = self.users.lock().unwrap().get_mut(token) {
+            f(stored);
+        }
+        Ok(())
+    }
+
+    fn find<T, F>(&self, token: &str, field: &str, f: F) -> Result<Vec<T>, RepositoryError>
+    where
+        F: FnOnce(&StoredUser) -> Option<Vec<T>>,
+    {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound(field.to_string()))?;
+        let data = f(stored).ok_or_else(|| RepositoryError::DataNotFound(field.to_string()))?;
+        if data.is_empty() {
+            return Err(RepositoryError::DataIsEmpty(field.to_string()));
+        }
+        Ok(data)
+    }
+}
+
+impl RepositoryInterfaces for MockRepository {}
+
+#[async_trait]
+impl TokenRepositoryInterface for MockRepository {
+    async fn find_token(&self, token: &Token) -> Result<(), RepositoryError> {
+        if self.users.lock().unwrap().contains_key(&token.token) {
+            return Err(RepositoryError::UserAlreadyExists);
+        }
+        Ok(())
+    }
+
+    async fn save_tokens(&self, token: &Token) -> Result<(), RepositoryError> {
+        self.find_token(token).await?;
+        self.users.lock().unwrap().insert(
+            token.token.clone(),
+            StoredUser {
+                devices: token.device().into_iter().collect(),
+                ..Default::default()
+            },
+        );
+        Ok(())
+    }
+
+    async fn find_all_device_tokens(
+        &self,
+        limit: i64,
+        skip: u64,
+    ) -> Result<Vec<Token>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let mut tokens: Vec<&String> = users.keys().collect();
+        tokens.sort();
+        // Like MongoDB, a limit of 0 reads everything
+        let limit = usize::try_from(limit).ok().filter(|&limit| limit > 0);
+        Ok(tokens
+            .into_iter()
+            .skip(skip as usize)
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|token| {
+                let mut tokens = Token::new(token.clone(), None);
+                tokens.devices = users[token].devices.clone();
+                tokens
+            })
+            .collect())
+    }
+
+    async fn delete(&self, token: &str) -> Result<(), RepositoryError> {
+        self.users
+            .lock()
+            .unwrap()
+            .remove(token)
+            .map(|_| ())
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))
+    }
+
+    async fn find_calendar_secret(&self, token: &str) -> Result<Option<String>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.calendar_secret.clone())
+    }
+
+    async fn save_calendar_secret(&self, token: &str, secret: &str) -> Result<(), RepositoryError> {
+        self.update(token, |stored| {
+            stored.calendar_secret = Some(secret.to_string())
+        })
+    }
+
+    async fn find_token_by_calendar_secret(&self, secret: &str) -> Result<String, RepositoryError> {
+        self.users
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, stored)| stored.calendar_secret.as_deref() == Some(secret))
+            .map(|(token, _)| token.clone())
+            .ok_or_else(|| RepositoryError::DataNotFound("Calendar".to_string()))
+    }
+
+    async fn find_tokens_by_device_token(
+        &self,
+        device_token: &str,
+    ) -> Result<Vec<String>, RepositoryError> {
+        Ok(self
+            .users
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, stored)| {
+                stored
+                    .devices
+                    .iter()
+                    .any(|device| device.token == device_token)
+            })
+            .map(|(token, _)| token.clone())
+            .collect())
+    }
+
+    async fn find_devices(&self, token: &str) -> Result<Vec<Device>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.devices.clone())
+    }
+
+    async fn pull_devices(
+        &self,
+        token: &str,
+        device_tokens: &[&str],
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| {
+            stored
+                .devices
+                .retain(|device| !device_tokens.contains(&device.token.as_str()))
+        })
+    }
+
+    async fn push_device(&self, token: &str, device: &Device) -> Result<(), RepositoryError> {
+        self.update(token, |stored| {
+            stored.devices.push(device.clone());
+            let excess = stored.devices.len().saturating_sub(MAX_DEVICES);
+            stored.devices.drain(..excess);
+        })
+    }
+
+    async fn find_pending_backfill(
+        &self,
+        token: &str,
+    ) -> Result<Vec<BackfillResource>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.pending_backfill.clone())
+    }
+
+    async fn save_pending_backfill(
+        &self,
+        token: &str,
+        pending: &[BackfillResource],
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| stored.pending_backfill = pending.to_vec())
+    }
+
+    async fn find_tokens_by_user_id(&self, userid: i64) -> Result<Vec<Token>, RepositoryError> {
+        Ok(self
+            .users
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, stored)| stored.user.as_ref().map(|user| user.userid) == Some(userid))
+            .map(|(token, stored)| Token {
+                devices: stored.devices.clone(),
+                ..Token::new(token.clone(), None)
+            })
+            .collect())
+    }
+
+    async fn find_broadcast_recipients(
+        &self,
+        segment: &BroadcastSegment,
+        after: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<Token>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let mut tokens: Vec<&String> = users.keys().collect();
+        tokens.sort();
+        Ok(tokens
+            .into_iter()
+            .filter(|token| after.is_none_or(|after| token.as_str() > after))
+            .filter(|token| {
+                let stored = &users[*token];
+                !stored
+                    .preferences
+                    .as_ref()
+                    .is_some_and(|preferences| preferences.notifications_off)
+                    && stored.devices.iter().any(|device| {
+                        segment
+                            .platform
+                            .is_none_or(|platform| device.platform == Some(platform))
+                    })
+                    && segment.course_id.is_none_or(|id| {
+                        stored
+                            .courses
+                            .iter()
+                            .flatten()
+                            .any(|course| course.id == id)
+                    })
+            })
+            .take(limit as usize)
+            .map(|token| Token {
+                devices: users[token].devices.clone(),
+                ..Token::new(token.clone(), None)
+            })
+            .collect())
+    }
+
+    async fn save_sync_status(
+        &self,
+        token: &str,
+        status: &SyncStatus,
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| stored.sync = Some(status.clone()))
+    }
+
+    /// Pages by token but leaves filtering to the database query.
+    async fn find_registered_users(
+        &self,
+        query: &UserListQuery,
+    ) -> Result<Vec<RegisteredUser>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let mut tokens: Vec<&String> = users.keys().collect();
+        tokens.sort();
+        Ok(tokens
+            .into_iter()
+            .skip(query.skip() as usize)
+            .take(USERS_PAGE_SIZE as usize)
+            .map(|token| {
+                let stored = &users[token];
+                RegisteredUser {
+                    token: token.clone(),
+                    has_device_token: !stored.devices.is_empty(),
+                    platform: stored.devices.first().and_then(|device| device.platform),
+                    userid: stored.user.as_ref().map(|user| user.userid),
+                    last_sync_at: stored
+                        .sync
+                        .as_ref()
+                        .map(|sync| sync.at.timestamp_millis() / 1000),
+                    sync_error: stored.sync.as_ref().and_then(|sync| sync.error.clone()),
+                }
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl UserRepositoryInterface for MockRepository {
+    async fn find_user_by_token(&self, token: &str) -> Result<User, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        stored
+            .user
+            .clone()
+            .ok_or_else(|| RepositoryError::DataIsEmpty("User".to_string()))
+    }
+
+    async fn save_user(&self, user: &User, token: &str) -> Result<(), RepositoryError> {
+        self.update(token, |stored| stored.user = Some(user.clone()))
+    }
+}
+
+#[async_trait]
+impl CourseRepositoryInterface for MockRepository {
+    async fn save_courses(&self, token: &str, courses: &[Course]) -> Result<(), RepositoryError> {
+        if self.drops("courses") {
+            return Ok(());
+        }
+        self.update(token, |stored| stored.courses = Some(courses.to_vec()))
+    }
+
+    async fn find_courses_by_token(&self, token: &str) -> Result<Vec<Course>, RepositoryError> {
+        self.find(token, "Courses", |stored| stored.courses.clone())
+    }
+
+    async fn find_course_contents(
+        &self,
+        token: &str,
+    ) -> Result<Vec<CourseContent>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.course_contents.clone())
+    }
+
+    async fn save_course_contents(
+        &self,
+        token: &str,
+        contents: &[CourseContent],
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| stored.course_contents = contents.to_vec())
+    }
+}
+
+#[async_trait]
+impl GradeRepositoryInterface for MockRepository {
+    async fn save_grades(&self, token: &str, grades: &[Grade]) -> Result<(), RepositoryError> {
+        if self.drops("grades") {
+            return Ok(());
+        }
+        self.update(token, |stored| stored.grades = Some(grades.to_vec()))
+    }
+
+    async fn find_grades_by_token(&self, token: &str) -> Result<Vec<Grade>, RepositoryError> {
+        self.find(token, "Grades", |stored| stored.grades.clone())
+    }
+
+    async fn find_grades_page(
+        &self,
+        token: &str,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Page<Grade>, RepositoryError> {
+        let grades = self.find_grades_by_token(token).await;
+        page(grades, skip, limit)
+    }
+
+    async fn save_grades_overview(
+        &self,
+        token: &str,
+        grades_overview: &GradesOverview,
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| {
+            stored.grades_overview = Some(grades_overview.grades.clone())
+        })
+    }
+
+    async fn find_grades_overview_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Vec<GradeOverview>, RepositoryError> {
+        self.find(token, "Grades", |stored| stored.grades_overview.clone())
+    }
+
+    async fn find_grade_marks_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Vec<GradeMark>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.grade_marks.clone())
+    }
+
+    async fn save_grade_marks(
+        &self,
+        token: &str,
+        marks: &[GradeMark],
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| stored.grade_marks = marks.to_vec())
+    }
+}
+
+#[async_trait]
+impl DeadlineRepositoryInterface for MockRepository {
+    async fn save_deadlines(
+        &self,
+        token: &str,
+        deadlines: &[Deadline],
+    ) -> Result<(), RepositoryError> {
+        if self.drops("deadlines") {
+            return Ok(());
+        }
+        self.update(token, |stored| stored.deadlines = Some(deadlines.to_vec()))
+    }
+
+    async fn find_deadlines_by_token(&self, token: &str) -> Result<Vec<Deadline>, RepositoryError> {
+        self.find(token, "Deadlines", |stored| stored.deadlines.clone())
+    }
+
+    async fn find_deadlines_page(
+        &self,
+        token: &str,
+        due: Option<(i64, i64)>,
+        skip: u64,
+        limit: u64,
+    ) -> Result<Page<Deadline>, RepositoryError> {
+        let deadlines = self.find_deadlines_by_token(token).await.map(|deadlines| {
+            deadlines
+                .into_iter()
+                .filter(|deadline| {
+                    due.is_none_or(|(from, to)| (from..=to).contains(&deadline.timeusermidnight))
+                })
+                .collect()
+        });
+        page(deadlines, skip, limit)
+    }
+
+    async fn find_deadlines_updated_at(
+        &self,
+        _token: &str,
+    ) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        Ok(None)
+    }
+
+    async fn find_snoozes(&self, token: &str) -> Result<Vec<DeadlineSnooze>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.snoozes.clone())
+    }
+
+    async fn save_snoozes(
+        &self,
+        token: &str,
+        snoozes: &[DeadlineSnooze],
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| stored.snoozes = snoozes.to_vec())
+    }
+
+    async fn find_sent_reminders(&self, token: &str) -> Result<Vec<SentReminder>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.sent_reminders.clone())
+    }
+
+    async fn save_sent_reminders(
+        &self,
+        token: &str,
+        sent: &[SentReminder],
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| stored.sent_reminders = sent.to_vec())
+    }
+}
+
+#[async_trait]
+impl PreferencesRepositoryInterface for MockRepository {
+    async fn find_preferences_by_token(&self, token: &str) -> Result<Preferences, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.preferences.clone().unwrap_or_default())
+    }
+
+    async fn save_preferences(
+        &self,
+        token: &str,
+        preferences: &Preferences,
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| {
+            stored.preferences = Some(preferences.clone())
+        })
+    }
+
+    async fn find_settings_by_token(&self, token: &str) -> Result<UserSettings, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.settings.clone().unwrap_or_default())
+    }
+
+    async fn save_settings(
+        &self,
+        token: &str,
+        settings: &UserSettings,
+    ) -> Result<(), RepositoryError> {
+        if !self.users.lock().unwrap().contains_key(token) {
+            return Err(RepositoryError::DataNotFound("User".to_string()));
+        }
+        self.update(token, |stored| stored.settings = Some(settings.clone()))
+    }
+
+    async fn push_queued_notification(
+        &self,
+        token: &str,
+        notification: &Notification,
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| stored.queued.push(notification.clone()))
+    }
+
+    async fn take_queued_notifications(
+        &self,
+        token: &str,
+    ) -> Result<Vec<Notification>, RepositoryError> {
+        let mut queued = Vec::new();
+        self.update(token, |stored| queued = std::mem::take(&mut stored.queued))?;
+        Ok(queued)
+    }
+}
+
+#[async_trait]
+impl UnreadRepositoryInterface for MockRepository {
+    async fn find_unread_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Vec<UnreadCourse>, RepositoryError> {
+        let users = self.users.lock().unwrap();
+        let stored = users
+            .get(token)
+            .ok_or_else(|| RepositoryError::DataNotFound("User".to_string()))?;
+        Ok(stored.unread.clone())
+    }
+
+    async fn save_unread(
+        &self,
+        token: &str,
+        unread: &[UnreadCourse],
+    ) -> Result<(), RepositoryError> {
+        self.update(token, |stored| stored.unread = unread.to_vec())
+    }
+}
+
+/// In-memory stand-in for `MoodleClient`. Every call is recorded in `calls`.
+#[derive(Clone, Default)]
+pub struct MockProvider {
+    pub invalid_tokens: Arc<Mutex<HashSet<String>>>,
+    pub users: Arc<Mutex<HashMap<String, User>>>,
+    pub courses: Arc<Mutex<Vec<Course>>>,
+    pub grades: Arc<Mutex<HashMap<i64, Vec<Grade>>>>,
+    pub deadlines: Arc<Mutex<HashMap<i64, Vec<Deadline>>>>,
+    pub grades_overview: Arc<Mutex<Vec<GradeOverview>>>,
+    pub contents: Arc<Mutex<HashMap<i64, Vec<ContentSection>>>>,
+    pub calls: Arc<Mutex<Vec<String>>>,
+    /// Calls starting with any of these prefixes fail.
+    pub failing_calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockProvider {
+    pub fn calls_to(&self, call: &str) -> usize {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|recorded| recorded.starts_with(call))
+            .count()
+    }
+
+    /// Yields once first, as a request would, so concurrent passes interleave.
+    async fn record(&self, token: &str, call: String) -> Result<(), reqwest::Error> {
+        tokio::task::yield_now().await;
+        let fails = self
+            .failing_calls
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|prefix| call.starts_with(prefix.as_str()));
+        self.calls.lock().unwrap().push(call);
+        if fails || self.invalid_tokens.lock().unwrap().contains(token) {
+            return Err(provider_error());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DataProviderInterface for MockProvider {
+    async fn get_user(&self, token: &str) -> Result<User, reqwest::Error> {
+        self.record(token, "get_user".to_string()).await?;
+        Ok(self
+            .users
+            .lock()
+            .unwrap()
+            .get(token)
+            .cloned()
+            .unwrap_or_else(|| user(1)))
+    }
+
+    async fn valid_token(&self, token: &str) -> Result<(), reqwest::Error> {
+        self.record(token, "valid_token".to_string()).await
+    }
+
+    async fn get_courses(&self, token: &str, _user_id: i64) -> Result<Vec<Course>, reqwest::Error> {
+        self.record(token, "get_courses".to_string()).await?;
+        Ok(self.courses.lock().unwrap().clone())
+    }
+
+    async fn get_grades_by_course_id(
+        &self,
+        token: &str,
+        _user_id: i64,
+        course_id: i64,
+    ) -> Result<UserGrades, reqwest::Error> {
+        self.record(token, format!("get_grades_by_course_id:{}", course_id))
+            .await?;
+        let usergrades = self
+            .grades
+            .lock()
+            .unwrap()
+            .get(&course_id)
+            .cloned()
+            .unwrap_or_default();
+        Ok(UserGrades { usergrades })
+    }
+
+    async fn get_deadline_by_course_id(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Events, reqwest::Error> {
+        self.record(token, format!("get_deadline_by_course_id:{}", course_id))
+            .await?;
+        let events = self
+            .deadlines
+            .lock()
+            .unwrap()
+            .get(&course_id)
+            .cloned()
+            .unwrap_or_default();
+        Ok(Events { events })
+    }
+
+    async fn get_grades_overview(&self, token: &str) -> Result<GradesOverview, reqwest::Error> {
+        self.record(token, "get_grades_overview".to_string())
+            .await?;
+        Ok(GradesOverview {
+            grades: self.grades_overview.lock().unwrap().clone(),
+        })
+    }
+
+    async fn get_course_contents(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Vec<ContentSection>, reqwest::Error> {
+        self.record(token, format!("get_course_contents:{}", course_id))
+            .await?;
+        Ok(self
+            .contents
+            .lock()
+            .unwrap()
+            .get(&course_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+/// The sound and badge a push arrives with.
+pub type Alert = (Option<String>, Option<u64>);
+
+/// Collects produced notifications as `(kind, title, body)`.
+#[derive(Clone, Default)]
+pub struct MockEventProducer {
+    pub sent: Arc<Mutex<Vec<(NotificationKind, String, String)>>>,
+    /// Device token of each sent notification, in order.
+    pub devices: Arc<Mutex<Vec<String>>>,
+    /// While set, every notification is rejected with this error.
+    pub failure: Arc<Mutex<Option<ProduceError>>>,
+    /// Size of each batch sent with `produce_notifications`.
+    pub batches: Arc<Mutex<Vec<usize>>>,
+    /// Sound and badge of each sent notification, in order.
+    pub alerts: Arc<Mutex<Vec<Alert>>>,
+}
+
+#[async_trait]
+impl EventProducerInterface for MockEventProducer {
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+        if let Some(e) = self.failure.lock().unwrap().clone() {
+            return Err(e);
+        }
+        self.sent
+            .lock()
+            .unwrap()
+            .push((msg.kind, msg.title.clone(), msg.body.clone()));
+        self.devices.lock().unwrap().push(msg.device_token.clone());
+        self.alerts
+            .lock()
+            .unwrap()
+            .push((msg.sound.clone(), msg.badge));
+        Ok(Receipt::Delivered(None))
+    }
+
+    async fn produce_notifications(
+        &self,
+        msgs: &[Notification],
+    ) -> Vec<Result<Receipt, ProduceError>> {
+        self.batches.lock().unwrap().push(msgs.len());
+        let mut results = Vec::new();
+        for msg in msgs {
+            results.push(self.produce_notification(msg).await);
+        }
+        results
+    }
+}
+
+/// In-memory stand-in for `StatsService`. Clones share the same storage.
+#[derive(Clone, Default)]
+pub struct MockStatsService {
+    /// Reported as the registered users, as no users collection backs the stand-in.
+    pub users: Arc<Mutex<UserStats>>,
+    pub notifications: Arc<Mutex<Vec<DailyNotificationCount>>>,
+    pub cycles: Arc<Mutex<Vec<CycleReport>>>,
+}
+
+#[async_trait]
+impl StatsServiceInterface for MockStatsService {
+    async fn record_notification(&self, kind: NotificationKind, cohort: Cohort) {
+        let now = Utc::now();
+        let day = now.format("%Y-%m-%d").to_string();
+        let mut counts = self.notifications.lock().unwrap();
+        let index = match counts
+            .iter()
+            .position(|count| count.day == day && count.kind == kind && count.cohort == cohort)
+        {
+            Some(index) => index,
+            None => {
+                counts.push(DailyNotificationCount {
+                    day,
+                    kind,
+                    cohort,
+                    count: 0,
+                    hours: HashMap::new(),
+                });
+                counts.len() - 1
+            }
+        };
+        let count = &mut counts[index];
+        count.count += 1;
+        *count.hours.entry(now.format("%H").to_string()).or_default() += 1;
+    }
+
+    async fn record_cycle(&self, report: &CycleReport) -> Result<(), ServiceError> {
+        self.cycles.lock().unwrap().push(report.clone());
+        Ok(())
+    }
+
+    async fn get_user_stats(&self) -> Result<UserStats, ServiceError> {
+        Ok(self.users.lock().unwrap().clone())
+    }
+
+    async fn get_stats(&self, days: i64) -> Result<AdminStats, ServiceError> {
+        let since = Utc::now() - Duration::days(days);
+        let since_day = since.format("%Y-%m-%d").to_string();
+        let notifications: Vec<DailyNotificationCount> = self
+            .notifications
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|count| count.day >= since_day)
+            .cloned()
+            .collect();
+        let cycles: Vec<CycleReport> = self
+            .cycles
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|report| report.started_at.timestamp_millis() >= since.timestamp_millis())
+            .cloned()
+            .collect();
+        Ok(AdminStats {
+            period_days: days,
+            users: self.get_user_stats().await?,
+            notifications_last_24h: notifications_since(
+                &notifications,
+                Utc::now() - Duration::hours(23),
+            ),
+            notifications,
+            cycles: CycleSummary::from_reports(&cycles),
+        })
+    }
+}
+
+/// In-memory stand-in for `ReminderRepository`. Clones share the same storage.
+#[derive(Clone, Default)]
+pub struct MockReminderRepository {
+    pub entries: Arc<Mutex<Vec<ReminderEntry>>>,
+}
+
+#[async_trait]
+impl ReminderRepositoryInterface for MockReminderRepository {
+    async fn save_reminder(&self, reminder: &ReminderEntry) -> Result<ObjectId, RepositoryError> {
+        let id = ObjectId::new();
+        self.entries.lock().unwrap().push(ReminderEntry {
+            id: Some(id),
+            ..reminder.clone()
+        });
+        Ok(id)
+    }
+
+    async fn find_pending(&self, token: &str) -> Result<Vec<ReminderEntry>, RepositoryError> {
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.token == token)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_reminder(&self, id: ObjectId) -> Result<(), RepositoryError> {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|entry| entry.id != Some(id));
+        Ok(())
+    }
+}
+
+/// In-memory stand-in for `DeadLetterRepository`. Clones share the same storage.
+#[derive(Clone, Default)]
+pub struct MockDeadLetterRepository {
+    pub dead_letters: Arc<Mutex<Vec<DeadLetter>>>,
+}
+
+#[async_trait]
+impl DeadLetterRepositoryInterface for MockDeadLetterRepository {
+    async fn save_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), RepositoryError> {
+        self.dead_letters.lock().unwrap().push(DeadLetter {
+            id: Some(ObjectId::new()),
+            ..dead_letter.clone()
+        });
+        Ok(())
+    }
+
+    async fn find_oldest(&self, limit: i64) -> Result<Vec<DeadLetter>, RepositoryError> {
+        let dead_letters = self.dead_letters.lock().unwrap();
+        Ok(dead_letters.iter().take(limit as usize).cloned().collect())
+    }
+
+    async fn delete_dead_letter(&self, id: ObjectId) -> Result<(), RepositoryError> {
+        self.dead_letters
+            .lock()
+            .unwrap()
+            .retain(|dead_letter| dead_letter.id != Some(id));
+        Ok(())
+    }
+
+    async fn record_redrive_failure(
+        &self,
+        id: ObjectId,
+        error: &str,
+    ) -> Result<(), RepositoryError> {
+        for dead_letter in self.dead_letters.lock().unwrap().iter_mut() {
+            if dead_letter.id == Some(id) {
+                dead_letter.error = error.to_string();
+                dead_letter.redrives += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// In-memory stand-in for `ExamPeriodRepository`. Clones share the same storage.
+#[derive(Clone, Default)]
+pub struct MockExamPeriodRepository {
+    pub global: Arc<Mutex<Option<ExamPeriod>>>,
+}
+
+#[async_trait]
+impl ExamPeriodRepositoryInterface for MockExamPeriodRepository {
+    async fn find_global(&self) -> Result<Option<ExamPeriod>, RepositoryError> {
+        Ok(self.global.lock().unwrap().clone())
+    }
+
+    async fn save_global(&self, period: Option<&ExamPeriod>) -> Result<(), RepositoryError> {
+        *self.global.lock().unwrap() = period.cloned();
+        Ok(())
+    }
+}
+
+/// Remembers every recorded idempotency key for as long as it lives.
+#[derive(Clone, Default)]
+pub struct MockHistoryService {
+    pub keys: Arc<Mutex<HashSet<(String, String)>>>,
+    pub entries: Arc<Mutex<Vec<HistoryEntry>>>,
+}
+
+impl MockHistoryService {
+    fn recorded_since(&self, token: &str, since: i64) -> Vec<HistoryEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| {
+                entry.token == token && entry.sent_at.timestamp_millis() >= since * 1000
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl HistoryServiceInterface for MockHistoryService {
+    async fn was_sent(&self, token: &str, key: &str) -> Result<bool, ServiceError> {
+        Ok(self
+            .keys
+            .lock()
+            .unwrap()
+            .contains(&(token.to_string(), key.to_string())))
+    }
+
+    async fn record(
+        &self,
+        token: &str,
+        notification: &Notification,
+        delivery: Delivery,
+    ) -> Result<(), ServiceError> {
+        if let Some(key) = &notification.idempotency_key {
+            self.keys
+                .lock()
+                .unwrap()
+                .insert((token.to_string(), key.clone()));
+            self.entries.lock().unwrap().push(HistoryEntry {
+                id: Some(ObjectId::new()),
+                ..HistoryEntry::new(
+                    token,
+                    key,
+                    notification,
+                    delivery,
+                    mongodb::bson::DateTime::now(),
+                )
+            });
+        }
+        Ok(())
+    }
+
+    async fn update_delivery(
+        &self,
+        token: &str,
+        key: &str,
+        from: DeliveryStatus,
+        delivery: Delivery,
+    ) -> Result<(), ServiceError> {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.token == token && entry.key == key && entry.delivery == from)
+        {
+            entry.delivery = delivery.status;
+            entry.provider_ids = delivery.provider_ids;
+            entry.error = delivery.error;
+            entry.updated_at = Some(mongodb::bson::DateTime::now());
+        }
+        Ok(())
+    }
+
+    async fn get_notification(
+        &self,
+        notification_id: &str,
+    ) -> Result<NotificationDelivery, ServiceError> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .find(|entry| entry.id.is_some_and(|id| id.to_hex() == notification_id))
+            .cloned()
+            .map(Into::into)
+            .ok_or_else(|| ServiceError::DataNotFound("Notification".to_string()))
+    }
+
+    async fn get_history_diff(
+        &self,
+        _token: &str,
+        _query: &HistoryDiffQuery,
+    ) -> Result<Vec<ItemHistory>, ServiceError> {
+        Ok(Vec::new())
+    }
+
+    async fn get_delivery_stats(
+        &self,
+        _token: &str,
+        _query: &DeliveryStatsQuery,
+    ) -> Result<DeliveryStats, ServiceError> {
+        Ok(DeliveryStats::default())
+    }
+
+    async fn get_inbox(
+        &self,
+        _token: &str,
+        _query: &PageQuery,
+    ) -> Result<Page<InboxNotification>, ServiceError> {
+        Ok(Page {
+            items: Vec::new(),
+            total: 0,
+        })
+    }
+
+    async fn mark_read(
+        &self,
+        _token: &str,
+        _notification_id: &str,
+    ) -> Result<UnreadNotifications, ServiceError> {
+        Ok(UnreadNotifications { unread: 0 })
+    }
+
+    async fn get_held(&self, token: &str, since: i64) -> Result<Vec<HistoryEntry>, ServiceError> {
+        Ok(self
+            .recorded_since(token, since)
+            .into_iter()
+            .filter(|entry| entry.delivery == DeliveryStatus::Held)
+            .collect())
+    }
+
+    async fn get_grade_changes(
+        &self,
+        token: &str,
+        since: i64,
+    ) -> Result<Vec<HistoryEntry>, ServiceError> {
+        Ok(self
+            .recorded_since(token, since)
+            .into_iter()
+            .filter(|entry| entry.kind == NotificationKind::Grade)
+            .collect())
+    }
+
+    async fn get_unread_count(&self, token: &str) -> Result<UnreadNotifications, ServiceError> {
+        let unread = self
+            .recorded_since(token, 0)
+            .iter()
+            .filter(|entry| entry.delivery != DeliveryStatus::Failed && !entry.read)
+            .count();
+        Ok(UnreadNotifications {
+            unread: unread as u64,
+        })
+    }
+
+    async fn count_pushed(&self, token: &str, since: i64) -> Result<usize, ServiceError> {
+        Ok(delivery_stats(&self.recorded_since(token, since)).sent as usize)
+    }
+}
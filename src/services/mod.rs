@@ -1,7 +1,23 @@
+pub mod change_listener;
+pub mod comparison;
 pub mod data_service;
 pub mod data_service_interfaces;
 pub mod errors;
 pub mod event_producer_interface;
+pub mod health;
+pub mod history_service;
+pub mod history_service_interfaces;
+pub mod live_updates;
+pub mod metrics;
+#[cfg(test)]
+pub mod mocks;
+pub mod notification_router;
 pub mod producer_service;
 pub mod producer_service_interfaces;
 pub mod provider_interfaces;
+pub mod reminder_service;
+pub mod reminder_service_interfaces;
+pub mod retention_service;
+pub mod retry_budget;
+pub mod stats_service;
+pub mod stats_service_interfaces;
@@ -0,0 +1,97 @@
+use async_trait::async_trait;
+
+use crate::models::notification::Notification;
+use crate::models::preferences::ChannelPreferences;
+use crate::models::token::Device;
+
+use super::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
+
+/// Sits between the producer service and the channel transports: picks which
+/// of a user's devices a notification goes out to, then hands the pushes to
+/// the transport of each device's channel.
+pub struct NotificationRouter {
+    transport: Box<dyn EventProducerInterface>,
+}
+
+impl NotificationRouter {
+    pub fn new(transport: Box<dyn EventProducerInterface>) -> Self {
+        Self { transport }
+    }
+
+    /// The devices whose channel takes the notification: silent pushes go to
+    /// every app, and otherwise each channel only gets notifications at or
+    /// above the user's minimum priority for it.
+    pub fn recipients(
+        &self,
+        devices: &[Device],
+        notification: &Notification,
+        channels: &ChannelPreferences,
+    ) -> Vec<Device> {
+        devices
+            .iter()
+            .filter(|device| match notification.silent {
+                true => device.platform.is_none_or(|platform| platform.is_app()),
+                false => notification.priority >= channels.min_priority(device.channel()),
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl EventProducerInterface for NotificationRouter {
+    async fn produce_notification(&self, msg: &Notification) -> Result<Receipt, ProduceError> {
+        self.transport.produce_notification(msg).await
+    }
+
+    async fn produce_notifications(
+        &self,
+        msgs: &[Notification],
+    ) -> Vec<Result<Receipt, ProduceError>> {
+        self.transport.produce_notifications(msgs).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::notification::{NotificationKind, Priority};
+    use crate::models::token::Platform;
+    use crate::services::mocks::{device, MockEventProducer};
+
+    #[test]
+    fn test_channels_get_notifications_by_priority() {
+        let router = NotificationRouter::new(Box::new(MockEventProducer::default()));
+        let devices = [
+            device("phone", Some(Platform::Android)),
+            device("legacy", None),
+            device("123", Some(Platform::Telegram)),
+            device("https://example.com/hook", Some(Platform::Webhook)),
+        ];
+        let channels = ChannelPreferences {
+            telegram: Priority::High,
+            webhook: Priority::Normal,
+            ..ChannelPreferences::default()
+        };
+        let tokens = |notification: &Notification| {
+            router
+                .recipients(&devices, notification, &channels)
+                .into_iter()
+                .map(|device| device.token)
+                .collect::<Vec<_>>()
+        };
+
+        let course = Notification::new(NotificationKind::Course, String::new(), String::new());
+        assert_eq!(tokens(&course), ["phone", "legacy"]);
+        let grade = Notification::new(NotificationKind::Grade, String::new(), String::new());
+        assert_eq!(
+            tokens(&grade),
+            ["phone", "legacy", "https://example.com/hook"]
+        );
+        assert_eq!(tokens(&grade.with_priority(Priority::High)).len(), 4);
+        assert_eq!(
+            tokens(&Notification::background_sync()),
+            ["phone", "legacy"]
+        );
+    }
+}
@@ -1,24 +1,104 @@
-use crate::models::course::{compare_courses, Course};
-use crate::models::deadline::{compare_deadlines, sort_deadlines};
-use crate::models::grade::{compare_grades, compare_grades_overview, sort_grades_overview};
-use crate::models::notification::Notification;
-use crate::models::token::Token;
+use crate::models::broadcast::{Broadcast, BroadcastReport, BROADCAST_BATCH_SIZE};
+use crate::models::cohort::Cohort;
+use crate::models::course::{
+    compare_courses, lacks_shortnames, removed_courses, sort_courses, Course,
+};
+use crate::models::course_content::{changed_course_contents, CourseContent};
+use crate::models::dead_letter::{DeadLetter, RedriveReport, REDRIVE_BATCH_SIZE};
+use crate::models::deadline::{format_due, moved_deadlines, removed_deadlines, sort_deadlines};
+use crate::models::feature_flags::FeatureFlags;
+use crate::models::grade::{
+    compare_grades_overview, lacks_graded_dates, passes_grade_mark, sort_grades_overview,
+};
+use crate::models::history::{
+    digest_body, weekly_report_body, Delivery, DeliveryStatus, DIGEST_HOUR,
+};
+use crate::models::i18n::{Language, Text};
+use crate::models::notification::{
+    ChangeEvent, Notification, NotificationKind, Priority, OPEN_COURSE_ACTION, SNOOZE_ACTION,
+};
+use crate::models::preferences::{ChannelPreferences, ExamPeriod, Preferences};
+use crate::models::reminder::{due_reminders, lead_text, scheduled_reminders};
+use crate::models::stats::BatchReport;
+use crate::models::templates::NotificationTemplates;
+use crate::models::token::{Device, Token};
 use crate::models::user::User;
+use crate::models::webhook::{ProviderEvent, ProviderEventKind};
+use crate::repositories::errors::RepositoryError;
 use crate::services::producer_service_interfaces::ProducerServiceInterface;
 use crate::services::provider_interfaces::DataProviderInterface;
 use anyhow::Result;
 use async_trait::async_trait;
-use futures_util::TryStreamExt;
-use std::sync::Arc;
+use chrono::{Datelike, Timelike, Utc};
+use futures::future::join_all;
+use mongodb::bson::oid::ObjectId;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 
+use super::comparison::{comparison_strategy, ComparisonStrategy, StableComparison};
 use super::data_service_interfaces::DataServiceInterfaces;
 use super::errors::ServiceError;
-use super::event_producer_interface::EventProducerInterface;
+use super::event_producer_interface::{EventProducerInterface, ProduceError, Receipt};
+use super::history_service_interfaces::HistoryServiceInterface;
+use super::live_updates::LiveUpdates;
+use super::notification_router::NotificationRouter;
+use super::reminder_service_interfaces::ReminderServiceInterface;
+use super::retry_budget::RetryBudget;
+use super::stats_service_interfaces::StatsServiceInterface;
+
+#[async_trait]
+pub trait DeadLetterRepositoryInterface: Send + Sync {
+    async fn save_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), RepositoryError>;
+    /// Dead letters that failed first, oldest first.
+    async fn find_oldest(&self, limit: i64) -> Result<Vec<DeadLetter>, RepositoryError>;
+    async fn delete_dead_letter(&self, id: ObjectId) -> Result<(), RepositoryError>;
+    /// Keeps the dead letter with the error of a re-drive that failed again.
+    async fn record_redrive_failure(
+        &self,
+        id: ObjectId,
+        error: &str,
+    ) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+pub trait ExamPeriodRepositoryInterface: Send + Sync {
+    /// The exam period admins set for everyone, if any.
+    async fn find_global(&self) -> Result<Option<ExamPeriod>, RepositoryError>;
+    /// Replaces the exam period for everyone; `None` ends it.
+    async fn save_global(&self, period: Option<&ExamPeriod>) -> Result<(), RepositoryError>;
+}
 
 pub struct ProducerService {
-    producer: Box<dyn EventProducerInterface>,
+    router: NotificationRouter,
     data_provider: Arc<dyn DataProviderInterface>,
     data_service: Arc<dyn DataServiceInterfaces>,
+    stats_service: Arc<dyn StatsServiceInterface>,
+    history_service: Arc<dyn HistoryServiceInterface>,
+    flags: FeatureFlags,
+    retry_budget: Arc<RetryBudget>,
+    live_updates: Arc<LiveUpdates>,
+    reminder_service: Option<Arc<dyn ReminderServiceInterface>>,
+    dead_letters: Option<Arc<dyn DeadLetterRepositoryInterface>>,
+    exam_periods: Option<Arc<dyn ExamPeriodRepositoryInterface>>,
+    templates: Arc<NotificationTemplates>,
+    stable_comparison: Box<dyn ComparisonStrategy>,
+    canary_comparison: Box<dyn ComparisonStrategy>,
+    /// Courses with item grade notifications in the current pass, per token.
+    graded_courses: Mutex<HashMap<String, HashSet<i64>>>,
+    /// Pushes collected during the current pass, sent together when it ends.
+    outbox: Mutex<HashMap<String, Vec<PendingPush>>>,
+    /// Held by the pass running for a token; later ones wait for it to end.
+    passes: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+/// A push waiting in the outbox for the end of the user's pass.
+struct PendingPush {
+    notification: Notification,
+    devices: Vec<Device>,
+    /// Recorded as queued during quiet hours; its history entry moves on instead.
+    queued: bool,
 }
 
 impl ProducerService {
@@ -26,102 +106,865 @@ impl ProducerService {
         producer: Box<dyn EventProducerInterface>,
         data_provider: Arc<dyn DataProviderInterface>,
         data_service: Arc<dyn DataServiceInterfaces>,
+        stats_service: Arc<dyn StatsServiceInterface>,
+        history_service: Arc<dyn HistoryServiceInterface>,
+        flags: FeatureFlags,
+        retry_budget: Arc<RetryBudget>,
     ) -> Self {
+        let canary_comparison = comparison_strategy(flags.canary_comparison);
         Self {
-            producer,
+            router: NotificationRouter::new(producer),
             data_provider,
             data_service,
+            stats_service,
+            history_service,
+            flags,
+            retry_budget,
+            live_updates: Arc::new(LiveUpdates::default()),
+            reminder_service: None,
+            dead_letters: None,
+            exam_periods: None,
+            templates: Arc::new(NotificationTemplates::default()),
+            stable_comparison: Box::new(StableComparison),
+            canary_comparison,
+            graded_courses: Mutex::new(HashMap::new()),
+            outbox: Mutex::new(HashMap::new()),
+            passes: Mutex::new(HashMap::new()),
         }
     }
-}
 
-#[async_trait]
-impl ProducerServiceInterface for ProducerService {
-    async fn get_batches<'a>(&self, limit: i64, skip: &'a mut u64) -> Result<()> {
-        let mut batch = Vec::new();
+    pub fn with_live_updates(mut self, live_updates: Arc<LiveUpdates>) -> Self {
+        self.live_updates = live_updates;
+        self
+    }
+
+    pub fn with_reminders(mut self, reminder_service: Arc<dyn ReminderServiceInterface>) -> Self {
+        self.reminder_service = Some(reminder_service);
+        self
+    }
+
+    pub fn with_dead_letters(
+        mut self,
+        dead_letters: Arc<dyn DeadLetterRepositoryInterface>,
+    ) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    pub fn with_exam_periods(
+        mut self,
+        exam_periods: Arc<dyn ExamPeriodRepositoryInterface>,
+    ) -> Self {
+        self.exam_periods = Some(exam_periods);
+        self
+    }
+
+    pub fn with_templates(mut self, templates: Arc<NotificationTemplates>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    fn cohort(&self, token: &str) -> Cohort {
+        Cohort::of(token, self.flags.canary_percent)
+    }
 
-        let mut cursor = self.data_service.find_all_tokens(limit, *skip).await?;
+    fn comparison(&self, token: &str) -> &dyn ComparisonStrategy {
+        match self.cohort(token) {
+            Cohort::Stable => self.stable_comparison.as_ref(),
+            Cohort::Canary => self.canary_comparison.as_ref(),
+        }
+    }
 
-        let mut has_documents = false;
+    /// Whether the user's quiet hours are on; lookup failures count as not quiet.
+    async fn in_quiet_hours(&self, token: &str, preferences: &Preferences) -> bool {
+        let Some(quiet_hours) = &preferences.quiet_hours else {
+            return false;
+        };
+        let settings = self
+            .data_service
+            .get_settings(token)
+            .await
+            .unwrap_or_default();
+        quiet_hours.contains(settings.tz(), Utc::now().timestamp())
+    }
 
-        while let Some(doc) = cursor.try_next().await? {
-            has_documents = true;
-            if let Ok(token) = doc.get_str("_id") {
-                match doc.get_str("device_token") {
-                    Ok(device_token) => batch.push(Token::new(
-                        token.to_string(),
-                        Some(device_token.to_string()),
-                    )),
-                    Err(_) => batch.push(Token::new(token.to_string(), None)),
-                };
-                *skip += 1;
+    /// The user's own exam period and the one admins set for everyone; lookup
+    /// failures count as none.
+    async fn exam_periods(&self, preferences: &Preferences) -> Vec<ExamPeriod> {
+        let mut periods: Vec<ExamPeriod> = preferences.exam_period.iter().cloned().collect();
+        if let Some(exam_periods) = &self.exam_periods {
+            match exam_periods.find_global().await {
+                Ok(period) => periods.extend(period),
+                Err(e) => eprintln!("Error loading exam period: {}", e),
             }
         }
+        periods
+    }
+
+    async fn in_exam_period(&self, preferences: &Preferences) -> bool {
+        let now = Utc::now().timestamp();
+        self.exam_periods(preferences)
+            .await
+            .iter()
+            .any(|period| period.contains(now))
+    }
 
-        // println!("{:?}", batch);
+    /// The user's notification language; lookup failures fall back to English.
+    async fn language(&self, token: &str) -> Language {
+        self.data_service
+            .get_settings(token)
+            .await
+            .unwrap_or_default()
+            .lang()
+    }
 
-        if !has_documents {
-            *skip = 0;
-            return Ok(());
+    /// Adds "Open course" when the notification names a course, and "Snooze 1h"
+    /// to deadline reminders.
+    fn with_actions(
+        &self,
+        notification: Notification,
+        language: Language,
+        snooze: bool,
+    ) -> Notification {
+        let mut notification = notification;
+        if notification.data.contains_key("course_id") {
+            let title = self.templates.render(Text::OpenCourseAction, language);
+            notification = notification.with_action(OPEN_COURSE_ACTION, title);
+        }
+        if snooze {
+            let title = self.templates.render(Text::SnoozeAction, language);
+            notification = notification.with_action(SNOOZE_ACTION, title);
         }
+        notification
+    }
 
-        if let Err(e) = self.process_batch(&batch).await {
-            eprintln!("Error processing batch: {}", e);
+    /// The user's push sound and, unless they turned badges off, how many
+    /// notifications they have unread.
+    async fn alert_style(&self, token: &str) -> (Option<String>, Option<u64>) {
+        let settings = self
+            .data_service
+            .get_settings(token)
+            .await
+            .unwrap_or_default();
+        if !settings.badge {
+            return (settings.sound, None);
+        }
+        match self.history_service.get_unread_count(token).await {
+            Ok(unread) => (settings.sound, Some(unread.unread)),
+            Err(e) => {
+                eprintln!("Error counting unread notifications: {}", e);
+                (settings.sound, None)
+            }
         }
-        Ok(())
     }
 
-    async fn process_batch(&self, batch: &[Token]) -> Result<()> {
-        for tokens in batch.iter() {
-            let token = &tokens.token;
+    /// Pushes the notification to each device; `queued` ones are already in
+    /// the history, so they don't add to the badge.
+    async fn push(
+        &self,
+        token: &str,
+        devices: &[Device],
+        notification: &Notification,
+        queued: bool,
+    ) -> Delivery {
+        let (sound, unread) = self.alert_style(token).await;
+        let badge = unread.map(|unread| unread + u64::from(!queued));
+        let notification = notification.clone().with_alert_style(sound, badge);
+        let pushes: Vec<Notification> = devices
+            .iter()
+            .map(|device| notification.for_device(device))
+            .collect();
+        let results = self.router.produce_notifications(&pushes).await;
+        self.settle(token, &pushes, results).await
+    }
+
+    /// Folds the results of pushing a notification to each device into its
+    /// delivery, keeping transient failures as dead letters.
+    async fn settle(
+        &self,
+        token: &str,
+        pushes: &[Notification],
+        results: Vec<Result<Receipt, ProduceError>>,
+    ) -> Delivery {
+        // Live-only users, or channels routed away, leave nothing to push
+        let mut delivery = Delivery::from(if pushes.is_empty() {
+            DeliveryStatus::Skipped
+        } else {
+            DeliveryStatus::Failed
+        });
+        for (notification, result) in pushes.iter().zip(results) {
+            match result {
+                Ok(Receipt::Delivered(provider_id)) => {
+                    delivery.status = DeliveryStatus::Delivered;
+                    delivery.provider_ids.extend(provider_id);
+                }
+                Ok(Receipt::Sent) => {
+                    if delivery.status == DeliveryStatus::Failed {
+                        delivery.status = DeliveryStatus::Sent;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error sending notification: {}", e);
+                    delivery.error = Some(e.to_string());
+                    if e.is_unregistered() {
+                        self.drop_device(token, &notification.device_token).await;
+                    }
+                    // Retries are exhausted; keep it for a re-drive after the outage
+                    if let (true, Some(dead_letters)) = (e.is_transient(), &self.dead_letters) {
+                        let dead_letter = DeadLetter::new(token, notification, e.to_string());
+                        if let Err(e) = dead_letters.save_dead_letter(&dead_letter).await {
+                            eprintln!("Error saving dead letter: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+        delivery
+    }
+
+    /// Forgets a device its transport no longer knows, so later passes skip it.
+    async fn drop_device(&self, token: &str, device_token: &str) {
+        match self
+            .data_service
+            .unregister_device(token, device_token)
+            .await
+        {
+            // Already gone, e.g. refreshed by the app in the meantime
+            Ok(()) | Err(ServiceError::DataNotFound(_)) => {}
+            Err(e) => eprintln!("Error dropping unregistered device: {}", e),
+        }
+    }
+
+    /// Runs the pass once no other pass of the user is running, so it has the
+    /// per-token state to itself.
+    async fn exclusive<T>(&self, token: &str, pass: impl Future<Output = T>) -> T {
+        let lock = Arc::clone(
+            self.passes
+                .lock()
+                .unwrap()
+                .entry(token.to_string())
+                .or_default(),
+        );
+        let result = {
+            let _running = lock.lock().await;
+            pass.await
+        };
+        let mut passes = self.passes.lock().unwrap();
+        // Only the table and this pass hold it, so no other pass is waiting
+        if Arc::strong_count(&lock) == 2 {
+            passes.remove(token);
+        }
+        result
+    }
+
+    /// Adds the push to the user's outbox when a pass has one open.
+    fn collect(
+        &self,
+        token: &str,
+        devices: &[Device],
+        notification: &Notification,
+        queued: bool,
+    ) -> bool {
+        let mut outbox = self.outbox.lock().unwrap();
+        let Some(pending) = outbox.get_mut(token) else {
+            return false;
+        };
+        pending.push(PendingPush {
+            notification: notification.clone(),
+            devices: devices.to_vec(),
+            queued,
+        });
+        true
+    }
+
+    fn is_collected(&self, token: &str, key: &str) -> bool {
+        self.outbox
+            .lock()
+            .unwrap()
+            .get(token)
+            .is_some_and(|pending| {
+                pending
+                    .iter()
+                    .any(|push| push.notification.idempotency_key.as_deref() == Some(key))
+            })
+    }
 
-            if let Some(device_token) = &tokens.device_token {
-                self.process_producing(token, device_token).await?;
+    /// Sends every push collected for the user in one batch and records how
+    /// each went.
+    async fn flush_outbox(&self, token: &str) {
+        let Some(pending) = self.outbox.lock().unwrap().remove(token) else {
+            return;
+        };
+        let pending = self.cap_hourly(token, pending).await;
+        let (sound, mut badge) = self.alert_style(token).await;
+        let pushes: Vec<Notification> = pending
+            .iter()
+            .flat_map(|push| {
+                // Each push counts the ones before it, as they land first
+                if !push.queued {
+                    badge = badge.map(|badge| badge + 1);
+                }
+                let notification = push
+                    .notification
+                    .clone()
+                    .with_alert_style(sound.clone(), badge);
+                push.devices
+                    .iter()
+                    .map(move |device| notification.for_device(device))
+            })
+            .collect();
+        let mut results = self.router.produce_notifications(&pushes).await.into_iter();
+        let mut pushes = pushes.as_slice();
+        for push in pending {
+            let (sent, rest) = pushes.split_at(push.devices.len());
+            pushes = rest;
+            let results = results.by_ref().take(sent.len()).collect();
+            let delivery = self.settle(token, sent, results).await;
+            if push.queued {
+                self.update_delivery(token, &push.notification, DeliveryStatus::Queued, delivery)
+                    .await;
             } else {
-                self.data_service.fetch_and_update_data(token).await?;
+                self.record(token, &push.notification, delivery).await;
             }
         }
+    }
 
-        Ok(())
+    /// Keeps the user within `notifications_per_hour`: the most urgent pushes
+    /// go out and the rest fold into one "and N more updates" push.
+    async fn cap_hourly(&self, token: &str, mut pending: Vec<PendingPush>) -> Vec<PendingPush> {
+        let Some(limit) = self.flags.notifications_per_hour else {
+            return pending;
+        };
+        let since = Utc::now().timestamp() - 3600;
+        let pushed = match self.history_service.count_pushed(token, since).await {
+            Ok(pushed) => pushed,
+            Err(e) => {
+                eprintln!("Error counting recent notifications: {}", e);
+                0
+            }
+        };
+        let allowed = limit.saturating_sub(pushed);
+        if pending.len() <= allowed {
+            return pending;
+        }
+        // The last allowed push is the summary of the folded ones
+        pending.sort_by_key(|push| Reverse(push.notification.priority));
+        let folded = pending.split_off(allowed.saturating_sub(1));
+        for push in &folded {
+            let delivery = DeliveryStatus::Folded.into();
+            if push.queued {
+                self.update_delivery(token, &push.notification, DeliveryStatus::Queued, delivery)
+                    .await;
+            } else {
+                self.record(token, &push.notification, delivery).await;
+            }
+        }
+        if allowed > 0 {
+            let language = self.language(token).await;
+            let keys: Vec<&str> = folded
+                .iter()
+                .filter_map(|push| push.notification.idempotency_key.as_deref())
+                .collect();
+            let notification = Notification::new(
+                NotificationKind::MoreUpdates,
+                self.templates.render(Text::MoreUpdatesTitle, language),
+                self.templates
+                    .render(Text::MoreUpdates(folded.len()), language),
+            )
+            .with_summary_key(token, &keys);
+            pending.push(PendingPush {
+                notification,
+                devices: folded[0].devices.clone(),
+                queued: false,
+            });
+        }
+        pending
+    }
+
+    /// Wakes the user's apps to refresh their cache. Not recorded: a missed one
+    /// is made up by the next pass.
+    async fn push_background_sync(&self, token: &str, devices: &[Device]) {
+        let sync = Notification::background_sync();
+        let pushes: Vec<Notification> = self
+            .router
+            .recipients(devices, &sync, &ChannelPreferences::default())
+            .iter()
+            .map(|device| sync.for_device(device))
+            .collect();
+        let results = self.router.produce_notifications(&pushes).await;
+        for (push, result) in pushes.iter().zip(results) {
+            match result {
+                Err(e) if e.is_unregistered() => self.drop_device(token, &push.device_token).await,
+                Err(e) => eprintln!("Error sending background sync push: {}", e),
+                Ok(_) => {}
+            }
+        }
+    }
+
+    async fn record(&self, token: &str, notification: &Notification, delivery: Delivery) {
+        if let Err(e) = self
+            .history_service
+            .record(token, notification, delivery)
+            .await
+        {
+            eprintln!("Error saving notification history: {}", e);
+        }
+    }
+
+    /// Moves the recorded notification on once a later push attempt settles.
+    async fn update_delivery(
+        &self,
+        token: &str,
+        notification: &Notification,
+        from: DeliveryStatus,
+        delivery: Delivery,
+    ) {
+        let Some(key) = &notification.idempotency_key else {
+            return;
+        };
+        if let Err(e) = self
+            .history_service
+            .update_delivery(token, key, from, delivery)
+            .await
+        {
+            eprintln!("Error updating notification delivery: {}", e);
+        }
+    }
+
+    /// Pushes the notification to each device, or queues it during quiet hours.
+    /// Returns false when the same change was already sent within the dedup window.
+    async fn send(&self, token: &str, devices: &[Device], notification: &Notification) -> bool {
+        self.deliver(token, devices, notification, false).await
+    }
+
+    /// Like [`Self::send`], but a `held` change is only recorded for the daily
+    /// digest instead of being pushed.
+    async fn deliver(
+        &self,
+        token: &str,
+        devices: &[Device],
+        notification: &Notification,
+        held: bool,
+    ) -> bool {
+        if let Some(key) = &notification.idempotency_key {
+            if self.is_collected(token, key) {
+                return false;
+            }
+            match self.history_service.was_sent(token, key).await {
+                Ok(true) => return false,
+                Ok(false) => {}
+                Err(e) => eprintln!("Error checking notification history: {}", e),
+            }
+        }
+
+        self.live_updates
+            .publish(token, ChangeEvent::from(notification));
+        // Lookup failures fall back to every channel and no quiet hours
+        let preferences = self
+            .data_service
+            .get_preferences(token)
+            .await
+            .unwrap_or_default();
+        let devices = &self
+            .router
+            .recipients(devices, notification, &preferences.channels);
+        // During exams everything but urgent deadlines waits for the digest
+        let held =
+            held || (!notification.passes_exam_period() && self.in_exam_period(&preferences).await);
+        let quiet = !held && !devices.is_empty() && self.in_quiet_hours(token, &preferences).await;
+        // Queued pushes are recorded now so repeats are dropped while they wait
+        let delivery = if held {
+            DeliveryStatus::Held.into()
+        } else if quiet {
+            match self
+                .data_service
+                .queue_notification(token, notification)
+                .await
+            {
+                Ok(()) => DeliveryStatus::Queued.into(),
+                Err(e) => {
+                    eprintln!("Error queueing notification: {}", e);
+                    self.push(token, devices, notification, false).await
+                }
+            }
+        } else if self.collect(token, devices, notification, false) {
+            // Sent and recorded with the rest of the pass
+            self.stats_service
+                .record_notification(notification.kind, self.cohort(token))
+                .await;
+            return true;
+        } else {
+            self.push(token, devices, notification, false).await
+        };
+        self.stats_service
+            .record_notification(notification.kind, self.cohort(token))
+            .await;
+        self.record(token, notification, delivery).await;
+        true
     }
 
-    async fn process_producing(&self, token: &str, device_token: &str) -> Result<()> {
-        match self.produce_user_info(token, device_token).await {
+    async fn produce_all(&self, token: &str, devices: &[Device]) -> Result<()> {
+        let preferences = self.data_service.get_preferences(token).await?;
+        if preferences.notifications_off || preferences.is_paused(Utc::now().timestamp()) {
+            // Keep snapshots current so nothing floods out on resume
+            self.data_service.fetch_and_update_data(token).await?;
+            return Ok(());
+        }
+        // Retried next pass; shouldn't hold back this one
+        if let Err(e) = self.data_service.backfill_pending(token).await {
+            eprintln!("Error backfilling data: {}", e);
+        }
+        if !devices.is_empty() && !self.in_quiet_hours(token, &preferences).await {
+            for notification in self.data_service.take_queued_notifications(token).await? {
+                let devices =
+                    &self
+                        .router
+                        .recipients(devices, &notification, &preferences.channels);
+                if !self.collect(token, devices, &notification, true) {
+                    let delivery = self.push(token, devices, &notification, true).await;
+                    self.update_delivery(token, &notification, DeliveryStatus::Queued, delivery)
+                        .await;
+                }
+            }
+        }
+
+        match self.produce_user_info(token, devices).await {
             Ok(user) => {
-                if let Ok(mut courses) = self.produce_course(token, device_token, &user).await {
-                    if let Err(e) = self
-                        .produce_grade(token, device_token, &user, &courses)
-                        .await
-                    {
+                if let Ok(mut courses) = self.produce_course(token, devices, &user).await {
+                    if let Err(e) = self.produce_grade(token, devices, &user, &courses).await {
                         eprintln!("Error sending grade: {:?}", e);
                     }
-                    if let Err(e) = self
-                        .produce_grade_overview(token, device_token, &courses)
-                        .await
-                    {
-                        eprintln!("Error sending grade overview: {:?}", e);
+                    if self.flags.grade_overview {
+                        if let Err(e) = self.produce_grade_overview(token, devices, &courses).await
+                        {
+                            eprintln!("Error sending grade overview: {:?}", e);
+                        }
                     }
                     Course::delete_past_courses(&mut courses);
-                    if let Err(e) = self.produce_deadline(token, device_token, &courses).await {
+                    if let Err(e) = self.produce_deadline(token, devices, &courses).await {
                         eprintln!("Error sending deadline: {:?}", e);
                     }
+                    if preferences.allows(NotificationKind::Material) {
+                        if let Err(e) = self.produce_material(token, devices, &courses).await {
+                            eprintln!("Error sending course material: {:?}", e);
+                        }
+                    }
+                    if let Some(hours) = self.flags.deadline_reminder_hours {
+                        if let Err(e) = self.produce_reminders(token, devices, hours).await {
+                            eprintln!("Error sending deadline reminder: {:?}", e);
+                        }
+                    }
+                    if self.flags.scheduled_reminders {
+                        if let Err(e) = self.produce_scheduled_reminders(token, devices).await {
+                            eprintln!("Error sending scheduled reminder: {:?}", e);
+                        }
+                    }
+                    if let Err(e) = self.produce_custom_reminders(token, devices).await {
+                        eprintln!("Error sending custom reminder: {:?}", e);
+                    }
+                    let now = Utc::now().timestamp();
+                    let exam_digest = self
+                        .exam_periods(&preferences)
+                        .await
+                        .iter()
+                        .any(|period| period.holds_digest(now));
+                    if preferences.digest || exam_digest {
+                        if let Err(e) = self.produce_digest(token, devices).await {
+                            eprintln!("Error sending digest: {:?}", e);
+                        }
+                    }
+                    if let Err(e) = self.produce_weekly_report(token, devices).await {
+                        eprintln!("Error sending weekly report: {:?}", e);
+                    }
                 }
             }
-            Err(e) => {
-                eprintln!("Error sending user info: {:?}", e);
+            Err(e) => return Err(e.context("Error sending user info")),
+        }
+        Ok(())
+    }
+
+    /// Runs only the produce step the event touches; polling still covers the rest.
+    async fn produce_event(
+        &self,
+        token: &str,
+        devices: &[Device],
+        event: &ProviderEvent,
+    ) -> Result<()> {
+        let preferences = self.data_service.get_preferences(token).await?;
+        if preferences.notifications_off || preferences.is_paused(Utc::now().timestamp()) {
+            return Ok(());
+        }
+
+        let user = self.data_service.get_user(token).await?;
+        match event.kind {
+            ProviderEventKind::User => {
+                self.produce_user_info(token, devices).await?;
+            }
+            ProviderEventKind::Course => {
+                self.produce_course(token, devices, &user).await?;
+            }
+            ProviderEventKind::Grade => {
+                let mut courses = self.data_service.get_courses(token).await?;
+                Course::delete_removed_courses(&mut courses);
+                if event
+                    .courseid
+                    .is_some_and(|id| !courses.iter().any(|course| course.id == id))
+                {
+                    // Grade in a course we haven't seen yet
+                    courses = self.produce_course(token, devices, &user).await?;
+                }
+                let affected: Vec<Course> = courses
+                    .iter()
+                    .filter(|course| event.courseid.is_none_or(|id| course.id == id))
+                    .cloned()
+                    .collect();
+                self.produce_grade(token, devices, &user, &affected).await?;
+                if self.flags.grade_overview {
+                    self.produce_grade_overview(token, devices, &courses)
+                        .await?;
+                }
+            }
+            ProviderEventKind::Deadline => {
+                // Deadlines are stored as a whole, so every current course is checked
+                let mut courses = self.data_service.get_courses(token).await?;
+                Course::delete_removed_courses(&mut courses);
+                Course::delete_past_courses(&mut courses);
+                self.produce_deadline(token, devices, &courses).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProducerServiceInterface for ProducerService {
+    async fn get_batches<'a>(&self, limit: i64, skip: &'a mut u64) -> Result<BatchReport> {
+        let batch = self.data_service.find_all_tokens(limit, *skip).await?;
+
+        if batch.is_empty() {
+            *skip = 0;
+            return Ok(BatchReport::default());
+        }
+        *skip += batch.len() as u64;
+
+        Ok(self.process_batch(&batch).await)
+    }
+
+    async fn process_batch(&self, batch: &[Token]) -> BatchReport {
+        let mut report = BatchReport::default();
+
+        for tokens in batch.iter() {
+            let token = &tokens.token;
+
+            let result = if !tokens.devices.is_empty() || self.live_updates.is_watched(token) {
+                self.process_producing(token, &tokens.devices).await
+            } else {
+                self.data_service
+                    .fetch_and_update_data(token)
+                    .await
+                    .map_err(Into::into)
+            };
+
+            let error = match result {
+                Ok(()) => {
+                    report.tokens_processed += 1;
+                    None
+                }
+                Err(e) => {
+                    eprintln!("Error processing token: {}", e);
+                    report.errors += 1;
+                    Some(e.to_string())
+                }
+            };
+            if let Err(e) = self.data_service.record_sync(token, error).await {
+                eprintln!("Error saving sync status: {}", e);
+            }
+        }
+
+        report
+    }
+
+    async fn broadcast(&self, broadcast: &Broadcast) -> Result<BroadcastReport> {
+        let mut report = BroadcastReport::default();
+        let mut after: Option<String> = None;
+        loop {
+            let recipients = self
+                .data_service
+                .find_broadcast_recipients(
+                    &broadcast.segment,
+                    after.as_deref(),
+                    BROADCAST_BATCH_SIZE,
+                )
+                .await?;
+            after = recipients.last().map(|tokens| tokens.token.clone());
+
+            let sends = recipients.iter().map(|tokens| {
+                let devices: Vec<Device> = tokens
+                    .devices
+                    .iter()
+                    .filter(|device| {
+                        broadcast
+                            .segment
+                            .platform
+                            .is_none_or(|platform| device.platform == Some(platform))
+                    })
+                    .cloned()
+                    .collect();
+                async move {
+                    let notification = Notification::new(
+                        NotificationKind::Announcement,
+                        broadcast.title.clone(),
+                        broadcast.body.clone(),
+                    )
+                    .with_image(broadcast.image_url.clone())
+                    .with_change(
+                        &tokens.token,
+                        None,
+                        None,
+                        &format!("{}\n{}", broadcast.title, broadcast.body),
+                    );
+                    self.send(&tokens.token, &devices, &notification).await
+                }
+            });
+            for sent in join_all(sends).await {
+                report.recipients += 1;
+                if sent {
+                    report.sent += 1;
+                }
+            }
+
+            if (recipients.len() as u64) < BROADCAST_BATCH_SIZE {
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    async fn redrive_dead_letters(&self) -> Result<RedriveReport> {
+        let mut report = RedriveReport::default();
+        let Some(dead_letters) = &self.dead_letters else {
+            return Ok(report);
+        };
+        for dead_letter in dead_letters.find_oldest(REDRIVE_BATCH_SIZE).await? {
+            let Some(id) = dead_letter.id else {
+                continue;
+            };
+            match self
+                .router
+                .produce_notification(&dead_letter.notification)
+                .await
+            {
+                Ok(receipt) => {
+                    dead_letters.delete_dead_letter(id).await?;
+                    report.delivered += 1;
+                    self.update_delivery(
+                        &dead_letter.token,
+                        &dead_letter.notification,
+                        DeliveryStatus::Failed,
+                        receipt.into(),
+                    )
+                    .await;
+                }
+                Err(e) if e.is_unregistered() => {
+                    // Nothing left to deliver it to
+                    dead_letters.delete_dead_letter(id).await?;
+                    self.drop_device(&dead_letter.token, &dead_letter.notification.device_token)
+                        .await;
+                    report.failed += 1;
+                }
+                Err(e) => {
+                    dead_letters
+                        .record_redrive_failure(id, &e.to_string())
+                        .await?;
+                    report.failed += 1;
+                }
             }
         }
+        Ok(report)
+    }
+
+    async fn get_exam_period(&self) -> Result<Option<ExamPeriod>> {
+        match &self.exam_periods {
+            Some(exam_periods) => Ok(exam_periods.find_global().await?),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_exam_period(&self, period: Option<&ExamPeriod>) -> Result<()> {
+        let Some(exam_periods) = &self.exam_periods else {
+            anyhow::bail!("Exam periods are not stored");
+        };
+        exam_periods.save_global(period).await?;
         Ok(())
     }
 
-    async fn produce_user_info(&self, token: &str, device_token: &str) -> Result<User> {
+    async fn process_producing(&self, token: &str, devices: &[Device]) -> Result<()> {
+        let result = self
+            .exclusive(token, async {
+                self.retry_budget.start(token);
+                self.outbox
+                    .lock()
+                    .unwrap()
+                    .insert(token.to_string(), Vec::new());
+                let result = self.produce_all(token, devices).await;
+                // Left over when the overview check didn't run, e.g. with it disabled
+                self.graded_courses.lock().unwrap().remove(token);
+                self.flush_outbox(token).await;
+                self.retry_budget.finish(token);
+                result
+            })
+            .await;
+        if result.is_ok() && self.flags.background_sync_pushes {
+            self.push_background_sync(token, devices).await;
+        }
+        result
+    }
+
+    async fn process_event(&self, event: &ProviderEvent) -> Result<usize> {
+        let tokens = self
+            .data_service
+            .find_tokens_by_user_id(event.userid)
+            .await?;
+
+        let mut processed = 0;
+        for tokens in tokens.iter() {
+            if tokens.devices.is_empty() {
+                continue;
+            }
+            let result = self
+                .exclusive(&tokens.token, async {
+                    let result = self
+                        .produce_event(&tokens.token, &tokens.devices, event)
+                        .await;
+                    self.graded_courses.lock().unwrap().remove(&tokens.token);
+                    result
+                })
+                .await;
+            match result {
+                Ok(()) => processed += 1,
+                Err(e) => eprintln!("Error processing provider event: {:?}", e),
+            }
+        }
+        Ok(processed)
+    }
+
+    async fn produce_user_info(&self, token: &str, devices: &[Device]) -> Result<User> {
         let external_user = self.data_provider.get_user(token).await?;
         let user = self.data_service.get_user(token).await?;
         if !user.eq(&external_user) {
-            let body = external_user.create_body_message_user();
-            let notification =
-                Notification::new(device_token.to_string(), "New user info".to_string(), body);
-            self.producer.produce_notification(&notification).await;
+            let preferences = self.data_service.get_preferences(token).await?;
+            if preferences.allows(NotificationKind::UserInfo) {
+                let language = self.language(token).await;
+                // Keyed on the English body so a language switch doesn't resend it
+                let value = external_user.body_text().render(Language::En);
+                let notification = Notification::new(
+                    NotificationKind::UserInfo,
+                    self.templates.render(Text::UserInfoTitle, language),
+                    self.templates.render(external_user.body_text(), language),
+                )
+                .with_change(token, None, None, &value);
+                self.send(token, devices, &notification).await;
+            }
 
             self.data_service.update_user(token).await?;
         }
@@ -131,22 +974,56 @@ impl ProducerServiceInterface for ProducerService {
     async fn produce_course(
         &self,
         token: &str,
-        device_token: &str,
+        devices: &[Device],
         user: &User,
     ) -> Result<Vec<Course>> {
-        let mut flag = false;
-        let external_courses = self.data_provider.get_courses(token, user.userid).await?;
+        let mut external_courses = self.data_provider.get_courses(token, user.userid).await?;
+        sort_courses(&mut external_courses);
         let courses = self.data_service.get_courses(token).await?;
         let new_courses = compare_courses(&external_courses, &courses);
+        let removed = removed_courses(&external_courses, &courses);
+        // A removed course listed again is stored as active
+        let mut flag = courses.iter().any(|course| {
+            course.removed
+                && external_courses
+                    .iter()
+                    .any(|external_course| external_course.id == course.id)
+        }) || lacks_shortnames(&external_courses, &courses);
 
-        if !new_courses.is_empty() {
+        if !new_courses.is_empty() || !removed.is_empty() {
             flag = true;
 
-            for new_course in new_courses {
-                let body = new_course.fullname.clone();
-                let notification =
-                    Notification::new(device_token.to_string(), "New course".to_string(), body);
-                self.producer.produce_notification(&notification).await;
+            let preferences = self.data_service.get_preferences(token).await?;
+            if preferences.allows(NotificationKind::Course) {
+                let language = self.language(token).await;
+                for removed_course in removed {
+                    let notification = Notification::new(
+                        NotificationKind::Course,
+                        self.templates.render(Text::CourseRemovedTitle, language),
+                        removed_course.fullname.clone(),
+                    )
+                    .with_change(
+                        token,
+                        Some(removed_course.id),
+                        None,
+                        &format!("removed:{}", removed_course.fullname),
+                    );
+                    self.send(token, devices, &notification).await;
+                }
+                for new_course in new_courses {
+                    let notification = Notification::new(
+                        NotificationKind::Course,
+                        self.templates.render(Text::CourseTitle, language),
+                        new_course.fullname.clone(),
+                    )
+                    .with_change(
+                        token,
+                        Some(new_course.id),
+                        None,
+                        &new_course.fullname,
+                    );
+                    self.send(token, devices, &notification).await;
+                }
             }
         }
 
@@ -159,9 +1036,10 @@ impl ProducerServiceInterface for ProducerService {
     async fn produce_deadline(
         &self,
         token: &str,
-        device_token: &str,
+        devices: &[Device],
         courses: &[Course],
     ) -> Result<()> {
+        let preferences = self.data_service.get_preferences(token).await?;
         let mut flag = false;
         for course in courses {
             let deadlines = match self.data_service.get_deadlines(token).await {
@@ -178,58 +1056,459 @@ impl ProducerServiceInterface for ProducerService {
                 .await?
                 .events;
 
-            if external_deadlines.is_empty() {
-                continue;
-            };
-
             for sorted_deadline in external_deadlines.iter_mut() {
                 sorted_deadline.coursename = Option::from(course.fullname.clone());
             }
 
             let sorted_deadlines = sort_deadlines(&mut external_deadlines)?;
-            let new_deadlines = compare_deadlines(&sorted_deadlines, &deadlines);
+            let new_deadlines = self
+                .comparison(token)
+                .compare_deadlines(&sorted_deadlines, &deadlines);
+            let removed = removed_deadlines(
+                &sorted_deadlines,
+                &deadlines,
+                course.id,
+                Utc::now().timestamp(),
+            );
+            let moved = moved_deadlines(&sorted_deadlines, &deadlines);
 
-            if !new_deadlines.is_empty() {
+            if !new_deadlines.is_empty() || !removed.is_empty() || !moved.is_empty() {
                 flag = true;
-                for new_deadline in new_deadlines {
-                    let body = new_deadline.create_body_message_deadline();
+                if !preferences.allows(NotificationKind::Deadline)
+                    || preferences.is_course_muted(course.id)
+                {
+                    continue;
+                }
+                let language = self.language(token).await;
+                for removed_deadline in removed {
                     let notification = Notification::new(
-                        device_token.to_string(),
-                        "New deadline".to_string(),
-                        body,
+                        NotificationKind::Deadline,
+                        self.templates.render(Text::DeadlineRemovedTitle, language),
+                        self.templates
+                            .render(removed_deadline.body_text(), language),
+                    )
+                    .with_change(
+                        token,
+                        Some(course.id),
+                        Some(removed_deadline.id.into()),
+                        &format!("removed:{}", removed_deadline.timeusermidnight),
                     );
-                    self.producer.produce_notification(&notification).await;
+                    let notification = self.with_actions(notification, language, false);
+                    self.deliver(token, devices, &notification, preferences.digest)
+                        .await;
+                }
+                let timezone = self
+                    .data_service
+                    .get_settings(token)
+                    .await
+                    .unwrap_or_default()
+                    .tz();
+                for (moved_deadline, stored) in moved {
+                    let (from, to) = (stored.due_at(), moved_deadline.due_at());
+                    let notification = Notification::new(
+                        NotificationKind::Deadline,
+                        self.templates.render(Text::DeadlineMovedTitle, language),
+                        self.templates.render(
+                            Text::DeadlineMovedBody {
+                                course: &course.fullname,
+                                task: &moved_deadline.name,
+                                from: &format_due(from, timezone),
+                                to: &format_due(to, timezone),
+                            },
+                            language,
+                        ),
+                    )
+                    .with_change(
+                        token,
+                        Some(course.id),
+                        Some(moved_deadline.id.into()),
+                        &format!("moved:{}:{}", from, to),
+                    )
+                    .with_due_change(from, to)
+                    .with_priority(Priority::for_deadline(to, Utc::now().timestamp()));
+                    let notification = self.with_actions(notification, language, false);
+                    self.deliver(token, devices, &notification, preferences.digest)
+                        .await;
+                }
+                let mut sent = 0;
+                for new_deadline in new_deadlines {
+                    let notification = Notification::new(
+                        NotificationKind::Deadline,
+                        self.templates.render(Text::DeadlineTitle, language),
+                        self.templates.render(new_deadline.body_text(), language),
+                    )
+                    .with_change(
+                        token,
+                        Some(course.id),
+                        Some(new_deadline.id.into()),
+                        &new_deadline.timeusermidnight.to_string(),
+                    )
+                    .with_priority(Priority::for_deadline(
+                        new_deadline.due_at(),
+                        Utc::now().timestamp(),
+                    ));
+                    let notification = self.with_actions(notification, language, false);
+                    if self
+                        .deliver(token, devices, &notification, preferences.digest)
+                        .await
+                    {
+                        sent += 1;
+                    }
+                }
+                // Best effort: failing here must not leave the change unsaved
+                if sent > 0 {
+                    if let Err(e) = self
+                        .data_service
+                        .add_unread(token, course.id, NotificationKind::Deadline, sent)
+                        .await
+                    {
+                        eprintln!("Error counting unread deadlines: {}", e);
+                    }
                 }
             }
         }
 
-        if flag {
+        // Deadlines stored before their course id was kept are rewritten once
+        let missing_course = self
+            .data_service
+            .get_deadlines(token)
+            .await
+            .is_ok_and(|deadlines| deadlines.iter().any(|d| d.courseid.is_none()));
+        if flag || missing_course {
             self.data_service.update_deadlines(token, courses).await?;
         }
 
         Ok(())
     }
 
-    async fn produce_grade(
+    /// Notifies about new or updated sections and files in each course; a
+    /// course's contents are stored quietly the first time they're fetched.
+    async fn produce_material(
         &self,
         token: &str,
-        device_token: &str,
-        user: &User,
+        devices: &[Device],
         courses: &[Course],
     ) -> Result<()> {
-        let mut flag = false;
-        let past_grades = self.data_service.get_grades(token).await?;
-
-        let all_courses_in_grades = courses
-            .iter()
-            .all(|course| past_grades.iter().any(|grade| grade.courseid == course.id));
-
-        if !all_courses_in_grades {
-            self.data_service
-                .update_grades(token, user, courses)
-                .await?;
-        }
-
+        let preferences = self.data_service.get_preferences(token).await?;
+        let stored = self.data_service.get_course_contents(token).await?;
+        let language = self.language(token).await;
+        for course in courses {
+            // One unavailable course shouldn't hold back the others
+            let sections = match self
+                .data_provider
+                .get_course_contents(token, course.id)
+                .await
+            {
+                Ok(sections) => sections,
+                Err(e) => {
+                    eprintln!("Error loading course contents: {}", e);
+                    continue;
+                }
+            };
+            let contents = CourseContent::from_sections(course.id, &sections);
+            let changed = changed_course_contents(&contents, &stored, course.id);
+            let is_new = !stored.iter().any(|content| content.course_id == course.id);
+            if changed.is_empty() && !is_new {
+                continue;
+            }
+
+            if !preferences.is_course_muted(course.id) {
+                for content in changed {
+                    let notification = Notification::new(
+                        NotificationKind::Material,
+                        self.templates.render(Text::MaterialTitle, language),
+                        self.templates.render(
+                            Text::MaterialBody {
+                                course: &course.fullname,
+                                name: &content.name,
+                            },
+                            language,
+                        ),
+                    )
+                    .with_change(
+                        token,
+                        Some(course.id),
+                        Some(content.module_id.unwrap_or(content.section_id)),
+                        &format!("{}:{}", content.section_id, content.timemodified),
+                    );
+                    self.deliver(token, devices, &notification, preferences.digest)
+                        .await;
+                }
+            }
+            self.data_service
+                .update_course_contents(token, course.id, &contents)
+                .await?;
+        }
+        // Courses the user left would otherwise be kept forever
+        let course_ids: Vec<i64> = courses.iter().map(|course| course.id).collect();
+        self.data_service
+            .retain_course_contents(token, &course_ids)
+            .await?;
+        Ok(())
+    }
+
+    /// Reminds about stored deadlines due within `hours`, skipping snoozed ones.
+    async fn produce_reminders(&self, token: &str, devices: &[Device], hours: i64) -> Result<()> {
+        let deadlines = match self.data_service.get_deadlines(token).await {
+            Ok(deadlines) => deadlines,
+            Err(ServiceError::DataIsEmpty(_) | ServiceError::DataNotFound(_)) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let preferences = self.data_service.get_preferences(token).await?;
+        if !preferences.allows(NotificationKind::Deadline) {
+            return Ok(());
+        }
+        let snoozes = self.data_service.get_snoozes(token).await?;
+        let language = self.language(token).await;
+        let now = Utc::now().timestamp();
+
+        for deadline in due_reminders(&deadlines, &snoozes, now, hours * 3600) {
+            if deadline
+                .courseid
+                .is_some_and(|id| preferences.is_course_muted(id))
+            {
+                continue;
+            }
+            // An expired snooze changes the key, so the reminder is sent once more
+            let snoozed_until = snoozes
+                .iter()
+                .find(|snooze| snooze.deadline_id == deadline.id)
+                .map_or(0, |snooze| snooze.until);
+            let notification = Notification::new(
+                NotificationKind::Deadline,
+                self.templates.render(Text::DeadlineReminderTitle, language),
+                self.templates.render(deadline.body_text(), language),
+            )
+            .with_change(
+                token,
+                deadline.courseid,
+                Some(deadline.id.into()),
+                &format!("reminder:{}:{}", deadline.timeusermidnight, snoozed_until),
+            )
+            .with_priority(Priority::for_deadline(deadline.due_at(), now));
+            let notification = self.with_actions(notification, language, true);
+            self.send(token, devices, &notification).await;
+        }
+        Ok(())
+    }
+
+    /// Reminds at each of the user's escalation tiers before each stored
+    /// deadline, each at most once. Submitted assignments drop out of the
+    /// provider's action events, so only unsubmitted ones are reminded about.
+    async fn produce_scheduled_reminders(&self, token: &str, devices: &[Device]) -> Result<()> {
+        let deadlines = match self.data_service.get_deadlines(token).await {
+            Ok(deadlines) => deadlines,
+            Err(ServiceError::DataIsEmpty(_) | ServiceError::DataNotFound(_)) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let preferences = self.data_service.get_preferences(token).await?;
+        if !preferences.allows(NotificationKind::Deadline) {
+            return Ok(());
+        }
+        let snoozes = self.data_service.get_snoozes(token).await?;
+        let sent = self.data_service.get_sent_reminders(token).await?;
+        let language = self.language(token).await;
+        let now = Utc::now().timestamp();
+
+        let mut reminded = Vec::new();
+        let tiers = preferences.reminder_tiers();
+        for (deadline, reminder) in scheduled_reminders(&deadlines, tiers, &sent, now) {
+            if deadline
+                .courseid
+                .is_some_and(|id| preferences.is_course_muted(id))
+            {
+                continue;
+            }
+            // Left unmarked so it goes out once the snooze expires, if still in time
+            if snoozes
+                .iter()
+                .any(|snooze| snooze.deadline_id == deadline.id && snooze.until > now)
+            {
+                continue;
+            }
+            let notification = Notification::new(
+                NotificationKind::Deadline,
+                self.templates
+                    .render(lead_text(reminder.lead_minutes), language),
+                self.templates.render(deadline.body_text(), language),
+            )
+            .with_change(
+                token,
+                deadline.courseid,
+                Some(deadline.id.into()),
+                &format!("scheduled:{}:{}m", reminder.due, reminder.lead_minutes),
+            )
+            .with_priority(Priority::for_deadline(reminder.due, now));
+            let notification = self.with_actions(notification, language, true);
+            self.send(token, devices, &notification).await;
+            reminded.push(reminder);
+        }
+        if !reminded.is_empty() {
+            self.data_service
+                .mark_reminders_sent(token, &reminded)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Sends the user's own reminders whose time has come, unless the deadline is snoozed.
+    async fn produce_custom_reminders(&self, token: &str, devices: &[Device]) -> Result<()> {
+        let Some(reminder_service) = &self.reminder_service else {
+            return Ok(());
+        };
+        let now = Utc::now().timestamp();
+        let pending = reminder_service.find_pending(token).await?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let deadlines = match self.data_service.get_deadlines(token).await {
+            Ok(deadlines) => deadlines,
+            Err(ServiceError::DataIsEmpty(_) | ServiceError::DataNotFound(_)) => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let preferences = self.data_service.get_preferences(token).await?;
+        let snoozes = self.data_service.get_snoozes(token).await?;
+        let language = self.language(token).await;
+
+        for reminder in pending {
+            let deadline = deadlines
+                .iter()
+                .find(|deadline| deadline.id == reminder.deadline_id);
+            if deadline.is_some_and(|deadline| {
+                deadline.due_at() > now && reminder.remind_at_before(deadline.due_at()) > now
+            }) {
+                continue;
+            }
+            // Left pending so it goes out once the snooze expires
+            if snoozes
+                .iter()
+                .any(|snooze| snooze.deadline_id == reminder.deadline_id && snooze.until > now)
+            {
+                continue;
+            }
+            let Some(id) = reminder.id else {
+                continue;
+            };
+            // Reminders of removed, passed or muted deadlines are dropped unsent
+            let deadline = deadline.filter(|deadline| {
+                preferences.allows(NotificationKind::Deadline)
+                    && deadline.due_at() > now
+                    && !deadline
+                        .courseid
+                        .is_some_and(|id| preferences.is_course_muted(id))
+            });
+            if let Some(deadline) = deadline {
+                let notification = Notification::new(
+                    NotificationKind::Deadline,
+                    self.templates.render(Text::DeadlineReminderTitle, language),
+                    self.templates.render(deadline.body_text(), language),
+                )
+                .with_change(
+                    token,
+                    deadline.courseid,
+                    Some(deadline.id.into()),
+                    &format!("reminder:{}", id.to_hex()),
+                )
+                .with_priority(Priority::for_deadline(deadline.due_at(), now));
+                let notification = self.with_actions(notification, language, true);
+                self.send(token, devices, &notification).await;
+            }
+            reminder_service.delete_reminder(id).await?;
+        }
+        Ok(())
+    }
+
+    /// Summarizes the changes held over the last day, once each morning in the
+    /// user's timezone.
+    async fn produce_digest(&self, token: &str, devices: &[Device]) -> Result<()> {
+        let now = Utc::now();
+        let settings = self.data_service.get_settings(token).await?;
+        let local = now.with_timezone(&settings.tz());
+        if local.hour() != DIGEST_HOUR {
+            return Ok(());
+        }
+        let held = self
+            .history_service
+            .get_held(token, now.timestamp() - 24 * 3600)
+            .await?;
+        let Some(body) = digest_body(&held, &self.templates, settings.lang()) else {
+            return Ok(());
+        };
+        let notification = Notification::new(
+            NotificationKind::Digest,
+            self.templates.render(Text::DigestTitle, settings.lang()),
+            body,
+        )
+        .with_change(token, None, None, &format!("digest:{}", local.date_naive()));
+        self.send(token, devices, &notification).await;
+        Ok(())
+    }
+
+    /// Sends the week's grade summary on the user's chosen weekday and time.
+    async fn produce_weekly_report(&self, token: &str, devices: &[Device]) -> Result<()> {
+        let preferences = self.data_service.get_preferences(token).await?;
+        let Some(report) = &preferences.weekly_report else {
+            return Ok(());
+        };
+        if !preferences.allows(NotificationKind::WeeklyReport) {
+            return Ok(());
+        }
+        let now = Utc::now();
+        let settings = self.data_service.get_settings(token).await?;
+        let timezone = settings.tz();
+        if !report.is_due(timezone, now.timestamp()) {
+            return Ok(());
+        }
+        let grades = self
+            .history_service
+            .get_grade_changes(token, now.timestamp() - 7 * 24 * 3600)
+            .await?;
+        let Some(body) = weekly_report_body(&grades, &self.templates, settings.lang()) else {
+            return Ok(());
+        };
+        let week = now.with_timezone(&timezone).iso_week();
+        let notification = Notification::new(
+            NotificationKind::WeeklyReport,
+            self.templates
+                .render(Text::WeeklyReportTitle, settings.lang()),
+            body,
+        )
+        .with_change(
+            token,
+            None,
+            None,
+            &format!("weekly:{}-{}", week.year(), week.week()),
+        );
+        self.send(token, devices, &notification).await;
+        Ok(())
+    }
+
+    async fn produce_grade(
+        &self,
+        token: &str,
+        devices: &[Device],
+        user: &User,
+        courses: &[Course],
+    ) -> Result<()> {
+        let preferences = self.data_service.get_preferences(token).await?;
+        let language = self.language(token).await;
+        let mut grade_marks = match self.flags.grade_notify_threshold {
+            Some(_) => self.data_service.get_grade_marks(token).await?,
+            None => Vec::new(),
+        };
+        let initial_grade_marks = grade_marks.clone();
+        let past_grades = self.data_service.get_grades(token).await?;
+
+        let all_courses_in_grades = courses
+            .iter()
+            .all(|course| past_grades.iter().any(|grade| grade.courseid == course.id));
+
+        if !all_courses_in_grades {
+            self.data_service
+                .update_grades(token, user, courses)
+                .await?;
+        }
+
         for course in courses {
             let mut external_grades = self
                 .data_provider
@@ -243,38 +1522,120 @@ impl ProducerServiceInterface for ProducerService {
 
             let mut grades = self.data_service.get_grades(token).await?;
 
-            for external_grade in external_grades.iter() {
-                for grade in grades.iter() {
-                    if external_grade.courseid == grade.courseid
+            // Grades stored before grading times were kept are rewritten once
+            let items_changed = external_grades.iter().any(|external_grade| {
+                grades.iter().any(|grade| {
+                    external_grade.courseid == grade.courseid
                         && external_grade.gradeitems.len() != grade.gradeitems.len()
-                    {
-                        self.data_service
-                            .update_grades(token, user, courses)
-                            .await?;
+                })
+            }) || lacks_graded_dates(&external_grades, &grades);
+
+            let new_grades = self
+                .comparison(token)
+                .compare_grades(&mut external_grades, &mut grades);
+            let grades_changed = !new_grades.is_empty();
+            let mut notifications = Vec::new();
+
+            for new_grade in new_grades {
+                if !preferences.allows_grade(course.id, new_grade.0) {
+                    continue;
+                }
+                if let Some(threshold) = self.flags.grade_notify_threshold {
+                    if !passes_grade_mark(&mut grade_marks, new_grade.0, threshold) {
+                        continue;
                     }
                 }
+                let title = course.fullname.clone();
+                let mut body = self.templates.render(
+                    Text::GradeBody {
+                        item: &new_grade.0.itemname,
+                        from: &new_grade.1.percentageformatted,
+                        to: &new_grade.0.percentageformatted,
+                    },
+                    language,
+                );
+                if let Some(feedback) = new_grade.0.feedback_snippet() {
+                    body.push('\n');
+                    body.push_str(
+                        &self
+                            .templates
+                            .render(Text::GradeFeedback(&feedback), language),
+                    );
+                }
+                let notification = Notification::new(NotificationKind::Grade, title, body)
+                    .with_change(
+                        token,
+                        Some(course.id),
+                        Some(new_grade.0.id),
+                        &new_grade.0.percentageformatted,
+                    );
+                notifications.push(self.with_actions(notification, language, false));
             }
 
-            let new_grades = compare_grades(&mut external_grades, &mut grades);
-
-            if !new_grades.is_empty() {
-                flag = true;
-                for new_grade in new_grades {
-                    let title = course.fullname.clone();
-                    let body = format!(
-                        "New grade | {}\n{} -> {}",
-                        new_grade.0.itemname,
-                        new_grade.1.percentageformatted,
-                        new_grade.0.percentageformatted
-                    );
-                    let notification = Notification::new(device_token.to_string(), title, body);
-                    self.producer.produce_notification(&notification).await;
+            let collapsed = match self.flags.grade_notifications_per_course {
+                Some(cap) if notifications.len() > cap => notifications.split_off(cap),
+                _ => Vec::new(),
+            };
+            let mut sent = 0;
+            for notification in &notifications {
+                if self
+                    .deliver(token, devices, notification, preferences.digest)
+                    .await
+                {
+                    sent += 1;
+                }
+            }
+            if !collapsed.is_empty() {
+                let body = self.templates.render(
+                    Text::GradesAdded {
+                        course: &course.fullname,
+                        count: collapsed.len(),
+                    },
+                    language,
+                );
+                let keys: Vec<&str> = collapsed
+                    .iter()
+                    .filter_map(|notification| notification.idempotency_key.as_deref())
+                    .collect();
+                let summary =
+                    Notification::new(NotificationKind::Grade, course.fullname.clone(), body)
+                        .with_thread(course.id)
+                        .with_summary_key(token, &keys);
+                if self
+                    .deliver(token, devices, &summary, preferences.digest)
+                    .await
+                {
+                    sent += collapsed.len() as u32;
+                }
+            }
+            // Only the affected course is stored; the grades were fetched above
+            if items_changed || grades_changed {
+                self.data_service
+                    .update_grades_for_course(token, course.id, &external_grades)
+                    .await?;
+            }
+            if sent > 0 {
+                // Best effort: failing here must not leave the change unsaved
+                if let Err(e) = self
+                    .data_service
+                    .add_unread(token, course.id, NotificationKind::Grade, sent)
+                    .await
+                {
+                    eprintln!("Error counting unread grades: {}", e);
+                }
+                if self.flags.suppress_overview_after_grade {
+                    self.graded_courses
+                        .lock()
+                        .unwrap()
+                        .entry(token.to_string())
+                        .or_default()
+                        .insert(course.id);
                 }
             }
         }
-        if flag {
+        if grade_marks != initial_grade_marks {
             self.data_service
-                .update_grades(token, user, courses)
+                .update_grade_marks(token, &grade_marks)
                 .await?;
         }
 
@@ -284,9 +1645,16 @@ impl ProducerServiceInterface for ProducerService {
     async fn produce_grade_overview(
         &self,
         token: &str,
-        device_token: &str,
+        devices: &[Device],
         courses: &[Course],
     ) -> Result<()> {
+        // Taken up front so a failed pass doesn't leak into the next one
+        let graded_courses = self
+            .graded_courses
+            .lock()
+            .unwrap()
+            .remove(token)
+            .unwrap_or_default();
         let mut flag = false;
         let external_grades_overview = self
             .data_service
@@ -296,18 +1664,36 @@ impl ProducerServiceInterface for ProducerService {
         let mut grades_overview = self.data_service.get_grades_overview(token).await?;
         sort_grades_overview(&mut grades_overview);
 
+        let preferences = self.data_service.get_preferences(token).await?;
+        let language = self.language(token).await;
         let new_external_grades =
             compare_grades_overview(&external_grades_overview.grades, &grades_overview);
         if !new_external_grades.is_empty() {
             flag = true;
             for new_external_grade in new_external_grades.iter() {
+                if !preferences.allows(NotificationKind::GradeOverview)
+                    || graded_courses.contains(&new_external_grade.courseid)
+                    || preferences.is_course_muted(new_external_grade.courseid)
+                {
+                    continue;
+                }
                 let title = new_external_grade
                     .course_name
                     .clone()
                     .unwrap_or("-".to_string());
-                let body = format!("New course total grade | {}", new_external_grade.grade);
-                let notification = Notification::new(device_token.to_string(), title, body);
-                self.producer.produce_notification(&notification).await;
+                let body = self
+                    .templates
+                    .render(Text::CourseTotalBody(&new_external_grade.grade), language);
+                let notification = Notification::new(NotificationKind::GradeOverview, title, body)
+                    .with_change(
+                        token,
+                        Some(new_external_grade.courseid),
+                        None,
+                        &new_external_grade.grade,
+                    );
+                let notification = self.with_actions(notification, language, false);
+                self.deliver(token, devices, &notification, preferences.digest)
+                    .await;
             }
         }
         if flag {
@@ -319,3 +1705,1772 @@ impl ProducerServiceInterface for ProducerService {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::event_producer::fcm::FcmError;
+    use crate::models::broadcast::BroadcastSegment;
+    use crate::models::course_content::{ContentFile, ContentModule, ContentSection};
+    use crate::models::deadline::Deadline;
+    use crate::models::grade::{Grade, GradeItems};
+    use crate::models::history::{delivery_stats, HistoryEntry};
+    use crate::models::preferences::{NotificationCategories, QuietHours, WeeklyReport};
+    use crate::models::registration::{BackfillResource, RegistrationSettings};
+    use crate::models::reminder::{DeadlineSnooze, ReminderEntry};
+    use crate::models::settings::UserSettings;
+    use crate::models::token::Platform;
+    use crate::models::user_list::UserListQuery;
+    use crate::services::data_service::DataService;
+    use crate::services::data_service_interfaces::UnreadServiceInterface;
+    use crate::services::mocks::{
+        course, deadline, device as mock_device, grade, grade_overview, user,
+        MockDeadLetterRepository, MockEventProducer, MockExamPeriodRepository, MockHistoryService,
+        MockProvider, MockReminderRepository, MockRepository, MockStatsService,
+    };
+    use crate::services::reminder_service::ReminderService;
+    use mongodb::bson::{oid::ObjectId, DateTime};
+    use reqwest::StatusCode;
+
+    fn producer_service(
+        producer: &MockEventProducer,
+        provider: &MockProvider,
+        repository: &MockRepository,
+    ) -> ProducerService {
+        producer_service_with(producer, provider, repository, FeatureFlags::default())
+    }
+
+    fn producer_service_with(
+        producer: &MockEventProducer,
+        provider: &MockProvider,
+        repository: &MockRepository,
+        flags: FeatureFlags,
+    ) -> ProducerService {
+        let data_provider: Arc<dyn DataProviderInterface> = Arc::new(provider.clone());
+        let data_service = DataService::new(
+            Arc::clone(&data_provider),
+            Box::new(repository.clone()),
+            RegistrationSettings::default(),
+            flags.clone(),
+        );
+        ProducerService::new(
+            Box::new(producer.clone()),
+            data_provider,
+            Arc::new(data_service),
+            Arc::new(MockStatsService::default()),
+            Arc::new(MockHistoryService::default()),
+            flags,
+            Arc::new(RetryBudget::new(0)),
+        )
+    }
+
+    fn device() -> Device {
+        Device {
+            token: "device".to_string(),
+            platform: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grade_change_in_one_course_does_not_refetch_all_courses() {
+        let courses = vec![course(1), course(2), course(3)];
+        let provider = MockProvider::default();
+        {
+            let mut grades = provider.grades.lock().unwrap();
+            grades.insert(1, vec![grade(1, &[(10, "50.00 %")])]);
+            // Course 2 gained an item and an existing item changed
+            grades.insert(2, vec![grade(2, &[(20, "90.00 %"), (21, "70.00 %")])]);
+            grades.insert(3, vec![grade(3, &[(30, "60.00 %")])]);
+        }
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .grades = Some(vec![
+            grade(1, &[(10, "50.00 %")]),
+            grade(2, &[(20, "80.00 %")]),
+            grade(3, &[(30, "60.00 %")]),
+        ]);
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        service
+            .produce_grade("token", &[device()], &user(1), &courses)
+            .await
+            .unwrap();
+
+        assert_eq!(provider.calls_to("get_grades_by_course_id"), courses.len());
+        assert_eq!(producer.sent.lock().unwrap().len(), 1);
+
+        let stored = repository.stored("token").unwrap().grades.unwrap();
+        assert_eq!(stored.len(), 3);
+        let course_2 = stored.iter().find(|g| g.courseid == 2).unwrap();
+        assert_eq!(course_2.gradeitems.len(), 2);
+        assert_eq!(course_2.gradeitems[0].percentageformatted, "90.00 %");
+    }
+
+    #[tokio::test]
+    async fn test_unread_grades_accrue_until_acknowledged() {
+        let courses = vec![course(1), course(2)];
+        let provider = MockProvider::default();
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .grades = Some(vec![
+            grade(1, &[(10, "50.00 %")]),
+            grade(2, &[(20, "0.00 %")]),
+        ]);
+        let service = producer_service(&MockEventProducer::default(), &provider, &repository);
+
+        for percentage in ["60.00 %", "70.00 %"] {
+            provider
+                .grades
+                .lock()
+                .unwrap()
+                .insert(1, vec![grade(1, &[(10, percentage)])]);
+            service
+                .produce_grade("token", &[device()], &user(1), &courses)
+                .await
+                .unwrap();
+        }
+
+        let unread = repository.stored("token").unwrap().unread;
+        assert_eq!(unread.len(), 1);
+        assert_eq!((unread[0].courseid, unread[0].grades), (1, 2));
+
+        let data_service = DataService::new(
+            Arc::new(provider.clone()),
+            Box::new(repository.clone()),
+            RegistrationSettings::default(),
+            FeatureFlags::default(),
+        );
+        let remaining = data_service.ack_unread("token", Some(&[1])).await.unwrap();
+        assert!(remaining.is_empty());
+        assert!(data_service
+            .get_unread_courses("token")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    /// Candidate logic that never finds anything new.
+    struct NothingIsNew;
+
+    impl ComparisonStrategy for NothingIsNew {
+        fn compare_grades<'a>(
+            &self,
+            _external_grades: &'a mut [Grade],
+            _grades: &'a mut [Grade],
+        ) -> Vec<(&'a GradeItems, &'a GradeItems)> {
+            Vec::new()
+        }
+
+        fn compare_deadlines<'a>(
+            &self,
+            _external_deadlines: &'a [Deadline],
+            _deadlines: &[Deadline],
+        ) -> Vec<&'a Deadline> {
+            Vec::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_canary_cohort_uses_canary_comparison() {
+        let mut sent = Vec::new();
+        for canary_percent in [0, 100] {
+            let provider = MockProvider::default();
+            provider
+                .grades
+                .lock()
+                .unwrap()
+                .insert(1, vec![grade(1, &[(10, "60.00 %")])]);
+            let repository = MockRepository::with_tokens(&["token"]);
+            repository
+                .users
+                .lock()
+                .unwrap()
+                .get_mut("token")
+                .unwrap()
+                .grades = Some(vec![grade(1, &[(10, "50.00 %")])]);
+            let producer = MockEventProducer::default();
+            let flags = FeatureFlags {
+                canary_percent,
+                ..Default::default()
+            };
+            let mut service = producer_service_with(&producer, &provider, &repository, flags);
+            service.canary_comparison = Box::new(NothingIsNew);
+
+            service
+                .produce_grade("token", &[device()], &user(1), &[course(1)])
+                .await
+                .unwrap();
+            sent.push(producer.sent.lock().unwrap().len());
+        }
+        assert_eq!(sent, vec![1, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_grade_notifications_over_course_cap_collapse_into_summary() {
+        let courses = vec![course(1)];
+        let stored: Vec<(i64, &str)> = (1..=5).map(|id| (id, "-")).collect();
+        let graded: Vec<(i64, &str)> = (1..=5).map(|id| (id, "80.00 %")).collect();
+        let provider = MockProvider::default();
+        provider
+            .grades
+            .lock()
+            .unwrap()
+            .insert(1, vec![grade(1, &graded)]);
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .grades = Some(vec![grade(1, &stored)]);
+        let producer = MockEventProducer::default();
+        let service = producer_service_with(
+            &producer,
+            &provider,
+            &repository,
+            FeatureFlags {
+                grade_notifications_per_course: Some(2),
+                ..Default::default()
+            },
+        );
+
+        // A re-run over the same snapshot finds every change already sent
+        for _ in 0..2 {
+            service
+                .produce_grade("token", &[device()], &user(1), &courses)
+                .await
+                .unwrap();
+            repository
+                .users
+                .lock()
+                .unwrap()
+                .get_mut("token")
+                .unwrap()
+                .grades = Some(vec![grade(1, &stored)]);
+        }
+
+        let bodies: Vec<_> = producer
+            .sent
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|n| n.2.clone())
+            .collect();
+        assert_eq!(bodies.len(), 3);
+        assert!(bodies[0].starts_with("New grade |"));
+        assert!(bodies[1].starts_with("New grade |"));
+        assert_eq!(bodies[2], "Course 1: 3 grades added");
+        let unread = repository.stored("token").unwrap().unread;
+        assert_eq!(unread[0].grades, 5);
+    }
+
+    #[tokio::test]
+    async fn test_notification_with_same_idempotency_key_is_suppressed() {
+        let producer = MockEventProducer::default();
+        let service = producer_service(
+            &producer,
+            &MockProvider::default(),
+            &MockRepository::default(),
+        );
+        let notification = || {
+            Notification::new(
+                NotificationKind::Grade,
+                "Math".to_string(),
+                "New grade".to_string(),
+            )
+            .with_change("token", Some(1), Some(2), "90 %")
+        };
+
+        assert!(service.send("token", &[device()], &notification()).await);
+        assert!(!service.send("token", &[device()], &notification()).await);
+        assert_eq!(producer.sent.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_notification_fans_out_to_every_device_once() {
+        let producer = MockEventProducer::default();
+        let service = producer_service(
+            &producer,
+            &MockProvider::default(),
+            &MockRepository::default(),
+        );
+        let devices = [
+            mock_device("phone", Some(Platform::Android)),
+            mock_device("ipad", Some(Platform::Ios)),
+        ];
+        let notification = Notification::new(
+            NotificationKind::Course,
+            "New course".to_string(),
+            "Math".to_string(),
+        )
+        .with_change("token", Some(1), None, "Math");
+
+        assert!(service.send("token", &devices, &notification).await);
+        assert!(!service.send("token", &devices, &notification).await);
+        assert_eq!(*producer.devices.lock().unwrap(), ["phone", "ipad"]);
+    }
+
+    #[tokio::test]
+    async fn test_sent_notification_counted_once_in_stats() {
+        let producer = MockEventProducer::default();
+        let stats = MockStatsService::default();
+        stats.users.lock().unwrap().total = 3;
+        let service = ProducerService {
+            stats_service: Arc::new(stats.clone()),
+            ..producer_service(
+                &producer,
+                &MockProvider::default(),
+                &MockRepository::default(),
+            )
+        };
+        let notification = Notification::new(
+            NotificationKind::Course,
+            "New course".to_string(),
+            "Math".to_string(),
+        )
+        .with_change("token", Some(1), None, "Math");
+
+        for _ in 0..2 {
+            service.send("token", &[device()], &notification).await;
+        }
+
+        let admin_stats = stats.get_stats(1).await.unwrap();
+        assert_eq!(admin_stats.notifications.len(), 1);
+        assert_eq!(admin_stats.notifications[0].kind, NotificationKind::Course);
+        assert_eq!(admin_stats.notifications[0].count, 1);
+        assert_eq!(admin_stats.notifications_last_24h, 1);
+        assert_eq!(admin_stats.users.total, 3);
+    }
+
+    #[tokio::test]
+    async fn test_pass_sends_user_notifications_in_one_batch() {
+        let (provider, repository) = single_item_change();
+        let producer = MockEventProducer::default();
+        let history = MockHistoryService::default();
+        let service = ProducerService {
+            history_service: Arc::new(history.clone()),
+            ..producer_service(&producer, &provider, &repository)
+        };
+        let devices = [mock_device("phone", None), mock_device("ipad", None)];
+
+        service.process_producing("token", &devices).await.unwrap();
+
+        assert_eq!(*producer.batches.lock().unwrap(), [4]);
+        assert_eq!(
+            *producer.devices.lock().unwrap(),
+            ["phone", "ipad", "phone", "ipad"]
+        );
+        let entries = history.entries.lock().unwrap();
+        let kinds: Vec<_> = entries.iter().map(|entry| entry.kind).collect();
+        assert_eq!(
+            kinds,
+            [NotificationKind::Grade, NotificationKind::GradeOverview]
+        );
+        assert!(entries
+            .iter()
+            .all(|entry| entry.delivery == DeliveryStatus::Delivered));
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_passes_for_a_token_drop_no_push() {
+        let (provider, repository) = single_item_change();
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+        let devices = [device()];
+
+        let (first, second) = tokio::join!(service.process_producing("token", &devices), async {
+            // Starts once the first pass has collected a push
+            while service
+                .outbox
+                .lock()
+                .unwrap()
+                .get("token")
+                .is_none_or(|pending| pending.is_empty())
+            {
+                tokio::task::yield_now().await;
+            }
+            service.process_producing("token", &devices).await
+        },);
+        first.unwrap();
+        second.unwrap();
+
+        let kinds: Vec<_> = producer.sent.lock().unwrap().iter().map(|n| n.0).collect();
+        assert_eq!(
+            kinds,
+            [NotificationKind::Grade, NotificationKind::GradeOverview]
+        );
+        assert_eq!(producer.batches.lock().unwrap()[0], 2);
+        assert!(service.passes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_background_sync_push_follows_pass_to_apps_only() {
+        let (provider, repository) = single_item_change();
+        let producer = MockEventProducer::default();
+        let flags = FeatureFlags {
+            background_sync_pushes: true,
+            ..FeatureFlags::default()
+        };
+        let service = producer_service_with(&producer, &provider, &repository, flags);
+        let devices = [
+            mock_device("phone", Some(Platform::Android)),
+            mock_device("123", Some(Platform::Telegram)),
+        ];
+
+        service.process_producing("token", &devices).await.unwrap();
+
+        assert_eq!(*producer.batches.lock().unwrap(), [4, 1]);
+        assert_eq!(producer.devices.lock().unwrap().last().unwrap(), "phone");
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.last().unwrap().0, NotificationKind::Sync);
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_device_dropped_after_pass() {
+        let (provider, repository) = single_item_change();
+        let devices = [
+            mock_device("dead", Some(Platform::Android)),
+            mock_device("ipad", Some(Platform::Ios)),
+        ];
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .devices = devices.to_vec();
+        let producer = MockEventProducer::default();
+        *producer.failure.lock().unwrap() = Some(ProduceError::Unregistered("gone".into()));
+        let service = producer_service(&producer, &provider, &repository);
+
+        service
+            .process_producing("token", &devices[..1])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.stored("token").unwrap().devices,
+            [mock_device("ipad", Some(Platform::Ios))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_not_found_without_error_code_keeps_device() {
+        let (provider, repository) = single_item_change();
+        let devices = [mock_device("android", Some(Platform::Android))];
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .devices = devices.to_vec();
+        let producer = MockEventProducer::default();
+        // What a wrong project id returns for every token
+        *producer.failure.lock().unwrap() =
+            Some(FcmError::from_response(StatusCode::NOT_FOUND, "Not Found").into());
+        let service = producer_service(&producer, &provider, &repository);
+
+        service.process_producing("token", &devices).await.unwrap();
+
+        assert_eq!(repository.stored("token").unwrap().devices, devices);
+    }
+
+    #[tokio::test]
+    async fn test_hourly_cap_folds_excess_into_one_push() {
+        let (provider, repository) = single_item_change();
+        let producer = MockEventProducer::default();
+        let history = MockHistoryService::default();
+        let earlier = Notification::new(NotificationKind::Course, String::new(), String::new());
+        history.entries.lock().unwrap().push(HistoryEntry::new(
+            "token",
+            "earlier",
+            &earlier,
+            DeliveryStatus::Delivered.into(),
+            DateTime::now(),
+        ));
+        let flags = FeatureFlags {
+            notifications_per_hour: Some(2),
+            ..FeatureFlags::default()
+        };
+        let service = ProducerService {
+            history_service: Arc::new(history.clone()),
+            ..producer_service_with(&producer, &provider, &repository, flags)
+        };
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].2, "And 2 more updates");
+        let entries = history.entries.lock().unwrap();
+        let deliveries: Vec<_> = entries
+            .iter()
+            .skip(1)
+            .map(|entry| (entry.kind, entry.delivery))
+            .collect();
+        assert_eq!(
+            deliveries,
+            [
+                (NotificationKind::Grade, DeliveryStatus::Folded),
+                (NotificationKind::GradeOverview, DeliveryStatus::Folded),
+                (NotificationKind::MoreUpdates, DeliveryStatus::Delivered),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pushes_carry_sound_and_unread_badge() {
+        let (provider, repository) = single_item_change();
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .settings = Some(UserSettings {
+            sound: Some("chime.caf".to_string()),
+            ..Default::default()
+        });
+        let producer = MockEventProducer::default();
+        let history = MockHistoryService::default();
+        let earlier = Notification::new(NotificationKind::Course, String::new(), String::new());
+        history.entries.lock().unwrap().push(HistoryEntry::new(
+            "token",
+            "earlier",
+            &earlier,
+            DeliveryStatus::Delivered.into(),
+            DateTime::now(),
+        ));
+        let service = ProducerService {
+            history_service: Arc::new(history.clone()),
+            ..producer_service_with(&producer, &provider, &repository, FeatureFlags::default())
+        };
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        let chime = Some("chime.caf".to_string());
+        assert_eq!(
+            *producer.alerts.lock().unwrap(),
+            [(chime.clone(), Some(2)), (chime, Some(3))]
+        );
+    }
+
+    fn single_item_change() -> (MockProvider, MockRepository) {
+        let provider = MockProvider::default();
+        provider.courses.lock().unwrap().push(course(1));
+        provider
+            .grades
+            .lock()
+            .unwrap()
+            .insert(1, vec![grade(1, &[(10, "60.00 %")])]);
+        provider
+            .grades_overview
+            .lock()
+            .unwrap()
+            .push(grade_overview(1, "60.00"));
+
+        let repository = MockRepository::with_tokens(&["token"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.user = Some(user(1));
+            stored.courses = Some(vec![course(1)]);
+            stored.grades = Some(vec![grade(1, &[(10, "50.00 %")])]);
+            stored.grades_overview = Some(vec![grade_overview(1, "50.00")]);
+        }
+        (provider, repository)
+    }
+
+    #[tokio::test]
+    async fn test_overview_suppressed_after_item_grade_in_same_course() {
+        let (provider, repository) = single_item_change();
+        let producer = MockEventProducer::default();
+        let service = producer_service_with(
+            &producer,
+            &provider,
+            &repository,
+            FeatureFlags {
+                suppress_overview_after_grade: true,
+                ..Default::default()
+            },
+        );
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        let kinds: Vec<_> = producer.sent.lock().unwrap().iter().map(|n| n.0).collect();
+        assert_eq!(kinds, vec![NotificationKind::Grade]);
+        let stored = repository.stored("token").unwrap().grades_overview.unwrap();
+        assert_eq!(stored[0].grade, "60.00");
+    }
+
+    #[tokio::test]
+    async fn test_graded_courses_forgotten_after_pass_without_overview() {
+        let (provider, repository) = single_item_change();
+        let producer = MockEventProducer::default();
+        let service = producer_service_with(
+            &producer,
+            &provider,
+            &repository,
+            FeatureFlags {
+                suppress_overview_after_grade: true,
+                grade_overview: false,
+                ..Default::default()
+            },
+        );
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        assert_eq!(producer.sent.lock().unwrap().len(), 1);
+        assert!(service.graded_courses.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_event_during_pass_keeps_its_graded_courses() {
+        let (provider, repository) = single_item_change();
+        // The pass grades course 1 before it checks course 2
+        provider.courses.lock().unwrap().push(course(2));
+        provider
+            .grades
+            .lock()
+            .unwrap()
+            .insert(2, vec![grade(2, &[(20, "70.00 %")])]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.devices = vec![device()];
+            stored.courses.as_mut().unwrap().push(course(2));
+            stored
+                .grades
+                .as_mut()
+                .unwrap()
+                .push(grade(2, &[(20, "70.00 %")]));
+        }
+        let producer = MockEventProducer::default();
+        let service = producer_service_with(
+            &producer,
+            &provider,
+            &repository,
+            FeatureFlags {
+                suppress_overview_after_grade: true,
+                ..Default::default()
+            },
+        );
+        let devices = [device()];
+        let event = ProviderEvent {
+            userid: 1,
+            kind: ProviderEventKind::Deadline,
+            courseid: None,
+        };
+
+        // Polled in order, so the event sees each step of the pass
+        let (pass, _) = futures::join!(service.process_producing("token", &devices), async {
+            // Arrives once the pass has graded the course
+            while !service.graded_courses.lock().unwrap().contains_key("token") {
+                tokio::task::yield_now().await;
+            }
+            service.process_event(&event).await
+        });
+        pass.unwrap();
+
+        let kinds: Vec<_> = producer.sent.lock().unwrap().iter().map(|n| n.0).collect();
+        assert_eq!(kinds, [NotificationKind::Grade]);
+        assert!(service.graded_courses.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_overview_sent_alongside_item_grade_by_default() {
+        let (provider, repository) = single_item_change();
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        let kinds: Vec<_> = producer.sent.lock().unwrap().iter().map(|n| n.0).collect();
+        assert_eq!(
+            kinds,
+            vec![NotificationKind::Grade, NotificationKind::GradeOverview]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_muted_course_sends_no_grade_or_deadline_notifications() {
+        let (provider, repository) = single_item_change();
+        provider
+            .deadlines
+            .lock()
+            .unwrap()
+            .insert(1, vec![deadline(5, Utc::now().timestamp() + 86400)]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.deadlines = Some(Vec::new());
+            stored.preferences = Some(Preferences {
+                ignore_courses: vec![1],
+                ..Default::default()
+            });
+        }
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        assert!(producer.sent.lock().unwrap().is_empty());
+        let stored = repository.stored("token").unwrap();
+        assert_eq!(
+            stored.grades.unwrap()[0].gradeitems[0].percentageformatted,
+            "60.00 %"
+        );
+        assert_eq!(stored.grades_overview.unwrap()[0].grade, "60.00");
+        assert_eq!(stored.deadlines.unwrap()[0].id, 5);
+    }
+
+    #[tokio::test]
+    async fn test_removed_deadline_notified_once_and_dropped_from_store() {
+        let now = Utc::now().timestamp();
+        let stored = |id| Deadline {
+            courseid: Some(1),
+            coursename: Some("Course 1".to_string()),
+            ..deadline(id, now + 86400)
+        };
+        let provider = MockProvider::default();
+        provider
+            .deadlines
+            .lock()
+            .unwrap()
+            .insert(1, vec![deadline(5, now + 86400)]);
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .deadlines = Some(vec![stored(5), stored(6)]);
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        for _ in 0..2 {
+            service
+                .produce_deadline("token", &[device()], &[course(1)])
+                .await
+                .unwrap();
+        }
+
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].1, "Deadline removed");
+        assert!(sent[0].2.contains("Task 6"));
+        let ids: Vec<i32> = repository
+            .stored("token")
+            .unwrap()
+            .deadlines
+            .unwrap()
+            .iter()
+            .map(|deadline| deadline.id)
+            .collect();
+        assert_eq!(ids, [5]);
+    }
+
+    #[tokio::test]
+    async fn test_deadlines_stored_without_course_are_backfilled() {
+        let now = Utc::now().timestamp();
+        let provider = MockProvider::default();
+        provider
+            .deadlines
+            .lock()
+            .unwrap()
+            .insert(1, vec![deadline(5, now + 86400)]);
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .deadlines = Some(vec![deadline(5, now + 86400)]);
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        service
+            .produce_deadline("token", &[device()], &[course(1)])
+            .await
+            .unwrap();
+
+        assert!(producer.sent.lock().unwrap().is_empty());
+        let stored = repository.stored("token").unwrap().deadlines.unwrap();
+        assert_eq!(stored[0].courseid, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_failed_backfill_does_not_hold_back_the_pass() {
+        let provider = MockProvider::default();
+        provider
+            .users
+            .lock()
+            .unwrap()
+            .insert("token".to_string(), user(1));
+        let repository = MockRepository::with_tokens(&["token"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.user = Some(user(1));
+            stored.pending_backfill = vec![BackfillResource::Grades];
+        }
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        // No courses are stored yet, so the backfill can't run
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        assert_eq!(provider.calls_to("get_courses"), 1);
+        assert_eq!(
+            repository.stored("token").unwrap().pending_backfill,
+            [BackfillResource::Grades]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_moved_deadline_notified_with_both_times() {
+        let now = Utc::now().timestamp();
+        let provider = MockProvider::default();
+        provider
+            .deadlines
+            .lock()
+            .unwrap()
+            .insert(1, vec![deadline(5, now + 2 * 86400)]);
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .deadlines = Some(vec![Deadline {
+            courseid: Some(1),
+            ..deadline(5, now + 86400)
+        }]);
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        for _ in 0..2 {
+            service
+                .produce_deadline("token", &[device()], &[course(1)])
+                .await
+                .unwrap();
+        }
+
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].1, "Deadline moved");
+        assert!(sent[0].2.contains("Task 5\nMoved from "));
+        let stored = repository.stored("token").unwrap().deadlines.unwrap();
+        assert_eq!(stored[0].timeusermidnight, now + 2 * 86400);
+    }
+
+    #[tokio::test]
+    async fn test_removed_course_notified_once_and_kept_flagged() {
+        let provider = MockProvider::default();
+        provider.courses.lock().unwrap().push(course(1));
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .courses = Some(vec![course(1), course(2)]);
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        for _ in 0..2 {
+            let courses = service
+                .produce_course("token", &[device()], &user(1))
+                .await
+                .unwrap();
+            assert_eq!(courses, [course(1)]);
+        }
+
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].1, "Course removed");
+        assert_eq!(sent[0].2, "Course 2");
+        let stored = repository.stored("token").unwrap().courses.unwrap();
+        let flags: Vec<(i64, bool)> = stored
+            .iter()
+            .map(|course| (course.id, course.removed))
+            .collect();
+        assert_eq!(flags, [(1, false), (2, true)]);
+    }
+
+    #[tokio::test]
+    async fn test_new_material_notified_after_contents_first_stored() {
+        let provider = MockProvider::default();
+        let module = |id: i64, timemodified: i64| ContentModule {
+            id,
+            name: format!("Lecture {}", id),
+            contents: vec![ContentFile { timemodified }],
+            contentsinfo: None,
+        };
+        let set_modules = |modules: Vec<ContentModule>| {
+            provider.contents.lock().unwrap().insert(
+                1,
+                vec![ContentSection {
+                    id: 10,
+                    name: "Week 1".to_string(),
+                    modules,
+                }],
+            );
+        };
+        let repository = MockRepository::with_tokens(&["token"]);
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        set_modules(vec![module(1, 100)]);
+        service
+            .produce_material("token", &[device()], &[course(1)])
+            .await
+            .unwrap();
+        assert!(producer.sent.lock().unwrap().is_empty());
+
+        set_modules(vec![module(1, 100), module(2, 200)]);
+        for _ in 0..2 {
+            service
+                .produce_material("token", &[device()], &[course(1)])
+                .await
+                .unwrap();
+        }
+
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, NotificationKind::Material);
+        assert_eq!(sent[0].2, "Course: Course 1\nMaterial: Lecture 2");
+        assert_eq!(repository.stored("token").unwrap().course_contents.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_material_skips_failing_course_and_prunes_left_courses() {
+        let provider = MockProvider::default();
+        let section = |id: i64| ContentSection {
+            id,
+            name: format!("Week {}", id),
+            modules: Vec::new(),
+        };
+        {
+            let mut contents = provider.contents.lock().unwrap();
+            contents.insert(1, vec![section(10)]);
+            contents.insert(2, vec![section(20)]);
+            contents.insert(3, vec![section(30)]);
+        }
+        let repository = MockRepository::with_tokens(&["token"]);
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+        service
+            .produce_material("token", &[device()], &[course(1), course(2), course(3)])
+            .await
+            .unwrap();
+
+        provider
+            .failing_calls
+            .lock()
+            .unwrap()
+            .push("get_course_contents:1".to_string());
+        service
+            .produce_material("token", &[device()], &[course(1), course(2)])
+            .await
+            .unwrap();
+
+        let mut course_ids: Vec<i64> = repository
+            .stored("token")
+            .unwrap()
+            .course_contents
+            .iter()
+            .map(|content| content.course_id)
+            .collect();
+        course_ids.sort();
+        assert_eq!(course_ids, [1, 2]);
+        assert_eq!(provider.calls_to("get_course_contents:2"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reminder_sent_once_and_skipped_while_snoozed() {
+        let now = Utc::now().timestamp();
+        let repository = MockRepository::with_tokens(&["token"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.deadlines = Some(vec![
+                deadline(1, now + 3600),
+                deadline(2, now + 3600),
+                deadline(3, now + 3 * 86400),
+            ]);
+            stored.snoozes = vec![DeadlineSnooze {
+                deadline_id: 2,
+                until: now + 1800,
+            }];
+        }
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &MockProvider::default(), &repository);
+
+        service
+            .produce_reminders("token", &[device()], 24)
+            .await
+            .unwrap();
+        service
+            .produce_reminders("token", &[device()], 24)
+            .await
+            .unwrap();
+
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].1, "Deadline reminder");
+        assert!(sent[0].2.contains("Task 1"));
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_reminders_sent_once_per_lead() {
+        let now = Utc::now().timestamp();
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .deadlines = Some(vec![
+            Deadline {
+                timestart: Some(now + 1200),
+                ..deadline(1, now - 3600)
+            },
+            deadline(2, now + 20 * 3600),
+            deadline(3, now + 3 * 86400),
+        ]);
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &MockProvider::default(), &repository);
+
+        for _ in 0..2 {
+            service
+                .produce_scheduled_reminders("token", &[device()])
+                .await
+                .unwrap();
+        }
+
+        let titles: Vec<_> = producer
+            .sent
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|n| n.1.clone())
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                "Deadline in 30 minutes",
+                "Deadline in 24 hours",
+                "Deadline in 7 days"
+            ]
+        );
+        let leads: Vec<_> = repository
+            .stored("token")
+            .unwrap()
+            .sent_reminders
+            .iter()
+            .map(|reminder| (reminder.deadline_id, reminder.lead_minutes))
+            .collect();
+        assert_eq!(leads, [(1, 30), (2, 24 * 60), (3, 7 * 24 * 60)]);
+    }
+
+    #[tokio::test]
+    async fn test_due_custom_reminders_sent_unless_snoozed() {
+        let now = Utc::now().timestamp();
+        let repository = MockRepository::with_tokens(&["token"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.deadlines = Some(vec![deadline(1, now + 1800), deadline(2, now + 1800)]);
+            stored.snoozes = vec![DeadlineSnooze {
+                deadline_id: 2,
+                until: now + 600,
+            }];
+        }
+        let reminders = MockReminderRepository::default();
+        // The last was set before deadline 1 moved; its stored time is stale
+        *reminders.entries.lock().unwrap() = [(1, 60), (2, 60), (3, 60), (1, 10)]
+            .into_iter()
+            .map(|(deadline_id, offset_minutes)| ReminderEntry {
+                id: Some(ObjectId::new()),
+                token: "token".to_string(),
+                deadline_id,
+                offset_minutes,
+                remind_at: now - 60,
+            })
+            .collect();
+        let data_service = DataService::new(
+            Arc::new(MockProvider::default()),
+            Box::new(repository.clone()),
+            RegistrationSettings::default(),
+            FeatureFlags::default(),
+        );
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &MockProvider::default(), &repository)
+            .with_reminders(Arc::new(ReminderService::new(
+                Box::new(reminders.clone()),
+                Arc::new(data_service),
+            )));
+
+        service
+            .produce_custom_reminders("token", &[device()])
+            .await
+            .unwrap();
+
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].2.contains("Task 1"));
+        let pending: Vec<(i32, i64)> = reminders
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.deadline_id, entry.offset_minutes))
+            .collect();
+        assert_eq!(pending, [(2, 60), (1, 10)]);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_reaches_segment_once() {
+        let repository = MockRepository::with_tokens(&["a", "b", "c"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            for (token, course_id) in [("a", 1), ("b", 2)] {
+                let stored = users.get_mut(token).unwrap();
+                stored.devices = vec![mock_device(&format!("device-{}", token), None)];
+                stored.courses = Some(vec![course(course_id)]);
+            }
+        }
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &MockProvider::default(), &repository);
+        let broadcast = |course_id| Broadcast {
+            title: "Schedule change".to_string(),
+            body: "Classes move online on Friday".to_string(),
+            image_url: None,
+            segment: BroadcastSegment {
+                platform: None,
+                course_id,
+            },
+        };
+
+        let report = service.broadcast(&broadcast(Some(1))).await.unwrap();
+        assert_eq!(
+            report,
+            BroadcastReport {
+                recipients: 1,
+                sent: 1
+            }
+        );
+
+        let report = service.broadcast(&broadcast(None)).await.unwrap();
+        assert_eq!(
+            report,
+            BroadcastReport {
+                recipients: 2,
+                sent: 1
+            }
+        );
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert!(sent
+            .iter()
+            .all(|notification| notification.0 == NotificationKind::Announcement));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_reaches_recipients_dropped_from_earlier_pages() {
+        let tokens: Vec<String> = (0..=BROADCAST_BATCH_SIZE)
+            .map(|i| format!("user-{:04}", i))
+            .collect();
+        let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+        let repository = MockRepository::with_tokens(&tokens);
+        for (token, stored) in repository.users.lock().unwrap().iter_mut() {
+            stored.devices = vec![mock_device(&format!("device-{}", token), None)];
+        }
+        let producer = MockEventProducer::default();
+        // Every device is dropped as the first page is sent
+        *producer.failure.lock().unwrap() = Some(ProduceError::Unregistered("gone".into()));
+        let service = producer_service(&producer, &MockProvider::default(), &repository);
+
+        let report = service
+            .broadcast(&Broadcast {
+                title: "Schedule change".to_string(),
+                body: "Classes move online on Friday".to_string(),
+                image_url: None,
+                segment: BroadcastSegment {
+                    platform: None,
+                    course_id: None,
+                },
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(report.recipients, BROADCAST_BATCH_SIZE + 1);
+    }
+
+    #[tokio::test]
+    async fn test_transient_failure_kept_as_dead_letter_until_redriven() {
+        let repository = MockRepository::with_tokens(&["a"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("a")
+            .unwrap()
+            .devices = vec![mock_device("device-a", None)];
+        let producer = MockEventProducer::default();
+        let dead_letters = MockDeadLetterRepository::default();
+        let history = MockHistoryService::default();
+        let service = ProducerService {
+            history_service: Arc::new(history.clone()),
+            ..producer_service(&producer, &MockProvider::default(), &repository)
+        }
+        .with_dead_letters(Arc::new(dead_letters.clone()));
+        let broadcast = |title: &str| Broadcast {
+            title: title.to_string(),
+            body: "Classes move online on Friday".to_string(),
+            image_url: None,
+            segment: BroadcastSegment {
+                platform: None,
+                course_id: None,
+            },
+        };
+
+        *producer.failure.lock().unwrap() = Some(ProduceError::Permanent("unregistered".into()));
+        service.broadcast(&broadcast("Unregistered")).await.unwrap();
+        assert!(dead_letters.dead_letters.lock().unwrap().is_empty());
+
+        *producer.failure.lock().unwrap() = Some(ProduceError::Transient("unavailable".into()));
+        service.broadcast(&broadcast("Outage")).await.unwrap();
+        assert_eq!(
+            service.redrive_dead_letters().await.unwrap(),
+            RedriveReport {
+                delivered: 0,
+                failed: 1
+            }
+        );
+        {
+            let stored = dead_letters.dead_letters.lock().unwrap();
+            assert_eq!(stored.len(), 1);
+            assert_eq!(stored[0].token, "a");
+            assert_eq!(stored[0].notification.device_token, "device-a");
+            assert_eq!(stored[0].redrives, 1);
+        }
+
+        *producer.failure.lock().unwrap() = None;
+        assert_eq!(
+            service.redrive_dead_letters().await.unwrap(),
+            RedriveReport {
+                delivered: 1,
+                failed: 0
+            }
+        );
+        assert!(dead_letters.dead_letters.lock().unwrap().is_empty());
+        let entries = history.entries.lock().unwrap();
+        let statuses: Vec<_> = entries.iter().map(|entry| entry.delivery).collect();
+        assert_eq!(
+            statuses,
+            [DeliveryStatus::Failed, DeliveryStatus::Delivered]
+        );
+        assert_eq!(
+            entries[0].error.as_deref(),
+            Some("Delivery rejected: unregistered")
+        );
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].1, "Outage");
+    }
+
+    #[tokio::test]
+    async fn test_grade_event_only_checks_affected_user_and_course() {
+        let provider = MockProvider::default();
+        provider
+            .grades
+            .lock()
+            .unwrap()
+            .insert(2, vec![grade(2, &[(20, "90.00 %")])]);
+        let repository = MockRepository::with_tokens(&["token", "other"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            for (token, userid) in [("token", 1), ("other", 2)] {
+                let stored = users.get_mut(token).unwrap();
+                stored.devices = vec![mock_device(&format!("device-{}", token), None)];
+                stored.user = Some(user(userid));
+                stored.courses = Some(vec![course(1), course(2)]);
+                stored.grades = Some(vec![
+                    grade(1, &[(10, "50.00 %")]),
+                    grade(2, &[(20, "80.00 %")]),
+                ]);
+                stored.grades_overview = Some(vec![grade_overview(2, "80.00")]);
+            }
+        }
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        let event = ProviderEvent {
+            userid: 1,
+            kind: ProviderEventKind::Grade,
+            courseid: Some(2),
+        };
+        assert_eq!(service.process_event(&event).await.unwrap(), 1);
+
+        assert_eq!(provider.calls_to("get_grades_by_course_id:1"), 0);
+        assert_eq!(provider.calls_to("get_grades_by_course_id:2"), 1);
+        assert_eq!(provider.calls_to("get_courses"), 0);
+        assert_eq!(provider.calls.lock().unwrap().len(), 2);
+        let kinds: Vec<_> = producer.sent.lock().unwrap().iter().map(|n| n.0).collect();
+        assert_eq!(kinds, vec![NotificationKind::Grade]);
+
+        let unknown = ProviderEvent { userid: 3, ..event };
+        assert_eq!(service.process_event(&unknown).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_grade_overview_flag_disables_course_total_checks() {
+        let (provider, repository) = single_item_change();
+        let producer = MockEventProducer::default();
+        let service = producer_service_with(
+            &producer,
+            &provider,
+            &repository,
+            FeatureFlags {
+                grade_overview: false,
+                ..Default::default()
+            },
+        );
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        assert_eq!(provider.calls_to("get_grades_overview"), 0);
+        let kinds: Vec<_> = producer.sent.lock().unwrap().iter().map(|n| n.0).collect();
+        assert_eq!(kinds, vec![NotificationKind::Grade]);
+        let stored = repository.stored("token").unwrap().grades_overview.unwrap();
+        assert_eq!(stored[0].grade, "50.00");
+    }
+
+    #[tokio::test]
+    async fn test_disabled_category_and_master_switch_sync_silently() {
+        for preferences in [
+            Preferences {
+                categories: NotificationCategories {
+                    grades: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            Preferences {
+                notifications_off: true,
+                ..Default::default()
+            },
+        ] {
+            let (provider, repository) = single_item_change();
+            repository
+                .users
+                .lock()
+                .unwrap()
+                .get_mut("token")
+                .unwrap()
+                .preferences = Some(preferences);
+            let producer = MockEventProducer::default();
+            let service = producer_service(&producer, &provider, &repository);
+
+            service
+                .process_producing("token", &[device()])
+                .await
+                .unwrap();
+
+            assert!(producer.sent.lock().unwrap().is_empty());
+            let stored = repository.stored("token").unwrap();
+            assert_eq!(
+                stored.grades.unwrap()[0].gradeitems[0].percentageformatted,
+                "60.00 %"
+            );
+            assert_eq!(stored.grades_overview.unwrap()[0].grade, "60.00");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quiet_hours_queue_pushes_until_they_end() {
+        let (provider, repository) = single_item_change();
+        let local_now = Utc::now().with_timezone(&chrono_tz::Asia::Almaty);
+        let local = |hours| {
+            (local_now + chrono::Duration::hours(hours))
+                .format("%H:%M")
+                .to_string()
+        };
+        let set_quiet_hours = |quiet_hours| {
+            repository
+                .users
+                .lock()
+                .unwrap()
+                .get_mut("token")
+                .unwrap()
+                .preferences = Some(Preferences {
+                quiet_hours,
+                ..Default::default()
+            });
+        };
+        set_quiet_hours(Some(QuietHours {
+            start: local(-1),
+            end: local(1),
+        }));
+        let producer = MockEventProducer::default();
+        let history = MockHistoryService::default();
+        let service = ProducerService {
+            history_service: Arc::new(history.clone()),
+            ..producer_service(&producer, &provider, &repository)
+        };
+        let statuses = || {
+            let entries = history.entries.lock().unwrap();
+            entries
+                .iter()
+                .map(|entry| entry.delivery)
+                .collect::<Vec<_>>()
+        };
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+        assert!(producer.sent.lock().unwrap().is_empty());
+        assert_eq!(repository.stored("token").unwrap().queued.len(), 2);
+        assert_eq!(statuses(), [DeliveryStatus::Queued; 2]);
+
+        set_quiet_hours(None);
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+        let kinds: Vec<_> = producer.sent.lock().unwrap().iter().map(|n| n.0).collect();
+        assert_eq!(
+            kinds,
+            [NotificationKind::Grade, NotificationKind::GradeOverview]
+        );
+        assert!(repository.stored("token").unwrap().queued.is_empty());
+        assert_eq!(statuses(), [DeliveryStatus::Delivered; 2]);
+        assert!(history
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|entry| entry.updated_at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_digest_holds_changes_for_one_morning_summary() {
+        let (provider, repository) = single_item_change();
+        // A fixed-offset zone where it is digest time right now
+        let offset = (DIGEST_HOUR as i64 - Utc::now().hour() as i64).rem_euclid(24);
+        let offset = if offset > 14 { offset - 24 } else { offset };
+        let timezone = match offset {
+            0 => "Etc/GMT".to_string(),
+            offset => format!("Etc/GMT{:+}", -offset),
+        };
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.preferences = Some(Preferences {
+                digest: true,
+                ..Default::default()
+            });
+            stored.settings = Some(UserSettings {
+                timezone,
+                ..Default::default()
+            });
+        }
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        for _ in 0..2 {
+            service
+                .process_producing("token", &[device()])
+                .await
+                .unwrap();
+        }
+
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, NotificationKind::Digest);
+        assert!(sent[0].2.starts_with("2 new grades\n"));
+    }
+
+    #[tokio::test]
+    async fn test_exam_period_only_lets_urgent_deadlines_through() {
+        let (provider, repository) = single_item_change();
+        let now = Utc::now().timestamp();
+        provider.deadlines.lock().unwrap().insert(
+            1,
+            vec![deadline(5, now + 1800), deadline(6, now + 7 * 86400)],
+        );
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .deadlines = Some(Vec::new());
+        let producer = MockEventProducer::default();
+        let history = MockHistoryService::default();
+        let service = ProducerService {
+            history_service: Arc::new(history.clone()),
+            ..producer_service(&producer, &provider, &repository)
+        }
+        .with_exam_periods(Arc::new(MockExamPeriodRepository::default()));
+        service
+            .set_exam_period(Some(&ExamPeriod {
+                start: now - 3600,
+                end: now + 86400,
+            }))
+            .await
+            .unwrap();
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        // The digest goes out too if it happens to be digest time
+        let sent = producer.sent.lock().unwrap();
+        let pushed: Vec<_> = sent
+            .iter()
+            .filter(|sent| sent.0 != NotificationKind::Digest)
+            .collect();
+        assert_eq!(pushed.len(), 1);
+        assert_eq!(pushed[0].0, NotificationKind::Deadline);
+        assert!(pushed[0].2.contains("Task 5"));
+        let held = history
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.delivery == DeliveryStatus::Held)
+            .count();
+        assert_eq!(held, 3);
+    }
+
+    #[tokio::test]
+    async fn test_grade_notification_includes_feedback_snippet() {
+        let (provider, repository) = single_item_change();
+        provider.grades.lock().unwrap().get_mut(&1).unwrap()[0].gradeitems[0].feedback =
+            Some("<p>Well structured, but cite your sources.</p>".to_string());
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        service
+            .produce_grade("token", &[device()], &user(1), &[course(1)])
+            .await
+            .unwrap();
+
+        let sent = producer.sent.lock().unwrap();
+        assert_eq!(
+            sent[0].2,
+            "New grade | Item 10\n50.00 % -> 60.00 %\nFeedback: Well structured, but cite your sources."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notifications_rendered_in_user_language() {
+        let (provider, repository) = single_item_change();
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .settings = Some(UserSettings {
+            language: "kk".to_string(),
+            ..Default::default()
+        });
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        service
+            .process_producing("token", &[device()])
+            .await
+            .unwrap();
+
+        let bodies: Vec<_> = producer
+            .sent
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|n| n.2.clone())
+            .collect();
+        assert_eq!(
+            bodies,
+            [
+                "Жаңа баға | Item 10\n50.00 % -> 60.00 %",
+                "Курстың жаңа қорытынды бағасы | 60.00",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_weekly_report_sent_once_on_its_day() {
+        let (provider, repository) = single_item_change();
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.preferences = Some(Preferences {
+                weekly_report: Some(WeeklyReport {
+                    weekday: Utc::now().weekday().to_string(),
+                    time: "00:00".to_string(),
+                }),
+                ..Default::default()
+            });
+            stored.settings = Some(UserSettings {
+                timezone: "UTC".to_string(),
+                ..Default::default()
+            });
+        }
+        let producer = MockEventProducer::default();
+        let service = producer_service(&producer, &provider, &repository);
+
+        for _ in 0..2 {
+            service
+                .process_producing("token", &[device()])
+                .await
+                .unwrap();
+        }
+
+        let sent = producer.sent.lock().unwrap();
+        let reports: Vec<_> = sent
+            .iter()
+            .filter(|n| n.0 == NotificationKind::WeeklyReport)
+            .collect();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].2, "This week: 1 new grade, average 60%");
+    }
+
+    #[tokio::test]
+    async fn test_watched_user_without_device_gets_live_changes_only() {
+        let provider = MockProvider::default();
+        provider.courses.lock().unwrap().push(course(1));
+        provider
+            .grades
+            .lock()
+            .unwrap()
+            .insert(1, vec![grade(1, &[(10, "60.00 %")])]);
+        let repository = MockRepository::with_tokens(&["token"]);
+        {
+            let mut users = repository.users.lock().unwrap();
+            let stored = users.get_mut("token").unwrap();
+            stored.user = Some(user(1));
+            stored.courses = Some(vec![course(1)]);
+            stored.grades = Some(vec![grade(1, &[(10, "50.00 %")])]);
+        }
+        let producer = MockEventProducer::default();
+        let history = MockHistoryService::default();
+        let live_updates = Arc::new(LiveUpdates::default());
+        let service = ProducerService {
+            history_service: Arc::new(history.clone()),
+            ..producer_service(&producer, &provider, &repository)
+        }
+        .with_live_updates(Arc::clone(&live_updates));
+        let mut events = live_updates.subscribe("token");
+
+        let report = service
+            .process_batch(&[Token::new("token".to_string(), None)])
+            .await;
+
+        assert_eq!(report.tokens_processed, 1);
+        assert!(producer.sent.lock().unwrap().is_empty());
+        assert_eq!(events.try_recv().unwrap().category, NotificationKind::Grade);
+        // Not counted as sent, as nothing was pushed
+        let entries = history.entries.lock().unwrap();
+        assert_eq!(entries[0].delivery, DeliveryStatus::Skipped);
+        assert_eq!(delivery_stats(&entries).sent, 0);
+    }
+
+    #[tokio::test]
+    async fn test_batch_records_sync_outcome_per_token() {
+        let provider = MockProvider::default();
+        provider
+            .invalid_tokens
+            .lock()
+            .unwrap()
+            .insert("broken".to_string());
+        let repository = MockRepository::with_tokens(&["token", "broken"]);
+        let service = producer_service(&MockEventProducer::default(), &provider, &repository);
+
+        service
+            .process_batch(&[
+                Token::new("token".to_string(), None),
+                Token::new("broken".to_string(), None),
+            ])
+            .await;
+
+        let users = service
+            .data_service
+            .list_users(&UserListQuery::default())
+            .await
+            .unwrap();
+        let broken = &users[0];
+        assert_eq!(broken.token, "broken");
+        assert!(broken.last_sync_at.is_some());
+        assert!(broken.sync_error.is_some());
+        assert!(users[1].last_sync_at.is_some());
+        assert!(users[1].sync_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_batches_page_through_tokens_and_start_over() {
+        let repository = MockRepository::with_tokens(&["a", "b", "c"]);
+        let service = producer_service(
+            &MockEventProducer::default(),
+            &MockProvider::default(),
+            &repository,
+        );
+        let mut skip = 0;
+
+        let mut sizes = Vec::new();
+        for _ in 0..3 {
+            let report = service.get_batches(2, &mut skip).await.unwrap();
+            sizes.push((report.tokens_processed + report.errors, skip));
+        }
+
+        assert_eq!(sizes, [(2, 2), (1, 3), (0, 0)]);
+    }
+}
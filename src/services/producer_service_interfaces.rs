@@ -1,37 +1,72 @@
+use crate::models::broadcast::{Broadcast, BroadcastReport};
 use crate::models::course::Course;
-use crate::models::token::Token;
+use crate::models::dead_letter::RedriveReport;
+use crate::models::preferences::ExamPeriod;
+use crate::models::stats::BatchReport;
+use crate::models::token::{Device, Token};
 use crate::models::user::User;
+use crate::models::webhook::ProviderEvent;
 use async_trait::async_trait;
 
 #[async_trait]
 pub trait ProducerServiceInterface: Send + Sync {
-    async fn get_batches<'a>(&self, limit: i64, skip: &'a mut u64) -> anyhow::Result<()>;
-    async fn process_batch(&self, batch: &[Token]) -> anyhow::Result<()>;
-    async fn process_producing(&self, token: &str, device_token: &str) -> anyhow::Result<()>;
-    async fn produce_user_info(&self, token: &str, device_token: &str) -> anyhow::Result<User>;
+    async fn get_batches<'a>(&self, limit: i64, skip: &'a mut u64) -> anyhow::Result<BatchReport>;
+    async fn process_batch(&self, batch: &[Token]) -> BatchReport;
+    /// Sends the announcement to every recipient in its segment, a batch at a time.
+    async fn broadcast(&self, broadcast: &Broadcast) -> anyhow::Result<BroadcastReport>;
+    /// Re-sends the oldest dead letters, dropping those that get through.
+    async fn redrive_dead_letters(&self) -> anyhow::Result<RedriveReport>;
+    /// The exam period admins set for everyone, if any.
+    async fn get_exam_period(&self) -> anyhow::Result<Option<ExamPeriod>>;
+    /// Starts an exam period for everyone, or ends it with `None`.
+    async fn set_exam_period(&self, period: Option<&ExamPeriod>) -> anyhow::Result<()>;
+    async fn process_producing(&self, token: &str, devices: &[Device]) -> anyhow::Result<()>;
+    async fn process_event(&self, event: &ProviderEvent) -> anyhow::Result<usize>;
+    async fn produce_user_info(&self, token: &str, devices: &[Device]) -> anyhow::Result<User>;
     async fn produce_course(
         &self,
         token: &str,
-        device_token: &str,
+        devices: &[Device],
         user: &User,
     ) -> anyhow::Result<Vec<Course>>;
     async fn produce_deadline(
         &self,
         token: &str,
-        device_token: &str,
+        devices: &[Device],
         courses: &[Course],
     ) -> anyhow::Result<()>;
+    async fn produce_material(
+        &self,
+        token: &str,
+        devices: &[Device],
+        courses: &[Course],
+    ) -> anyhow::Result<()>;
+    async fn produce_reminders(
+        &self,
+        token: &str,
+        devices: &[Device],
+        hours: i64,
+    ) -> anyhow::Result<()>;
+    async fn produce_scheduled_reminders(
+        &self,
+        token: &str,
+        devices: &[Device],
+    ) -> anyhow::Result<()>;
+    async fn produce_custom_reminders(&self, token: &str, devices: &[Device])
+        -> anyhow::Result<()>;
+    async fn produce_digest(&self, token: &str, devices: &[Device]) -> anyhow::Result<()>;
+    async fn produce_weekly_report(&self, token: &str, devices: &[Device]) -> anyhow::Result<()>;
     async fn produce_grade(
         &self,
         token: &str,
-        device_token: &str,
+        devices: &[Device],
         user: &User,
         courses: &[Course],
     ) -> anyhow::Result<()>;
     async fn produce_grade_overview(
         &self,
         token: &str,
-        device_token: &str,
+        devices: &[Device],
         courses: &[Course],
     ) -> anyhow::Result<()>;
 }
@@ -1,4 +1,5 @@
 use crate::models::course::Course;
+use crate::models::course_content::ContentSection;
 use crate::models::deadline::Events;
 use crate::models::grade::{GradesOverview, UserGrades};
 use crate::models::user::User;
@@ -21,4 +22,9 @@ pub trait DataProviderInterface: Send + Sync {
         course_id: i64,
     ) -> Result<Events, reqwest::Error>;
     async fn get_grades_overview(&self, token: &str) -> Result<GradesOverview, reqwest::Error>;
+    async fn get_course_contents(
+        &self,
+        token: &str,
+        course_id: i64,
+    ) -> Result<Vec<ContentSection>, reqwest::Error>;
 }
@@ -0,0 +1,136 @@
+use crate::models::reminder::{Reminder, ReminderEntry, ReminderRequest};
+use crate::repositories::errors::RepositoryError;
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+
+use super::data_service_interfaces::DataServiceInterfaces;
+use super::errors::ServiceError;
+use super::reminder_service_interfaces::ReminderServiceInterface;
+
+#[async_trait]
+pub trait ReminderRepositoryInterface: Send + Sync {
+    async fn save_reminder(&self, reminder: &ReminderEntry) -> Result<ObjectId, RepositoryError>;
+    async fn find_pending(&self, token: &str) -> Result<Vec<ReminderEntry>, RepositoryError>;
+    async fn delete_reminder(&self, id: ObjectId) -> Result<(), RepositoryError>;
+}
+
+pub struct ReminderService {
+    reminder_repository: Box<dyn ReminderRepositoryInterface>,
+    data_service: Arc<dyn DataServiceInterfaces>,
+}
+
+impl ReminderService {
+    pub fn new(
+        reminder_repository: Box<dyn ReminderRepositoryInterface>,
+        data_service: Arc<dyn DataServiceInterfaces>,
+    ) -> Self {
+        Self {
+            reminder_repository,
+            data_service,
+        }
+    }
+}
+
+#[async_trait]
+impl ReminderServiceInterface for ReminderService {
+    async fn create_reminder(
+        &self,
+        token: &str,
+        request: &ReminderRequest,
+    ) -> Result<Reminder, ServiceError> {
+        let offset_minutes = request
+            .offset_minutes()
+            .map_err(ServiceError::InvalidInput)?;
+        let deadline = match self.data_service.get_deadlines(token).await {
+            Ok(deadlines) => deadlines
+                .into_iter()
+                .find(|deadline| deadline.id == request.deadline_id),
+            Err(ServiceError::DataIsEmpty(_)) => None,
+            Err(e) => return Err(e),
+        }
+        .ok_or_else(|| ServiceError::DataNotFound("Deadline".to_string()))?;
+
+        let mut entry = ReminderEntry {
+            id: None,
+            token: token.to_string(),
+            deadline_id: deadline.id,
+            offset_minutes,
+            remind_at: 0,
+        };
+        entry.remind_at = entry.remind_at_before(deadline.due_at());
+        if entry.remind_at <= Utc::now().timestamp() {
+            return Err(ServiceError::InvalidInput("offset".to_string()));
+        }
+        entry.id = Some(self.reminder_repository.save_reminder(&entry).await?);
+        Ok(entry.into())
+    }
+
+    async fn find_pending(&self, token: &str) -> Result<Vec<ReminderEntry>, ServiceError> {
+        Ok(self.reminder_repository.find_pending(token).await?)
+    }
+
+    async fn delete_reminder(&self, id: ObjectId) -> Result<(), ServiceError> {
+        Ok(self.reminder_repository.delete_reminder(id).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::feature_flags::FeatureFlags;
+    use crate::models::registration::RegistrationSettings;
+    use crate::services::data_service::DataService;
+    use crate::services::mocks::{deadline, MockProvider, MockReminderRepository, MockRepository};
+
+    fn reminder_service(
+        repository: &MockRepository,
+        reminders: &MockReminderRepository,
+    ) -> ReminderService {
+        let data_service = DataService::new(
+            Arc::new(MockProvider::default()),
+            Box::new(repository.clone()),
+            RegistrationSettings::default(),
+            FeatureFlags::default(),
+        );
+        ReminderService::new(Box::new(reminders.clone()), Arc::new(data_service))
+    }
+
+    #[tokio::test]
+    async fn test_reminder_scheduled_offset_before_deadline() {
+        let due = Utc::now().timestamp() + 5 * 86400;
+        let repository = MockRepository::with_tokens(&["token"]);
+        repository
+            .users
+            .lock()
+            .unwrap()
+            .get_mut("token")
+            .unwrap()
+            .deadlines = Some(vec![deadline(7, due)]);
+        let reminders = MockReminderRepository::default();
+        let service = reminder_service(&repository, &reminders);
+        let request = |deadline_id, days| ReminderRequest {
+            deadline_id,
+            days,
+            hours: 2,
+            minutes: 0,
+        };
+
+        let reminder = service
+            .create_reminder("token", &request(7, 3))
+            .await
+            .unwrap();
+        assert_eq!(reminder.remind_at, due - 3 * 86400 - 2 * 3600);
+        assert_eq!(reminders.entries.lock().unwrap().len(), 1);
+
+        assert!(matches!(
+            service.create_reminder("token", &request(7, 6)).await,
+            Err(ServiceError::InvalidInput(field)) if field == "offset"
+        ));
+        assert!(matches!(
+            service.create_reminder("token", &request(8, 1)).await,
+            Err(ServiceError::DataNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,19 @@
+use crate::models::reminder::{Reminder, ReminderEntry, ReminderRequest};
+use async_trait::async_trait;
+use mongodb::bson::oid::ObjectId;
+
+use super::errors::ServiceError;
+
+#[async_trait]
+pub trait ReminderServiceInterface: Send + Sync {
+    /// Schedules a reminder before one of the user's stored deadlines.
+    async fn create_reminder(
+        &self,
+        token: &str,
+        request: &ReminderRequest,
+    ) -> Result<Reminder, ServiceError>;
+    /// Reminders of the user that have not been sent yet.
+    async fn find_pending(&self, token: &str) -> Result<Vec<ReminderEntry>, ServiceError>;
+    /// Drops a reminder once it is sent or no longer applies.
+    async fn delete_reminder(&self, id: ObjectId) -> Result<(), ServiceError>;
+}
@@ -0,0 +1,103 @@
+use crate::repositories::errors::RepositoryError;
+use async_trait::async_trait;
+use mongodb::bson::DateTime;
+use std::time::Duration;
+
+use super::errors::ServiceError;
+
+/// How often the retention job runs.
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// History entries moved to the archive at a time.
+const ARCHIVE_BATCH_SIZE: i64 = 500;
+
+#[async_trait]
+pub trait HistoryArchiveRepositoryInterface: Send + Sync {
+    /// Copies up to `limit` of the oldest entries sent before `before` to the
+    /// archive, then deletes them from the history. Returns how many moved.
+    async fn archive_before(&self, before: DateTime, limit: i64) -> Result<u64, RepositoryError>;
+}
+
+/// Keeps the notification history to its retention period by archiving and
+/// then deleting older entries.
+pub struct RetentionService {
+    archive_repository: Box<dyn HistoryArchiveRepositoryInterface>,
+    retention_days: u64,
+}
+
+impl RetentionService {
+    pub fn new(
+        archive_repository: Box<dyn HistoryArchiveRepositoryInterface>,
+        retention_days: u64,
+    ) -> Self {
+        Self {
+            archive_repository,
+            retention_days,
+        }
+    }
+
+    /// Archives every entry past the retention period; returns how many.
+    pub async fn prune_history(&self) -> Result<u64, ServiceError> {
+        let retention_millis = self.retention_days as i64 * 24 * 60 * 60 * 1000;
+        let before = DateTime::from_millis(DateTime::now().timestamp_millis() - retention_millis);
+        let mut archived = 0;
+        loop {
+            let moved = self
+                .archive_repository
+                .archive_before(before, ARCHIVE_BATCH_SIZE)
+                .await?;
+            archived += moved;
+            if moved < ARCHIVE_BATCH_SIZE as u64 {
+                return Ok(archived);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Sent-at times in unix seconds, oldest first. Clones share the same storage.
+    #[derive(Clone, Default)]
+    struct MockArchive {
+        history: Arc<Mutex<Vec<i64>>>,
+        archive: Arc<Mutex<Vec<i64>>>,
+    }
+
+    #[async_trait]
+    impl HistoryArchiveRepositoryInterface for MockArchive {
+        async fn archive_before(
+            &self,
+            before: DateTime,
+            limit: i64,
+        ) -> Result<u64, RepositoryError> {
+            let mut history = self.history.lock().unwrap();
+            let old = history
+                .iter()
+                .take_while(|&&sent_at| sent_at * 1000 < before.timestamp_millis())
+                .take(limit as usize)
+                .count();
+            self.archive.lock().unwrap().extend(history.drain(..old));
+            Ok(old as u64)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prune_archives_old_entries_in_batches() {
+        let now = DateTime::now().timestamp_millis() / 1000;
+        let day = 24 * 60 * 60;
+        let archive = MockArchive::default();
+        {
+            let mut history = archive.history.lock().unwrap();
+            history.extend((0..1200).map(|i| now - 40 * day + i));
+            history.extend([now - 29 * day, now]);
+        }
+        let service = RetentionService::new(Box::new(archive.clone()), 30);
+
+        assert_eq!(service.prune_history().await.unwrap(), 1200);
+        assert_eq!(archive.archive.lock().unwrap().len(), 1200);
+        assert_eq!(*archive.history.lock().unwrap(), [now - 29 * day, now]);
+        assert_eq!(service.prune_history().await.unwrap(), 0);
+    }
+}
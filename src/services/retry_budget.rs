@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Caps provider retries across all calls of one user's producer cycle.
+pub struct RetryBudget {
+    per_cycle: u32,
+    cycles: Mutex<HashMap<String, Cycle>>,
+}
+
+/// Overlapping cycles of a token share one budget until the last one ends.
+struct Cycle {
+    running: u32,
+    remaining: Arc<AtomicU32>,
+}
+
+impl RetryBudget {
+    pub fn new(per_cycle: u32) -> Self {
+        Self {
+            per_cycle,
+            cycles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn start(&self, token: &str) {
+        self.cycles
+            .lock()
+            .unwrap()
+            .entry(token.to_string())
+            .or_insert_with(|| Cycle {
+                running: 0,
+                remaining: Arc::new(AtomicU32::new(self.per_cycle)),
+            })
+            .running += 1;
+    }
+
+    pub fn finish(&self, token: &str) {
+        let mut cycles = self.cycles.lock().unwrap();
+        if let Some(cycle) = cycles.get_mut(token) {
+            cycle.running -= 1;
+            if cycle.running == 0 {
+                cycles.remove(token);
+            }
+        }
+    }
+
+    /// Calls outside of a cycle are only bounded by the per-call limit.
+    pub fn try_take(&self, token: &str) -> bool {
+        let Some(remaining) = self
+            .cycles
+            .lock()
+            .unwrap()
+            .get(token)
+            .map(|cycle| Arc::clone(&cycle.remaining))
+        else {
+            return true;
+        };
+        // Concurrent calls of the cycle never take the last retry twice
+        remaining
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_is_per_token_and_cycle() {
+        let budget = RetryBudget::new(2);
+        budget.start("token");
+        assert!(budget.try_take("token"));
+        assert!(budget.try_take("token"));
+        assert!(!budget.try_take("token"));
+        assert!(budget.try_take("other"));
+
+        budget.finish("token");
+        assert!(budget.try_take("token"));
+        budget.start("token");
+        assert!(budget.try_take("token"));
+    }
+
+    #[test]
+    fn test_overlapping_cycles_share_the_budget() {
+        let budget = RetryBudget::new(2);
+        budget.start("token");
+        assert!(budget.try_take("token"));
+
+        budget.start("token");
+        assert!(budget.try_take("token"));
+        assert!(!budget.try_take("token"));
+
+        budget.finish("token");
+        assert!(!budget.try_take("token"));
+        budget.finish("token");
+        assert!(budget.try_take("token"));
+    }
+
+    #[test]
+    fn test_concurrent_calls_share_the_budget() {
+        let budget = Arc::new(RetryBudget::new(5));
+        budget.start("token");
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let budget = Arc::clone(&budget);
+                std::thread::spawn(move || (0..10).filter(|_| budget.try_take("token")).count())
+            })
+            .collect();
+        let taken: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        assert_eq!(taken, 5);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::models::cohort::Cohort;
+use crate::models::notification::NotificationKind;
+use crate::models::stats::{
+    notifications_since, AdminStats, CycleReport, CycleSummary, DailyNotificationCount, UserStats,
+};
+use crate::repositories::errors::RepositoryError;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use mongodb::bson::DateTime;
+use std::sync::Arc;
+
+use super::errors::ServiceError;
+use super::metrics::Metrics;
+use super::stats_service_interfaces::StatsServiceInterface;
+
+#[async_trait]
+pub trait StatsRepositoryInterface: Send + Sync {
+    async fn stats(&self) -> Result<UserStats, RepositoryError>;
+    async fn increment_notification_count(
+        &self,
+        day: &str,
+        kind: NotificationKind,
+        cohort: Cohort,
+    ) -> Result<(), RepositoryError>;
+    async fn find_notification_counts(
+        &self,
+        since_day: &str,
+    ) -> Result<Vec<DailyNotificationCount>, RepositoryError>;
+    async fn save_cycle_report(&self, report: &CycleReport) -> Result<(), RepositoryError>;
+    async fn find_cycle_reports(
+        &self,
+        since: DateTime,
+    ) -> Result<Vec<CycleReport>, RepositoryError>;
+}
+
+pub struct StatsService {
+    stats_repository: Box<dyn StatsRepositoryInterface>,
+    metrics: Arc<Metrics>,
+}
+
+impl StatsService {
+    pub fn new(stats_repository: Box<dyn StatsRepositoryInterface>, metrics: Arc<Metrics>) -> Self {
+        Self {
+            stats_repository,
+            metrics,
+        }
+    }
+}
+
+#[async_trait]
+impl StatsServiceInterface for StatsService {
+    async fn record_notification(&self, kind: NotificationKind, cohort: Cohort) {
+        self.metrics.notification_sent(kind);
+        let day = Utc::now().format("%Y-%m-%d").to_string();
+        if let Err(e) = self
+            .stats_repository
+            .increment_notification_count(&day, kind, cohort)
+            .await
+        {
+            eprintln!("Error recording notification stats: {}", e);
+        }
+    }
+
+    async fn record_cycle(&self, report: &CycleReport) -> Result<(), ServiceError> {
+        self.metrics.sync_errors(report.errors as u64);
+        self.stats_repository
+            .save_cycle_report(report)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_user_stats(&self) -> Result<UserStats, ServiceError> {
+        let users = self.stats_repository.stats().await?;
+        self.metrics.set_registered_users(users.total);
+        Ok(users)
+    }
+
+    async fn get_stats(&self, days: i64) -> Result<AdminStats, ServiceError> {
+        let since = Utc::now() - Duration::days(days);
+        let since_day = since.format("%Y-%m-%d").to_string();
+
+        let users = self.stats_repository.stats().await?;
+        let notifications = self
+            .stats_repository
+            .find_notification_counts(&since_day)
+            .await?;
+        let cycle_reports = self
+            .stats_repository
+            .find_cycle_reports(DateTime::from_millis(since.timestamp_millis()))
+            .await?;
+
+        Ok(AdminStats {
+            period_days: days,
+            users,
+            // The current hour is partial, so 24 hourly buckets start 23 hours back
+            notifications_last_24h: notifications_since(
+                &notifications,
+                Utc::now() - Duration::hours(23),
+            ),
+            notifications,
+            cycles: CycleSummary::from_reports(&cycle_reports),
+        })
+    }
+}
@@ -0,0 +1,14 @@
+use crate::models::cohort::Cohort;
+use crate::models::notification::NotificationKind;
+use crate::models::stats::{AdminStats, CycleReport, UserStats};
+use async_trait::async_trait;
+
+use super::errors::ServiceError;
+
+#[async_trait]
+pub trait StatsServiceInterface: Send + Sync {
+    async fn record_notification(&self, kind: NotificationKind, cohort: Cohort);
+    async fn record_cycle(&self, report: &CycleReport) -> Result<(), ServiceError>;
+    async fn get_user_stats(&self) -> Result<UserStats, ServiceError>;
+    async fn get_stats(&self, days: i64) -> Result<AdminStats, ServiceError>;
+}